@@ -1,12 +1,14 @@
 use actix_cors::Cors;
 use actix_files::Files;
-use actix_session::{SessionMiddleware, storage::CookieSessionStore};
-use actix_web::{App, HttpResponse, HttpServer, Responder, cookie::Key, web, middleware::Logger};
+use actix_session::SessionMiddleware;
+use actix_web::{App, HttpResponse, HttpServer, Responder, web, middleware::Logger};
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
 use rust_embed::RustEmbed;
 
 use crate::backend::routes;
+use crate::sap::SapGateway;
+use crate::backend::session::{SessionSettings, load_or_create_key};
 
 #[derive(RustEmbed)]
 #[folder = "static/"]
@@ -26,9 +28,11 @@ async fn static_handler(path: web::Path<String>) -> impl Responder {
 
 pub async fn start_backend(
     local_pool: web::Data<Pool<SqliteConnectionManager>>,
+    sap: web::Data<dyn SapGateway>,
     port: u16,
 ) -> std::io::Result<()> {
-    let secret_key = Key::from(&[0; 64]);
+    let secret_key = load_or_create_key()?;
+    let session_settings = SessionSettings::from_env();
     HttpServer::new(move || {
         App::new()
             .wrap(
@@ -37,9 +41,10 @@ pub async fn start_backend(
             )
             .wrap(Cors::permissive())
             .app_data(local_pool.clone())
+            .app_data(sap.clone())
             .wrap(
-                SessionMiddleware::builder(CookieSessionStore::default(), secret_key.clone())
-                    .cookie_secure(false)
+                SessionMiddleware::builder(session_settings.store(&local_pool), secret_key.clone())
+                    .cookie_secure(session_settings.cookie_secure)
                     .build(),
             )
             .route("/static/{_:.*}", web::get().to(static_handler))
@@ -0,0 +1,15 @@
+use actix_web::{web, HttpResponse, Responder};
+use r2d2_sqlite::SqliteConnectionManager;
+use r2d2::Pool;
+use crate::backend::models::{AuditLog, AuditFilterPayload};
+
+pub async fn filter_audit_logs(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    web::Query(filter): web::Query<AuditFilterPayload>
+) -> impl Responder {
+    let conn = conn_data.get().unwrap();
+    match AuditLog::filter(&conn, &filter) {
+        Ok(logs) => HttpResponse::Ok().json(logs),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
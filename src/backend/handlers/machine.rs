@@ -3,33 +3,36 @@ use r2d2_sqlite::SqliteConnectionManager;
 use r2d2::Pool;
 use crate::backend::models::{Machine, MachineCreatePayload, MachinePayload, IdPayload, MachineFilterPayload};
 
-pub async fn create_machine(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<MachineCreatePayload>) -> impl Responder {
+pub async fn create_machine(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<MachineCreatePayload>, session: actix_session::Session) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
-    match Machine::create(&conn, &data) {
+    match Machine::create(&conn, &data, user_id) {
         Ok(machine) => HttpResponse::Ok().json(machine),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
 }
 
-pub async fn update_machine(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<MachinePayload>) -> impl Responder {
+pub async fn update_machine(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<MachinePayload>, session: actix_session::Session) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match Machine::find_by_id(&conn, data.id) {
         Ok(mut machine) => {
-            if let Err(e) = machine.update(&conn, &data) { return HttpResponse::InternalServerError().body(e.to_string()); }
+            if let Err(e) = machine.update(&conn, &data, user_id) { return HttpResponse::InternalServerError().body(e.to_string()); }
             HttpResponse::Ok().json(machine)
         }
         Err(_) => HttpResponse::NotFound().body("Machine not found"),
     }
 }
 
-pub async fn delete_machine(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<IdPayload>) -> impl Responder {
+pub async fn delete_machine(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<IdPayload>, session: actix_session::Session) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match Machine::find_by_id(&conn, data.id) {
         Ok(machine) => {
             match Machine::has_jobs(&conn, data.id) {
                 Ok(true) => return HttpResponse::BadRequest().body("Cannot delete machine with existing jobs"),
                 Ok(false) => {
-                    if let Err(e) = machine.delete(&conn) { return HttpResponse::InternalServerError().body(e.to_string()); }
+                    if let Err(e) = machine.delete(&conn, user_id) { return HttpResponse::InternalServerError().body(e.to_string()); }
                     HttpResponse::Ok().body("Machine deleted successfully")
                 }
                 Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
@@ -12,3 +12,6 @@ pub use report::*;
 
 pub mod machine;
 pub use machine::*;
+
+pub mod audit;
+pub use audit::*;
@@ -0,0 +1,59 @@
+use crate::backend::models::{
+    BomAlternative, BomAlternativeCreatePayload, BomAlternativeFilterPayload, BomCheck,
+    BomCheckPayload, IdPayload,
+};
+use actix_web::{HttpResponse, Responder, web};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+
+pub async fn check_bom(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    web::Query(data): web::Query<BomCheckPayload>,
+) -> impl Responder {
+    let conn = conn_data.get().unwrap();
+    match BomCheck::run(&conn, &data.process_order, &data.material_number) {
+        Ok(check) => HttpResponse::Ok().json(check),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+pub async fn filter_bom_alternatives(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    web::Query(filter): web::Query<BomAlternativeFilterPayload>,
+) -> impl Responder {
+    let conn = conn_data.get().unwrap();
+    match BomAlternative::filter(&conn, &filter) {
+        Ok(alternatives) => HttpResponse::Ok().json(alternatives),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+pub async fn create_bom_alternative(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    data: web::Json<BomAlternativeCreatePayload>,
+    session: actix_session::Session,
+) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
+    let conn = conn_data.get().unwrap();
+    match BomAlternative::create(&conn, &data, user_id) {
+        Ok(alternative) => HttpResponse::Ok().json(alternative),
+        Err(rusqlite::Error::InvalidParameterName(msg)) => HttpResponse::BadRequest().body(msg),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+pub async fn delete_bom_alternative(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    data: web::Json<IdPayload>,
+    session: actix_session::Session,
+) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
+    let conn = conn_data.get().unwrap();
+    match BomAlternative::find_by_id(&conn, data.id) {
+        Ok(alternative) => match alternative.delete(&conn, user_id) {
+            Ok(_) => HttpResponse::Ok().body("Alternative deleted successfully"),
+            Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+        },
+        Err(_) => HttpResponse::NotFound().body("Alternative not found"),
+    }
+}
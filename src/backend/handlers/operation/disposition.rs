@@ -0,0 +1,35 @@
+use crate::backend::models::{DispositionFilterPayload, DispositionPayload, RollDisposition};
+use actix_web::{HttpResponse, Responder, web};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+
+pub async fn dispose_output_roll(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    data: web::Json<DispositionPayload>,
+    session: actix_session::Session,
+) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
+    if user_id.is_none() {
+        return HttpResponse::Unauthorized().body("User not authenticated");
+    }
+    let mut conn = conn_data.get().unwrap();
+    match RollDisposition::create(&mut conn, &data, user_id.unwrap()) {
+        Ok(disposition) => HttpResponse::Ok().json(disposition),
+        Err(rusqlite::Error::QueryReturnedNoRows) => {
+            HttpResponse::NotFound().body("Output roll not found")
+        }
+        Err(rusqlite::Error::InvalidParameterName(msg)) => HttpResponse::BadRequest().body(msg),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+pub async fn output_roll_dispositions(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    web::Query(data): web::Query<DispositionFilterPayload>,
+) -> impl Responder {
+    let conn = conn_data.get().unwrap();
+    match RollDisposition::for_roll(&conn, data.output_roll_id) {
+        Ok(history) => HttpResponse::Ok().json(history),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
@@ -2,6 +2,7 @@ use crate::backend::models::{
     EndInputRollPayload, IdPayload, InputRoll, InputRollCreatePayload, InputRollFilterPayload,
     InputRollUpdatePayload,
 };
+use crate::sap::SapGateway;
 use actix_web::{HttpResponse, Responder, web};
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
@@ -15,22 +16,30 @@ pub async fn create_input_roll(
     if user_id.is_none() {
         return HttpResponse::Unauthorized().body("User not authenticated");
     }
-    let conn = conn_data.get().unwrap();
-    match InputRoll::create(&conn, &data, user_id.unwrap()) {
+    let mut conn = conn_data.get().unwrap();
+    match InputRoll::create(&mut conn, &data, user_id.unwrap()) {
         Ok(roll) => HttpResponse::Ok().json(roll),
+        Err(rusqlite::Error::InvalidParameterName(msg)) => HttpResponse::BadRequest().body(msg),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
 }
 
 pub async fn update_input_roll(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    sap: web::Data<dyn SapGateway>,
     data: web::Json<InputRollUpdatePayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match InputRoll::find_by_id(&conn, data.id) {
         Ok(mut roll) => {
-            if let Err(e) = roll.update(&conn, &data) {
-                return HttpResponse::InternalServerError().body(e.to_string());
+            match roll.update(&conn, sap.as_ref(), &data, user_id).await {
+                Ok(()) => {}
+                Err(rusqlite::Error::InvalidParameterName(msg)) => {
+                    return HttpResponse::BadRequest().body(msg);
+                }
+                Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
             }
             HttpResponse::Ok().json(roll)
         }
@@ -40,13 +49,20 @@ pub async fn update_input_roll(
 
 pub async fn delete_input_roll(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    sap: web::Data<dyn SapGateway>,
     data: web::Json<IdPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match InputRoll::find_by_id(&conn, data.id) {
         Ok(roll) => {
-            if let Err(e) = roll.delete(&conn) {
-                return HttpResponse::InternalServerError().body(e.to_string());
+            match roll.delete(&conn, sap.as_ref(), user_id).await {
+                Ok(()) => {}
+                Err(rusqlite::Error::InvalidParameterName(msg)) => {
+                    return HttpResponse::BadRequest().body(msg);
+                }
+                Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
             }
             HttpResponse::Ok().body("Input roll deleted successfully")
         }
@@ -88,10 +104,13 @@ pub async fn input_roll_details(
 
 pub async fn end_input_roll(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    sap: web::Data<dyn SapGateway>,
     data: web::Json<EndInputRollPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
-    match InputRoll::end_input_roll(&conn, &data).await {
+    match InputRoll::end_input_roll(&conn, sap.as_ref(), &data, user_id).await {
         Ok(document_number) => HttpResponse::Ok().json(document_number),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
@@ -14,9 +14,10 @@ pub async fn create_job(
     if user_id.is_none() {
         return HttpResponse::Unauthorized().body("User not authenticated");
     }
-    let conn = conn_data.get().unwrap();
-    match Job::create(&conn, &data, user_id.unwrap()) {
+    let mut conn = conn_data.get().unwrap();
+    match Job::create(&mut conn, &data, user_id.unwrap()) {
         Ok(job) => HttpResponse::Ok().json(job),
+        Err(rusqlite::Error::InvalidParameterName(msg)) => HttpResponse::BadRequest().body(msg),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
 }
@@ -24,11 +25,13 @@ pub async fn create_job(
 pub async fn update_job(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<JobUpdatePayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match Job::find_by_id(&conn, data.id) {
         Ok(mut job) => {
-            if let Err(e) = job.update(&conn, &data) {
+            if let Err(e) = job.update(&conn, &data, user_id) {
                 return HttpResponse::InternalServerError().body(e.to_string());
             }
             HttpResponse::Ok().json(job)
@@ -40,11 +43,13 @@ pub async fn update_job(
 pub async fn delete_job(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<IdPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let mut conn = conn_data.get().unwrap();
     match Job::find_by_id(&mut conn, data.id) {
         Ok(job) => {
-            if let Err(e) = job.delete(&mut conn) {
+            if let Err(e) = job.delete(&mut conn, user_id) {
                 return HttpResponse::InternalServerError().body(e.to_string());
             }
             HttpResponse::Ok().body("Job deleted successfully")
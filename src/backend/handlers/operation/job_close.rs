@@ -0,0 +1,38 @@
+use crate::backend::models::{IdPayload, Job, JobClose, JobClosePayload, JobCloseSummary};
+use actix_web::{HttpResponse, Responder, web};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use serde_json::json;
+
+pub async fn close_job(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    data: web::Json<JobClosePayload>,
+    session: actix_session::Session,
+) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
+    let mut conn = conn_data.get().unwrap();
+    let mut job = match Job::find_by_id(&conn, data.id) {
+        Ok(job) => job,
+        Err(_) => return HttpResponse::NotFound().body("Job not found"),
+    };
+    match job.close(&mut conn, user_id) {
+        Ok(JobClose::Closed(summary)) => HttpResponse::Ok().json(summary),
+        Ok(JobClose::Blocked(missing)) => HttpResponse::Conflict().json(json!({
+            "message": format!("Job {} cannot be closed yet", job.production_order),
+            "missing": missing
+        })),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+pub async fn job_summary(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    web::Query(query): web::Query<IdPayload>,
+) -> impl Responder {
+    let conn = conn_data.get().unwrap();
+    match JobCloseSummary::for_job(&conn, query.id) {
+        Ok(summary) => HttpResponse::Ok().json(summary),
+        Err(rusqlite::Error::QueryReturnedNoRows) => HttpResponse::NotFound().body("Job not found"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
@@ -0,0 +1,28 @@
+use crate::backend::models::{RollLineage, TracePayload};
+use actix_web::{HttpResponse, Responder, web};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+
+pub async fn trace_backward(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    web::Query(data): web::Query<TracePayload>,
+) -> impl Responder {
+    let conn = conn_data.get().unwrap();
+    match RollLineage::trace_backward(&conn, data.batch.trim()) {
+        Ok(tree) if tree.is_empty() => HttpResponse::NotFound().body("No roll found for batch"),
+        Ok(tree) => HttpResponse::Ok().json(tree),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+pub async fn trace_forward(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    web::Query(data): web::Query<TracePayload>,
+) -> impl Responder {
+    let conn = conn_data.get().unwrap();
+    match RollLineage::trace_forward(&conn, data.batch.trim()) {
+        Ok(tree) if tree.is_empty() => HttpResponse::NotFound().body("No roll found for batch"),
+        Ok(tree) => HttpResponse::Ok().json(tree),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
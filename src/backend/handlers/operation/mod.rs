@@ -1,7 +1,17 @@
+pub mod bom;
+pub mod disposition;
 pub mod input_rolls;
 pub mod job;
+pub mod job_close;
+pub mod lineage;
 pub mod output_roll;
+pub mod remnant;
 
+pub use bom::*;
+pub use disposition::*;
 pub use input_rolls::*;
 pub use job::*;
+pub use job_close::*;
+pub use lineage::*;
 pub use output_roll::*;
+pub use remnant::*;
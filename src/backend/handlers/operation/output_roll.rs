@@ -1,6 +1,9 @@
+use crate::backend::labels::{self, LabelData, LabelFormat, LabelTemplate};
 use crate::backend::models::{
-    IdPayload, OutputRoll, OutputRollCreatePayload, OutputRollFilterPayload, OutputRollPayload,
+    IdPayload, LabelPayload, LabelPrint, OutputRoll, OutputRollCreatePayload,
+    OutputRollFilterPayload, OutputRollPayload,
 };
+use crate::sap::SapGateway;
 use actix_web::{HttpResponse, Responder, web};
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
@@ -17,19 +20,27 @@ pub async fn create_output_roll(
     let mut conn = conn_data.get().unwrap();
     match OutputRoll::create(&mut conn, &data, user_id.unwrap()) {
         Ok(roll) => HttpResponse::Ok().json(roll),
+        Err(rusqlite::Error::InvalidParameterName(msg)) => HttpResponse::BadRequest().body(msg),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
 }
 
 pub async fn update_output_roll(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    sap: web::Data<dyn SapGateway>,
     data: web::Json<OutputRollPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match OutputRoll::find_by_id(&conn, data.id) {
         Ok(mut roll) => {
-            if let Err(e) = roll.update(&conn, &data).await {
-                return HttpResponse::InternalServerError().body(e.to_string());
+            match roll.update(&conn, sap.as_ref(), &data, user_id).await {
+                Ok(()) => {}
+                Err(rusqlite::Error::InvalidParameterName(msg)) => {
+                    return HttpResponse::BadRequest().body(msg);
+                }
+                Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
             }
             HttpResponse::Ok().json(roll)
         }
@@ -39,13 +50,20 @@ pub async fn update_output_roll(
 
 pub async fn delete_output_roll(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    sap: web::Data<dyn SapGateway>,
     data: web::Json<IdPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match OutputRoll::find_by_id(&conn, data.id) {
         Ok(roll) => {
-            if let Err(e) = roll.delete(&conn) {
-                return HttpResponse::InternalServerError().body(e.to_string());
+            match roll.delete(&conn, sap.as_ref(), user_id).await {
+                Ok(()) => {}
+                Err(rusqlite::Error::InvalidParameterName(msg)) => {
+                    return HttpResponse::BadRequest().body(msg);
+                }
+                Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
             }
             HttpResponse::Ok().body("Output roll deleted successfully")
         }
@@ -63,6 +81,16 @@ pub async fn all_output_rolls(
     }
 }
 
+pub async fn available_output_rolls(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+) -> impl Responder {
+    let conn = conn_data.get().unwrap();
+    match OutputRoll::available_for_input(&conn) {
+        Ok(rolls) => HttpResponse::Ok().json(rolls),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
 pub async fn filter_output_rolls(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     web::Query(filter): web::Query<OutputRollFilterPayload>,
@@ -84,3 +112,56 @@ pub async fn output_roll_details(
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
 }
+
+pub async fn output_roll_label(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    web::Query(data): web::Query<LabelPayload>,
+    session: actix_session::Session,
+) -> impl Responder {
+    let format = match LabelFormat::parse(data.format.as_deref()) {
+        Ok(format) => format,
+        Err(msg) => return HttpResponse::BadRequest().body(msg),
+    };
+    let template = match LabelTemplate::load() {
+        Ok(template) => template,
+        Err(msg) => return HttpResponse::InternalServerError().body(msg),
+    };
+    let conn = conn_data.get().unwrap();
+    let details = match OutputRoll::get_details(&conn, data.id) {
+        Ok(details) => details,
+        Err(rusqlite::Error::QueryReturnedNoRows) => {
+            return HttpResponse::NotFound().body("Output roll not found");
+        }
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let reprint = match LabelPrint::count(&conn, data.id) {
+        Ok(reprint) => reprint,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let filename = format!("label-{}.{}", details.output_batch, format.as_str());
+    let label = LabelData {
+        roll_id: data.id,
+        details,
+        reprint,
+        remnant: false,
+    };
+    let bytes = match labels::render(&template, &label, format) {
+        Ok(bytes) => bytes,
+        Err(msg) => return HttpResponse::InternalServerError().body(msg),
+    };
+    // Only a label that was rendered for the print button counts as printed
+    if data.print {
+        let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
+        if let Err(e) = LabelPrint::record(&conn, data.id, format.as_str(), user_id) {
+            return HttpResponse::InternalServerError().body(e.to_string());
+        }
+    }
+    HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((
+            "Content-Disposition",
+            format!("inline; filename=\"{}\"", filename),
+        ))
+        .insert_header(("X-Reprint", reprint.to_string()))
+        .body(bytes)
+}
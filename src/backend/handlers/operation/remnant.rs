@@ -0,0 +1,86 @@
+use crate::backend::labels::{self, LabelData, LabelFormat, LabelTemplate};
+use crate::backend::models::{EndWithRemnantPayload, LabelPayload, Remnant, RemnantFilterPayload};
+use crate::sap::SapGateway;
+use actix_web::{HttpResponse, Responder, web};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+
+pub async fn end_input_roll_with_remnant(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    sap: web::Data<dyn SapGateway>,
+    data: web::Json<EndWithRemnantPayload>,
+    session: actix_session::Session,
+) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
+    let conn = conn_data.get().unwrap();
+    match Remnant::end_input_roll(&conn, sap.as_ref(), &data, user_id).await {
+        Ok(ended) => HttpResponse::Ok().json(ended),
+        Err(rusqlite::Error::InvalidParameterName(msg)) => HttpResponse::BadRequest().body(msg),
+        Err(rusqlite::Error::QueryReturnedNoRows) => {
+            HttpResponse::NotFound().body("Input roll not found")
+        }
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+pub async fn available_remnants(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    web::Query(filter): web::Query<RemnantFilterPayload>,
+) -> impl Responder {
+    let conn = conn_data.get().unwrap();
+    match Remnant::available(&conn, &filter) {
+        Ok(remnants) => HttpResponse::Ok().json(remnants),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+pub async fn remnant_label(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    web::Query(data): web::Query<LabelPayload>,
+) -> impl Responder {
+    let format = match LabelFormat::parse(data.format.as_deref()) {
+        Ok(format) => format,
+        Err(msg) => return HttpResponse::BadRequest().body(msg),
+    };
+    let template = match LabelTemplate::load() {
+        Ok(template) => template,
+        Err(msg) => return HttpResponse::InternalServerError().body(msg),
+    };
+    let conn = conn_data.get().unwrap();
+    let remnant = match Remnant::find_by_id(&conn, data.id) {
+        Ok(remnant) => remnant,
+        Err(rusqlite::Error::QueryReturnedNoRows) => {
+            return HttpResponse::NotFound().body("Remnant not found");
+        }
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let details = match remnant.label_details(&conn) {
+        Ok(details) => details,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let reprint = remnant.label_prints;
+    let filename = format!("remnant-{}.{}", remnant.batch, format.as_str());
+    let label = LabelData {
+        roll_id: remnant.id,
+        details,
+        reprint,
+        remnant: true,
+    };
+    let bytes = match labels::render(&template, &label, format) {
+        Ok(bytes) => bytes,
+        Err(msg) => return HttpResponse::InternalServerError().body(msg),
+    };
+    if data.print
+        && let Err(e) = Remnant::record_print(&conn, remnant.id)
+    {
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
+    HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((
+            "Content-Disposition",
+            format!("inline; filename=\"{}\"", filename),
+        ))
+        .insert_header(("X-Reprint", reprint.to_string()))
+        .body(bytes)
+}
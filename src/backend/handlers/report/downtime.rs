@@ -1,7 +1,7 @@
 use actix_web::{web, HttpResponse, Responder};
 use r2d2_sqlite::SqliteConnectionManager;
 use r2d2::Pool;
-use crate::backend::models::{Downtime, DowntimeCreatePayload, DowntimePayload, IdPayload, DowntimeFilterPayload};
+use crate::backend::models::{Downtime, DowntimeCreatePayload, DowntimePayload, DowntimeClosePayload, IdPayload, DowntimeFilterPayload};
 
 pub async fn create_downtime(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<DowntimeCreatePayload>, session: actix_session::Session) -> impl Responder {
     let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
@@ -12,26 +12,48 @@ pub async fn create_downtime(conn_data: web::Data<Pool<SqliteConnectionManager>>
     let conn = conn_data.get().unwrap();
     match Downtime::create(&conn, &data, user_id.unwrap()) {
         Ok(downtime) => HttpResponse::Ok().json(downtime),
+        Err(rusqlite::Error::InvalidParameterName(msg)) => HttpResponse::BadRequest().body(msg),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
 }
 
-pub async fn update_downtime(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<DowntimePayload>) -> impl Responder {
+pub async fn update_downtime(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<DowntimePayload>, session: actix_session::Session) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match Downtime::find_by_id(&conn, data.id) {
         Ok(mut downtime) => {
-            if let Err(e) = downtime.update(&conn, &data) { return HttpResponse::InternalServerError().body(e.to_string()); }
-            HttpResponse::Ok().json(downtime)
+            match downtime.update(&conn, &data, user_id) {
+                Ok(()) => HttpResponse::Ok().json(downtime),
+                Err(rusqlite::Error::InvalidParameterName(msg)) => HttpResponse::BadRequest().body(msg),
+                Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+            }
         }
         Err(_) => HttpResponse::NotFound().body("Downtime not found"),
     }
 }
 
-pub async fn delete_downtime(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<IdPayload>) -> impl Responder {
+pub async fn close_downtime(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<DowntimeClosePayload>, session: actix_session::Session) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
+    let conn = conn_data.get().unwrap();
+    match Downtime::find_by_id(&conn, data.id) {
+        Ok(mut downtime) => {
+            let data = data.into_inner();
+            match downtime.close(&conn, data.end_time, user_id) {
+                Ok(()) => HttpResponse::Ok().json(downtime),
+                Err(rusqlite::Error::InvalidParameterName(msg)) => HttpResponse::BadRequest().body(msg),
+                Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+            }
+        }
+        Err(_) => HttpResponse::NotFound().body("Downtime not found"),
+    }
+}
+
+pub async fn delete_downtime(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<IdPayload>, session: actix_session::Session) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match Downtime::find_by_id(&conn, data.id) {
         Ok(downtime) => {
-            if let Err(e) = downtime.delete(&conn) { return HttpResponse::InternalServerError().body(e.to_string()); }
+            if let Err(e) = downtime.delete(&conn, user_id) { return HttpResponse::InternalServerError().body(e.to_string()); }
             HttpResponse::Ok().body("Downtime deleted successfully")
         }
         Err(_) => HttpResponse::NotFound().body("Downtime not found")
@@ -16,22 +16,24 @@ pub async fn create_ink_usage(conn_data: web::Data<Pool<SqliteConnectionManager>
     }
 }
 
-pub async fn update_ink_usage(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<ActualInkUsagePayload>) -> impl Responder {
+pub async fn update_ink_usage(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<ActualInkUsagePayload>, session: actix_session::Session) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match ActualInkUsage::find_by_id(&conn, data.id) {
         Ok(mut ink_usage) => {
-            if let Err(e) = ink_usage.update(&conn, &data) { return HttpResponse::InternalServerError().body(e.to_string()); }
+            if let Err(e) = ink_usage.update(&conn, &data, user_id) { return HttpResponse::InternalServerError().body(e.to_string()); }
             HttpResponse::Ok().json(ink_usage)
         }
         Err(_) => HttpResponse::NotFound().body("Ink usage not found"),
     }
 }
 
-pub async fn delete_ink_usage(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<IdPayload>) -> impl Responder {
+pub async fn delete_ink_usage(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<IdPayload>, session: actix_session::Session) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match ActualInkUsage::find_by_id(&conn, data.id) {
         Ok(ink_usage) => {
-            if let Err(e) = ink_usage.delete(&conn) { return HttpResponse::InternalServerError().body(e.to_string()); }
+            if let Err(e) = ink_usage.delete(&conn, user_id) { return HttpResponse::InternalServerError().body(e.to_string()); }
             HttpResponse::Ok().body("Ink usage deleted successfully")
         }
         Err(_) => HttpResponse::NotFound().body("Ink usage not found")
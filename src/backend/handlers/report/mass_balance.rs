@@ -0,0 +1,29 @@
+use crate::backend::models::{MassBalanceFilterPayload, MassBalanceJobQuery, MassBalanceReport};
+use actix_web::{HttpResponse, Responder, web};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+
+pub async fn mass_balance_report(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    web::Query(filter): web::Query<MassBalanceFilterPayload>,
+) -> impl Responder {
+    let conn = conn_data.get().unwrap();
+    match MassBalanceReport::calculate(&conn, &filter) {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(rusqlite::Error::InvalidParameterName(msg)) => HttpResponse::BadRequest().body(msg),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+pub async fn mass_balance_job(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    web::Query(query): web::Query<MassBalanceJobQuery>,
+) -> impl Responder {
+    let conn = conn_data.get().unwrap();
+    match MassBalanceReport::job_detail(&conn, &query) {
+        Ok(detail) => HttpResponse::Ok().json(detail),
+        Err(rusqlite::Error::QueryReturnedNoRows) => HttpResponse::NotFound().body("Job not found"),
+        Err(rusqlite::Error::InvalidParameterName(msg)) => HttpResponse::BadRequest().body(msg),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
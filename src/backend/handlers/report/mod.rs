@@ -1,9 +1,13 @@
 pub mod downtime;
 pub mod ink_usage;
+pub mod mass_balance;
+pub mod oee;
 pub mod scrap;
 pub mod solvent_usage;
 
 pub use downtime::*;
 pub use ink_usage::*;
+pub use mass_balance::*;
+pub use oee::*;
 pub use scrap::*;
 pub use solvent_usage::*;
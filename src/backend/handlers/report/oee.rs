@@ -0,0 +1,16 @@
+use crate::backend::models::{OeeFilterPayload, OeeReport};
+use actix_web::{HttpResponse, Responder, web};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+
+pub async fn oee_report(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    web::Query(filter): web::Query<OeeFilterPayload>,
+) -> impl Responder {
+    let conn = conn_data.get().unwrap();
+    match OeeReport::calculate(&conn, &filter) {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(rusqlite::Error::InvalidParameterName(msg)) => HttpResponse::BadRequest().body(msg),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
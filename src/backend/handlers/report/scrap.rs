@@ -16,22 +16,24 @@ pub async fn create_scrap(conn_data: web::Data<Pool<SqliteConnectionManager>>, d
     }
 }
 
-pub async fn update_scrap(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<ScrapPayload>) -> impl Responder {
+pub async fn update_scrap(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<ScrapPayload>, session: actix_session::Session) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match Scrap::find_by_id(&conn, data.id) {
         Ok(mut scrap) => {
-            if let Err(e) = scrap.update(&conn, &data) { return HttpResponse::InternalServerError().body(e.to_string()); }
+            if let Err(e) = scrap.update(&conn, &data, user_id) { return HttpResponse::InternalServerError().body(e.to_string()); }
             HttpResponse::Ok().json(scrap)
         }
         Err(_) => HttpResponse::NotFound().body("Scrap not found"),
     }
 }
 
-pub async fn delete_scrap(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<IdPayload>) -> impl Responder {
+pub async fn delete_scrap(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<IdPayload>, session: actix_session::Session) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match Scrap::find_by_id(&conn, data.id) {
         Ok(scrap) => {
-            if let Err(e) = scrap.delete(&conn) { return HttpResponse::InternalServerError().body(e.to_string()); }
+            if let Err(e) = scrap.delete(&conn, user_id) { return HttpResponse::InternalServerError().body(e.to_string()); }
             HttpResponse::Ok().body("Scrap deleted successfully")
         }
         Err(_) => HttpResponse::NotFound().body("Scrap not found")
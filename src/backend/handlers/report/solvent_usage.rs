@@ -16,22 +16,24 @@ pub async fn create_solvent_usage(conn_data: web::Data<Pool<SqliteConnectionMana
     }
 }
 
-pub async fn update_solvent_usage(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<ActualSolventUsagePayload>) -> impl Responder {
+pub async fn update_solvent_usage(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<ActualSolventUsagePayload>, session: actix_session::Session) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match ActualSolventUsage::find_by_id(&conn, data.id) {
         Ok(mut solvent_usage) => {
-            if let Err(e) = solvent_usage.update(&conn, &data) { return HttpResponse::InternalServerError().body(e.to_string()); }
+            if let Err(e) = solvent_usage.update(&conn, &data, user_id) { return HttpResponse::InternalServerError().body(e.to_string()); }
             HttpResponse::Ok().json(solvent_usage)
         }
         Err(_) => HttpResponse::NotFound().body("Solvent usage not found"),
     }
 }
 
-pub async fn delete_solvent_usage(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<IdPayload>) -> impl Responder {
+pub async fn delete_solvent_usage(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<IdPayload>, session: actix_session::Session) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match ActualSolventUsage::find_by_id(&conn, data.id) {
         Ok(solvent_usage) => {
-            if let Err(e) = solvent_usage.delete(&conn) { return HttpResponse::InternalServerError().body(e.to_string()); }
+            if let Err(e) = solvent_usage.delete(&conn, user_id) { return HttpResponse::InternalServerError().body(e.to_string()); }
             HttpResponse::Ok().body("Solvent usage deleted successfully")
         }
         Err(_) => HttpResponse::NotFound().body("Solvent usage not found")
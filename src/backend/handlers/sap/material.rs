@@ -8,9 +8,11 @@ use r2d2_sqlite::SqliteConnectionManager;
 pub async fn create_material(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<MaterialCreatePayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
-    match Material::create(&conn, &data) {
+    match Material::create(&conn, &data, user_id) {
         Ok(material) => HttpResponse::Ok().json(material),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
@@ -19,11 +21,13 @@ pub async fn create_material(
 pub async fn update_material(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<MaterialPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match Material::find_by_id(&conn, data.id) {
         Ok(mut material) => {
-            if let Err(e) = material.update(&conn, &data) {
+            if let Err(e) = material.update(&conn, &data, user_id) {
                 return HttpResponse::InternalServerError().body(e.to_string());
             }
             HttpResponse::Ok().json(material)
@@ -35,11 +39,13 @@ pub async fn update_material(
 pub async fn delete_material(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<IdPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match Material::find_by_id(&conn, data.id) {
         Ok(material) => {
-            if let Err(e) = material.delete(&conn) {
+            if let Err(e) = material.delete(&conn, user_id) {
                 return HttpResponse::InternalServerError().body(e.to_string());
             }
             HttpResponse::Ok().body("Material deleted successfully")
@@ -1,5 +1,7 @@
 pub mod material;
+pub mod outbox;
 pub mod process_order;
 
 pub use material::*;
+pub use outbox::*;
 pub use process_order::*;
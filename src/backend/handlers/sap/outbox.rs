@@ -0,0 +1,100 @@
+use crate::backend::models::{
+    IdPayload, SapOutbox, SapOutboxFilterPayload, SapReversal, SapReversalFilterPayload,
+};
+use crate::sap::{SapGateway, deliver_now};
+use actix_web::{HttpResponse, Responder, web};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+
+pub async fn filter_sap_outbox(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    web::Query(filter): web::Query<SapOutboxFilterPayload>,
+) -> impl Responder {
+    let conn = conn_data.get().unwrap();
+    match SapOutbox::filter(&conn, &filter) {
+        Ok(messages) => HttpResponse::Ok().json(messages),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+pub async fn sap_outbox_stats(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+) -> impl Responder {
+    let conn = conn_data.get().unwrap();
+    match SapOutbox::stats(&conn) {
+        Ok(stats) => HttpResponse::Ok().json(stats),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+pub async fn retry_sap_outbox(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    sap: web::Data<dyn SapGateway>,
+    data: web::Json<IdPayload>,
+    session: actix_session::Session,
+) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
+    let conn = conn_data.get().unwrap();
+    match SapOutbox::find_by_id(&conn, data.id) {
+        Ok(mut message) => {
+            if let Err(e) = message.retry(&conn, user_id) {
+                return HttpResponse::BadRequest().body(e.to_string());
+            }
+            match deliver_now(&conn, sap.as_ref(), message.id).await {
+                Ok(message) => HttpResponse::Ok().json(message),
+                Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+            }
+        }
+        Err(_) => HttpResponse::NotFound().body("Outbox message not found"),
+    }
+}
+
+pub async fn cancel_sap_outbox(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    data: web::Json<IdPayload>,
+    session: actix_session::Session,
+) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
+    let conn = conn_data.get().unwrap();
+    match SapOutbox::find_by_id(&conn, data.id) {
+        Ok(mut message) => match message.cancel(&conn, user_id) {
+            Ok(_) => HttpResponse::Ok().json(message),
+            Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+        },
+        Err(_) => HttpResponse::NotFound().body("Outbox message not found"),
+    }
+}
+
+pub async fn filter_sap_reversals(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    web::Query(filter): web::Query<SapReversalFilterPayload>,
+) -> impl Responder {
+    let conn = conn_data.get().unwrap();
+    match SapReversal::filter(&conn, &filter) {
+        Ok(reversals) => HttpResponse::Ok().json(reversals),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+pub async fn repost_sap_reversal(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    sap: web::Data<dyn SapGateway>,
+    data: web::Json<IdPayload>,
+    session: actix_session::Session,
+) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
+    let conn = conn_data.get().unwrap();
+    match SapReversal::find_by_id(&conn, data.id) {
+        Ok(mut reversal) => {
+            let message = match reversal.repost(&conn, user_id) {
+                Ok(message) => message,
+                Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+            };
+            match deliver_now(&conn, sap.as_ref(), message.id).await {
+                Ok(message) => HttpResponse::Ok().json(message),
+                Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+            }
+        }
+        Err(_) => HttpResponse::NotFound().body("Reversal not found"),
+    }
+}
@@ -1,11 +1,12 @@
 use crate::backend::models::{ProcessOrder, ProcessOrderFilterPayload};
-use crate::sap::{materials, pso};
+use crate::sap::{SapGateway, materials, pso};
 use actix_web::{HttpResponse, Responder, web};
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
 
 pub async fn get_process_orders(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    sap: web::Data<dyn SapGateway>,
     web::Query(filter): web::Query<ProcessOrderFilterPayload>,
 ) -> impl Responder {
     let conn = conn_data.get().unwrap();
@@ -15,10 +16,10 @@ pub async fn get_process_orders(
             let should_resync = orders_response.total_count == 0;
 
             if should_resync {
-                if let Err(e) = pso::sync_process_orders(&conn_data).await {
+                if let Err(e) = pso::sync_process_orders(&conn_data, sap.as_ref()).await {
                     eprintln!("Failed to sync process orders: {}", e);
                 } else {
-                    if let Err(e) = materials::sync_material_codes(&conn_data).await {
+                    if let Err(e) = materials::sync_material_codes(&conn_data, sap.as_ref()).await {
                         eprintln!("Failed to sync material codes: {}", e);
                     }
                 }
@@ -32,11 +33,11 @@ pub async fn get_process_orders(
             }
         }
         Err(e) => {
-            if let Err(_) = pso::sync_process_orders(&conn_data).await {
+            if let Err(_) = pso::sync_process_orders(&conn_data, sap.as_ref()).await {
                 return HttpResponse::InternalServerError().body(e.to_string());
             }
 
-            if let Err(e) = materials::sync_material_codes(&conn_data).await {
+            if let Err(e) = materials::sync_material_codes(&conn_data, sap.as_ref()).await {
                 eprintln!("Failed to sync material codes: {}", e);
             }
 
@@ -5,24 +5,33 @@ use r2d2_sqlite::SqliteConnectionManager;
 
 pub async fn create_shift(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
-    data: web::Json<LookupCreatePayload>,
+    data: web::Json<ShiftCreatePayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
-    match Shift::create(&conn, &data) {
+    match Shift::create(&conn, &data, user_id) {
         Ok(shift) => HttpResponse::Ok().json(shift),
+        Err(rusqlite::Error::InvalidParameterName(msg)) => HttpResponse::BadRequest().body(msg),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
 }
 
 pub async fn update_shift(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
-    data: web::Json<LookupPayload>,
+    data: web::Json<ShiftPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match Shift::find_by_id(&conn, data.id) {
         Ok(mut shift) => {
-            if let Err(e) = shift.update(&conn, &data) {
-                return HttpResponse::InternalServerError().body(e.to_string());
+            match shift.update(&conn, &data, user_id) {
+                Ok(()) => {}
+                Err(rusqlite::Error::InvalidParameterName(msg)) => {
+                    return HttpResponse::BadRequest().body(msg);
+                }
+                Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
             }
             HttpResponse::Ok().json(shift)
         }
@@ -33,7 +42,9 @@ pub async fn update_shift(
 pub async fn delete_shift(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<IdPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match Shift::find_by_id(&conn, data.id) {
         Ok(shift) => match Shift::has_related_records(&conn, data.id) {
@@ -41,7 +52,7 @@ pub async fn delete_shift(
                 return HttpResponse::BadRequest().body("Cannot delete shift with existing records");
             }
             Ok(false) => {
-                if let Err(e) = shift.delete(&conn) {
+                if let Err(e) = shift.delete(&conn, user_id) {
                     return HttpResponse::InternalServerError().body(e.to_string());
                 }
                 HttpResponse::Ok().body("Shift deleted successfully")
@@ -60,12 +71,71 @@ pub async fn all_shifts(conn_data: web::Data<Pool<SqliteConnectionManager>>) ->
     }
 }
 
+/// The calendar shift at `at` (default now), `null` when none is scheduled.
+pub async fn current_shift(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    query: web::Query<CurrentShiftPayload>,
+) -> impl Responder {
+    let at = match query.at.as_deref().filter(|v| !v.trim().is_empty()) {
+        Some(value) => match parse_local_datetime(value) {
+            Some(at) => at,
+            None => return HttpResponse::BadRequest().body("Invalid date and time"),
+        },
+        None => chrono::Local::now().naive_local(),
+    };
+    let conn = conn_data.get().unwrap();
+    match Shift::current(&conn, at) {
+        Ok(slot) => HttpResponse::Ok().json(slot),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+pub async fn create_holiday(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    data: web::Json<PlantHolidayCreatePayload>,
+    session: actix_session::Session,
+) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
+    let conn = conn_data.get().unwrap();
+    match PlantHoliday::create(&conn, &data, user_id) {
+        Ok(holiday) => HttpResponse::Ok().json(holiday),
+        Err(rusqlite::Error::InvalidParameterName(msg)) => HttpResponse::BadRequest().body(msg),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+pub async fn delete_holiday(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    data: web::Json<IdPayload>,
+    session: actix_session::Session,
+) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
+    let conn = conn_data.get().unwrap();
+    match PlantHoliday::find_by_id(&conn, data.id) {
+        Ok(holiday) => match holiday.delete(&conn, user_id) {
+            Ok(()) => HttpResponse::Ok().body("Holiday deleted successfully"),
+            Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+        },
+        Err(_) => HttpResponse::NotFound().body("Holiday not found"),
+    }
+}
+
+pub async fn all_holidays(conn_data: web::Data<Pool<SqliteConnectionManager>>) -> impl Responder {
+    let conn = conn_data.get().unwrap();
+    match PlantHoliday::all(&conn) {
+        Ok(holidays) => HttpResponse::Ok().json(holidays),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
 pub async fn create_colour(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<LookupCreatePayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
-    match Colour::create(&conn, &data) {
+    match Colour::create(&conn, &data, user_id) {
         Ok(colour) => HttpResponse::Ok().json(colour),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
@@ -74,11 +144,13 @@ pub async fn create_colour(
 pub async fn update_colour(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<LookupPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match Colour::find_by_id(&conn, data.id) {
         Ok(mut colour) => {
-            if let Err(e) = colour.update(&conn, &data) {
+            if let Err(e) = colour.update(&conn, &data, user_id) {
                 return HttpResponse::InternalServerError().body(e.to_string());
             }
             HttpResponse::Ok().json(colour)
@@ -90,7 +162,9 @@ pub async fn update_colour(
 pub async fn delete_colour(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<IdPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match Colour::find_by_id(&conn, data.id) {
         Ok(colour) => match Colour::has_related_records(&conn, data.id) {
@@ -99,7 +173,7 @@ pub async fn delete_colour(
                     .body("Cannot delete colour with existing ink usage records");
             }
             Ok(false) => {
-                if let Err(e) = colour.delete(&conn) {
+                if let Err(e) = colour.delete(&conn, user_id) {
                     return HttpResponse::InternalServerError().body(e.to_string());
                 }
                 HttpResponse::Ok().body("Colour deleted successfully")
@@ -121,9 +195,11 @@ pub async fn all_colours(conn_data: web::Data<Pool<SqliteConnectionManager>>) ->
 pub async fn create_solvent_type(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<LookupCreatePayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
-    match SolventType::create(&conn, &data) {
+    match SolventType::create(&conn, &data, user_id) {
         Ok(solvent_type) => HttpResponse::Ok().json(solvent_type),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
@@ -132,11 +208,13 @@ pub async fn create_solvent_type(
 pub async fn update_solvent_type(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<LookupPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match SolventType::find_by_id(&conn, data.id) {
         Ok(mut solvent_type) => {
-            if let Err(e) = solvent_type.update(&conn, &data) {
+            if let Err(e) = solvent_type.update(&conn, &data, user_id) {
                 return HttpResponse::InternalServerError().body(e.to_string());
             }
             HttpResponse::Ok().json(solvent_type)
@@ -148,7 +226,9 @@ pub async fn update_solvent_type(
 pub async fn delete_solvent_type(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<IdPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match SolventType::find_by_id(&conn, data.id) {
         Ok(solvent_type) => match SolventType::has_related_records(&conn, data.id) {
@@ -157,7 +237,7 @@ pub async fn delete_solvent_type(
                     .body("Cannot delete solvent type with existing usage records");
             }
             Ok(false) => {
-                if let Err(e) = solvent_type.delete(&conn) {
+                if let Err(e) = solvent_type.delete(&conn, user_id) {
                     return HttpResponse::InternalServerError().body(e.to_string());
                 }
                 HttpResponse::Ok().body("Solvent type deleted successfully")
@@ -181,9 +261,11 @@ pub async fn all_solvent_types(
 pub async fn create_scrap_type(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<LookupCreatePayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
-    match ScrapType::create(&conn, &data) {
+    match ScrapType::create(&conn, &data, user_id) {
         Ok(scrap_type) => HttpResponse::Ok().json(scrap_type),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
@@ -192,11 +274,13 @@ pub async fn create_scrap_type(
 pub async fn update_scrap_type(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<LookupPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match ScrapType::find_by_id(&conn, data.id) {
         Ok(mut scrap_type) => {
-            if let Err(e) = scrap_type.update(&conn, &data) {
+            if let Err(e) = scrap_type.update(&conn, &data, user_id) {
                 return HttpResponse::InternalServerError().body(e.to_string());
             }
             HttpResponse::Ok().json(scrap_type)
@@ -208,7 +292,9 @@ pub async fn update_scrap_type(
 pub async fn delete_scrap_type(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<IdPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match ScrapType::find_by_id(&conn, data.id) {
         Ok(scrap_type) => match ScrapType::has_related_records(&conn, data.id) {
@@ -217,7 +303,7 @@ pub async fn delete_scrap_type(
                     .body("Cannot delete scrap type with existing scrap records");
             }
             Ok(false) => {
-                if let Err(e) = scrap_type.delete(&conn) {
+                if let Err(e) = scrap_type.delete(&conn, user_id) {
                     return HttpResponse::InternalServerError().body(e.to_string());
                 }
                 HttpResponse::Ok().body("Scrap type deleted successfully")
@@ -241,9 +327,11 @@ pub async fn all_scrap_types(
 pub async fn create_downtime_reason(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<LookupCreatePayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
-    match DowntimeReason::create(&conn, &data) {
+    match DowntimeReason::create(&conn, &data, user_id) {
         Ok(downtime_reason) => HttpResponse::Ok().json(downtime_reason),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
@@ -252,11 +340,13 @@ pub async fn create_downtime_reason(
 pub async fn update_downtime_reason(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<LookupPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match DowntimeReason::find_by_id(&conn, data.id) {
         Ok(mut downtime_reason) => {
-            if let Err(e) = downtime_reason.update(&conn, &data) {
+            if let Err(e) = downtime_reason.update(&conn, &data, user_id) {
                 return HttpResponse::InternalServerError().body(e.to_string());
             }
             HttpResponse::Ok().json(downtime_reason)
@@ -268,7 +358,9 @@ pub async fn update_downtime_reason(
 pub async fn delete_downtime_reason(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<IdPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match DowntimeReason::find_by_id(&conn, data.id) {
         Ok(downtime_reason) => match DowntimeReason::has_related_records(&conn, data.id) {
@@ -277,7 +369,7 @@ pub async fn delete_downtime_reason(
                     .body("Cannot delete downtime reason with existing downtime records");
             }
             Ok(false) => {
-                if let Err(e) = downtime_reason.delete(&conn) {
+                if let Err(e) = downtime_reason.delete(&conn, user_id) {
                     return HttpResponse::InternalServerError().body(e.to_string());
                 }
                 HttpResponse::Ok().body("Downtime reason deleted successfully")
@@ -302,10 +394,12 @@ pub async fn create_flag_reason(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<LookupCreatePayload>,
     query: web::Query<std::collections::HashMap<String, String>>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     let section_id = query.get("section_id").and_then(|s| s.parse::<i32>().ok());
-    match FlagReason::create(&conn, &data, section_id) {
+    match FlagReason::create(&conn, &data, section_id, user_id) {
         Ok(flag_reason) => HttpResponse::Ok().json(flag_reason),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
@@ -314,11 +408,13 @@ pub async fn create_flag_reason(
 pub async fn update_flag_reason(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<LookupPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match FlagReason::find_by_id(&conn, data.id) {
         Ok(mut flag_reason) => {
-            if let Err(e) = flag_reason.update(&conn, &data) {
+            if let Err(e) = flag_reason.update(&conn, &data, user_id) {
                 return HttpResponse::InternalServerError().body(e.to_string());
             }
             HttpResponse::Ok().json(flag_reason)
@@ -330,7 +426,9 @@ pub async fn update_flag_reason(
 pub async fn delete_flag_reason(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<IdPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match FlagReason::find_by_id(&conn, data.id) {
         Ok(flag_reason) => match FlagReason::has_related_records(&conn, data.id) {
@@ -339,7 +437,7 @@ pub async fn delete_flag_reason(
                     .body("Cannot delete flag reason with existing roll records");
             }
             Ok(false) => {
-                if let Err(e) = flag_reason.delete(&conn) {
+                if let Err(e) = flag_reason.delete(&conn, user_id) {
                     return HttpResponse::InternalServerError().body(e.to_string());
                 }
                 HttpResponse::Ok().body("Flag reason deleted successfully")
@@ -375,9 +473,11 @@ pub async fn flag_reasons_by_section(
 pub async fn add_po_code_section(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<POCodeSectionPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
-    match POCodeSection::create(&conn, data.po_code_id, data.section_id) {
+    match POCodeSection::create(&conn, data.po_code_id, data.section_id, user_id) {
         Ok(_) => HttpResponse::Ok().body("POCode section added successfully"),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
@@ -386,9 +486,11 @@ pub async fn add_po_code_section(
 pub async fn remove_po_code_section(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<POCodeSectionPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
-    match POCodeSection::delete(&conn, data.po_code_id, data.section_id) {
+    match POCodeSection::delete(&conn, data.po_code_id, data.section_id, user_id) {
         Ok(_) => HttpResponse::Ok().body("POCode section removed successfully"),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
@@ -408,9 +510,11 @@ pub async fn get_po_code_sections(
 pub async fn create_po_code(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<LookupCreatePayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
-    match POCode::create(&conn, &data) {
+    match POCode::create(&conn, &data, user_id) {
         Ok(po_code) => HttpResponse::Ok().json(po_code),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
@@ -419,11 +523,13 @@ pub async fn create_po_code(
 pub async fn update_po_code(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<LookupPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match POCode::find_by_id(&conn, data.id) {
         Ok(mut po_code) => {
-            if let Err(e) = po_code.update(&conn, &data) {
+            if let Err(e) = po_code.update(&conn, &data, user_id) {
                 return HttpResponse::InternalServerError().body(e.to_string());
             }
             HttpResponse::Ok().json(po_code)
@@ -435,7 +541,9 @@ pub async fn update_po_code(
 pub async fn delete_po_code(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<IdPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match POCode::find_by_id(&conn, data.id) {
         Ok(po_code) => match POCode::has_related_records(&conn, data.id) {
@@ -444,7 +552,7 @@ pub async fn delete_po_code(
                     .body("Cannot delete PO Code with existing records");
             }
             Ok(false) => {
-                if let Err(e) = po_code.delete(&conn) {
+                if let Err(e) = po_code.delete(&conn, user_id) {
                     return HttpResponse::InternalServerError().body(e.to_string());
                 }
                 HttpResponse::Ok().body("PO Code deleted successfully")
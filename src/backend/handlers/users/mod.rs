@@ -2,10 +2,12 @@ pub mod lookup;
 pub mod permission;
 pub mod role;
 pub mod section;
+pub mod session;
 pub mod user;
 
 pub use lookup::*;
 pub use permission::*;
 pub use role::*;
 pub use section::*;
+pub use session::*;
 pub use user::*;
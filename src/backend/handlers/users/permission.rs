@@ -6,9 +6,11 @@ use r2d2_sqlite::SqliteConnectionManager;
 pub async fn create_permission(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<PermissionPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
-    match Permission::create(&conn, &data) {
+    match Permission::create(&conn, &data, user_id) {
         Ok(p) => HttpResponse::Ok().json(p),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
@@ -17,11 +19,13 @@ pub async fn create_permission(
 pub async fn update_permission(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<PermissionUpdatePayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let mut conn = conn_data.get().unwrap();
     let perm_list = Permission::all(&mut conn).unwrap();
     if let Some(mut p) = perm_list.into_iter().find(|p| p.id == data.id) {
-        p.update(&conn, &data).unwrap();
+        p.update(&conn, &data, user_id).unwrap();
         HttpResponse::Ok().json(p)
     } else {
         HttpResponse::NotFound().body("Permission not found")
@@ -31,7 +35,9 @@ pub async fn update_permission(
 pub async fn delete_permission(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<IdPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match Permission::find_by_id(&conn, data.id) {
         Ok(found) => {
@@ -49,7 +55,7 @@ pub async fn delete_permission(
                     return HttpResponse::InternalServerError().body(e.to_string());
                 }
             }
-            if let Err(e) = Permission::delete(&conn, data.id) {
+            if let Err(e) = Permission::delete(&conn, data.id, user_id) {
                 return HttpResponse::InternalServerError().body(e.to_string());
             }
             HttpResponse::Ok().body("Permission deleted successfully.")
@@ -6,9 +6,11 @@ use r2d2_sqlite::SqliteConnectionManager;
 pub async fn create_role(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<RolePayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
-    match Role::create(&conn, &data) {
+    match Role::create(&conn, &data, user_id) {
         Ok(r) => HttpResponse::Ok().json(r),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
@@ -17,11 +19,13 @@ pub async fn create_role(
 pub async fn update_role(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<RoleUpdatePayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match Role::find_by_id(&conn, data.id) {
         Ok(mut r) => {
-            r.update(&conn, &data).unwrap();
+            r.update(&conn, &data, user_id).unwrap();
             HttpResponse::Ok().json(r)
         }
         Err(_) => HttpResponse::NotFound().body("Role not found"),
@@ -31,11 +35,13 @@ pub async fn update_role(
 pub async fn delete_role(
     conn_data: web::Data<Pool<SqliteConnectionManager>>,
     data: web::Json<IdPayload>,
+    session: actix_session::Session,
 ) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let mut conn = conn_data.get().unwrap();
     match Role::find_by_id(&mut conn, data.id) {
         Ok(r) => {
-            if let Err(e) = r.delete(&mut conn) {
+            if let Err(e) = r.delete(&mut conn, user_id) {
                 return HttpResponse::InternalServerError().body(e.to_string());
             }
             HttpResponse::Ok().body("Role deleted successfully.")
@@ -3,33 +3,41 @@ use r2d2_sqlite::SqliteConnectionManager;
 use r2d2::Pool;
 use crate::backend::models::*;
 
-pub async fn create_section(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<SectionCreatePayload>) -> impl Responder {
+pub async fn create_section(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<SectionCreatePayload>, session: actix_session::Session) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
-    match Section::create(&conn, &data) {
+    match Section::create(&conn, &data, user_id) {
         Ok(section) => HttpResponse::Ok().json(section),
+        Err(rusqlite::Error::InvalidParameterName(msg)) => HttpResponse::BadRequest().body(msg),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
 }
 
-pub async fn update_section(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<SectionPayload>) -> impl Responder {
+pub async fn update_section(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<SectionPayload>, session: actix_session::Session) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match Section::find_by_id(&conn, data.id) {
         Ok(mut section) => {
-            if let Err(e) = section.update(&conn, &data) { return HttpResponse::InternalServerError().body(e.to_string()); }
+            match section.update(&conn, &data, user_id) {
+                Ok(()) => {}
+                Err(rusqlite::Error::InvalidParameterName(msg)) => return HttpResponse::BadRequest().body(msg),
+                Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+            }
             HttpResponse::Ok().json(section)
         }
         Err(_) => HttpResponse::NotFound().body("Section not found"),
     }
 }
 
-pub async fn delete_section(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<IdPayload>) -> impl Responder {
+pub async fn delete_section(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<IdPayload>, session: actix_session::Session) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match Section::find_by_id(&conn, data.id) {
         Ok(section) => {
             match Section::has_machines(&conn, data.id) {
                 Ok(true) => return HttpResponse::BadRequest().body("Cannot delete section with existing machines"),
                 Ok(false) => {
-                    if let Err(e) = section.delete(&conn) { return HttpResponse::InternalServerError().body(e.to_string()); }
+                    if let Err(e) = section.delete(&conn, user_id) { return HttpResponse::InternalServerError().body(e.to_string()); }
                     HttpResponse::Ok().body("Section deleted successfully")
                 }
                 Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
@@ -55,11 +63,12 @@ pub async fn filter_sections(conn_data: web::Data<Pool<SqliteConnectionManager>>
     }
 }
 
-pub async fn update_po_code_sections(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<SectionPoCodesPayload>) -> impl Responder {
+pub async fn update_po_code_sections(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<SectionPoCodesPayload>, session: actix_session::Session) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match Section::find_by_id(&conn, data.id) {
         Ok(section) => {
-            if let Err(e) = section.update_po_codes(&conn, &data) { return HttpResponse::InternalServerError().body(e.to_string()); }
+            if let Err(e) = section.update_po_codes(&conn, &data, user_id) { return HttpResponse::InternalServerError().body(e.to_string()); }
             HttpResponse::Ok().json("PO codes updated successfully")
         }
         Err(_) => HttpResponse::NotFound().body("Section not found"),
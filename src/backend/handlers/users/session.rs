@@ -0,0 +1,31 @@
+use crate::backend::models::{SessionFilterPayload, SessionRevokePayload, UserSession};
+use crate::backend::session::SessionSettings;
+use actix_web::{HttpResponse, Responder, web};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+
+pub async fn all_sessions(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    web::Query(filter): web::Query<SessionFilterPayload>,
+) -> impl Responder {
+    let conn = conn_data.get().unwrap();
+    let idle_timeout = SessionSettings::from_env().idle_timeout;
+    match UserSession::active(&conn, &filter, idle_timeout) {
+        Ok(list) => HttpResponse::Ok().json(list),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+pub async fn revoke_sessions(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    data: web::Json<SessionRevokePayload>,
+    session: actix_session::Session,
+) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
+    let conn = conn_data.get().unwrap();
+    match UserSession::revoke(&conn, &data, user_id) {
+        Ok(count) => HttpResponse::Ok().json(serde_json::json!({ "revoked": count })),
+        Err(rusqlite::Error::InvalidParameterName(msg)) => HttpResponse::BadRequest().body(msg),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
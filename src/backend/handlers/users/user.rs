@@ -5,13 +5,14 @@ use actix_session::Session;
 use crate::backend::models::{User, UserPayload, UserCreatePayload, SigninPayload, IdPayload, UserFilterPayload};
 
 
-pub async fn create_user(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<UserCreatePayload>) -> impl Responder {
+pub async fn create_user(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<UserCreatePayload>, session: Session) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     
     match User::staffid_exists(&conn, &data.staffid) {
         Ok(true) => return HttpResponse::BadRequest().body("Staff ID already exists"),
         Ok(false) => {
-            match User::create(&conn, &data) {
+            match User::create(&conn, &data, user_id) {
                 Ok(u) => HttpResponse::Ok().json(u),
                 Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
             }
@@ -20,18 +21,20 @@ pub async fn create_user(conn_data: web::Data<Pool<SqliteConnectionManager>>, da
     }
 }
 
-pub async fn update_user(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<UserPayload>) -> impl Responder {
+pub async fn update_user(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<UserPayload>, session: Session) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match User::find_by_id(&conn, data.id) {
         Ok(mut u) => {
-            if let Err(e) = u.update(&conn, &data) { return HttpResponse::InternalServerError().body(e.to_string()); }
+            if let Err(e) = u.update(&conn, &data, user_id) { return HttpResponse::InternalServerError().body(e.to_string()); }
             HttpResponse::Ok().json(u)
         }
         Err(_) => HttpResponse::NotFound().body("User not found"),
     }
 }
 
-pub async fn delete_user(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<IdPayload>) -> impl Responder {
+pub async fn delete_user(conn_data: web::Data<Pool<SqliteConnectionManager>>, data: web::Json<IdPayload>, session: Session) -> impl Responder {
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
     let conn = conn_data.get().unwrap();
     match User::find_by_id(&conn, data.id) {
         Ok(u) => {
@@ -45,7 +48,7 @@ pub async fn delete_user(conn_data: web::Data<Pool<SqliteConnectionManager>>, da
                     return HttpResponse::InternalServerError().body(e.to_string());
                 }
             }
-            if let Err(e) = u.delete(&conn) {
+            if let Err(e) = u.delete(&conn, user_id) {
                 return HttpResponse::InternalServerError().body(e.to_string());
             }
             HttpResponse::Ok().body("User deleted successfully.")
@@ -70,6 +73,7 @@ pub async fn signin_user(conn_data: web::Data<Pool<SqliteConnectionManager>>, se
             session.insert("user_name", user.whois.clone()).unwrap();
             HttpResponse::Ok().json(user)
         }
+        Err(rusqlite::Error::InvalidParameterName(msg)) => HttpResponse::Unauthorized().body(msg),
         Err(_) => HttpResponse::Unauthorized().body("Invalid credentials"),
     }
 }
@@ -86,7 +90,7 @@ pub async fn get_me(conn_data: web::Data<Pool<SqliteConnectionManager>>, session
 }
 
 pub async fn signout_user(session: Session) -> impl Responder {
-    session.remove("user_id");
+    session.purge();
     HttpResponse::Ok().body("Signed out")
 }
 
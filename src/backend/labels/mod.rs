@@ -0,0 +1,429 @@
+//! Roll labels rendered on the server from a template, as PDF for office printers and ZPL
+//! for Zebra printers. The template is read from `data/label.json` (or `LABEL_TEMPLATE_FILE`)
+//! and falls back to the standard 4 x 6 in production label.
+
+pub mod pdf;
+pub mod zpl;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{env, fs, path::Path};
+
+use crate::backend::models::OutputRollDetails;
+
+const TEMPLATE_FILE: &str = "data/label.json";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LabelFormat {
+    Pdf,
+    Zpl,
+}
+
+impl LabelFormat {
+    pub fn parse(value: Option<&str>) -> Result<Self, String> {
+        match value
+            .map(str::trim)
+            .unwrap_or("pdf")
+            .to_lowercase()
+            .as_str()
+        {
+            "" | "pdf" => Ok(LabelFormat::Pdf),
+            "zpl" => Ok(LabelFormat::Zpl),
+            other => Err(format!("Unknown label format '{}'", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LabelFormat::Pdf => "pdf",
+            LabelFormat::Zpl => "zpl",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            LabelFormat::Pdf => "application/pdf",
+            LabelFormat::Zpl => "text/plain; charset=utf-8",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// 2D code type; PDF labels always draw QR, DataMatrix is printed natively by Zebra printers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Symbology {
+    #[default]
+    Qr,
+    DataMatrix,
+}
+
+/// Positions are in points (1/72 in) from the top-left corner; text `y` is the baseline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LabelElement {
+    Text {
+        x: f32,
+        y: f32,
+        size: f32,
+        #[serde(default)]
+        bold: bool,
+        #[serde(default)]
+        align: Align,
+        /// Width of the box the text is aligned and wrapped in; defaults to the rest of the page
+        #[serde(default)]
+        width: Option<f32>,
+        #[serde(default = "one_line")]
+        max_lines: usize,
+        /// Literal text with `{field}` placeholders
+        text: String,
+    },
+    Code {
+        x: f32,
+        y: f32,
+        size: f32,
+        #[serde(default)]
+        symbology: Symbology,
+    },
+}
+
+fn one_line() -> usize {
+    1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LabelTemplate {
+    pub width: f32,
+    pub height: f32,
+    /// Printer resolution used for ZPL
+    pub dpi: u32,
+    pub elements: Vec<LabelElement>,
+}
+
+impl Default for LabelTemplate {
+    fn default() -> Self {
+        let text = |x: f32, y: f32, size: f32, align: Align, text: &str| LabelElement::Text {
+            x,
+            y,
+            size,
+            bold: true,
+            align,
+            width: None,
+            max_lines: 1,
+            text: text.to_string(),
+        };
+        let right = |y: f32, size: f32, value: &str| LabelElement::Text {
+            x: 0.0,
+            y,
+            size,
+            bold: true,
+            align: Align::Right,
+            width: Some(264.0),
+            max_lines: 1,
+            text: value.to_string(),
+        };
+        LabelTemplate {
+            width: 288.0,
+            height: 432.0,
+            dpi: 203,
+            elements: vec![
+                right(10.0, 7.0, "{reprint}"),
+                text(0.0, 24.0, 14.0, Align::Center, "INSIGNIA PRODUCTION LABEL"),
+                LabelElement::Text {
+                    x: 0.0,
+                    y: 40.0,
+                    size: 11.0,
+                    bold: true,
+                    align: Align::Center,
+                    width: None,
+                    max_lines: 2,
+                    text: "{process_order_description}".to_string(),
+                },
+                LabelElement::Code {
+                    x: 69.0,
+                    y: 62.0,
+                    size: 150.0,
+                    symbology: Symbology::Qr,
+                },
+                text(24.0, 232.0, 10.0, Align::Left, "PRODUCTION ORDER"),
+                text(24.0, 248.0, 13.0, Align::Left, "{production_order}"),
+                right(232.0, 10.0, "MATERIAL NUMBER"),
+                right(248.0, 13.0, "{material_number}"),
+                text(24.0, 270.0, 10.0, Align::Left, "SECTION"),
+                text(24.0, 286.0, 13.0, Align::Left, "{section}"),
+                right(270.0, 10.0, "ROLL NO"),
+                right(286.0, 13.0, "{output_batch}"),
+                text(24.0, 312.0, 10.0, Align::Left, "FINAL METER"),
+                text(24.0, 328.0, 13.0, Align::Left, "{final_meter} m"),
+                right(312.0, 10.0, "FINAL WEIGHT"),
+                right(328.0, 13.0, "{final_weight} kg"),
+                LabelElement::Text {
+                    x: 24.0,
+                    y: 366.0,
+                    size: 12.0,
+                    bold: false,
+                    align: Align::Left,
+                    width: None,
+                    max_lines: 1,
+                    text: "{created_at}".to_string(),
+                },
+                LabelElement::Text {
+                    x: 0.0,
+                    y: 366.0,
+                    size: 12.0,
+                    bold: false,
+                    align: Align::Right,
+                    width: Some(264.0),
+                    max_lines: 1,
+                    text: "{operator}".to_string(),
+                },
+                LabelElement::Text {
+                    x: 24.0,
+                    y: 396.0,
+                    size: 7.0,
+                    bold: false,
+                    align: Align::Center,
+                    width: Some(240.0),
+                    max_lines: 4,
+                    text: "{remark}".to_string(),
+                },
+            ],
+        }
+    }
+}
+
+impl LabelTemplate {
+    pub fn load() -> Result<Self, String> {
+        let path = env::var("LABEL_TEMPLATE_FILE").unwrap_or_else(|_| TEMPLATE_FILE.to_string());
+        if !Path::new(&path).exists() {
+            return Ok(LabelTemplate::default());
+        }
+        let raw = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?;
+        serde_json::from_str(&raw).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+/// What goes on one roll label.
+#[derive(Debug, Clone)]
+pub struct LabelData {
+    pub roll_id: i32,
+    pub details: OutputRollDetails,
+    /// 0 for the first print
+    pub reprint: i32,
+    /// Remnant of an input roll going back to stock; `roll_id` is then the remnant id
+    pub remnant: bool,
+}
+
+impl LabelData {
+    /// Compact JSON scanned back at the next machine or in the warehouse.
+    pub fn code_payload(&self) -> String {
+        let mut payload = json!({
+            "id": self.roll_id,
+            "batch": self.details.output_batch,
+            "po": self.details.production_order,
+            "weight": self.details.final_weight,
+            "meter": self.details.final_meter,
+        });
+        if self.remnant {
+            payload["remnant"] = json!(true);
+        }
+        payload.to_string()
+    }
+
+    fn field(&self, name: &str) -> Option<String> {
+        let d = &self.details;
+        Some(match name {
+            "output_batch" => d.output_batch.clone(),
+            "production_order" => d.production_order.clone(),
+            "material_number" => d.material_number.clone(),
+            "material_description" => d.material_description.clone(),
+            "process_order_description" => d.process_order_description.clone(),
+            "section" => d.section.clone(),
+            "final_meter" => format_number(d.final_meter),
+            "final_weight" => format_number(d.final_weight),
+            "created_at" => d.created_at.clone(),
+            "operator" => d
+                .operator_name
+                .clone()
+                .unwrap_or_else(|| "SYSTEM".to_string()),
+            "remark" if self.remnant => "REMNANT".to_string(),
+            "remark" => match (&d.flag_reason, d.flag_count > 0) {
+                (Some(reason), true) => reason.clone(),
+                (None, true) => "FLAGGED".to_string(),
+                _ => "GOOD".to_string(),
+            },
+            "reprint" if self.reprint > 0 => format!("REPRINT {}", self.reprint),
+            "reprint" => String::new(),
+            _ => return None,
+        })
+    }
+
+    /// Fills `{field}` placeholders; unknown ones are left as written.
+    pub fn fill(&self, text: &str) -> String {
+        let mut out = String::new();
+        let mut rest = text;
+        while let Some(open) = rest.find('{') {
+            out.push_str(&rest[..open]);
+            match rest[open..].find('}') {
+                Some(close) => {
+                    let name = &rest[open + 1..open + close];
+                    match self.field(name) {
+                        Some(value) => out.push_str(&value),
+                        None => out.push_str(&rest[open..=open + close]),
+                    }
+                    rest = &rest[open + close + 1..];
+                }
+                None => {
+                    out.push_str(&rest[open..]);
+                    rest = "";
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+pub fn render(
+    template: &LabelTemplate,
+    data: &LabelData,
+    format: LabelFormat,
+) -> Result<Vec<u8>, String> {
+    match format {
+        LabelFormat::Pdf => pdf::render(template, data),
+        LabelFormat::Zpl => Ok(zpl::render(template, data).into_bytes()),
+    }
+}
+
+/// A laid-out line of text: left edge and baseline in points from the top-left.
+pub(crate) struct TextLine {
+    pub x: f32,
+    pub y: f32,
+    pub text: String,
+}
+
+/// Wraps and aligns a text element with Helvetica metrics, shared by both renderers so PDF
+/// and ZPL labels break lines in the same places.
+pub(crate) fn layout_text(
+    template: &LabelTemplate,
+    element: &LabelElement,
+    text: &str,
+) -> Vec<TextLine> {
+    let LabelElement::Text {
+        x,
+        y,
+        size,
+        bold,
+        align,
+        width,
+        max_lines,
+        ..
+    } = *element
+    else {
+        return vec![];
+    };
+    let box_width = width.unwrap_or(template.width - x).max(1.0);
+    let fits = |t: &str| text_width(t, size, bold) <= box_width;
+    let mut lines: Vec<String> = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let next = if line.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", line, word)
+        };
+        if fits(&next) || line.is_empty() {
+            line = next;
+        } else {
+            lines.push(std::mem::replace(&mut line, word.to_string()));
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines.truncate(max_lines.max(1));
+
+    lines
+        .into_iter()
+        .enumerate()
+        .map(|(i, text)| {
+            let line_width = text_width(&text, size, bold);
+            let left = match align {
+                Align::Left => x,
+                Align::Center => x + (box_width - line_width) / 2.0,
+                Align::Right => x + box_width - line_width,
+            };
+            TextLine {
+                x: left.max(0.0),
+                y: y + i as f32 * size * 1.2,
+                text,
+            }
+        })
+        .collect()
+}
+
+/// Width of `text` in points, from the standard Helvetica font metrics.
+pub fn text_width(text: &str, size: f32, bold: bool) -> f32 {
+    let table = if bold {
+        &HELVETICA_BOLD_WIDTHS
+    } else {
+        &HELVETICA_WIDTHS
+    };
+    let units: u32 = text
+        .chars()
+        .map(|c| match c as u32 {
+            code @ 32..=126 => table[(code - 32) as usize] as u32,
+            _ => 556,
+        })
+        .sum();
+    units as f32 * size / 1000.0
+}
+
+/// `11800` -> `11,800`; up to two decimals as the rolls page shows them.
+pub fn format_number(value: f64) -> String {
+    let rounded = (value * 100.0).round() / 100.0;
+    let raw = rounded.to_string();
+    let (int, frac) = raw.split_once('.').unwrap_or((&raw, ""));
+    let (sign, digits) = int.strip_prefix('-').map_or(("", int), |d| ("-", d));
+    let mut grouped = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    if frac.is_empty() {
+        format!("{}{}", sign, grouped)
+    } else {
+        format!("{}{}.{}", sign, grouped, frac)
+    }
+}
+
+#[rustfmt::skip]
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+#[rustfmt::skip]
+const HELVETICA_BOLD_WIDTHS: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611,
+    975, 722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 333, 278, 333, 584, 556,
+    333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889, 611, 611,
+    611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
@@ -0,0 +1,118 @@
+use lopdf::content::{Content, Operation};
+use lopdf::{Document, Object, Stream, dictionary};
+use qrcode::{Color, EcLevel, QrCode};
+
+use super::{LabelData, LabelElement, LabelTemplate, layout_text};
+
+/// One-page PDF with the standard Helvetica fonts, so nothing has to be embedded.
+pub fn render(template: &LabelTemplate, data: &LabelData) -> Result<Vec<u8>, String> {
+    let mut ops = vec![];
+    for element in &template.elements {
+        match element {
+            LabelElement::Text {
+                size, bold, text, ..
+            } => {
+                let font = if *bold { "F2" } else { "F1" };
+                for line in layout_text(template, element, &data.fill(text)) {
+                    ops.push(Operation::new("BT", vec![]));
+                    ops.push(Operation::new("Tf", vec![font.into(), (*size).into()]));
+                    ops.push(Operation::new(
+                        "Td",
+                        vec![line.x.into(), (template.height - line.y).into()],
+                    ));
+                    ops.push(Operation::new(
+                        "Tj",
+                        vec![Object::string_literal(win_ansi(&line.text))],
+                    ));
+                    ops.push(Operation::new("ET", vec![]));
+                }
+            }
+            LabelElement::Code { x, y, size, .. } => {
+                ops.extend(qr_operations(
+                    &data.code_payload(),
+                    *x,
+                    template.height - y - size,
+                    *size,
+                )?);
+            }
+        }
+    }
+    let content = Content { operations: ops }
+        .encode()
+        .map_err(|e| e.to_string())?;
+
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let regular = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+        "Encoding" => "WinAnsiEncoding",
+    });
+    let bold = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica-Bold",
+        "Encoding" => "WinAnsiEncoding",
+    });
+    let resources_id = doc.add_object(dictionary! {
+        "Font" => dictionary! { "F1" => regular, "F2" => bold },
+    });
+    let content_id = doc.add_object(Stream::new(dictionary! {}, content));
+    let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "Contents" => content_id,
+    });
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page_id.into()],
+            "Count" => 1,
+            "Resources" => resources_id,
+            "MediaBox" => vec![0.into(), 0.into(), template.width.into(), template.height.into()],
+        }),
+    );
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    doc.trailer.set("Root", catalog_id);
+
+    let mut bytes = Vec::new();
+    doc.save_to(&mut bytes).map_err(|e| e.to_string())?;
+    Ok(bytes)
+}
+
+/// Dark modules as filled squares; `bottom` is the lower edge in PDF coordinates.
+fn qr_operations(payload: &str, x: f32, bottom: f32, size: f32) -> Result<Vec<Operation>, String> {
+    let code =
+        QrCode::with_error_correction_level(payload, EcLevel::M).map_err(|e| e.to_string())?;
+    let width = code.width();
+    let module = size / width as f32;
+    let mut ops = vec![Operation::new("rg", vec![0.into(), 0.into(), 0.into()])];
+    for (i, color) in code.to_colors().into_iter().enumerate() {
+        if color == Color::Dark {
+            let (col, row) = (i % width, i / width);
+            ops.push(Operation::new(
+                "re",
+                vec![
+                    (x + col as f32 * module).into(),
+                    (bottom + (width - 1 - row) as f32 * module).into(),
+                    module.into(),
+                    module.into(),
+                ],
+            ));
+        }
+    }
+    ops.push(Operation::new("f", vec![]));
+    Ok(ops)
+}
+
+/// The standard fonts only cover Latin-1; anything else prints as `?`.
+fn win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| if (c as u32) < 256 { c as u8 } else { b'?' })
+        .collect()
+}
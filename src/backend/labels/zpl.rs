@@ -0,0 +1,64 @@
+use super::{LabelData, LabelElement, LabelTemplate, Symbology, layout_text};
+
+/// ZPL II for Zebra printers. Text uses the scalable font 0 and codes are drawn by the
+/// printer (`^BQ` QR, `^BX` DataMatrix).
+pub fn render(template: &LabelTemplate, data: &LabelData) -> String {
+    let dots = |points: f32| (points * template.dpi as f32 / 72.0).round() as i32;
+    let mut zpl = format!(
+        "^XA\n^CI28\n^PW{}\n^LL{}\n",
+        dots(template.width),
+        dots(template.height)
+    );
+    for element in &template.elements {
+        match element {
+            LabelElement::Text { size, text, .. } => {
+                let height = dots(*size);
+                for line in layout_text(template, element, &data.fill(text)) {
+                    // ^FT places the baseline, matching the template's text positions
+                    zpl.push_str(&format!(
+                        "^FT{},{}^A0N,{},{}^FH^FD{}^FS\n",
+                        dots(line.x),
+                        dots(line.y),
+                        height,
+                        height,
+                        field_data(&line.text)
+                    ));
+                }
+            }
+            LabelElement::Code {
+                x,
+                y,
+                size,
+                symbology,
+            } => {
+                let payload = field_data(&data.code_payload());
+                match symbology {
+                    // Magnification 1-10 sized for a version 5-6 code; ^BQ adds a 10-dot top margin
+                    Symbology::Qr => zpl.push_str(&format!(
+                        "^FO{},{}^BQN,2,{}^FH^FDMA,{}^FS\n",
+                        dots(*x),
+                        dots(*y) - 10,
+                        (dots(*size) / 41).clamp(1, 10),
+                        payload
+                    )),
+                    Symbology::DataMatrix => zpl.push_str(&format!(
+                        "^FO{},{}^BXN,{},200^FH^FD{}^FS\n",
+                        dots(*x),
+                        dots(*y),
+                        (dots(*size) / 32).max(1),
+                        payload
+                    )),
+                }
+            }
+        }
+    }
+    zpl.push_str("^XZ\n");
+    zpl
+}
+
+/// Escapes the ZPL control characters for use after `^FH`.
+fn field_data(text: &str) -> String {
+    text.replace('_', "_5F")
+        .replace('^', "_5E")
+        .replace('~', "_7E")
+}
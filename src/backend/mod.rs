@@ -3,4 +3,6 @@ pub mod routes;
 pub mod templates;
 pub mod models;
 pub mod handlers;
+pub mod labels;
 pub mod middlewares;
+pub mod session;
@@ -0,0 +1,203 @@
+use chrono::Local;
+use rusqlite::{Connection, Result, params};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use crate::backend::models::FilterResponse;
+
+#[derive(Debug, Serialize)]
+pub struct AuditLog {
+    pub id: i32,
+    pub user_id: Option<i32>,
+    pub user_name: Option<String>,
+    pub model: String,
+    pub row_id: i32,
+    pub action: String,
+    pub changes: Value,
+    pub created_at: String,
+}
+
+#[derive(Deserialize)]
+pub struct AuditFilterPayload {
+    pub model: Option<String>,
+    pub row_id: Option<String>,
+    pub user_id: Option<String>,
+    pub action: Option<String>,
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
+    pub per_page: Option<String>,
+    pub page: Option<String>,
+}
+
+// Fields that change on every write and would only add noise to the diff
+const IGNORED_FIELDS: &[&str] = &["updated_at"];
+
+impl AuditLog {
+    pub fn snapshot<T: Serialize>(value: &T) -> Value {
+        serde_json::to_value(value).unwrap_or(Value::Null)
+    }
+
+    /// Diffs two snapshots into `{"field": {"before": .., "after": ..}}`. A missing side is
+    /// treated as null, so creates and deletes list every field.
+    pub fn diff(before: Option<&Value>, after: Option<&Value>) -> Value {
+        let empty = Map::new();
+        let before = before.and_then(Value::as_object).unwrap_or(&empty);
+        let after = after.and_then(Value::as_object).unwrap_or(&empty);
+
+        let mut changes = Map::new();
+        for key in before.keys().chain(after.keys()) {
+            if IGNORED_FIELDS.contains(&key.as_str()) || changes.contains_key(key) {
+                continue;
+            }
+            let old = before.get(key).unwrap_or(&Value::Null);
+            let new = after.get(key).unwrap_or(&Value::Null);
+            if old != new {
+                changes.insert(key.clone(), json!({ "before": old, "after": new }));
+            }
+        }
+        Value::Object(changes)
+    }
+
+    pub fn record(
+        conn: &Connection,
+        user_id: Option<i32>,
+        model: &str,
+        row_id: i32,
+        action: &str,
+        before: Option<&Value>,
+        after: Option<&Value>,
+    ) -> Result<()> {
+        let changes = Self::diff(before, after);
+        if action == "update" && changes.as_object().is_some_and(|c| c.is_empty()) {
+            return Ok(());
+        }
+        let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "INSERT INTO audit_log (user_id, model, row_id, action, changes, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![user_id, model, row_id, action, changes.to_string(), now],
+        )?;
+        Ok(())
+    }
+
+    pub fn created<T: Serialize>(conn: &Connection, user_id: Option<i32>, model: &str, row_id: i32, after: &T) -> Result<()> {
+        Self::record(conn, user_id, model, row_id, "create", None, Some(&Self::snapshot(after)))
+    }
+
+    pub fn updated<T: Serialize>(conn: &Connection, user_id: Option<i32>, model: &str, row_id: i32, before: &Value, after: &T) -> Result<()> {
+        Self::record(conn, user_id, model, row_id, "update", Some(before), Some(&Self::snapshot(after)))
+    }
+
+    pub fn deleted<T: Serialize>(conn: &Connection, user_id: Option<i32>, model: &str, row_id: i32, before: &T) -> Result<()> {
+        Self::record(conn, user_id, model, row_id, "delete", Some(&Self::snapshot(before)), None)
+    }
+
+    pub fn filter(conn: &Connection, filter: &AuditFilterPayload) -> Result<FilterResponse<Self>> {
+        let base = " FROM audit_log a LEFT JOIN users u ON a.user_id = u.id WHERE 1=1";
+        let mut count_query = format!("SELECT COUNT(*){}", base);
+        let mut data_query = format!(
+            "SELECT a.id, a.user_id, u.full_name, a.model, a.row_id, a.action, a.changes, a.created_at{}",
+            base
+        );
+        let mut params_vec: Vec<&dyn rusqlite::ToSql> = vec![];
+
+        let mut models: Vec<String> = vec![];
+        let mut row_ids: Vec<i32> = vec![];
+        let mut user_ids: Vec<i32> = vec![];
+        let mut actions: Vec<String> = vec![];
+        let mut start_dates: Vec<String> = vec![];
+        let mut end_dates: Vec<String> = vec![];
+        let mut pages: Vec<i32> = vec![];
+        let mut per_pages: Vec<i32> = vec![];
+
+        if let Some(val) = &filter.model
+            && !val.is_empty()
+        {
+            models.push(val.clone());
+            params_vec.push(models.last().unwrap());
+            count_query.push_str(" AND a.model = ?");
+            data_query.push_str(" AND a.model = ?");
+        }
+
+        if let Some(val) = &filter.row_id
+            && let Ok(parsed) = val.parse::<i32>()
+        {
+            row_ids.push(parsed);
+            params_vec.push(row_ids.last().unwrap());
+            count_query.push_str(" AND a.row_id = ?");
+            data_query.push_str(" AND a.row_id = ?");
+        }
+
+        if let Some(val) = &filter.user_id
+            && let Ok(parsed) = val.parse::<i32>()
+        {
+            user_ids.push(parsed);
+            params_vec.push(user_ids.last().unwrap());
+            count_query.push_str(" AND a.user_id = ?");
+            data_query.push_str(" AND a.user_id = ?");
+        }
+
+        if let Some(val) = &filter.action
+            && !val.is_empty()
+        {
+            actions.push(val.clone());
+            params_vec.push(actions.last().unwrap());
+            count_query.push_str(" AND a.action = ?");
+            data_query.push_str(" AND a.action = ?");
+        }
+
+        if let Some(val) = &filter.start_date
+            && !val.is_empty()
+        {
+            start_dates.push(val.clone());
+            params_vec.push(start_dates.last().unwrap());
+            count_query.push_str(" AND date(a.created_at) >= date(?)");
+            data_query.push_str(" AND date(a.created_at) >= date(?)");
+        }
+
+        if let Some(val) = &filter.end_date
+            && !val.is_empty()
+        {
+            end_dates.push(val.clone());
+            params_vec.push(end_dates.last().unwrap());
+            count_query.push_str(" AND date(a.created_at) <= date(?)");
+            data_query.push_str(" AND date(a.created_at) <= date(?)");
+        }
+
+        let total_count: i32 = conn.query_row(&count_query, params_vec.as_slice(), |row| row.get(0))?;
+
+        data_query.push_str(" ORDER BY a.created_at DESC, a.id DESC");
+
+        if let (Some(page), Some(per_page)) = (&filter.page, &filter.per_page)
+            && let (Ok(page_val), Ok(per_page_val)) = (page.parse::<i32>(), per_page.parse::<i32>())
+            && per_page_val > 0
+        {
+            let offset = (page_val - 1) * per_page_val;
+            pages.push(offset);
+            per_pages.push(per_page_val);
+            data_query.push_str(" LIMIT ? OFFSET ?");
+            params_vec.push(per_pages.last().unwrap());
+            params_vec.push(pages.last().unwrap());
+        }
+
+        let mut stmt = conn.prepare(&data_query)?;
+        let rows = stmt.query_map(params_vec.as_slice(), |row| {
+            let changes: String = row.get(6)?;
+            Ok(AuditLog {
+                id: row.get(0)?,
+                user_id: row.get(1)?,
+                user_name: row.get(2)?,
+                model: row.get(3)?,
+                row_id: row.get(4)?,
+                action: row.get(5)?,
+                changes: serde_json::from_str(&changes).unwrap_or(Value::Null),
+                created_at: row.get(7)?,
+            })
+        })?;
+
+        let data = rows.collect::<Result<Vec<_>, _>>()?;
+
+        Ok(FilterResponse {
+            total_count,
+            data,
+        })
+    }
+}
@@ -1,6 +1,6 @@
 use rusqlite::{params, Connection, Result};
 use serde::{Serialize, Deserialize};
-use crate::backend::models::FilterResponse;
+use crate::backend::models::{AuditLog, FilterResponse};
 
 #[derive(Debug, Serialize)]
 pub struct Machine {
@@ -11,6 +11,8 @@ pub struct Machine {
     pub section_name: String,
     pub user_count: i32,
     pub job_count: i32,
+    /// Nameplate speed in meters per minute, the performance target for OEE
+    pub rated_speed: Option<f64>,
 }
 
 #[derive(Debug, Serialize)]
@@ -26,6 +28,7 @@ pub struct MachineCreatePayload {
     pub name: String,
     pub label: String,
     pub section_id: i32,
+    pub rated_speed: Option<f64>,
 }
 
 #[derive(Deserialize)]
@@ -34,6 +37,7 @@ pub struct MachinePayload {
     pub name: Option<String>,
     pub label: Option<String>,
     pub section_id: Option<i32>,
+    pub rated_speed: Option<f64>,
 }
 
 #[derive(Deserialize)]
@@ -49,17 +53,17 @@ pub struct MachineFilterPayload {
 }
 
 impl Machine {
-    pub fn create(conn: &Connection, data: &MachineCreatePayload) -> Result<Self> {
+    pub fn create(conn: &Connection, data: &MachineCreatePayload, user_id: Option<i32>) -> Result<Self> {
         conn.execute(
-            "INSERT INTO machines (name, label, section_id) VALUES (?1, ?2, ?3)",
-            params![data.name, data.label, data.section_id],
+            "INSERT INTO machines (name, label, section_id, rated_speed) VALUES (?1, ?2, ?3, ?4)",
+            params![data.name, data.label, data.section_id, data.rated_speed],
         )?;
         let id = conn.last_insert_rowid() as i32;
         
         let mut stmt = conn.prepare(
-            "SELECT m.*, s.name as section_name,
+            "SELECT m.id, m.name, m.label, m.section_id, s.name as section_name,
              COUNT(DISTINCT us.user_id) as user_count,
-             COUNT(DISTINCT j.id) as job_count
+             COUNT(DISTINCT j.id) as job_count, m.rated_speed
              FROM machines m
              JOIN sections s ON m.section_id = s.id
              LEFT JOIN user_sections us ON m.section_id = us.section_id
@@ -68,7 +72,7 @@ impl Machine {
              GROUP BY m.id"
         )?;
         
-        stmt.query_row(params![id], |row| Ok(Machine {
+        let machine = stmt.query_row(params![id], |row| Ok(Machine {
             id: row.get(0)?,
             name: row.get(1)?,
             label: row.get(2)?,
@@ -76,10 +80,14 @@ impl Machine {
             section_name: row.get(4)?,
             user_count: row.get(5)?,
             job_count: row.get(6)?,
-        }))
+            rated_speed: row.get(7)?,
+        }))?;
+        AuditLog::created(conn, user_id, "machines", id, &machine)?;
+        Ok(machine)
     }
 
-    pub fn update(&mut self, conn: &Connection, data: &MachinePayload) -> Result<()> {
+    pub fn update(&mut self, conn: &Connection, data: &MachinePayload, user_id: Option<i32>) -> Result<()> {
+        let before = AuditLog::snapshot(self);
         if let Some(name) = &data.name {
             conn.execute("UPDATE machines SET name = ?1 WHERE id = ?2", params![name, self.id])?;
             self.name = name.clone();
@@ -103,12 +111,18 @@ impl Machine {
                 Ok(())
             })?;
         }
+        if let Some(rated_speed) = data.rated_speed {
+            conn.execute("UPDATE machines SET rated_speed = ?1 WHERE id = ?2", params![rated_speed, self.id])?;
+            self.rated_speed = Some(rated_speed);
+        }
+        AuditLog::updated(conn, user_id, "machines", self.id, &before, self)?;
         Ok(())
     }
 
-    pub fn delete(&self, conn: &Connection) -> Result<()> {
+    pub fn delete(&self, conn: &Connection, user_id: Option<i32>) -> Result<()> {
         conn.execute("DELETE FROM jobs WHERE machine_id = ?1", params![self.id])?;
         conn.execute("DELETE FROM machines WHERE id = ?1", params![self.id])?;
+        AuditLog::deleted(conn, user_id, "machines", self.id, self)?;
         Ok(())
     }
 
@@ -123,9 +137,9 @@ impl Machine {
 
     pub fn find_by_id(conn: &Connection, id: i32) -> Result<Self> {
         let mut stmt = conn.prepare(
-            "SELECT m.*, s.name as section_name,
+            "SELECT m.id, m.name, m.label, m.section_id, s.name as section_name,
              COUNT(DISTINCT us.user_id) as user_count,
-             COUNT(DISTINCT j.id) as job_count
+             COUNT(DISTINCT j.id) as job_count, m.rated_speed
              FROM machines m
              JOIN sections s ON m.section_id = s.id
              LEFT JOIN user_sections us ON m.section_id = us.section_id
@@ -141,14 +155,15 @@ impl Machine {
             section_name: row.get(4)?,
             user_count: row.get(5)?,
             job_count: row.get(6)?,
+            rated_speed: row.get(7)?,
         }))
     }
 
     pub fn all(conn: &Connection) -> Result<Vec<Self>> {
         let mut stmt = conn.prepare(
-            "SELECT m.*, s.name as section_name,
+            "SELECT m.id, m.name, m.label, m.section_id, s.name as section_name,
              COUNT(DISTINCT us.user_id) as user_count,
-             COUNT(DISTINCT j.id) as job_count
+             COUNT(DISTINCT j.id) as job_count, m.rated_speed
              FROM machines m
              JOIN sections s ON m.section_id = s.id
              LEFT JOIN user_sections us ON m.section_id = us.section_id
@@ -164,6 +179,7 @@ impl Machine {
             section_name: row.get(4)?,
             user_count: row.get(5)?,
             job_count: row.get(6)?,
+            rated_speed: row.get(7)?,
         }))?.collect::<Result<Vec<_>, _>>()?;
         Ok(machines)
     }
@@ -184,9 +200,9 @@ impl Machine {
 
     pub fn filter(conn: &Connection, filter: &MachineFilterPayload) -> Result<FilterResponse<Self>> {
         let mut count_query = "SELECT COUNT(DISTINCT m.id) FROM machines m JOIN sections s ON m.section_id = s.id WHERE 1=1".to_string();
-        let mut data_query = "SELECT m.*, s.name as section_name,
+        let mut data_query = "SELECT m.id, m.name, m.label, m.section_id, s.name as section_name,
                             COUNT(DISTINCT us.user_id) as user_count,
-                            COUNT(DISTINCT j.id) as job_count
+                            COUNT(DISTINCT j.id) as job_count, m.rated_speed
                             FROM machines m
                             JOIN sections s ON m.section_id = s.id
                             LEFT JOIN user_sections us ON m.section_id = us.section_id
@@ -288,6 +304,7 @@ impl Machine {
                 section_name: row.get(4)?,
                 user_count: row.get(5)?,
                 job_count: row.get(6)?,
+                rated_speed: row.get(7)?,
             })
         })?;
 
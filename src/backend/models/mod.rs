@@ -13,5 +13,8 @@ pub use report::*;
 pub mod machine;
 pub use machine::*;
 
+pub mod audit;
+pub use audit::*;
+
 pub mod id;
 pub use id::*;
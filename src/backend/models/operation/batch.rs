@@ -0,0 +1,278 @@
+use chrono::{Datelike, Local};
+use rusqlite::{Connection, OptionalExtension, Result, params};
+
+use crate::backend::models::ShiftSlot;
+
+/// `yy` + shift number in the year + machine label + 3-digit roll number, as batches were
+/// numbered before patterns could be configured.
+pub const DEFAULT_BATCH_PATTERN: &str = "{YY}{SHIFTNO}{MACHINE}{SEQ}";
+
+const DEFAULT_WIDTH: usize = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Text(String),
+    Year2,
+    Year4,
+    JulianDay,
+    /// The shift's place in the production day
+    Shift,
+    /// Running shift number within the year
+    ShiftNumber(usize),
+    Machine,
+    Sequence(usize),
+    CheckDigit,
+}
+
+/// An output batch format made of literal text and `{TOKEN}`s:
+///
+/// - `{YY}`, `{YYYY}`: year of the production date
+/// - `{JJJ}`: day of the year (julian day)
+/// - `{SHIFT}`: shift sequence in the day; `{SHIFTNO[:n]}`: shift number in the year
+/// - `{MACHINE}`: machine label
+/// - `{SEQ[:n]}`: roll sequence, zero-padded to `n` digits (required)
+/// - `{CHECK}`: Luhn check digit over the rest of the batch
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchPattern {
+    tokens: Vec<Token>,
+}
+
+impl BatchPattern {
+    pub fn parse(pattern: &str) -> Result<Self> {
+        let invalid = |msg: String| rusqlite::Error::InvalidParameterName(msg);
+        let mut tokens = vec![];
+        let mut rest = pattern.trim();
+        while !rest.is_empty() {
+            let Some(open) = rest.find('{') else {
+                tokens.push(Token::Text(rest.to_string()));
+                break;
+            };
+            if open > 0 {
+                tokens.push(Token::Text(rest[..open].to_string()));
+            }
+            let close = rest[open..]
+                .find('}')
+                .ok_or_else(|| invalid(format!("Unclosed token in batch pattern '{}'", pattern)))?
+                + open;
+            let (name, width) = match rest[open + 1..close].split_once(':') {
+                Some((name, width)) => {
+                    let width = width
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|w| (1..=9).contains(w))
+                        .ok_or_else(|| invalid(format!("Invalid width in {{{}}}", name)))?;
+                    (name, Some(width))
+                }
+                None => (&rest[open + 1..close], None),
+            };
+            let token = match (name.to_uppercase().as_str(), width) {
+                ("YY", None) => Token::Year2,
+                ("YYYY", None) => Token::Year4,
+                ("JJJ", None) => Token::JulianDay,
+                ("SHIFT", None) => Token::Shift,
+                ("SHIFTNO", width) => Token::ShiftNumber(width.unwrap_or(DEFAULT_WIDTH)),
+                ("MACHINE", None) => Token::Machine,
+                ("SEQ", width) => Token::Sequence(width.unwrap_or(DEFAULT_WIDTH)),
+                ("CHECK", None) => Token::CheckDigit,
+                _ => {
+                    return Err(invalid(format!(
+                        "Unknown token {{{}}} in batch pattern",
+                        &rest[open + 1..close]
+                    )));
+                }
+            };
+            tokens.push(token);
+            rest = &rest[close + 1..];
+        }
+
+        let sequences = tokens
+            .iter()
+            .filter(|t| matches!(t, Token::Sequence(_)))
+            .count();
+        if sequences != 1 {
+            return Err(invalid(
+                "A batch pattern needs exactly one {SEQ} token".to_string(),
+            ));
+        }
+        if tokens.iter().filter(|t| **t == Token::CheckDigit).count() > 1 {
+            return Err(invalid(
+                "A batch pattern can have only one {CHECK} token".to_string(),
+            ));
+        }
+        Ok(BatchPattern { tokens })
+    }
+
+    /// The pattern configured for a section, or the default one.
+    pub fn for_section(conn: &Connection, section_id: Option<i32>) -> Result<Self> {
+        let pattern: Option<String> = match section_id {
+            Some(id) => conn
+                .query_row(
+                    "SELECT batch_pattern FROM sections WHERE id = ?1",
+                    params![id],
+                    |row| row.get(0),
+                )
+                .optional()?
+                .flatten(),
+            None => None,
+        };
+        Self::parse(
+            pattern
+                .as_deref()
+                .filter(|p| !p.trim().is_empty())
+                .unwrap_or(DEFAULT_BATCH_PATTERN),
+        )
+    }
+
+    fn sequence_width(&self) -> usize {
+        self.tokens
+            .iter()
+            .find_map(|t| match t {
+                Token::Sequence(width) => Some(*width),
+                _ => None,
+            })
+            .unwrap_or(DEFAULT_WIDTH)
+    }
+
+    /// Builds the batch; without a sequence the roll number and check digit are left as
+    /// `#` and `?`, which keys the sequence counter for that shift and machine.
+    fn render(&self, slot: &ShiftSlot, machine: &str, sequence: Option<u32>) -> String {
+        let date = slot.production_date;
+        let mut batch = String::new();
+        let mut check_at = None;
+        for token in &self.tokens {
+            match token {
+                Token::Text(text) => batch.push_str(text),
+                Token::Year2 => batch.push_str(&format!("{:02}", date.year() % 100)),
+                Token::Year4 => batch.push_str(&format!("{:04}", date.year())),
+                Token::JulianDay => batch.push_str(&format!("{:03}", date.ordinal())),
+                Token::Shift => batch.push_str(&slot.sequence.to_string()),
+                Token::ShiftNumber(width) => {
+                    batch.push_str(&format!("{:0width$}", slot.number_in_year()))
+                }
+                Token::Machine => batch.push_str(machine),
+                Token::Sequence(width) => match sequence {
+                    Some(value) => batch.push_str(&format!("{:0width$}", value)),
+                    None => batch.push_str(&"#".repeat(*width)),
+                },
+                Token::CheckDigit => {
+                    check_at = Some(batch.len());
+                }
+            }
+        }
+        if let Some(at) = check_at {
+            let digit = match sequence {
+                Some(_) => check_digit(&batch),
+                None => '?',
+            };
+            batch.insert(at, digit);
+        }
+        batch
+    }
+
+    /// Takes the next free batch for the shift and machine. Call inside the transaction that
+    /// inserts the roll so two stations can never be handed the same number.
+    pub fn allocate(&self, conn: &Connection, slot: &ShiftSlot, machine: &str) -> Result<String> {
+        let scope = self.render(slot, machine, None);
+        let limit = 10u64.pow(self.sequence_width() as u32);
+        let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        loop {
+            let value: u32 = conn.query_row(
+                "INSERT INTO batch_sequences (scope, last_value, updated_at) VALUES (?1, 1, ?2)
+                 ON CONFLICT(scope) DO UPDATE SET last_value = last_value + 1, updated_at = ?2
+                 RETURNING last_value",
+                params![scope, now],
+                |row| row.get(0),
+            )?;
+            if u64::from(value) >= limit {
+                return Err(rusqlite::Error::InvalidParameterName(format!(
+                    "Batch numbers for {} are used up; widen {{SEQ}} in the section's batch pattern",
+                    scope
+                )));
+            }
+            let batch = self.render(slot, machine, Some(value));
+            // Rolls numbered before the counter existed keep their batches
+            let taken: bool = conn.query_row(
+                "SELECT EXISTS (SELECT 1 FROM output_rolls WHERE output_batch = ?1)",
+                params![batch],
+                |row| row.get(0),
+            )?;
+            if !taken {
+                return Ok(batch);
+            }
+        }
+    }
+}
+
+/// Luhn (mod 10) check digit; letters count as their base-36 value, A = 10.
+pub fn check_digit(batch: &str) -> char {
+    let digits: Vec<u32> = batch
+        .chars()
+        .filter_map(|c| c.to_digit(36))
+        .flat_map(|v| {
+            if v >= 10 {
+                vec![v / 10, v % 10]
+            } else {
+                vec![v]
+            }
+        })
+        .collect();
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| {
+            if i % 2 == 0 {
+                let doubled = d * 2;
+                if doubled > 9 { doubled - 9 } else { doubled }
+            } else {
+                d
+            }
+        })
+        .sum();
+    char::from_digit((10 - sum % 10) % 10, 10).unwrap_or('0')
+}
+
+#[test]
+fn test_batch_pattern_allocation() {
+    use chrono::NaiveDate;
+
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::manager::migrations::migrate_up(&mut conn, None).unwrap();
+    let slot = ShiftSlot {
+        shift_id: 2,
+        name: "Night".to_string(),
+        sequence: 2,
+        production_date: NaiveDate::from_ymd_opt(2026, 1, 9).unwrap(),
+        start: None,
+        end: None,
+        shifts_per_day: 2,
+        holiday: None,
+    };
+
+    let legacy = BatchPattern::for_section(&conn, None).unwrap();
+    assert_eq!(legacy.allocate(&conn, &slot, "P1").unwrap(), "26018P1001");
+    // A roll already carrying the next number is skipped rather than duplicated
+    conn.execute_batch(
+        "INSERT INTO output_rolls (output_batch, final_weight) VALUES ('26018P1002', 0);",
+    )
+    .unwrap();
+    assert_eq!(legacy.allocate(&conn, &slot, "P1").unwrap(), "26018P1003");
+    assert!(
+        conn.execute(
+            "INSERT INTO output_rolls (output_batch, final_weight) VALUES ('26018P1002', 0)",
+            [],
+        )
+        .is_err()
+    );
+
+    let custom = BatchPattern::parse("{YYYY}-{JJJ}{SHIFT}-{MACHINE}{SEQ:4}{CHECK}").unwrap();
+    let batch = custom.allocate(&conn, &slot, "S1").unwrap();
+    assert_eq!(&batch[..15], "2026-0092-S1000");
+    assert_eq!(batch.len(), 17);
+    assert_eq!(check_digit(&batch[..16]), batch.chars().last().unwrap());
+    assert_eq!(check_digit("7992739871"), '3');
+
+    assert!(BatchPattern::parse("{YY}{MACHINE}").is_err());
+    assert!(BatchPattern::parse("{YY}{LOT}{SEQ}").is_err());
+    assert!(BatchPattern::parse("{YY}{SEQ:0}").is_err());
+}
@@ -0,0 +1,420 @@
+use chrono::Local;
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::models::{AuditLog, Permission, SigninPayload, User};
+
+/// Material approved in place of a BOM component, for one process order or for every order
+/// when `process_order` is empty.
+#[derive(Debug, Serialize)]
+pub struct BomAlternative {
+    pub id: i32,
+    pub material_number: String,
+    pub alternative_number: String,
+    pub process_order: Option<String>,
+    pub note: Option<String>,
+    pub created_by: Option<i32>,
+    pub created_at: String,
+}
+
+#[derive(Deserialize)]
+pub struct BomAlternativeCreatePayload {
+    pub material_number: String,
+    pub alternative_number: String,
+    pub process_order: Option<String>,
+    pub note: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct BomAlternativeFilterPayload {
+    pub material_number: Option<String>,
+    pub process_order: Option<String>,
+}
+
+/// Supervisor sign-off for loading a material that is not on the order's BOM
+#[derive(Deserialize, Clone)]
+pub struct BomOverride {
+    pub staffid: String,
+    pub password: String,
+    pub reason: String,
+    /// Set by [`BomOverride::verify`] once the credentials and rights were checked
+    #[serde(skip)]
+    pub supervisor_id: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct BomCheckPayload {
+    pub process_order: String,
+    pub material_number: String,
+}
+
+/// Whether a material may be loaded on a process order. `status` is `component` or
+/// `alternative` when it conforms, `mismatch` when it does not, and `unknown` when the order's
+/// BOM has not been synced from SAP yet, which is let through.
+#[derive(Debug, Serialize)]
+pub struct BomCheck {
+    pub process_order: String,
+    pub material_number: String,
+    pub status: String,
+    /// Component the material stands in for, when it is an approved alternative
+    pub alternative_for: Option<String>,
+    pub components: Vec<String>,
+    pub override_by: Option<i32>,
+    pub override_reason: Option<String>,
+}
+
+/// Permission model a supervisor needs create rights on to override a mismatch
+pub const BOM_OVERRIDE_MODEL: &str = "bom_overrides";
+
+/// SAP pads material numbers with zeros, the scanned label may not
+fn same_material(a: &str, b: &str) -> bool {
+    a.trim().trim_start_matches('0') == b.trim().trim_start_matches('0')
+}
+
+impl BomAlternative {
+    fn from_row(row: &rusqlite::Row) -> Result<Self> {
+        Ok(BomAlternative {
+            id: row.get(0)?,
+            material_number: row.get(1)?,
+            alternative_number: row.get(2)?,
+            process_order: row.get(3)?,
+            note: row.get(4)?,
+            created_by: row.get(5)?,
+            created_at: row.get(6)?,
+        })
+    }
+
+    pub fn create(
+        conn: &Connection,
+        data: &BomAlternativeCreatePayload,
+        user_id: Option<i32>,
+    ) -> Result<Self> {
+        let material_number = data.material_number.trim();
+        let alternative_number = data.alternative_number.trim();
+        if material_number.is_empty() || alternative_number.is_empty() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Material and alternative are required".to_string(),
+            ));
+        }
+        if same_material(material_number, alternative_number) {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "A material cannot be its own alternative".to_string(),
+            ));
+        }
+        let process_order = data
+            .process_order
+            .as_deref()
+            .map(str::trim)
+            .filter(|o| !o.is_empty());
+        let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "INSERT INTO bom_alternatives (material_number, alternative_number, process_order, note, created_by, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![material_number, alternative_number, process_order, data.note, user_id, now],
+        )?;
+        let id = conn.last_insert_rowid() as i32;
+        let alternative = Self::find_by_id(conn, id)?;
+        AuditLog::created(conn, user_id, "bom_alternatives", id, &alternative)?;
+        Ok(alternative)
+    }
+
+    pub fn delete(&self, conn: &Connection, user_id: Option<i32>) -> Result<()> {
+        conn.execute(
+            "DELETE FROM bom_alternatives WHERE id = ?1",
+            params![self.id],
+        )?;
+        AuditLog::deleted(conn, user_id, "bom_alternatives", self.id, self)?;
+        Ok(())
+    }
+
+    pub fn find_by_id(conn: &Connection, id: i32) -> Result<Self> {
+        conn.query_row(
+            "SELECT id, material_number, alternative_number, process_order, note, created_by, created_at FROM bom_alternatives WHERE id = ?1",
+            params![id],
+            Self::from_row,
+        )
+    }
+
+    pub fn filter(conn: &Connection, filter: &BomAlternativeFilterPayload) -> Result<Vec<Self>> {
+        let mut query = String::from(
+            "SELECT id, material_number, alternative_number, process_order, note, created_by, created_at FROM bom_alternatives WHERE 1=1",
+        );
+        let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+        if let Some(material_number) = filter.material_number.as_deref().filter(|m| !m.is_empty()) {
+            query.push_str(" AND (material_number LIKE ? OR alternative_number LIKE ?)");
+            params_vec.push(Box::new(format!("%{}%", material_number)));
+            params_vec.push(Box::new(format!("%{}%", material_number)));
+        }
+        if let Some(process_order) = filter.process_order.as_deref().filter(|o| !o.is_empty()) {
+            query.push_str(" AND (process_order = ? OR process_order IS NULL)");
+            params_vec.push(Box::new(process_order.to_string()));
+        }
+        query.push_str(" ORDER BY material_number, alternative_number");
+        let mut stmt = conn.prepare(&query)?;
+        stmt.query_map(
+            params_vec
+                .iter()
+                .map(|b| &**b)
+                .collect::<Vec<_>>()
+                .as_slice(),
+            Self::from_row,
+        )?
+        .collect()
+    }
+}
+
+impl BomCheck {
+    /// Component materials of a process order as synced from SAP into `materials.value`,
+    /// `None` while the sync has not filled them in.
+    pub fn components(conn: &Connection, process_order: &str) -> Result<Option<Vec<String>>> {
+        let value: Option<String> = conn
+            .query_row(
+                "SELECT value FROM materials WHERE code = ?1",
+                params![process_order],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(value) = value.filter(|v| !v.is_empty() && v != "Loading...") else {
+            return Ok(None);
+        };
+        let mut stmt =
+            conn.prepare("SELECT value FROM materials_value_description WHERE id = ?1")?;
+        let mut components = Vec::new();
+        for id in value
+            .split(',')
+            .filter_map(|id| id.trim().parse::<i64>().ok())
+        {
+            if let Some(material) = stmt
+                .query_row(params![id], |row| row.get::<_, String>(0))
+                .optional()?
+            {
+                components.push(material);
+            }
+        }
+        Ok(Some(components))
+    }
+
+    pub fn run(conn: &Connection, process_order: &str, material_number: &str) -> Result<Self> {
+        let mut check = BomCheck {
+            process_order: process_order.to_string(),
+            material_number: material_number.to_string(),
+            status: "unknown".to_string(),
+            alternative_for: None,
+            components: Vec::new(),
+            override_by: None,
+            override_reason: None,
+        };
+        let Some(components) = Self::components(conn, process_order)?.filter(|c| !c.is_empty())
+        else {
+            return Ok(check);
+        };
+        check.components = components;
+
+        if check
+            .components
+            .iter()
+            .any(|c| same_material(c, material_number))
+        {
+            check.status = "component".to_string();
+            return Ok(check);
+        }
+
+        let mut stmt = conn.prepare(
+            "SELECT material_number, alternative_number FROM bom_alternatives WHERE process_order IS NULL OR process_order = ?1",
+        )?;
+        let alternatives = stmt
+            .query_map(params![process_order], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>>>()?;
+        check.alternative_for = alternatives
+            .into_iter()
+            .filter(|(_, alternative)| same_material(alternative, material_number))
+            .find_map(|(material, _)| {
+                check
+                    .components
+                    .iter()
+                    .find(|c| same_material(c, &material))
+                    .cloned()
+            });
+        check.status = if check.alternative_for.is_some() {
+            "alternative"
+        } else {
+            "mismatch"
+        }
+        .to_string();
+        Ok(check)
+    }
+
+    pub fn conforms(&self) -> bool {
+        self.status != "mismatch"
+    }
+
+    /// Rejects a material that is not on the order's BOM unless a supervisor with create rights
+    /// on [`BOM_OVERRIDE_MODEL`] signed it off with a reason, checked by [`BomOverride::verify`].
+    pub fn enforce(
+        conn: &Connection,
+        process_order: &str,
+        material_number: &str,
+        bom_override: Option<&BomOverride>,
+    ) -> Result<Self> {
+        let mut check = Self::run(conn, process_order, material_number)?;
+        if check.conforms() {
+            return Ok(check);
+        }
+        let invalid = |msg: String| rusqlite::Error::InvalidParameterName(msg);
+        let Some(bom_override) = bom_override else {
+            return Err(invalid(format!(
+                "Material {} is not on the BOM of process order {} ({}), a supervisor override is required",
+                material_number,
+                process_order,
+                check.components.join(", ")
+            )));
+        };
+        let reason = bom_override.reason.trim();
+        if reason.is_empty() {
+            return Err(invalid("A reason is required for the override".to_string()));
+        }
+        let Some(supervisor_id) = bom_override.supervisor_id else {
+            return Err(invalid(
+                "The supervisor override has not been verified".to_string(),
+            ));
+        };
+        check.override_by = Some(supervisor_id);
+        check.override_reason = Some(reason.to_string());
+        Ok(check)
+    }
+}
+
+impl BomOverride {
+    /// Signs the supervisor in and checks their right to override the BOM. The credentials go
+    /// through the normal sign-in, so call this outside any transaction: a failed attempt has
+    /// to stay recorded to count towards the lockout.
+    pub fn verify(&mut self, conn: &Connection) -> Result<()> {
+        let invalid = |msg: String| rusqlite::Error::InvalidParameterName(msg);
+        let supervisor = User::signin(
+            conn,
+            &SigninPayload {
+                staffid: self.staffid.clone(),
+                password: self.password.clone(),
+            },
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => {
+                invalid("Supervisor staff ID or password is wrong".to_string())
+            }
+            e => e,
+        })?;
+        let allowed = Permission::all_for_user(conn, supervisor.id)?
+            .iter()
+            .any(|p| p.model == BOM_OVERRIDE_MODEL && p.can_create);
+        if !allowed {
+            return Err(invalid(format!(
+                "{} is not allowed to override the BOM",
+                supervisor.staffid
+            )));
+        }
+        self.supervisor_id = Some(supervisor.id);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_bom_check_alternatives_and_override() {
+    use crate::backend::models::hash_password;
+
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::manager::migrations::migrate_up(&mut conn, None).unwrap();
+    conn.execute_batch(
+        "INSERT INTO materials (code, key, value, created_at) VALUES ('220012061', 'FILM,INK', '1,2', datetime('now'));
+         INSERT INTO materials_value_description (id, value) VALUES (1, '30000950'), (2, '40000120'), (3, '30000951');
+         INSERT INTO roles (id, name) VALUES (2, 'Supervisor'), (3, 'Operator');
+         INSERT INTO content_type (id, model) VALUES (1, 'bom_overrides');
+         INSERT INTO permissions (id, codename, name, content_type_id, can_create) VALUES (1, 'can_create_bom_overrides', 'Can create bom_overrides', 1, 1);
+         INSERT INTO role_permissions (role_id, permission_id) VALUES (2, 1);",
+    )
+    .unwrap();
+    let password = hash_password("secret").unwrap();
+    conn.execute(
+        "INSERT INTO users (id, full_name, staffid, password, role_id, page_id) VALUES (1, 'Supervisor', 'SUP1', ?1, 2, '/'), (2, 'Operator', 'OP1', ?1, 3, '/')",
+        params![password],
+    )
+    .unwrap();
+
+    assert_eq!(
+        BomCheck::run(&conn, "220012061", "000000000030000950")
+            .unwrap()
+            .status,
+        "component"
+    );
+    assert_eq!(
+        BomCheck::run(&conn, "220099999", "30000951")
+            .unwrap()
+            .status,
+        "unknown"
+    );
+    assert!(BomCheck::enforce(&conn, "220012061", "30000951", None).is_err());
+
+    BomAlternative::create(
+        &conn,
+        &BomAlternativeCreatePayload {
+            material_number: "30000950".to_string(),
+            alternative_number: "30000951".to_string(),
+            process_order: Some("220012061".to_string()),
+            note: None,
+        },
+        Some(1),
+    )
+    .unwrap();
+    let check = BomCheck::enforce(&conn, "220012061", "30000951", None).unwrap();
+    assert_eq!(check.alternative_for.as_deref(), Some("30000950"));
+
+    let mut bom_override = BomOverride {
+        staffid: "OP1".to_string(),
+        password: "secret".to_string(),
+        reason: "Film out of stock".to_string(),
+        supervisor_id: None,
+    };
+    assert!(bom_override.verify(&conn).is_err());
+    assert!(BomCheck::enforce(&conn, "220012061", "30000999", Some(&bom_override)).is_err());
+    bom_override.staffid = "SUP1".to_string();
+    bom_override.password = "wrong".to_string();
+    assert!(bom_override.verify(&conn).is_err());
+    bom_override.password = "secret".to_string();
+    bom_override.verify(&conn).unwrap();
+    let check = BomCheck::enforce(&conn, "220012061", "30000999", Some(&bom_override)).unwrap();
+    assert_eq!(check.status, "mismatch");
+    assert_eq!(check.override_by, Some(1));
+
+    // Wrong overrides on a roll count towards the supervisor's lockout even though the roll is
+    // never written
+    conn.execute(
+        "INSERT INTO jobs (id, production_order) VALUES (1, '220012061')",
+        [],
+    )
+    .unwrap();
+    bom_override.password = "wrong".to_string();
+    bom_override.supervisor_id = None;
+    let roll = crate::backend::models::InputRollCreatePayload {
+        job_id: 1,
+        batch: "J23-612".to_string(),
+        material_document: None,
+        material_number: "30000999".to_string(),
+        start_meter: 0.0,
+        start_weight: "500KG".to_string(),
+        consumed_weight: None,
+        output_batch: None,
+        remnant_id: None,
+        bom_override: Some(bom_override.clone()),
+    };
+    for _ in 0..5 {
+        assert!(crate::backend::models::InputRoll::create(&mut conn, &roll, 2).is_err());
+    }
+    let locked_until: Option<String> = conn
+        .query_row("SELECT locked_until FROM users WHERE id = 1", [], |r| {
+            r.get(0)
+        })
+        .unwrap();
+    assert!(locked_until.is_some());
+    bom_override.password = "secret".to_string();
+    assert!(bom_override.verify(&conn).is_err());
+}
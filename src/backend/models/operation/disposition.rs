@@ -0,0 +1,236 @@
+use chrono::Local;
+use rusqlite::{Connection, Result, params};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::models::{AuditLog, OutputRoll, Scrap, ScrapCreatePayload, Shift};
+
+/// A QA decision on a held output roll.
+#[derive(Debug, Serialize)]
+pub struct RollDisposition {
+    pub id: i32,
+    pub output_roll_id: i32,
+    pub action: String,
+    pub from_status: Option<String>,
+    pub to_status: String,
+    pub notes: String,
+    pub scrap_id: Option<i32>,
+    pub created_by: Option<i32>,
+    pub created_by_name: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Deserialize)]
+pub struct DispositionPayload {
+    pub output_roll_id: i32,
+    /// `release`, `rework`, `downgrade` or `scrap`
+    pub action: String,
+    pub notes: String,
+    /// Required when scrapping, for the `scraps` row
+    pub scrap_type_id: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct DispositionFilterPayload {
+    pub output_roll_id: i32,
+}
+
+impl RollDisposition {
+    /// Applies a QA decision to a roll that is on hold or in rework. Scrapping books the
+    /// roll's net weight as scrap against its job.
+    pub fn create(conn: &mut Connection, data: &DispositionPayload, user_id: i32) -> Result<Self> {
+        let invalid = |msg: String| rusqlite::Error::InvalidParameterName(msg);
+        let to_status = match data.action.as_str() {
+            "release" => "released",
+            "rework" => "rework",
+            "downgrade" => "downgraded",
+            "scrap" => "scrapped",
+            other => return Err(invalid(format!("Unknown disposition '{}'", other))),
+        };
+        let notes = data.notes.trim();
+        if notes.is_empty() {
+            return Err(invalid(
+                "Notes are required for a QA disposition".to_string(),
+            ));
+        }
+
+        let tx = conn.transaction()?;
+        let mut roll = OutputRoll::find_by_id(&tx, data.output_roll_id)?;
+        let from_status = roll.qa_status.clone();
+        if !matches!(from_status.as_deref(), Some("on_hold") | Some("rework")) {
+            return Err(invalid(format!(
+                "Output roll {} is not on hold",
+                roll.output_batch
+            )));
+        }
+        let before = AuditLog::snapshot(&roll);
+        let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+
+        let scrap_id = if to_status == "scrapped" {
+            let scrap_type_id = data
+                .scrap_type_id
+                .ok_or_else(|| invalid("Scrap type is required to scrap a roll".to_string()))?;
+            if roll.final_weight <= 0.0 {
+                return Err(invalid(format!(
+                    "Output roll {} must be weighed before it is scrapped",
+                    roll.output_batch
+                )));
+            }
+            let job_shift: Option<i32> = tx.query_row(
+                "SELECT shift_id FROM jobs WHERE id = ?1",
+                params![roll.job_id],
+                |row| row.get(0),
+            )?;
+            // Older jobs may have no shift, book the scrap on the one running now
+            let shift_id = match job_shift {
+                Some(shift_id) => shift_id,
+                None => Shift::resolve(&tx, Local::now().naive_local(), None)?.shift_id,
+            };
+            let scrap = Scrap::create(
+                &tx,
+                &ScrapCreatePayload {
+                    shift_id,
+                    time: now.clone(),
+                    scrap_type_id,
+                    weight_kg: roll.final_weight,
+                    notes: Some(format!("QA scrap of roll {}: {}", roll.output_batch, notes)),
+                    job_id: Some(roll.job_id),
+                },
+                user_id,
+            )?;
+            Some(scrap.id)
+        } else {
+            None
+        };
+
+        tx.execute(
+            "UPDATE output_rolls SET qa_status = ?1, updated_by = ?2, updated_at = ?3 WHERE id = ?4",
+            params![to_status, user_id, now, roll.id],
+        )?;
+        tx.execute(
+            "INSERT INTO roll_dispositions (output_roll_id, action, from_status, to_status, notes, scrap_id, created_by, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![roll.id, data.action, from_status, to_status, notes, scrap_id, user_id, now],
+        )?;
+        let id = tx.last_insert_rowid() as i32;
+
+        roll.qa_status = Some(to_status.to_string());
+        roll.updated_by = Some(user_id);
+        roll.updated_at = now.clone();
+        AuditLog::updated(&tx, Some(user_id), "output_rolls", roll.id, &before, &roll)?;
+        let record = RollDisposition {
+            id,
+            output_roll_id: roll.id,
+            action: data.action.clone(),
+            from_status,
+            to_status: to_status.to_string(),
+            notes: notes.to_string(),
+            scrap_id,
+            created_by: Some(user_id),
+            created_by_name: None,
+            created_at: now,
+        };
+        AuditLog::created(&tx, Some(user_id), "roll_dispositions", id, &record)?;
+        tx.commit()?;
+        Ok(record)
+    }
+
+    pub fn for_roll(conn: &Connection, output_roll_id: i32) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT d.id, d.output_roll_id, d.action, d.from_status, d.to_status, d.notes, d.scrap_id, d.created_by, u.full_name, d.created_at \
+             FROM roll_dispositions d \
+             LEFT JOIN users u ON d.created_by = u.id \
+             WHERE d.output_roll_id = ?1 \
+             ORDER BY d.id",
+        )?;
+        stmt.query_map(params![output_roll_id], |row| {
+            Ok(RollDisposition {
+                id: row.get(0)?,
+                output_roll_id: row.get(1)?,
+                action: row.get(2)?,
+                from_status: row.get(3)?,
+                to_status: row.get(4)?,
+                notes: row.get(5)?,
+                scrap_id: row.get(6)?,
+                created_by: row.get(7)?,
+                created_by_name: row.get(8)?,
+                created_at: row.get(9)?,
+            })
+        })?
+        .collect()
+    }
+}
+
+#[test]
+fn test_flagged_roll_hold_and_scrap() {
+    use crate::backend::models::OutputRollCreatePayload;
+
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::manager::migrations::migrate_up(&mut conn, None).unwrap();
+    conn.execute_batch(
+        "INSERT INTO users (id, full_name) VALUES (1, 'QA Inspector');
+         INSERT INTO machines (id, name, label) VALUES (1, 'Printer 1', 'P1');
+         INSERT INTO shifts (id, name) VALUES (1, 'Day');
+         INSERT INTO scrap_types (id, name) VALUES (1, 'Print defect');
+         INSERT INTO jobs (id, machine_id, shift_id, created_by, production_order) VALUES (1, 1, 1, 1, '220012061');
+         INSERT INTO input_rolls (id, job_id, batch, created_by, start_weight) VALUES (1, 1, 'J23-612', 1, '520KG');",
+    )
+    .unwrap();
+
+    let roll = OutputRoll::create(
+        &mut conn,
+        &OutputRollCreatePayload {
+            final_meter: 6000.0,
+            batch: "J23-612".to_string(),
+            flag_reason: Some("3:Colour out of tolerance".to_string()),
+            core_weight: None,
+            shift_id: Some(1),
+            job_id: 1,
+            machine_id: 1,
+            input_roll_id: 1,
+            flag_count: 1,
+        },
+        1,
+    )
+    .unwrap();
+    assert_eq!(roll.qa_status.as_deref(), Some("on_hold"));
+    conn.execute(
+        "UPDATE output_rolls SET final_weight = 240.0 WHERE id = ?1",
+        params![roll.id],
+    )
+    .unwrap();
+
+    let payload = |action: &str, scrap_type_id| DispositionPayload {
+        output_roll_id: roll.id,
+        action: action.to_string(),
+        notes: "Checked against master sample".to_string(),
+        scrap_type_id,
+    };
+    assert!(RollDisposition::create(&mut conn, &payload("scrap", None), 1).is_err());
+    RollDisposition::create(&mut conn, &payload("rework", None), 1).unwrap();
+    // A job without a shift books the scrap on the shift running now
+    conn.execute_batch(
+        "UPDATE jobs SET shift_id = NULL WHERE id = 1;
+         UPDATE shifts SET start_time = '00:00', end_time = '00:00' WHERE id = 1;",
+    )
+    .unwrap();
+    let scrapped = RollDisposition::create(&mut conn, &payload("scrap", Some(1)), 1).unwrap();
+
+    let (weight, shift_id): (f64, i32) = conn
+        .query_row(
+            "SELECT weight_kg, shift_id FROM scraps WHERE id = ?1",
+            params![scrapped.scrap_id],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!((weight, shift_id), (240.0, 1));
+    assert!(RollDisposition::create(&mut conn, &payload("release", None), 1).is_err());
+    let history = RollDisposition::for_roll(&conn, roll.id).unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[1].from_status.as_deref(), Some("rework"));
+    assert_eq!(
+        OutputRoll::find_by_id(&conn, roll.id)
+            .unwrap()
+            .qa_status
+            .as_deref(),
+        Some("scrapped")
+    );
+}
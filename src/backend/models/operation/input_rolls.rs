@@ -10,10 +10,13 @@ pub struct EndInputRollPayload {
     pub production_order: String,
 }
 use chrono::Local;
-use rusqlite::{Connection, Result, params};
+use rusqlite::{Connection, OptionalExtension, Result, TransactionBehavior, params};
 use serde::{Deserialize, Serialize};
 
-use crate::sap::post_material_document;
+use crate::backend::models::{
+    AuditLog, BomCheck, BomOverride, Job, Remnant, SapOutbox, SapReversal,
+};
+use crate::sap::{SapGateway, SapPosting, deliver_now};
 
 #[derive(Debug, Serialize)]
 pub struct InputRoll {
@@ -41,6 +44,16 @@ pub struct InputRollCreatePayload {
     pub start_meter: f64,
     pub start_weight: String,
     pub consumed_weight: Option<f64>,
+    /// Output roll of an upstream job loaded as this input; its batch, weight and meterage
+    /// replace the values above
+    #[serde(default)]
+    pub output_batch: Option<String>,
+    /// Remnant of an earlier roll loaded as this input; it fills the same values
+    #[serde(default)]
+    pub remnant_id: Option<i32>,
+    /// Supervisor sign-off when the material is not on the process order's BOM
+    #[serde(default)]
+    pub bom_override: Option<BomOverride>,
 }
 
 #[derive(Deserialize)]
@@ -71,6 +84,105 @@ pub struct InputRollFilterPayload {
     pub page: Option<i32>,
 }
 
+impl InputRollCreatePayload {
+    /// Checks the material against the BOM of `process_order`. A remnant is checked by its own
+    /// material, which replaces the one sent.
+    pub fn check_bom(&self, conn: &Connection, process_order: &str) -> Result<BomCheck> {
+        let remnant_material = match self.remnant_id {
+            Some(remnant_id) => Remnant::loadable(conn, remnant_id)?.material_number,
+            None => None,
+        };
+        BomCheck::enforce(
+            conn,
+            process_order,
+            remnant_material.as_deref().unwrap_or(&self.material_number),
+            self.bom_override.as_ref(),
+        )
+    }
+
+    /// Verifies the supervisor override, if one was sent, before the roll's transaction opens.
+    pub fn verify_override(&mut self, conn: &Connection) -> Result<()> {
+        match &mut self.bom_override {
+            Some(bom_override) => bom_override.verify(conn),
+            None => Ok(()),
+        }
+    }
+
+    /// Fills batch, weight and meterage from `output_batch` when an upstream output roll is
+    /// loaded, returning that roll's id. The roll must be weighed and not yet consumed. A
+    /// remnant is only checked here, [`Remnant::claim`] fills it in.
+    pub fn resolve(&self, conn: &Connection) -> Result<(InputRollCreatePayload, Option<i32>)> {
+        if let Some(remnant_id) = self.remnant_id {
+            if self
+                .output_batch
+                .as_deref()
+                .is_some_and(|b| !b.trim().is_empty())
+            {
+                return Err(rusqlite::Error::InvalidParameterName(
+                    "Load either an output roll or a remnant, not both".to_string(),
+                ));
+            }
+            Remnant::loadable(conn, remnant_id)?;
+        }
+        let Some(output_batch) = self
+            .output_batch
+            .as_deref()
+            .map(str::trim)
+            .filter(|b| !b.is_empty())
+        else {
+            return Ok((self.clone(), None));
+        };
+
+        let roll = conn
+            .query_row(
+                "SELECT id, CAST(final_weight AS REAL), CAST(final_meter AS REAL), consumed_at, qa_status FROM output_rolls WHERE output_batch = ?1 ORDER BY id DESC LIMIT 1",
+                params![output_batch],
+                |row| {
+                    Ok((
+                        row.get::<_, i32>(0)?,
+                        row.get::<_, Option<f64>>(1)?,
+                        row.get::<_, Option<f64>>(2)?,
+                        row.get::<_, Option<String>>(3)?,
+                        row.get::<_, Option<String>>(4)?,
+                    ))
+                },
+            )
+            .optional()?;
+        let invalid = |msg: String| rusqlite::Error::InvalidParameterName(msg);
+        let Some((id, final_weight, final_meter, consumed_at, qa_status)) = roll else {
+            return Err(invalid(format!("Output roll {} not found", output_batch)));
+        };
+        let final_weight = final_weight.unwrap_or(0.0);
+        if final_weight <= 0.0 {
+            return Err(invalid(format!(
+                "Output roll {} has not been weighed",
+                output_batch
+            )));
+        }
+        if let Some(status) =
+            qa_status.filter(|s| matches!(s.as_str(), "on_hold" | "rework" | "scrapped"))
+        {
+            return Err(invalid(format!(
+                "Output roll {} is {} by QA",
+                output_batch,
+                status.replace('_', " ")
+            )));
+        }
+        if consumed_at.is_some() {
+            return Err(invalid(format!(
+                "Output roll {} is already consumed",
+                output_batch
+            )));
+        }
+
+        let mut resolved = self.clone();
+        resolved.batch = output_batch.to_string();
+        resolved.start_weight = format!("{}KG", final_weight);
+        resolved.start_meter = final_meter.unwrap_or(0.0);
+        Ok((resolved, Some(id)))
+    }
+}
+
 impl InputRoll {
     pub fn find_by_job_id(conn: &Connection, job_id: i32) -> Result<Self> {
         let mut stmt = conn.prepare(
@@ -95,38 +207,160 @@ impl InputRoll {
         })
     }
 
-    pub async fn end_input_roll(conn: &Connection, data: &EndInputRollPayload) -> Result<String> {
+    /// Records the consumed weight locally and queues the goods issue in the SAP outbox. Returns
+    /// the material document when SAP accepted the posting straight away, `None` when it stays
+    /// queued for the outbox worker.
+    pub async fn end_input_roll(
+        conn: &Connection,
+        sap: &dyn SapGateway,
+        data: &EndInputRollPayload,
+        user_id: Option<i32>,
+    ) -> Result<Option<String>> {
+        // The local update and the outbox entry commit together; SAP is only called afterwards
+        let tx = conn.unchecked_transaction()?;
+        let queued = Self::queue_end(&tx, sap, data, user_id)?;
+        tx.commit()?;
+        Self::deliver_end(conn, sap, queued).await
+    }
+
+    /// The local half of [`InputRoll::end_input_roll`], on the caller's transaction. Returns the
+    /// outbox message to post and whether it is the goods issue itself rather than the reversal
+    /// of one SAP already holds.
+    pub(crate) fn queue_end(
+        conn: &Connection,
+        sap: &dyn SapGateway,
+        data: &EndInputRollPayload,
+        user_id: Option<i32>,
+    ) -> Result<(Option<i32>, bool)> {
+        let before = AuditLog::snapshot(&InputRoll::find_by_id(conn, data.input_roll_id)?);
         let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-        let now_str = now.as_str();
-
-        let document_number = post_material_document(
-            &data.material_number,
-            &data.batch,
-            &data.production_order,
-            &data.consumed_weight,
-            &data.weight_unit,
-            &data.posting_date,
-            "DW01",
-        )
-        .await
-        .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
 
         conn.execute(
-        "UPDATE input_rolls SET consumed_weight = ?1, updated_at = ?2, material_document = ?3 WHERE id = ?4",
-        params![&data.consumed_weight, now_str, &document_number, data.input_roll_id],
-    )?;
+            "UPDATE input_rolls SET consumed_weight = ?1, updated_at = ?2 WHERE id = ?3",
+            params![&data.consumed_weight, now, data.input_roll_id],
+        )?;
 
-        Ok(document_number)
+        let section_id: Option<i32> = conn.query_row(
+            "SELECT j.section_id FROM input_rolls ir JOIN jobs j ON ir.job_id = j.id WHERE ir.id = ?1",
+            params![data.input_roll_id],
+            |row| row.get(0),
+        )?;
+        // A reloaded remnant is issued from wherever it is stocked now
+        let remnant_location: Option<String> = conn
+            .query_row(
+                "SELECT r.storage_location FROM input_rolls ir JOIN remnants r ON r.id = ir.source_remnant_id WHERE ir.id = ?1",
+                params![data.input_roll_id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+        let storage_location = match remnant_location {
+            Some(location) => location,
+            None => sap.settings().section_storage_location(conn, section_id)?,
+        };
+
+        let posting = SapPosting::GoodsIssue {
+            material: data.material_number.clone(),
+            batch: data.batch.clone(),
+            order: data.production_order.clone(),
+            quantity: data.consumed_weight.clone(),
+            unit: data.weight_unit.clone(),
+            posting_date: data.posting_date.clone(),
+            storage_location,
+        };
+        // Ending a roll again with another weight corrects an issue SAP may already hold
+        let reversal = SapReversal::correct(
+            conn,
+            "input_rolls",
+            data.input_roll_id,
+            "Consumed weight corrected",
+            user_id,
+        )?;
+        let queued = match reversal {
+            Some(reversal) => (reversal.message_id, false),
+            None => {
+                let key = SapReversal::current_key(conn, "input_rolls", data.input_roll_id)?
+                    .unwrap_or_else(|| format!("GI-IR{}", data.input_roll_id));
+                let message = SapOutbox::enqueue(
+                    conn,
+                    &key,
+                    &posting,
+                    Some(("input_rolls", data.input_roll_id)),
+                    user_id,
+                )?;
+                (Some(message.id), true)
+            }
+        };
+
+        let after = InputRoll::find_by_id(conn, data.input_roll_id)?;
+        AuditLog::updated(conn, user_id, "input_rolls", after.id, &before, &after)?;
+        Ok(queued)
+    }
+
+    /// Posts what [`InputRoll::queue_end`] queued once its transaction has committed.
+    pub(crate) async fn deliver_end(
+        conn: &Connection,
+        sap: &dyn SapGateway,
+        (message_id, goods_issue): (Option<i32>, bool),
+    ) -> Result<Option<String>> {
+        let Some(id) = message_id else {
+            return Ok(None);
+        };
+        let message = deliver_now(conn, sap, id).await?;
+        Ok(message.document.filter(|_| goods_issue))
     }
 
-    pub fn create(conn: &Connection, data: &InputRollCreatePayload, user_id: i32) -> Result<Self> {
+    pub fn create(
+        conn: &mut Connection,
+        data: &InputRollCreatePayload,
+        user_id: i32,
+    ) -> Result<Self> {
+        let mut data = data.clone();
+        data.verify_override(conn)?;
+        // Immediate so a remnant or upstream roll cannot be claimed twice
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let record = Self::insert(&tx, &data, user_id)?;
+        tx.commit()?;
+        Ok(record)
+    }
+
+    /// Claims the source and inserts the roll with its audit entries. Call inside the
+    /// transaction of the caller.
+    pub(crate) fn insert(
+        conn: &Connection,
+        data: &InputRollCreatePayload,
+        user_id: i32,
+    ) -> Result<Self> {
         let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let (mut data, source_output_roll_id) = data.resolve(conn)?;
+        Job::ensure_open(conn, data.job_id)?;
+        let process_order: String = conn.query_row(
+            "SELECT production_order FROM jobs WHERE id = ?1",
+            params![data.job_id],
+            |row| row.get(0),
+        )?;
+        let bom = data.check_bom(conn, &process_order)?;
+        if let Some(remnant_id) = data.remnant_id {
+            Remnant::claim(conn, remnant_id, &mut data, &now)?;
+        }
+        if let Some(output_roll_id) = source_output_roll_id {
+            let claimed = conn.execute(
+                "UPDATE output_rolls SET consumed_at = ?1, consumed_job_id = ?2 WHERE id = ?3 AND consumed_at IS NULL",
+                params![now, data.job_id, output_roll_id],
+            )?;
+            if claimed == 0 {
+                return Err(rusqlite::Error::InvalidParameterName(format!(
+                    "Output roll {} is already consumed",
+                    data.batch
+                )));
+            }
+        }
         conn.execute(
-            "INSERT INTO input_rolls (job_id, batch, material_document, material_number, start_meter, created_by, start_weight, consumed_weight, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
-            params![data.job_id, data.batch, data.material_document.clone().unwrap_or_default(), data.material_number, data.start_meter, user_id, data.start_weight, data.consumed_weight, now, now],
+            "INSERT INTO input_rolls (job_id, batch, material_document, material_number, start_meter, created_by, start_weight, consumed_weight, created_at, updated_at, source_output_roll_id, source_remnant_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![data.job_id, data.batch, data.material_document.clone().unwrap_or_default(), data.material_number, data.start_meter, user_id, data.start_weight, data.consumed_weight, now, now, source_output_roll_id, data.remnant_id],
         )?;
         let id = conn.last_insert_rowid() as i32;
-        Ok(InputRoll {
+        let record = InputRoll {
             id,
             job_id: data.job_id,
             batch: data.batch.clone(),
@@ -140,63 +374,124 @@ impl InputRoll {
             consumed_weight: data.consumed_weight,
             created_at: now.clone(),
             updated_at: now.clone(),
-        })
+        };
+        AuditLog::created(conn, Some(user_id), "input_rolls", id, &record)?;
+        if let Some(supervisor_id) = bom.override_by {
+            conn.execute(
+                "UPDATE input_rolls SET bom_override_by = ?1, bom_override_reason = ?2 WHERE id = ?3",
+                params![supervisor_id, bom.override_reason, id],
+            )?;
+            AuditLog::created(conn, Some(supervisor_id), "bom_overrides", id, &bom)?;
+        }
+        Ok(record)
     }
 
-    pub fn update(&mut self, conn: &Connection, data: &InputRollUpdatePayload) -> Result<()> {
+    pub async fn update(
+        &mut self,
+        conn: &Connection,
+        sap: &dyn SapGateway,
+        data: &InputRollUpdatePayload,
+        user_id: Option<i32>,
+    ) -> Result<()> {
+        let before = AuditLog::snapshot(self);
+        // A correction SAP cannot take right now rolls the whole update back
+        let tx = conn.unchecked_transaction()?;
         let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
         if let Some(batch) = &data.batch {
-            conn.execute(
+            tx.execute(
                 "UPDATE input_rolls SET batch = ?1 WHERE id = ?2",
                 params![batch, self.id],
             )?;
             self.batch = batch.clone();
         }
         if let Some(material_document) = &data.material_document {
-            conn.execute(
+            tx.execute(
                 "UPDATE input_rolls SET material_document = ?1 WHERE id = ?2",
                 params![material_document, self.id],
             )?;
             self.material_document = Some(material_document.clone());
         }
         if let Some(material_number) = &data.material_number {
-            conn.execute(
+            tx.execute(
                 "UPDATE input_rolls SET material_number = ?1 WHERE id = ?2",
                 params![material_number, self.id],
             )?;
             self.material_number = Some(material_number.clone());
         }
         if let Some(start_meter) = data.start_meter {
-            conn.execute(
+            tx.execute(
                 "UPDATE input_rolls SET start_meter = ?1 WHERE id = ?2",
                 params![start_meter, self.id],
             )?;
             self.start_meter = start_meter;
         }
         if let Some(start_weight) = &data.start_weight {
-            conn.execute(
+            tx.execute(
                 "UPDATE input_rolls SET start_weight = ?1 WHERE id = ?2",
                 params![start_weight, self.id],
             )?;
             self.start_weight = start_weight.clone();
         }
         if let Some(consumed_weight) = data.consumed_weight {
-            conn.execute(
+            tx.execute(
                 "UPDATE input_rolls SET consumed_weight = ?1 WHERE id = ?2",
                 params![consumed_weight, self.id],
             )?;
             self.consumed_weight = Some(consumed_weight);
         }
-        conn.execute(
+        tx.execute(
             "UPDATE input_rolls SET updated_at = ?1 WHERE id = ?2",
             params![now, self.id],
         )?;
         self.updated_at = now;
+        AuditLog::updated(&tx, user_id, "input_rolls", self.id, &before, self)?;
+        let reversal = if data.consumed_weight.is_some() {
+            SapReversal::correct(
+                &tx,
+                "input_rolls",
+                self.id,
+                "Consumed weight corrected",
+                user_id,
+            )?
+            .and_then(|reversal| reversal.message_id)
+        } else {
+            None
+        };
+        tx.commit()?;
+
+        if let Some(id) = reversal {
+            deliver_now(conn, sap, id).await?;
+        }
         Ok(())
     }
 
-    pub fn delete(&self, conn: &Connection) -> Result<()> {
-        conn.execute("DELETE FROM input_rolls WHERE id = ?1", params![self.id])?;
+    pub async fn delete(
+        &self,
+        conn: &Connection,
+        sap: &dyn SapGateway,
+        user_id: Option<i32>,
+    ) -> Result<()> {
+        // Hand an upstream output roll or remnant back so it can be loaded again, but only
+        // once the roll itself is really gone
+        let tx = conn.unchecked_transaction()?;
+        tx.execute(
+            "UPDATE output_rolls SET consumed_at = NULL, consumed_job_id = NULL WHERE id = (SELECT source_output_roll_id FROM input_rolls WHERE id = ?1)",
+            params![self.id],
+        )?;
+        tx.execute(
+            "UPDATE remnants SET consumed_at = NULL, consumed_job_id = NULL WHERE id = (SELECT source_remnant_id FROM input_rolls WHERE id = ?1)",
+            params![self.id],
+        )?;
+        tx.execute("DELETE FROM input_rolls WHERE id = ?1", params![self.id])?;
+        AuditLog::deleted(&tx, user_id, "input_rolls", self.id, self)?;
+        let reversal =
+            SapReversal::correct(&tx, "input_rolls", self.id, "Input roll deleted", user_id)?
+                .and_then(|reversal| reversal.message_id);
+        tx.commit()?;
+
+        if let Some(id) = reversal {
+            deliver_now(conn, sap, id).await?;
+        }
         Ok(())
     }
 
@@ -335,3 +630,103 @@ impl InputRoll {
         rows.collect::<Result<Vec<_>, _>>()
     }
 }
+
+#[actix_web::test]
+async fn test_output_roll_loaded_as_input() {
+    use crate::sap::{FakeSapGateway, FakeSapState};
+
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::manager::migrations::migrate_up(&mut conn, None).unwrap();
+    conn.execute_batch(
+        "INSERT INTO users (id, full_name) VALUES (1, 'Operator');
+         INSERT INTO jobs (id, production_order) VALUES (1, '220012061'), (2, '220012062');
+         INSERT INTO input_rolls (id, job_id, batch, created_by, start_weight) VALUES (1, 1, 'J23-612', 1, '520KG');
+         INSERT INTO output_rolls (id, input_roll_id, output_batch, final_weight, final_meter, created_by) VALUES (1, 1, '26001P1001', 0, 0, 1);",
+    )
+    .unwrap();
+    let sap = FakeSapGateway::new(FakeSapState::default());
+
+    let payload = InputRollCreatePayload {
+        job_id: 2,
+        batch: String::new(),
+        material_document: None,
+        material_number: "30000950".to_string(),
+        start_meter: 0.0,
+        start_weight: String::new(),
+        consumed_weight: None,
+        output_batch: Some("26001P1001".to_string()),
+        remnant_id: None,
+        bom_override: None,
+    };
+    let unweighed = InputRoll::create(&mut conn, &payload, 1);
+    assert!(matches!(
+        unweighed,
+        Err(rusqlite::Error::InvalidParameterName(_))
+    ));
+
+    conn.execute(
+        "UPDATE output_rolls SET final_weight = 250.5, final_meter = 6100 WHERE id = 1",
+        [],
+    )
+    .unwrap();
+    let roll = InputRoll::create(&mut conn, &payload, 1).unwrap();
+    assert_eq!(roll.batch, "26001P1001");
+    assert_eq!(roll.start_weight, "250.5KG");
+    assert_eq!(roll.start_meter, 6100.0);
+    let consumed_job: Option<i32> = conn
+        .query_row(
+            "SELECT consumed_job_id FROM output_rolls WHERE id = 1",
+            [],
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(consumed_job, Some(2));
+    assert!(InputRoll::create(&mut conn, &payload, 1).is_err());
+
+    roll.delete(&conn, &sap, Some(1)).await.unwrap();
+    let roll = InputRoll::create(&mut conn, &payload, 1).unwrap();
+
+    // A delete that fails keeps the upstream roll claimed by the roll still loaded
+    conn.execute_batch(
+        "CREATE TEMP TRIGGER keep_input_roll BEFORE DELETE ON input_rolls
+         BEGIN SELECT RAISE(ABORT, 'rejected'); END;",
+    )
+    .unwrap();
+    assert!(roll.delete(&conn, &sap, Some(1)).await.is_err());
+    let consumed_job: Option<i32> = conn
+        .query_row(
+            "SELECT consumed_job_id FROM output_rolls WHERE id = 1",
+            [],
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(consumed_job, Some(2));
+    conn.execute_batch("DROP TRIGGER keep_input_roll").unwrap();
+    roll.delete(&conn, &sap, Some(1)).await.unwrap();
+
+    // A roll that fails to insert leaves neither a new job nor a claimed upstream roll behind
+    conn.execute_batch(
+        "INSERT INTO sections (id, name) VALUES (1, 'Printing');
+         INSERT INTO machines (id, name, label, section_id) VALUES (1, 'Printer 1', 'P1', 1);
+         INSERT INTO shifts (id, name) VALUES (1, 'Day');
+         CREATE TEMP TRIGGER reject_input_roll BEFORE INSERT ON input_rolls
+         BEGIN SELECT RAISE(ABORT, 'rejected'); END;",
+    )
+    .unwrap();
+    let job = crate::backend::models::JobCreatePayload {
+        machine_id: 1,
+        shift_id: Some(1),
+        production_order: "220012099".to_string(),
+        input_roll: payload,
+    };
+    assert!(crate::backend::models::Job::create(&mut conn, &job, 1).is_err());
+    let (jobs, consumed): (i32, Option<String>) = conn
+        .query_row(
+            "SELECT (SELECT COUNT(*) FROM jobs WHERE production_order = '220012099'), (SELECT consumed_at FROM output_rolls WHERE id = 1)",
+            [],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(jobs, 0);
+    assert_eq!(consumed, None);
+}
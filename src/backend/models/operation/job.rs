@@ -1,5 +1,7 @@
-use crate::backend::models::{InputRoll, InputRollCreatePayload, InputRollFilterPayload};
-use rusqlite::{Connection, Result, params};
+use crate::backend::models::{
+    AuditLog, InputRoll, InputRollCreatePayload, InputRollFilterPayload, Shift, stale_hours,
+};
+use rusqlite::{Connection, Result, TransactionBehavior, params};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize)]
@@ -21,6 +23,7 @@ pub struct JobFilterPayload {
     pub machine_id: Option<i32>,
     pub shift_id: Option<i32>,
     pub created_by: Option<i32>,
+    /// `active`, `stale` (open longer than `JOB_STALE_HOURS`) or `closed`
     pub status: Option<String>,
     pub production_order: Option<String>,
     pub start_datetime: Option<String>,
@@ -32,7 +35,8 @@ pub struct JobFilterPayload {
 #[derive(Deserialize)]
 pub struct JobCreatePayload {
     pub machine_id: i32,
-    pub shift_id: i32,
+    /// Only used when the shift calendar does not cover the current time
+    pub shift_id: Option<i32>,
     pub production_order: String,
     pub input_roll: InputRollCreatePayload,
 }
@@ -77,6 +81,9 @@ pub struct JobSummary {
     pub created_by: i32,
     pub last_updated: String,
     pub total_consumed_weight: f64,
+    pub end_datetime: Option<String>,
+    /// Still open after `JOB_STALE_HOURS`
+    pub stale: bool,
 }
 
 #[derive(Serialize)]
@@ -93,15 +100,23 @@ pub struct JobInputRollMergedResponse {
 
 impl Job {
     pub fn create(
-        conn: &Connection,
+        conn: &mut Connection,
         data: &JobCreatePayload,
         user_id: i32,
     ) -> Result<JobInputRollMerged> {
         let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let mut roll_data = data.input_roll.clone();
+        // Outside the transaction so a wrong supervisor password still counts towards the lockout
+        roll_data.verify_override(conn)?;
+        // The job and its first input roll are written together, so a rejected roll leaves no
+        // empty job behind
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        // Reject an unusable upstream output roll before anything is written
+        roll_data.resolve(&tx)?;
+        roll_data.check_bom(&tx, &data.production_order)?;
         // Check for existing job with same production_order and end_datetime IS NULL (active job)
-        let mut stmt = conn.prepare("SELECT id, machine_id, shift_id, created_by, production_order, start_datetime, end_datetime, created_at, updated_at FROM jobs WHERE production_order = ?1 AND end_datetime IS NULL LIMIT 1")?;
-        let existing_job = stmt
-            .query_row(params![data.production_order.clone()], |row| {
+        let existing_job = tx
+            .query_row("SELECT id, machine_id, shift_id, created_by, production_order, start_datetime, end_datetime, created_at, updated_at FROM jobs WHERE production_order = ?1 AND end_datetime IS NULL LIMIT 1", params![data.production_order.clone()], |row| {
                 Ok(Job {
                     id: row.get(0)?,
                     machine_id: row.get(1)?,
@@ -118,9 +133,10 @@ impl Job {
 
         if let Some(job) = existing_job {
             // Always create a new input roll for the existing job
-            let mut input_roll_payload = data.input_roll.clone();
+            let mut input_roll_payload = roll_data.clone();
             input_roll_payload.job_id = job.id;
-            let input_roll = InputRoll::create(conn, &input_roll_payload, user_id)?;
+            let input_roll = InputRoll::insert(&tx, &input_roll_payload, user_id)?;
+            tx.commit()?;
             return Ok(JobInputRollMerged {
                 id: job.id,
                 shift_id: job.shift_id,
@@ -141,25 +157,27 @@ impl Job {
             });
         }
 
-        // Otherwise, create new job
-        conn.execute(
-            "INSERT INTO jobs (machine_id, shift_id, created_by, production_order, start_datetime, end_datetime, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        // Otherwise, create new job on the shift running now
+        let slot = Shift::resolve(&tx, chrono::Local::now().naive_local(), data.shift_id)?;
+        tx.execute(
+            "INSERT INTO jobs (machine_id, shift_id, created_by, production_order, start_datetime, end_datetime, created_at, updated_at, section_id, production_date) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, (SELECT section_id FROM machines WHERE id = ?1), ?9)",
             params![
                 data.machine_id,
-                data.shift_id,
+                slot.shift_id,
                 user_id,
                 data.production_order,
                 Some(now.as_str()), // start_datetime is now
                 Option::<String>::None, // end_datetime is None
                 now.as_str(),
                 now.as_str(),
+                slot.production_date.to_string(),
             ],
         )?;
-        let id = conn.last_insert_rowid() as i32;
+        let id = tx.last_insert_rowid() as i32;
         let job = Job {
             id,
             machine_id: data.machine_id,
-            shift_id: data.shift_id,
+            shift_id: slot.shift_id,
             created_by: user_id,
             production_order: data.production_order.clone(),
             start_datetime: Some(now.clone()),
@@ -167,10 +185,12 @@ impl Job {
             created_at: now.clone(),
             updated_at: now,
         };
+        AuditLog::created(&tx, Some(user_id), "jobs", id, &job)?;
 
-        let mut input_roll_payload = data.input_roll.clone();
+        let mut input_roll_payload = roll_data.clone();
         input_roll_payload.job_id = job.id;
-        let input_roll = InputRoll::create(conn, &input_roll_payload, user_id)?;
+        let input_roll = InputRoll::insert(&tx, &input_roll_payload, user_id)?;
+        tx.commit()?;
         Ok(JobInputRollMerged {
             id: job.id,
             shift_id: job.shift_id,
@@ -191,24 +211,23 @@ impl Job {
         })
     }
 
-    pub fn filter(
-        conn: &Connection,
-        filter: &JobFilterPayload,
-    ) -> Result<JobSummaryResponse> {
-        let status_is_active = filter
-            .status
-            .as_deref()
-            .map(|s| s.eq_ignore_ascii_case("active"))
-            .unwrap_or(false);
-        let mut sql = String::from(
+    pub fn filter(conn: &Connection, filter: &JobFilterPayload) -> Result<JobSummaryResponse> {
+        let status = filter.status.as_deref().unwrap_or("").to_lowercase();
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let stale_sql =
+            "(j.end_datetime IS NULL AND (julianday(?) - julianday(j.start_datetime)) * 24 > ?)";
+        let mut sql = format!(
             "SELECT j.id, j.production_order, j.machine_id, j.shift_id, j.created_by, \
                 j.updated_at, MAX(ir.updated_at) as last_input_update, \
-                COALESCE(SUM(ir.consumed_weight), 0) as total_consumed_weight \
+                COALESCE(SUM(ir.consumed_weight), 0) as total_consumed_weight, \
+                j.end_datetime, {} \
              FROM jobs j \
              LEFT JOIN input_rolls ir ON ir.job_id = j.id \
              WHERE 1=1",
+            stale_sql
         );
-        let mut params: Vec<rusqlite::types::Value> = Vec::new();
+        let mut params: Vec<rusqlite::types::Value> =
+            vec![now.clone().into(), stale_hours().into()];
         if let Some(id) = filter.id {
             sql.push_str(" AND j.id = ?");
             params.push(id.into());
@@ -245,8 +264,15 @@ impl Job {
             sql.push_str(" AND j.updated_at = ?");
             params.push(updated_at.clone().into());
         }
-        if status_is_active {
-            sql.push_str(" AND j.end_datetime IS NULL");
+        match status.as_str() {
+            "active" => sql.push_str(" AND j.end_datetime IS NULL"),
+            "closed" => sql.push_str(" AND j.end_datetime IS NOT NULL"),
+            "stale" => {
+                sql.push_str(&format!(" AND {}", stale_sql));
+                params.push(now.into());
+                params.push(stale_hours().into());
+            }
+            _ => {}
         }
         sql.push_str(" GROUP BY j.id");
         sql.push_str(" ORDER BY j.created_at DESC");
@@ -267,6 +293,8 @@ impl Job {
                 created_by: row.get(4)?,
                 last_updated,
                 total_consumed_weight: row.get(7)?,
+                end_datetime: row.get(8)?,
+                stale: row.get(9)?,
             })
         })?;
 
@@ -440,11 +468,17 @@ impl Job {
         Ok(jobs)
     }
 
-    pub fn update(&mut self, conn: &Connection, data: &JobUpdatePayload) -> Result<()> {
+    pub fn update(
+        &mut self,
+        conn: &Connection,
+        data: &JobUpdatePayload,
+        user_id: Option<i32>,
+    ) -> Result<()> {
+        let before = AuditLog::snapshot(self);
         let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
         if let Some(machine_id) = data.machine_id {
             conn.execute(
-                "UPDATE jobs SET machine_id = ?1 WHERE id = ?2",
+                "UPDATE jobs SET machine_id = ?1, section_id = (SELECT section_id FROM machines WHERE id = ?1) WHERE id = ?2",
                 params![machine_id, self.id],
             )?;
             self.machine_id = machine_id;
@@ -489,10 +523,11 @@ impl Job {
             params![now, self.id],
         )?;
         self.updated_at = now;
+        AuditLog::updated(conn, user_id, "jobs", self.id, &before, self)?;
         Ok(())
     }
 
-    pub fn delete(&self, conn: &mut Connection) -> Result<()> {
+    pub fn delete(&self, conn: &mut Connection, user_id: Option<i32>) -> Result<()> {
         let has_output_rolls: i32 = conn.query_row(
             "SELECT COUNT(*) FROM output_rolls o \
              JOIN input_rolls i ON o.input_roll_id = i.id \
@@ -513,6 +548,7 @@ impl Job {
             params![self.id],
         )?;
         tx.execute("DELETE FROM jobs WHERE id = ?1", params![self.id])?;
+        AuditLog::deleted(&tx, user_id, "jobs", self.id, self)?;
         tx.commit()?;
         Ok(())
     }
@@ -0,0 +1,355 @@
+use chrono::Local;
+use rusqlite::{Connection, Result, params};
+use serde::{Deserialize, Serialize};
+use std::env;
+
+use crate::backend::models::{AuditLog, Job};
+
+/// Hours a job may stay open before it is flagged stale, unless `JOB_STALE_HOURS` says otherwise
+const DEFAULT_STALE_HOURS: f64 = 24.0;
+
+/// Age after which an open job is stale, from `JOB_STALE_HOURS`.
+pub fn stale_hours() -> f64 {
+    env::var("JOB_STALE_HOURS")
+        .ok()
+        .and_then(|v| v.trim().parse::<f64>().ok())
+        .filter(|h| *h > 0.0)
+        .unwrap_or(DEFAULT_STALE_HOURS)
+}
+
+#[derive(Deserialize)]
+pub struct JobClosePayload {
+    pub id: i32,
+}
+
+/// What a job produced, built when it is closed.
+#[derive(Debug, Serialize)]
+pub struct JobCloseSummary {
+    pub job_id: i32,
+    pub production_order: String,
+    pub start_datetime: Option<String>,
+    pub end_datetime: Option<String>,
+    pub duration_minutes: f64,
+    pub input_rolls: i32,
+    pub input_meters: f64,
+    pub consumed_kg: f64,
+    pub output_rolls: i32,
+    pub output_meters: f64,
+    pub output_kg: f64,
+    pub flagged_rolls: i32,
+    pub flags: i32,
+    pub scrap_kg: f64,
+    pub downtimes: i32,
+    pub downtime_minutes: f64,
+}
+
+pub enum JobClose {
+    Closed(JobCloseSummary),
+    /// Nothing was changed; each entry is something still to do before the job can close
+    Blocked(Vec<String>),
+}
+
+impl Job {
+    /// Everything that stops the job from closing: input rolls not ended or whose goods issue
+    /// has no material document yet, output rolls not weighed and downtimes still open.
+    pub fn close_blockers(&self, conn: &Connection) -> Result<Vec<String>> {
+        let mut missing = Vec::new();
+        if let Some(end) = &self.end_datetime {
+            missing.push(format!("Job was already closed at {}", end));
+            return Ok(missing);
+        }
+
+        let mut stmt = conn.prepare(
+            "SELECT ir.batch, ir.consumed_weight IS NOT NULL, \
+             (SELECT status FROM sap_outbox s WHERE s.ref_model = 'input_rolls' AND s.ref_id = ir.id ORDER BY s.id DESC LIMIT 1) \
+             FROM input_rolls ir \
+             WHERE ir.job_id = ?1 AND COALESCE(ir.material_document, '') = '' \
+             ORDER BY ir.id",
+        )?;
+        let inputs = stmt
+            .query_map(params![self.id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, bool>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>>>()?;
+        for (batch, ended, posting) in inputs {
+            missing.push(match (ended, posting.as_deref()) {
+                (false, _) => format!("Input roll {} has not been ended", batch),
+                (true, Some("failed")) => format!(
+                    "Input roll {} goods issue failed in SAP, retry it from the outbox",
+                    batch
+                ),
+                (true, _) => format!(
+                    "Input roll {} is waiting for its SAP material document",
+                    batch
+                ),
+            });
+        }
+
+        let mut stmt = conn.prepare(
+            "SELECT o.output_batch FROM output_rolls o JOIN input_rolls ir ON o.input_roll_id = ir.id \
+             WHERE ir.job_id = ?1 AND COALESCE(o.final_weight, 0) <= 0 ORDER BY o.id",
+        )?;
+        for batch in stmt.query_map(params![self.id], |row| row.get::<_, String>(0))? {
+            missing.push(format!("Output roll {} has not been weighed", batch?));
+        }
+
+        let mut stmt = conn.prepare(
+            "SELECT start_time FROM downtimes \
+             WHERE COALESCE(end_time, '') = '' \
+             AND (job_id = ?1 OR (job_id IS NULL AND machine_id = ?2 AND julianday(start_time) >= julianday(?3))) \
+             ORDER BY start_time",
+        )?;
+        let open = stmt.query_map(
+            params![self.id, self.machine_id, self.start_datetime],
+            |row| row.get::<_, String>(0),
+        )?;
+        for start in open {
+            missing.push(format!("Downtime from {} is still open", start?));
+        }
+        Ok(missing)
+    }
+
+    /// Rejects new input rolls, output rolls and downtimes for a job that has been closed.
+    pub fn ensure_open(conn: &Connection, job_id: i32) -> Result<()> {
+        let end: Option<String> = conn.query_row(
+            "SELECT end_datetime FROM jobs WHERE id = ?1",
+            params![job_id],
+            |row| row.get(0),
+        )?;
+        match end {
+            Some(end) => Err(rusqlite::Error::InvalidParameterName(format!(
+                "Job {} was closed at {}",
+                job_id, end
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Ends the job when nothing is missing, stamping `end_datetime` and returning its summary.
+    pub fn close(&mut self, conn: &mut Connection, user_id: Option<i32>) -> Result<JobClose> {
+        let tx = conn.transaction()?;
+        let missing = self.close_blockers(&tx)?;
+        if !missing.is_empty() {
+            return Ok(JobClose::Blocked(missing));
+        }
+        let before = AuditLog::snapshot(self);
+        let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        tx.execute(
+            "UPDATE jobs SET end_datetime = ?1, updated_at = ?1 WHERE id = ?2",
+            params![now, self.id],
+        )?;
+        self.end_datetime = Some(now.clone());
+        self.updated_at = now;
+        AuditLog::updated(&tx, user_id, "jobs", self.id, &before, self)?;
+        let summary = JobCloseSummary::for_job(&tx, self.id)?;
+        tx.commit()?;
+        Ok(JobClose::Closed(summary))
+    }
+}
+
+impl JobCloseSummary {
+    /// Running jobs are summarised up to now.
+    pub fn for_job(conn: &Connection, job_id: i32) -> Result<Self> {
+        let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.query_row(
+            "SELECT j.id, j.production_order, j.start_datetime, j.end_datetime, \
+             COALESCE((julianday(COALESCE(j.end_datetime, ?2)) - julianday(j.start_datetime)) * 1440, 0), \
+             (SELECT COUNT(*) FROM input_rolls ir WHERE ir.job_id = j.id), \
+             (SELECT COALESCE(SUM(CAST(ir.start_meter AS REAL)), 0) FROM input_rolls ir WHERE ir.job_id = j.id), \
+             (SELECT COALESCE(SUM(CAST(ir.consumed_weight AS REAL)), 0) FROM input_rolls ir WHERE ir.job_id = j.id), \
+             (SELECT COUNT(*) FROM output_rolls o JOIN input_rolls ir ON o.input_roll_id = ir.id WHERE ir.job_id = j.id), \
+             (SELECT COALESCE(SUM(CAST(o.final_meter AS REAL)), 0) FROM output_rolls o JOIN input_rolls ir ON o.input_roll_id = ir.id WHERE ir.job_id = j.id), \
+             (SELECT COALESCE(SUM(CAST(o.final_weight AS REAL)), 0) FROM output_rolls o JOIN input_rolls ir ON o.input_roll_id = ir.id WHERE ir.job_id = j.id), \
+             (SELECT COUNT(*) FROM output_rolls o JOIN input_rolls ir ON o.input_roll_id = ir.id WHERE ir.job_id = j.id AND COALESCE(o.flag_count, 0) > 0), \
+             (SELECT COALESCE(SUM(o.flag_count), 0) FROM output_rolls o JOIN input_rolls ir ON o.input_roll_id = ir.id WHERE ir.job_id = j.id), \
+             (SELECT COALESCE(SUM(CAST(sc.weight_kg AS REAL)), 0) FROM scraps sc WHERE sc.job_id = j.id), \
+             (SELECT COUNT(*) FROM downtimes d WHERE d.job_id = j.id), \
+             (SELECT COALESCE(SUM(COALESCE(d.duration_minutes, (julianday(?2) - julianday(d.start_time)) * 1440)), 0) FROM downtimes d WHERE d.job_id = j.id) \
+             FROM jobs j WHERE j.id = ?1",
+            params![job_id, now],
+            |row| {
+                let round = |v: f64| (v * 100.0).round() / 100.0;
+                Ok(JobCloseSummary {
+                    job_id: row.get(0)?,
+                    production_order: row.get(1)?,
+                    start_datetime: row.get(2)?,
+                    end_datetime: row.get(3)?,
+                    duration_minutes: round(row.get(4)?),
+                    input_rolls: row.get(5)?,
+                    input_meters: round(row.get(6)?),
+                    consumed_kg: round(row.get(7)?),
+                    output_rolls: row.get(8)?,
+                    output_meters: round(row.get(9)?),
+                    output_kg: round(row.get(10)?),
+                    flagged_rolls: row.get(11)?,
+                    flags: row.get(12)?,
+                    scrap_kg: round(row.get(13)?),
+                    downtimes: row.get(14)?,
+                    downtime_minutes: round(row.get(15)?),
+                })
+            },
+        )
+    }
+}
+
+#[test]
+fn test_job_close_checks_and_summary() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::manager::migrations::migrate_up(&mut conn, None).unwrap();
+    conn.execute_batch(
+        "INSERT INTO users (id, full_name) VALUES (1, 'Operator');
+         INSERT INTO machines (id, name, label) VALUES (1, 'Printer 1', 'P1');
+         INSERT INTO shifts (id, name) VALUES (1, 'Day');
+         INSERT INTO jobs (id, machine_id, shift_id, created_by, production_order, start_datetime) VALUES
+             (1, 1, 1, 1, '220012061', '2026-01-09 07:00:00'),
+             (2, 1, 1, 1, '220012062', datetime('now', 'localtime'));
+         INSERT INTO input_rolls (id, job_id, batch, created_by, start_weight, start_meter, consumed_weight, material_document) VALUES
+             (1, 1, 'J23-612', 1, '520KG', 12000, 500, '4900012345'),
+             (2, 1, 'J23-613', 1, '520KG', 12000, NULL, '');
+         INSERT INTO output_rolls (id, input_roll_id, output_batch, final_meter, final_weight, flag_count, created_by) VALUES
+             (1, 1, '26018P1001', 11800, 480, 2, 1), (2, 1, '26018P1002', 5000, 0, 0, 1);
+         INSERT INTO downtimes (id, shift_id, machine_id, start_time, created_by) VALUES (1, 1, 1, '2026-01-09 08:00:00', 1);",
+    )
+    .unwrap();
+
+    let mut job = Job::find_by_id(&conn, 1).unwrap();
+    let JobClose::Blocked(missing) = job.close(&mut conn, Some(1)).unwrap() else {
+        panic!("job closed with open work");
+    };
+    assert_eq!(
+        missing,
+        vec![
+            "Input roll J23-613 has not been ended",
+            "Output roll 26018P1002 has not been weighed",
+            "Downtime from 2026-01-09 08:00:00 is still open",
+        ]
+    );
+    assert!(job.end_datetime.is_none());
+
+    // Ended, but the goods issue is still queued
+    conn.execute_batch(
+        "UPDATE input_rolls SET consumed_weight = 510 WHERE id = 2;
+         INSERT INTO sap_outbox (kind, idempotency_key, payload, status, attempts, ref_model, ref_id, next_attempt_at, created_at, updated_at)
+             VALUES ('goods_issue', 'GI-IR2', '{}', 'pending', 1, 'input_rolls', 2, '2026-01-09 12:00:00', '2026-01-09 12:00:00', '2026-01-09 12:00:00');
+         UPDATE output_rolls SET final_weight = 200 WHERE id = 2;
+         UPDATE downtimes SET end_time = '2026-01-09 08:30:00', duration_minutes = 30, job_id = 1 WHERE id = 1;",
+    )
+    .unwrap();
+    let JobClose::Blocked(missing) = job.close(&mut conn, Some(1)).unwrap() else {
+        panic!("job closed before SAP posted");
+    };
+    assert_eq!(
+        missing,
+        vec!["Input roll J23-613 is waiting for its SAP material document"]
+    );
+
+    conn.execute(
+        "UPDATE input_rolls SET material_document = '4900012346' WHERE id = 2",
+        [],
+    )
+    .unwrap();
+    let JobClose::Closed(summary) = job.close(&mut conn, Some(1)).unwrap() else {
+        panic!("job did not close");
+    };
+    assert!(job.end_datetime.is_some());
+    assert_eq!(summary.end_datetime, job.end_datetime);
+    assert_eq!((summary.input_rolls, summary.consumed_kg), (2, 1010.0));
+    assert_eq!((summary.output_rolls, summary.output_meters), (2, 16800.0));
+    assert_eq!(summary.output_kg, 680.0);
+    assert_eq!((summary.flagged_rolls, summary.flags), (1, 2));
+    assert_eq!((summary.downtimes, summary.downtime_minutes), (1, 30.0));
+
+    let JobClose::Blocked(missing) = job.close(&mut conn, Some(1)).unwrap() else {
+        panic!("job closed twice");
+    };
+    assert!(missing[0].starts_with("Job was already closed"));
+
+    // Nothing new can be attached to the closed job
+    let closed = |result: Result<_>| matches!(result, Err(rusqlite::Error::InvalidParameterName(msg)) if msg.starts_with("Job 1 was closed"));
+    let input_roll = crate::backend::models::InputRollCreatePayload {
+        job_id: 1,
+        batch: "J23-614".to_string(),
+        material_document: None,
+        material_number: "30000950".to_string(),
+        start_meter: 12000.0,
+        start_weight: "520KG".to_string(),
+        consumed_weight: None,
+        output_batch: None,
+        remnant_id: None,
+        bom_override: None,
+    };
+    assert!(closed(
+        crate::backend::models::InputRoll::create(&mut conn, &input_roll, 1).map(|_| ())
+    ));
+    let output_roll = crate::backend::models::OutputRollCreatePayload {
+        final_meter: 1000.0,
+        batch: "J23-612".to_string(),
+        flag_reason: None,
+        core_weight: None,
+        shift_id: Some(1),
+        job_id: 1,
+        machine_id: 1,
+        input_roll_id: 1,
+        flag_count: 0,
+    };
+    assert!(closed(
+        crate::backend::models::OutputRoll::create(&mut conn, &output_roll, 1).map(|_| ())
+    ));
+    let downtime = crate::backend::models::DowntimeCreatePayload {
+        shift_id: Some(1),
+        machine_id: 1,
+        job_id: Some(1),
+        start_time: "2026-01-09 09:00:00".to_string(),
+        end_time: Some("2026-01-09 09:10:00".to_string()),
+        downtime_reason_id: 1,
+    };
+    assert!(closed(
+        crate::backend::models::Downtime::create(&conn, &downtime, 1).map(|_| ())
+    ));
+
+    // Job 1 is closed and job 2 was started just now, so neither is stale
+    let jobs = Job::filter(
+        &conn,
+        &crate::backend::models::JobFilterPayload {
+            id: None,
+            machine_id: None,
+            shift_id: None,
+            created_by: None,
+            status: None,
+            production_order: None,
+            start_datetime: None,
+            end_datetime: None,
+            created_at: None,
+            updated_at: None,
+        },
+    )
+    .unwrap();
+    assert!(jobs.data.iter().all(|j| !j.stale));
+    conn.execute(
+        "UPDATE jobs SET start_datetime = '2026-01-01 07:00:00' WHERE id = 2",
+        [],
+    )
+    .unwrap();
+    let stale = Job::filter(
+        &conn,
+        &crate::backend::models::JobFilterPayload {
+            id: None,
+            machine_id: None,
+            shift_id: None,
+            created_by: None,
+            status: Some("stale".to_string()),
+            production_order: None,
+            start_datetime: None,
+            end_datetime: None,
+            created_at: None,
+            updated_at: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(stale.data.len(), 1);
+    assert_eq!(stale.data[0].id, 2);
+}
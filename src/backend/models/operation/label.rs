@@ -0,0 +1,144 @@
+use chrono::Local;
+use rusqlite::{Connection, Result, params};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::models::AuditLog;
+
+/// One printed roll label; `reprint` counts the prints before it.
+#[derive(Debug, Serialize)]
+pub struct LabelPrint {
+    pub id: i32,
+    pub output_roll_id: i32,
+    pub format: String,
+    pub reprint: i32,
+    pub printed_by: Option<i32>,
+    pub printed_by_name: Option<String>,
+    pub printed_at: String,
+}
+
+#[derive(Deserialize)]
+pub struct LabelPayload {
+    pub id: i32,
+    /// `pdf` (default) or `zpl`
+    pub format: Option<String>,
+    /// Set by the print button; previews leave the print count alone
+    #[serde(default)]
+    pub print: bool,
+}
+
+impl LabelPrint {
+    /// How many labels were printed for the roll so far.
+    pub fn count(conn: &Connection, output_roll_id: i32) -> Result<i32> {
+        conn.query_row(
+            "SELECT COUNT(*) FROM label_prints WHERE output_roll_id = ?1",
+            params![output_roll_id],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn record(
+        conn: &Connection,
+        output_roll_id: i32,
+        format: &str,
+        user_id: Option<i32>,
+    ) -> Result<Self> {
+        let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let reprint = Self::count(conn, output_roll_id)?;
+        conn.execute(
+            "INSERT INTO label_prints (output_roll_id, format, reprint, printed_by, printed_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![output_roll_id, format, reprint, user_id, now],
+        )?;
+        let id = conn.last_insert_rowid() as i32;
+        let record = LabelPrint {
+            id,
+            output_roll_id,
+            format: format.to_string(),
+            reprint,
+            printed_by: user_id,
+            printed_by_name: None,
+            printed_at: now,
+        };
+        AuditLog::created(conn, user_id, "label_prints", id, &record)?;
+        Ok(record)
+    }
+
+    pub fn for_roll(conn: &Connection, output_roll_id: i32) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT l.id, l.output_roll_id, l.format, l.reprint, l.printed_by, u.full_name, l.printed_at \
+             FROM label_prints l \
+             LEFT JOIN users u ON l.printed_by = u.id \
+             WHERE l.output_roll_id = ?1 \
+             ORDER BY l.id",
+        )?;
+        stmt.query_map(params![output_roll_id], |row| {
+            Ok(LabelPrint {
+                id: row.get(0)?,
+                output_roll_id: row.get(1)?,
+                format: row.get(2)?,
+                reprint: row.get(3)?,
+                printed_by: row.get(4)?,
+                printed_by_name: row.get(5)?,
+                printed_at: row.get(6)?,
+            })
+        })?
+        .collect()
+    }
+}
+
+#[test]
+fn test_roll_label_pdf_zpl_and_reprints() {
+    use crate::backend::labels::{LabelData, LabelFormat, LabelTemplate, Symbology, render};
+    use crate::backend::models::OutputRoll;
+
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::manager::migrations::migrate_up(&mut conn, None).unwrap();
+    conn.execute_batch(
+        "INSERT INTO users (id, full_name) VALUES (1, 'Test Operator');
+         INSERT INTO machines (id, name, label) VALUES (1, 'Printer 1', 'P1');
+         INSERT INTO shifts (id, name) VALUES (1, 'Day');
+         INSERT INTO jobs (id, machine_id, shift_id, created_by, production_order) VALUES (1, 1, 1, 1, '220012061');
+         INSERT INTO input_rolls (id, job_id, batch, created_by, start_weight, material_number) VALUES (1, 1, 'J23-612', 1, '520KG', '30000950');
+         INSERT INTO output_rolls (id, input_roll_id, output_batch, final_weight, final_meter, created_by, created_at, flag_count) VALUES (1, 1, '26018P1001', 492.5, 11800, 1, '2026-01-09 10:15:00', 0);",
+    )
+    .unwrap();
+
+    assert_eq!(LabelPrint::count(&conn, 1).unwrap(), 0);
+    let first = LabelPrint::record(&conn, 1, "pdf", Some(1)).unwrap();
+    let second = LabelPrint::record(&conn, 1, "zpl", Some(1)).unwrap();
+    assert_eq!((first.reprint, second.reprint), (0, 1));
+    assert_eq!(LabelPrint::for_roll(&conn, 1).unwrap().len(), 2);
+    assert_eq!(LabelPrint::count(&conn, 1).unwrap(), 2);
+
+    let data = LabelData {
+        roll_id: 1,
+        details: OutputRoll::get_details(&conn, 1).unwrap(),
+        reprint: second.reprint,
+        remnant: false,
+    };
+    assert_eq!(
+        data.fill("{final_meter} m / {reprint} / {unknown}"),
+        "11,800 m / REPRINT 1 / {unknown}"
+    );
+    let payload: serde_json::Value = serde_json::from_str(&data.code_payload()).unwrap();
+    assert_eq!(payload["batch"], "26018P1001");
+    assert_eq!(payload["po"], "220012061");
+    assert_eq!(payload["weight"], 492.5);
+
+    let mut template = LabelTemplate::default();
+    let pdf = render(&template, &data, LabelFormat::Pdf).unwrap();
+    let doc = lopdf::Document::load_mem(&pdf).unwrap();
+    let page = *doc.get_pages().values().next().unwrap();
+    let text = String::from_utf8_lossy(&doc.get_page_content(page).unwrap()).to_string();
+    assert!(text.contains("(26018P1001) Tj"));
+    assert!(text.contains("(REPRINT 1) Tj"));
+
+    for element in template.elements.iter_mut() {
+        if let crate::backend::labels::LabelElement::Code { symbology, .. } = element {
+            *symbology = Symbology::DataMatrix;
+        }
+    }
+    let zpl = String::from_utf8(render(&template, &data, LabelFormat::Zpl).unwrap()).unwrap();
+    assert!(zpl.starts_with("^XA") && zpl.trim_end().ends_with("^XZ"));
+    assert!(zpl.contains("^FD26018P1001^FS"));
+    assert!(zpl.contains("^BXN,"));
+}
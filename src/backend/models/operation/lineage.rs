@@ -0,0 +1,317 @@
+use chrono::Local;
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Deepest tree a trace returns; real lineage is a handful of levels.
+const MAX_TRACE_DEPTH: usize = 20;
+
+/// One edge of the roll genealogy: `parent_batch` (through `parent_input_roll_id` when the
+/// reel was loaded as an input roll) went into `output_roll_id`.
+#[derive(Debug, Serialize)]
+pub struct RollLineage {
+    pub id: i32,
+    pub parent_batch: String,
+    pub parent_input_roll_id: Option<i32>,
+    pub output_roll_id: i32,
+    pub created_at: String,
+}
+
+#[derive(Deserialize)]
+pub struct TracePayload {
+    pub batch: String,
+}
+
+/// A roll in a trace tree with where, when and by whom it was run.
+#[derive(Debug, Serialize)]
+pub struct TraceNode {
+    /// `input_roll`, `output_roll`, or `batch` for a parent that was never loaded in the MES
+    pub kind: &'static str,
+    pub id: Option<i32>,
+    pub batch: String,
+    pub job_id: Option<i32>,
+    pub production_order: Option<String>,
+    pub machine: Option<String>,
+    pub shift: Option<String>,
+    pub operator: Option<String>,
+    pub start_weight: Option<String>,
+    pub consumed_weight: Option<f64>,
+    pub final_weight: Option<f64>,
+    pub final_meter: Option<f64>,
+    pub created_at: Option<String>,
+    pub children: Vec<TraceNode>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Direction {
+    Backward,
+    Forward,
+}
+
+impl RollLineage {
+    /// Replaces the parents of an output roll with the batches in its `from_input_batch`.
+    /// Each batch is linked to the input roll that carried it, preferring the roll's own job.
+    pub fn record(
+        conn: &Connection,
+        output_roll_id: i32,
+        job_id: i32,
+        from_input_batch: &str,
+    ) -> Result<()> {
+        let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "DELETE FROM roll_lineage WHERE output_roll_id = ?1",
+            params![output_roll_id],
+        )?;
+        for batch in from_input_batch
+            .split(',')
+            .map(str::trim)
+            .filter(|b| !b.is_empty())
+        {
+            let parent_input_roll_id: Option<i32> = conn
+                .query_row(
+                    "SELECT id FROM input_rolls WHERE batch = ?1 ORDER BY job_id = ?2 DESC, id DESC LIMIT 1",
+                    params![batch, job_id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            conn.execute(
+                "INSERT OR IGNORE INTO roll_lineage (parent_batch, parent_input_roll_id, output_roll_id, created_at) VALUES (?1, ?2, ?3, ?4)",
+                params![batch, parent_input_roll_id, output_roll_id, now],
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn parents(conn: &Connection, output_roll_id: i32) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, parent_batch, parent_input_roll_id, output_roll_id, created_at FROM roll_lineage WHERE output_roll_id = ?1 ORDER BY id",
+        )?;
+        stmt.query_map(params![output_roll_id], |row| {
+            Ok(RollLineage {
+                id: row.get(0)?,
+                parent_batch: row.get(1)?,
+                parent_input_roll_id: row.get(2)?,
+                output_roll_id: row.get(3)?,
+                created_at: row.get(4)?,
+            })
+        })?
+        .collect()
+    }
+
+    /// Where a batch came from: the output roll that carries it, back through every input
+    /// reel and the rolls those reels were cut from.
+    pub fn trace_backward(conn: &Connection, batch: &str) -> Result<Vec<TraceNode>> {
+        Self::trace(conn, batch, Direction::Backward)
+    }
+
+    /// Where a batch went: every output roll made from it and the later jobs that consumed
+    /// those rolls.
+    pub fn trace_forward(conn: &Connection, batch: &str) -> Result<Vec<TraceNode>> {
+        Self::trace(conn, batch, Direction::Forward)
+    }
+
+    fn trace(conn: &Connection, batch: &str, direction: Direction) -> Result<Vec<TraceNode>> {
+        let mut seen = HashSet::new();
+        let outputs = ids(
+            conn,
+            "SELECT id FROM output_rolls WHERE output_batch = ?1 ORDER BY id",
+            batch,
+        )?;
+        let mut roots = Vec::new();
+        if outputs.is_empty() {
+            for id in ids(
+                conn,
+                "SELECT id FROM input_rolls WHERE batch = ?1 ORDER BY id",
+                batch,
+            )? {
+                roots.push(input_node(conn, id, direction, 0, &mut seen)?);
+            }
+        } else {
+            for id in outputs {
+                roots.push(output_node(conn, id, direction, 0, &mut seen)?);
+            }
+        }
+        Ok(roots)
+    }
+}
+
+fn ids(conn: &Connection, sql: &str, value: impl rusqlite::ToSql) -> Result<Vec<i32>> {
+    let mut stmt = conn.prepare(sql)?;
+    stmt.query_map(params![value], |row| row.get(0))?.collect()
+}
+
+fn input_node(
+    conn: &Connection,
+    id: i32,
+    direction: Direction,
+    depth: usize,
+    seen: &mut HashSet<(&'static str, i32)>,
+) -> Result<TraceNode> {
+    let mut node = conn.query_row(
+        "SELECT ir.id, ir.batch, ir.job_id, j.production_order, m.name, s.name, u.full_name, CAST(ir.start_weight AS TEXT), CAST(ir.consumed_weight AS REAL), ir.created_at \
+         FROM input_rolls ir \
+         LEFT JOIN jobs j ON ir.job_id = j.id \
+         LEFT JOIN machines m ON j.machine_id = m.id \
+         LEFT JOIN shifts s ON j.shift_id = s.id \
+         LEFT JOIN users u ON ir.created_by = u.id \
+         WHERE ir.id = ?1",
+        params![id],
+        |row| {
+            Ok(TraceNode {
+                kind: "input_roll",
+                id: row.get(0)?,
+                batch: row.get(1)?,
+                job_id: row.get(2)?,
+                production_order: row.get(3)?,
+                machine: row.get(4)?,
+                shift: row.get(5)?,
+                operator: row.get(6)?,
+                start_weight: row.get(7)?,
+                consumed_weight: row.get(8)?,
+                final_weight: None,
+                final_meter: None,
+                created_at: row.get(9)?,
+                children: vec![],
+            })
+        },
+    )?;
+    if depth >= MAX_TRACE_DEPTH || !seen.insert(("input_roll", id)) {
+        return Ok(node);
+    }
+
+    let children = match direction {
+        // The output roll this reel was cut from, when it was made in the MES
+        Direction::Backward => ids(
+            conn,
+            "SELECT id FROM output_rolls WHERE output_batch = ?1 ORDER BY id",
+            &node.batch,
+        )?,
+        Direction::Forward => ids(
+            conn,
+            "SELECT output_roll_id FROM roll_lineage WHERE parent_input_roll_id = ?1 ORDER BY output_roll_id",
+            id,
+        )?,
+    };
+    for child in children {
+        node.children
+            .push(output_node(conn, child, direction, depth + 1, seen)?);
+    }
+    Ok(node)
+}
+
+fn output_node(
+    conn: &Connection,
+    id: i32,
+    direction: Direction,
+    depth: usize,
+    seen: &mut HashSet<(&'static str, i32)>,
+) -> Result<TraceNode> {
+    let mut node = conn.query_row(
+        "SELECT o.id, o.output_batch, ir.job_id, j.production_order, m.name, s.name, u.full_name, CAST(o.final_weight AS REAL), CAST(o.final_meter AS REAL), o.created_at \
+         FROM output_rolls o \
+         LEFT JOIN input_rolls ir ON o.input_roll_id = ir.id \
+         LEFT JOIN jobs j ON ir.job_id = j.id \
+         LEFT JOIN machines m ON j.machine_id = m.id \
+         LEFT JOIN shifts s ON j.shift_id = s.id \
+         LEFT JOIN users u ON o.created_by = u.id \
+         WHERE o.id = ?1",
+        params![id],
+        |row| {
+            Ok(TraceNode {
+                kind: "output_roll",
+                id: row.get(0)?,
+                batch: row.get(1)?,
+                job_id: row.get(2)?,
+                production_order: row.get(3)?,
+                machine: row.get(4)?,
+                shift: row.get(5)?,
+                operator: row.get(6)?,
+                start_weight: None,
+                consumed_weight: None,
+                final_weight: row.get(7)?,
+                final_meter: row.get(8)?,
+                created_at: row.get(9)?,
+                children: vec![],
+            })
+        },
+    )?;
+    if depth >= MAX_TRACE_DEPTH || !seen.insert(("output_roll", id)) {
+        return Ok(node);
+    }
+
+    match direction {
+        Direction::Backward => {
+            for parent in RollLineage::parents(conn, id)? {
+                let child = match parent.parent_input_roll_id {
+                    Some(input_id) => input_node(conn, input_id, direction, depth + 1, seen)?,
+                    None => batch_node(parent.parent_batch),
+                };
+                node.children.push(child);
+            }
+        }
+        // Later jobs that loaded this roll as an input
+        Direction::Forward => {
+            let consumers = ids(
+                conn,
+                "SELECT id FROM input_rolls WHERE batch = ?1 ORDER BY id",
+                &node.batch,
+            )?;
+            for input_id in consumers {
+                node.children
+                    .push(input_node(conn, input_id, direction, depth + 1, seen)?);
+            }
+        }
+    }
+    Ok(node)
+}
+
+fn batch_node(batch: String) -> TraceNode {
+    TraceNode {
+        kind: "batch",
+        id: None,
+        batch,
+        job_id: None,
+        production_order: None,
+        machine: None,
+        shift: None,
+        operator: None,
+        start_weight: None,
+        consumed_weight: None,
+        final_weight: None,
+        final_meter: None,
+        created_at: None,
+        children: vec![],
+    }
+}
+
+#[test]
+fn test_trace_follows_rolls_across_jobs() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::manager::migrations::migrate_up(&mut conn, None).unwrap();
+    conn.execute_batch(
+        "INSERT INTO users (id, full_name) VALUES (1, 'Operator');
+         INSERT INTO machines (id, name, label) VALUES (1, 'Printer 1', 'P1'), (2, 'Slitter 1', 'S1');
+         INSERT INTO shifts (id, name) VALUES (1, 'Day');
+         INSERT INTO jobs (id, machine_id, shift_id, created_by, production_order) VALUES (1, 1, 1, 1, '220012061'), (2, 2, 1, 1, '220012062');
+         INSERT INTO input_rolls (id, job_id, batch, created_by, start_weight) VALUES (1, 1, 'J23-612', 1, '520KG');
+         INSERT INTO output_rolls (id, input_roll_id, output_batch, from_input_batch, final_weight, created_by) VALUES (1, 1, '26001P1001', 'J23-612', 250.5, 1);
+         INSERT INTO input_rolls (id, job_id, batch, created_by, start_weight) VALUES (2, 2, '26001P1001', 1, '250.5KG');
+         INSERT INTO output_rolls (id, input_roll_id, output_batch, from_input_batch, final_weight, created_by) VALUES (2, 2, '26001S1001', '26001P1001', 120.0, 1);",
+    )
+    .unwrap();
+    RollLineage::record(&conn, 1, 1, "J23-612").unwrap();
+    RollLineage::record(&conn, 2, 2, "26001P1001").unwrap();
+
+    let back = RollLineage::trace_backward(&conn, "26001S1001").unwrap();
+    assert_eq!(back.len(), 1);
+    let reel = &back[0].children[0].children[0].children[0];
+    assert_eq!(reel.batch, "J23-612");
+    assert_eq!(reel.machine.as_deref(), Some("Printer 1"));
+    assert_eq!(reel.operator.as_deref(), Some("Operator"));
+
+    let forward = RollLineage::trace_forward(&conn, "J23-612").unwrap();
+    let finished = &forward[0].children[0].children[0].children[0];
+    assert_eq!(finished.batch, "26001S1001");
+    assert_eq!(finished.final_weight, Some(120.0));
+    assert_eq!(finished.production_order.as_deref(), Some("220012062"));
+}
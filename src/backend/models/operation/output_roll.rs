@@ -1,13 +1,20 @@
-use std::io::{Error, ErrorKind};
-
 use crate::{
-    backend::models::FilterResponse,
-    sap::{RollData, post_rolls},
+    backend::models::{
+        AuditLog, BatchPattern, FilterResponse, Job, RollLineage, SapOutbox, SapReversal, Shift,
+    },
+    sap::{RollData, SapGateway, SapPosting, deliver_now},
 };
-use chrono::{Datelike, Local};
-use rusqlite::{Connection, OptionalExtension, Result, params};
+use chrono::Local;
+use rusqlite::{Connection, OptionalExtension, Result, TransactionBehavior, params};
 use serde::{Deserialize, Serialize};
 
+pub const QA_ON_HOLD: &str = "on_hold";
+
+/// Whether a roll carries any flag and so needs a QA disposition
+pub fn is_flagged(flag_reason: Option<&str>, flag_count: i32) -> bool {
+    flag_count > 0 || flag_reason.is_some_and(|r| !r.trim().is_empty())
+}
+
 #[derive(Debug, Serialize)]
 pub struct OutputRoll {
     pub id: i32,
@@ -20,6 +27,9 @@ pub struct OutputRoll {
     pub job_id: i32,
     pub from_input_batch: String,
     pub flag_count: i32,
+    /// QA disposition: `on_hold` once flagged, then `released`, `rework`, `downgraded` or
+    /// `scrapped`. `None` for rolls that were never flagged.
+    pub qa_status: Option<String>,
     pub created_by: i32,
     pub operator_name: Option<String>,
     pub updated_by: Option<i32>,
@@ -33,7 +43,8 @@ pub struct OutputRollCreatePayload {
     pub batch: String,
     pub flag_reason: Option<String>,
     pub core_weight: Option<f64>,
-    pub shift_id: i32,
+    /// Only used when the shift calendar does not cover the current time
+    pub shift_id: Option<i32>,
     pub job_id: i32,
     pub machine_id: i32,
     pub input_roll_id: i32,
@@ -73,7 +84,7 @@ pub struct OutputRollFilterPayload {
     pub production_order: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct OutputRollDetails {
     pub material_number: String,
     pub material_description: String,
@@ -91,7 +102,7 @@ pub struct OutputRollDetails {
 
 impl OutputRoll {
     pub fn build_from_input_batch(
-        conn: &mut Connection,
+        conn: &Connection,
         job_id: i64,
         input_roll_id: i64,
         current_batch: &str,
@@ -160,14 +171,12 @@ impl OutputRoll {
 
         if current_is_unconsumed {
             let now = chrono::Utc::now().naive_utc().to_string();
-            let tx = conn.transaction()?;
             for id in &include_ids {
-                tx.execute(
+                conn.execute(
                 "UPDATE input_rolls SET is_consumed = 1, consumed_at = ? WHERE id = ? AND job_id = ?",
                 params![now, id, job_id],
             )?;
             }
-            tx.commit()?;
         }
 
         Ok(from_input)
@@ -185,42 +194,41 @@ impl OutputRoll {
         let job_id = data.job_id;
         let input_roll_id = data.input_roll_id;
 
-        let machine = conn.query_row(
-            "SELECT label FROM machines WHERE id = ?1",
+        // Immediate so the batch counter is locked before it is read
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let input_job_id: i32 = tx.query_row(
+            "SELECT job_id FROM input_rolls WHERE id = ?1",
+            params![input_roll_id],
+            |row| row.get(0),
+        )?;
+        Job::ensure_open(&tx, input_job_id)?;
+        let (machine, section_id) = tx.query_row(
+            "SELECT label, section_id FROM machines WHERE id = ?1",
             params![machine_id],
-            |row| row.get::<_, String>(0),
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<i32>>(1)?)),
         )?;
 
-        let current_date = Local::now();
-        let year = current_date.format("%y").to_string();
-        let day_of_year = current_date.date_naive().ordinal() as i32;
-        let shift_number = (day_of_year - 1) * 2 + shift_id;
-        let today_start = current_date.format("%Y-%m-%d").to_string();
+        let slot = Shift::resolve(&tx, Local::now().naive_local(), shift_id)?;
+        let output_batch =
+            BatchPattern::for_section(&tx, section_id)?.allocate(&tx, &slot, &machine)?;
 
         let from_batch = OutputRoll::build_from_input_batch(
-            conn,
+            &tx,
             job_id as i64,
             input_roll_id as i64,
             current_batch,
         )?;
-        let roll_count_for_job: i32 = conn
-            .query_row(
-                "SELECT COUNT(*) FROM output_rolls o JOIN input_rolls ir ON o.input_roll_id = ir.id WHERE ir.job_id = ?1 AND date(o.created_at) = date(?2)",
-                params![job_id, today_start],
-                |row| row.get(0),
-            )
-            .unwrap_or(0);
 
-        let roll_number = roll_count_for_job + 1;
-        let output_batch = format!("{}{:03}{}{:03}", year, shift_number, machine, roll_number);
-
-        conn.execute(
-            "INSERT INTO output_rolls (output_batch, final_meter, flag_reason, final_weight, core_weight, input_roll_id, created_by, created_at, updated_at, from_input_batch, flag_count) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
-            params![output_batch, data.final_meter, data.flag_reason, 0.0, data.core_weight, data.input_roll_id, user_id, now, now, from_batch, data.flag_count],
+        let qa_status = is_flagged(data.flag_reason.as_deref(), data.flag_count)
+            .then(|| QA_ON_HOLD.to_string());
+        tx.execute(
+            "INSERT INTO output_rolls (output_batch, final_meter, flag_reason, final_weight, core_weight, input_roll_id, created_by, created_at, updated_at, from_input_batch, flag_count, qa_status) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![output_batch, data.final_meter, data.flag_reason, 0.0, data.core_weight, data.input_roll_id, user_id, now, now, from_batch, data.flag_count, qa_status],
         )?;
 
-        let id = conn.last_insert_rowid() as i32;
-        Ok(OutputRoll {
+        let id = tx.last_insert_rowid() as i32;
+        RollLineage::record(&tx, id, job_id, &from_batch)?;
+        let roll = OutputRoll {
             id,
             output_batch,
             final_meter: data.final_meter,
@@ -236,13 +244,27 @@ impl OutputRoll {
             updated_at: now.clone(),
             from_input_batch: from_batch,
             flag_count: data.flag_count,
-        })
+            qa_status,
+        };
+        AuditLog::created(&tx, Some(user_id), "output_rolls", id, &roll)?;
+        tx.commit()?;
+        Ok(roll)
     }
 
-    pub async fn update(&mut self, conn: &Connection, data: &OutputRollPayload) -> Result<()> {
+    pub async fn update(
+        &mut self,
+        conn: &Connection,
+        sap: &dyn SapGateway,
+        data: &OutputRollPayload,
+        user_id: Option<i32>,
+    ) -> Result<()> {
+        let before = AuditLog::snapshot(self);
+        // Local changes and their outbox entries commit together; SAP is only called afterwards
+        let tx = conn.unchecked_transaction()?;
+        let mut confirmation = None;
         if let Some(final_weight) = data.final_weight {
             // Fetch job info via input_rolls (using input_roll_id)
-            let (job_production_order,): (String,) = conn.query_row(
+            let (job_production_order,): (String,) = tx.query_row(
                 "SELECT j.production_order FROM input_rolls ir JOIN jobs j ON ir.job_id = j.id WHERE ir.id = ?1",
                 params![self.input_roll_id],
                 |row| Ok((row.get(0)?,)),
@@ -272,16 +294,28 @@ impl OutputRoll {
                 production_order: job_production_order,
             };
 
-            if let Err(e) = post_rolls(roll_data).await {
-                return Err(rusqlite::Error::ToSqlConversionFailure(Box::new(
-                    Error::new(ErrorKind::Other, e),
-                )));
-            }
-
-            conn.execute(
+            tx.execute(
                 "UPDATE output_rolls SET final_meter = ?1, final_weight = ?2 WHERE id = ?3",
                 params![new_alternate_quantity, net_weight, self.id],
             )?;
+
+            // Keyed per roll so re-weighing a confirmed roll never confirms it twice; a
+            // confirmation SAP already holds is reversed below when the weight changed
+            let key = SapReversal::current_key(&tx, "output_rolls", self.id)?
+                .unwrap_or_else(|| format!("RC-OR{}", self.id));
+            let posted = SapOutbox::find_by_key(&tx, &key)
+                .optional()?
+                .is_some_and(|message| message.status == "posted");
+            if !posted {
+                let message = SapOutbox::enqueue(
+                    &tx,
+                    &key,
+                    &SapPosting::RollConfirmation(roll_data),
+                    Some(("output_rolls", self.id)),
+                    user_id,
+                )?;
+                confirmation = Some(message.id);
+            }
             self.final_meter = new_alternate_quantity;
             self.final_weight = net_weight;
         }
@@ -289,63 +323,129 @@ impl OutputRoll {
         let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
 
         if let Some(output_batch) = &data.output_batch {
-            conn.execute(
+            let taken: bool = tx.query_row(
+                "SELECT EXISTS (SELECT 1 FROM output_rolls WHERE output_batch = ?1 AND id <> ?2)",
+                params![output_batch, self.id],
+                |row| row.get(0),
+            )?;
+            if taken {
+                return Err(rusqlite::Error::InvalidParameterName(format!(
+                    "Batch {} is already used by another roll",
+                    output_batch
+                )));
+            }
+            tx.execute(
                 "UPDATE output_rolls SET output_batch = ?1 WHERE id = ?2",
                 params![output_batch, self.id],
             )?;
             self.output_batch = output_batch.clone();
         }
         if let Some(final_meter) = data.final_meter {
-            conn.execute(
+            tx.execute(
                 "UPDATE output_rolls SET final_meter = ?1 WHERE id = ?2",
                 params![final_meter, self.id],
             )?;
             self.final_meter = final_meter;
         }
         if let Some(flag_reason) = &data.flag_reason {
-            conn.execute(
+            tx.execute(
                 "UPDATE output_rolls SET flag_reason = ?1 WHERE id = ?2",
                 params![flag_reason, self.id],
             )?;
             self.flag_reason = Some(flag_reason.to_string());
+            // A roll flagged after the fact goes on hold until QA disposes it
+            if self.qa_status.is_none() && is_flagged(Some(flag_reason), 0) {
+                tx.execute(
+                    "UPDATE output_rolls SET qa_status = ?1 WHERE id = ?2",
+                    params![QA_ON_HOLD, self.id],
+                )?;
+                self.qa_status = Some(QA_ON_HOLD.to_string());
+            }
         }
         if let Some(input_roll_id) = data.input_roll_id {
-            conn.execute(
+            tx.execute(
                 "UPDATE output_rolls SET input_roll_id = ?1 WHERE id = ?2",
                 params![input_roll_id, self.id],
             )?;
             self.input_roll_id = input_roll_id;
         }
         if let Some(from_input_batch) = &data.from_input_batch {
-            conn.execute(
+            tx.execute(
                 "UPDATE output_rolls SET from_input_batch = ?1 WHERE id = ?2",
                 params![from_input_batch, self.id],
             )?;
+            RollLineage::record(&tx, self.id, self.job_id, from_input_batch)?;
             self.from_input_batch = from_input_batch.clone();
         }
         if let Some(updated_by) = data.updated_by {
-            conn.execute(
+            tx.execute(
                 "UPDATE output_rolls SET updated_by = ?1 WHERE id = ?2",
                 params![updated_by, self.id],
             )?;
             self.updated_by = Some(updated_by);
         }
-        conn.execute(
+        tx.execute(
             "UPDATE output_rolls SET updated_at = ?1 WHERE id = ?2",
             params![now, self.id],
         )?;
         self.updated_at = now;
+        AuditLog::updated(&tx, user_id, "output_rolls", self.id, &before, self)?;
+        let corrected = data.final_weight.is_some()
+            || data.final_meter.is_some()
+            || data.output_batch.is_some();
+        let reversal = if corrected {
+            SapReversal::correct(&tx, "output_rolls", self.id, "Roll corrected", user_id)?
+                .and_then(|reversal| reversal.message_id)
+        } else {
+            None
+        };
+        tx.commit()?;
+
+        if let Some(id) = confirmation {
+            let message = deliver_now(conn, sap, id).await?;
+            if let Some(e) = message.last_error.filter(|_| message.status != "posted") {
+                log::warn!(
+                    "Roll confirmation for {} queued for retry: {}",
+                    self.output_batch,
+                    e
+                );
+            }
+        }
+        if let Some(id) = reversal {
+            deliver_now(conn, sap, id).await?;
+        }
         Ok(())
     }
 
-    pub fn delete(&self, conn: &Connection) -> Result<()> {
-        conn.execute("DELETE FROM output_rolls WHERE id = ?1", params![self.id])?;
+    pub async fn delete(
+        &self,
+        conn: &Connection,
+        sap: &dyn SapGateway,
+        user_id: Option<i32>,
+    ) -> Result<()> {
+        // The roll stays when SAP cannot take the correction right now
+        let tx = conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM output_rolls WHERE id = ?1", params![self.id])?;
+        AuditLog::deleted(&tx, user_id, "output_rolls", self.id, self)?;
+        let reversal = SapReversal::correct(
+            &tx,
+            "output_rolls",
+            self.id,
+            "Output roll deleted",
+            user_id,
+        )?
+        .and_then(|reversal| reversal.message_id);
+        tx.commit()?;
+
+        if let Some(id) = reversal {
+            deliver_now(conn, sap, id).await?;
+        }
         Ok(())
     }
 
     pub fn find_by_id(conn: &Connection, id: i32) -> Result<Self> {
         let mut stmt = conn.prepare(
-                "SELECT o.id, o.output_batch, o.final_meter, o.flag_reason, o.final_weight, o.core_weight, o.input_roll_id, j.id as job_id, o.created_by, u.full_name, o.updated_by, o.created_at, o.updated_at, o.from_input_batch, o.flag_count \
+                "SELECT o.id, o.output_batch, o.final_meter, o.flag_reason, o.final_weight, o.core_weight, o.input_roll_id, j.id as job_id, o.created_by, u.full_name, o.updated_by, o.created_at, o.updated_at, o.from_input_batch, o.flag_count, o.qa_status \
                  FROM output_rolls o \
                  JOIN input_rolls ir ON o.input_roll_id = ir.id \
                  JOIN jobs j ON ir.job_id = j.id \
@@ -369,17 +469,55 @@ impl OutputRoll {
                 updated_at: row.get(12)?,
                 from_input_batch: row.get(13)?,
                 flag_count: row.get(14)?,
+                qa_status: row.get(15)?,
             })
         })
     }
 
     pub fn all(conn: &Connection) -> Result<Vec<Self>> {
         let mut stmt = conn.prepare(
-                "SELECT o.id, o.output_batch, o.final_meter, o.flag_reason, o.final_weight, o.core_weight, o.input_roll_id, j.id as job_id, o.created_by, u.full_name, o.updated_by, o.created_at, o.updated_at, o.from_input_batch, o.flag_count \
+                "SELECT o.id, o.output_batch, o.final_meter, o.flag_reason, o.final_weight, o.core_weight, o.input_roll_id, j.id as job_id, o.created_by, u.full_name, o.updated_by, o.created_at, o.updated_at, o.from_input_batch, o.flag_count, o.qa_status \
+                 FROM output_rolls o \
+                 JOIN input_rolls ir ON o.input_roll_id = ir.id \
+                 JOIN jobs j ON ir.job_id = j.id \
+                 LEFT JOIN users u ON o.created_by = u.id \
+                 ORDER BY o.created_at DESC"
+        )?;
+        let rolls = stmt
+            .query_map([], |row| {
+                Ok(OutputRoll {
+                    id: row.get(0)?,
+                    output_batch: row.get(1)?,
+                    final_meter: row.get(2)?,
+                    flag_reason: row.get(3)?,
+                    final_weight: row.get(4)?,
+                    core_weight: row.get(5)?,
+                    input_roll_id: row.get(6)?,
+                    job_id: row.get(7)?,
+                    created_by: row.get(8)?,
+                    operator_name: row.get(9)?,
+                    updated_by: row.get(10)?,
+                    created_at: row.get(11)?,
+                    updated_at: row.get(12)?,
+                    from_input_batch: row.get(13)?,
+                    flag_count: row.get(14)?,
+                    qa_status: row.get(15)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rolls)
+    }
+
+    /// Weighed rolls not yet loaded into a downstream job
+    pub fn available_for_input(conn: &Connection) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(
+                "SELECT o.id, o.output_batch, o.final_meter, o.flag_reason, o.final_weight, o.core_weight, o.input_roll_id, j.id as job_id, o.created_by, u.full_name, o.updated_by, o.created_at, o.updated_at, o.from_input_batch, o.flag_count, o.qa_status \
                  FROM output_rolls o \
                  JOIN input_rolls ir ON o.input_roll_id = ir.id \
                  JOIN jobs j ON ir.job_id = j.id \
                  LEFT JOIN users u ON o.created_by = u.id \
+                 WHERE o.final_weight > 0 AND o.consumed_at IS NULL \
+                 AND COALESCE(o.qa_status, '') NOT IN ('on_hold', 'rework', 'scrapped') \
                  ORDER BY o.created_at DESC"
         )?;
         let rolls = stmt
@@ -400,6 +538,7 @@ impl OutputRoll {
                     updated_at: row.get(12)?,
                     from_input_batch: row.get(13)?,
                     flag_count: row.get(14)?,
+                    qa_status: row.get(15)?,
                 })
             })?
             .collect::<Result<Vec<_>, _>>()?;
@@ -414,7 +553,7 @@ impl OutputRoll {
             "SELECT COUNT(*) FROM output_rolls r JOIN input_rolls ir ON r.input_roll_id = ir.id JOIN jobs j ON ir.job_id = j.id WHERE 1=1"
                 .to_string();
         let mut data_query =
-            "SELECT r.id, r.output_batch, r.final_meter, r.flag_reason, r.final_weight, r.core_weight, r.input_roll_id, j.id as job_id, r.created_by, u.full_name, r.updated_by, r.created_at, r.updated_at, r.from_input_batch, r.flag_count \
+            "SELECT r.id, r.output_batch, r.final_meter, r.flag_reason, r.final_weight, r.core_weight, r.input_roll_id, j.id as job_id, r.created_by, u.full_name, r.updated_by, r.created_at, r.updated_at, r.from_input_batch, r.flag_count, r.qa_status \
              FROM output_rolls r \
              JOIN input_rolls ir ON r.input_roll_id = ir.id \
              JOIN jobs j ON ir.job_id = j.id \
@@ -434,6 +573,7 @@ impl OutputRoll {
         let mut pages: Vec<i32> = vec![];
         let mut per_pages: Vec<i32> = vec![];
         let mut production_orders: Vec<String> = vec![];
+        let mut qa_statuses: Vec<String> = vec![];
         if let Some(val) = &filter.production_order {
             if !val.is_empty() {
                 production_orders.push(val.clone());
@@ -480,6 +620,14 @@ impl OutputRoll {
             } else if val == "completed" {
                 count_query.push_str(" AND r.final_weight > 0");
                 data_query.push_str(" AND r.final_weight > 0");
+            } else if matches!(
+                val.as_str(),
+                "on_hold" | "released" | "rework" | "downgraded" | "scrapped"
+            ) {
+                qa_statuses.push(val.clone());
+                params_vec.push(qa_statuses.last().unwrap());
+                count_query.push_str(" AND r.qa_status = ?");
+                data_query.push_str(" AND r.qa_status = ?");
             }
         }
 
@@ -572,6 +720,7 @@ impl OutputRoll {
                 updated_at: row.get(12)?,
                 from_input_batch: row.get(13)?,
                 flag_count: row.get(14)?,
+                qa_status: row.get(15)?,
             })
         })?;
 
@@ -644,20 +793,19 @@ impl OutputRoll {
                             None => (part, None),
                         };
 
-                        let resolved_name = id_part
-                            .parse::<i32>()
+                        let resolved_name = id_part.parse::<i32>().ok().and_then(|id| {
+                            conn.query_row(
+                                "SELECT name FROM flag_reasons WHERE id = ?1",
+                                params![id],
+                                |row| row.get::<_, String>(0),
+                            )
                             .ok()
-                            .and_then(|id| {
-                                conn.query_row(
-                                    "SELECT name FROM flag_reasons WHERE id = ?1",
-                                    params![id],
-                                    |row| row.get::<_, String>(0),
-                                )
-                                .ok()
-                            });
+                        });
 
                         let display = match (resolved_name, msg_part) {
-                            (Some(name), Some(msg)) if !msg.is_empty() => format!("{}: {}", name, msg),
+                            (Some(name), Some(msg)) if !msg.is_empty() => {
+                                format!("{}: {}", name, msg)
+                            }
                             (Some(name), _) => name,
                             (None, _) => part.to_string(),
                         };
@@ -0,0 +1,501 @@
+use chrono::Local;
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::models::{
+    AuditLog, EndInputRollPayload, InputRoll, InputRollCreatePayload, OutputRollDetails, SapOutbox,
+};
+use crate::sap::{SapGateway, SapPosting, SapSettings, deliver_now};
+
+/// Unused part of an input roll ended early and sent back to the store. It keeps the SAP batch
+/// it was issued from, so a later job can load it as an input roll again.
+#[derive(Debug, Serialize)]
+pub struct Remnant {
+    pub id: i32,
+    pub input_roll_id: i32,
+    pub job_id: i32,
+    pub production_order: Option<String>,
+    pub batch: String,
+    pub material_number: Option<String>,
+    pub material_description: Option<String>,
+    pub weight: f64,
+    pub unit: String,
+    pub meter: f64,
+    /// Where the remnant is stocked in SAP: where it was issued from, then the return store
+    /// once the transfer posted
+    pub storage_location: Option<String>,
+    /// Return store the remnant is transferred to, when a transfer was queued
+    pub return_location: Option<String>,
+    /// Material document of the return movement, once SAP posted it
+    pub return_document: Option<String>,
+    pub label_prints: i32,
+    pub consumed_job_id: Option<i32>,
+    pub consumed_at: Option<String>,
+    pub created_by: Option<i32>,
+    pub created_at: String,
+}
+
+#[derive(Deserialize)]
+pub struct EndWithRemnantPayload {
+    #[serde(flatten)]
+    pub end: EndInputRollPayload,
+    pub remnant_weight: f64,
+    #[serde(default)]
+    pub remnant_meter: f64,
+    /// Queues the transfer of the remnant to the SAP return storage location
+    #[serde(default)]
+    pub post_return: bool,
+}
+
+#[derive(Serialize)]
+pub struct RemnantEnded {
+    /// Material document of the goods issue, `None` while it waits in the outbox
+    pub material_document: Option<String>,
+    pub remnant: Remnant,
+}
+
+#[derive(Deserialize)]
+pub struct RemnantFilterPayload {
+    pub material_number: Option<String>,
+}
+
+const SELECT: &str = "SELECT r.id, r.input_roll_id, ir.job_id, j.production_order, r.batch, r.material_number, mvd.desc, CAST(r.weight AS REAL), r.unit, CAST(r.meter AS REAL), r.storage_location, r.return_document, r.label_prints, r.consumed_job_id, r.consumed_at, r.created_by, r.created_at, r.return_location \
+     FROM remnants r \
+     JOIN input_rolls ir ON r.input_roll_id = ir.id \
+     LEFT JOIN jobs j ON ir.job_id = j.id \
+     LEFT JOIN materials_value_description mvd ON r.material_number = mvd.value";
+
+/// `520.000KG` -> `520.0`
+fn leading_number(value: &str) -> Option<f64> {
+    let end = value
+        .trim()
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.trim().len());
+    value.trim()[..end].parse().ok()
+}
+
+impl Remnant {
+    fn from_row(row: &rusqlite::Row) -> Result<Self> {
+        Ok(Remnant {
+            id: row.get(0)?,
+            input_roll_id: row.get(1)?,
+            job_id: row.get(2)?,
+            production_order: row.get(3)?,
+            batch: row.get(4)?,
+            material_number: row.get(5)?,
+            material_description: row.get(6)?,
+            weight: row.get(7)?,
+            unit: row.get(8)?,
+            meter: row.get(9)?,
+            storage_location: row.get(10)?,
+            return_document: row.get(11)?,
+            label_prints: row.get(12)?,
+            consumed_job_id: row.get(13)?,
+            consumed_at: row.get(14)?,
+            created_by: row.get(15)?,
+            created_at: row.get(16)?,
+            return_location: row.get(17)?,
+        })
+    }
+
+    pub fn find_by_id(conn: &Connection, id: i32) -> Result<Self> {
+        conn.query_row(
+            &format!("{} WHERE r.id = ?1", SELECT),
+            params![id],
+            Self::from_row,
+        )
+    }
+
+    /// Remnants back in the store and not yet loaded into another job
+    pub fn available(conn: &Connection, filter: &RemnantFilterPayload) -> Result<Vec<Self>> {
+        let mut query = format!("{} WHERE r.consumed_at IS NULL", SELECT);
+        let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+        if let Some(material_number) = filter.material_number.as_deref().map(str::trim)
+            && !material_number.is_empty()
+        {
+            query.push_str(" AND r.material_number = ?");
+            params_vec.push(Box::new(material_number.to_string()));
+        }
+        query.push_str(" ORDER BY r.created_at DESC");
+        let mut stmt = conn.prepare(&query)?;
+        stmt.query_map(
+            params_vec
+                .iter()
+                .map(|b| &**b)
+                .collect::<Vec<_>>()
+                .as_slice(),
+            Self::from_row,
+        )?
+        .collect()
+    }
+
+    fn check(
+        conn: &Connection,
+        settings: &SapSettings,
+        data: &EndWithRemnantPayload,
+        roll: &InputRoll,
+    ) -> Result<()> {
+        let invalid = |msg: String| rusqlite::Error::InvalidParameterName(msg);
+        let existing: Option<i32> = conn
+            .query_row(
+                "SELECT id FROM remnants WHERE input_roll_id = ?1",
+                params![roll.id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if existing.is_some() {
+            return Err(invalid(format!(
+                "Input roll {} already has a remnant",
+                roll.batch
+            )));
+        }
+        if data.remnant_weight <= 0.0 {
+            return Err(invalid("Remnant weight must be greater than 0".to_string()));
+        }
+        if data.remnant_meter < 0.0 {
+            return Err(invalid("Remnant meters cannot be negative".to_string()));
+        }
+        let consumed = leading_number(&data.end.consumed_weight).unwrap_or(0.0);
+        if let Some(start) = leading_number(&roll.start_weight)
+            && start > 0.0
+            && consumed + data.remnant_weight > start + 0.01
+        {
+            return Err(invalid(format!(
+                "Used {} and remnant {} are more than the {} the roll started with",
+                consumed, data.remnant_weight, roll.start_weight
+            )));
+        }
+        if roll.start_meter > 0.0 && data.remnant_meter > roll.start_meter {
+            return Err(invalid(format!(
+                "Remnant of {} m is longer than the {} m the roll started with",
+                data.remnant_meter, roll.start_meter
+            )));
+        }
+        if data.post_return && settings.return_storage_location.is_empty() {
+            return Err(invalid(
+                "No SAP return storage location is set, the remnant stays where it was issued from and needs no posting"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Ends the input roll with the usual goods issue of the used weight, records what is left
+    /// as a remnant and, when asked, queues its transfer to the return store. Only the used
+    /// weight is issued, so the remnant never left stock and is moved rather than returned.
+    pub async fn end_input_roll(
+        conn: &Connection,
+        sap: &dyn SapGateway,
+        data: &EndWithRemnantPayload,
+        user_id: Option<i32>,
+    ) -> Result<RemnantEnded> {
+        // The remnant, the goods issue and the transfer commit together before SAP is called
+        let tx = conn.unchecked_transaction()?;
+        let roll = InputRoll::find_by_id(&tx, data.end.input_roll_id)?;
+        Self::check(&tx, sap.settings(), data, &roll)?;
+
+        let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let section_id: Option<i32> = tx.query_row(
+            "SELECT section_id FROM jobs WHERE id = ?1",
+            params![roll.job_id],
+            |row| row.get(0),
+        )?;
+        let storage_location = sap.settings().section_storage_location(&tx, section_id)?;
+        let return_location = data
+            .post_return
+            .then(|| sap.settings().return_storage_location.clone());
+        let unit = Some(data.end.weight_unit.trim())
+            .filter(|unit| !unit.is_empty())
+            .unwrap_or("KG");
+        tx.execute(
+            "INSERT INTO remnants (input_roll_id, batch, material_number, weight, unit, meter, storage_location, return_location, created_by, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                roll.id,
+                roll.batch,
+                roll.material_number.clone().unwrap_or_else(|| data.end.material_number.clone()),
+                data.remnant_weight,
+                unit,
+                data.remnant_meter,
+                storage_location,
+                return_location,
+                user_id,
+                now
+            ],
+        )?;
+        let id = tx.last_insert_rowid() as i32;
+        AuditLog::created(&tx, user_id, "remnants", id, &Self::find_by_id(&tx, id)?)?;
+
+        let issue = InputRoll::queue_end(&tx, sap, &data.end, user_id)?;
+        let transfer = if data.post_return {
+            // What is moved is the stored roll, whatever the client sent
+            let invalid = |msg: String| rusqlite::Error::InvalidParameterName(msg);
+            let material = roll
+                .material_number
+                .clone()
+                .filter(|material| !material.trim().is_empty())
+                .ok_or_else(|| invalid(format!("Input roll {} has no material", roll.batch)))?;
+            let order = roll.process_order.clone().ok_or_else(|| {
+                invalid(format!("Input roll {} has no process order", roll.batch))
+            })?;
+            let posting = SapPosting::GoodsReturn {
+                material,
+                batch: roll.batch.clone(),
+                order,
+                quantity: data.remnant_weight.to_string(),
+                unit: unit.to_string(),
+                posting_date: data.end.posting_date.clone(),
+                storage_location,
+            };
+            let message = SapOutbox::enqueue(
+                &tx,
+                &format!("GR-RM{}", id),
+                &posting,
+                Some(("remnants", id)),
+                user_id,
+            )?;
+            Some(message.id)
+        } else {
+            None
+        };
+        tx.commit()?;
+
+        let material_document = InputRoll::deliver_end(conn, sap, issue).await?;
+        if let Some(message_id) = transfer {
+            deliver_now(conn, sap, message_id).await?;
+        }
+        Ok(RemnantEnded {
+            material_document,
+            remnant: Self::find_by_id(conn, id)?,
+        })
+    }
+
+    /// The remnant if it can be loaded as an input roll: it exists and is not loaded yet.
+    pub fn loadable(conn: &Connection, id: i32) -> Result<Self> {
+        let invalid = |msg: String| rusqlite::Error::InvalidParameterName(msg);
+        let remnant = match Self::find_by_id(conn, id) {
+            Err(rusqlite::Error::QueryReturnedNoRows) => {
+                return Err(invalid(format!("Remnant {} not found", id)));
+            }
+            other => other?,
+        };
+        if remnant.consumed_at.is_some() {
+            return Err(invalid(format!(
+                "Remnant of {} is already loaded",
+                remnant.batch
+            )));
+        }
+        Ok(remnant)
+    }
+
+    /// Fills an input roll from the remnant and marks it loaded into `data.job_id`.
+    pub fn claim(
+        conn: &Connection,
+        id: i32,
+        data: &mut InputRollCreatePayload,
+        now: &str,
+    ) -> Result<()> {
+        let remnant = Self::loadable(conn, id)?;
+        let claimed = conn.execute(
+            "UPDATE remnants SET consumed_at = ?1, consumed_job_id = ?2 WHERE id = ?3 AND consumed_at IS NULL",
+            params![now, data.job_id, id],
+        )?;
+        if claimed == 0 {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Remnant of {} is already loaded",
+                remnant.batch
+            )));
+        }
+        data.batch = remnant.batch;
+        if let Some(material_number) = remnant.material_number {
+            data.material_number = material_number;
+        }
+        data.start_weight = format!("{}{}", remnant.weight, remnant.unit);
+        data.start_meter = remnant.meter;
+        Ok(())
+    }
+
+    /// Counts a label print, returning how many came before it.
+    pub fn record_print(conn: &Connection, id: i32) -> Result<i32> {
+        let reprint: i32 = conn.query_row(
+            "SELECT label_prints FROM remnants WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
+        )?;
+        conn.execute(
+            "UPDATE remnants SET label_prints = label_prints + 1 WHERE id = ?1",
+            params![id],
+        )?;
+        Ok(reprint)
+    }
+
+    /// What the roll label shows for the remnant: its batch, weight and meters under the job it
+    /// came off.
+    pub fn label_details(&self, conn: &Connection) -> Result<OutputRollDetails> {
+        let (process_order_description, section, operator_name) = conn.query_row(
+            "SELECT po.description, m.name, u.full_name \
+             FROM jobs j \
+             LEFT JOIN process_order po ON j.production_order = po.process_order \
+             LEFT JOIN machines m ON j.machine_id = m.id \
+             LEFT JOIN users u ON u.id = ?2 \
+             WHERE j.id = ?1",
+            params![self.job_id, self.created_by],
+            |row| {
+                Ok((
+                    row.get::<_, Option<String>>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                ))
+            },
+        )?;
+        Ok(OutputRollDetails {
+            material_number: self.material_number.clone().unwrap_or_default(),
+            material_description: self.material_description.clone().unwrap_or_default(),
+            process_order_description: process_order_description.unwrap_or_default(),
+            production_order: self.production_order.clone().unwrap_or_default(),
+            output_batch: self.batch.clone(),
+            final_weight: self.weight,
+            final_meter: self.meter,
+            flag_reason: None,
+            created_at: self.created_at.clone(),
+            section: section.unwrap_or_default(),
+            flag_count: 0,
+            operator_name,
+        })
+    }
+}
+
+#[actix_web::test]
+async fn test_end_with_remnant_and_reload() {
+    use crate::sap::{FakeSapGateway, FakeSapState};
+
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::manager::migrations::migrate_up(&mut conn, None).unwrap();
+    conn.execute_batch(
+        "INSERT INTO users (id, full_name) VALUES (1, 'Operator');
+         INSERT INTO jobs (id, production_order) VALUES (1, '220012061'), (2, '220012062');
+         INSERT INTO input_rolls (id, job_id, batch, material_number, start_meter, created_by, start_weight) VALUES (1, 1, 'J23-612', '30000950', 12000, 1, '520.000KG');",
+    )
+    .unwrap();
+    let sap = FakeSapGateway::new(FakeSapState::default()).with_settings(crate::sap::SapSettings {
+        return_storage_location: "RM01".to_string(),
+        ..Default::default()
+    });
+    let payload = |consumed: &str, remnant_weight: f64| EndWithRemnantPayload {
+        end: EndInputRollPayload {
+            id: 1,
+            weight_unit: "KG".to_string(),
+            posting_date: "2026-01-09T00:00:00".to_string(),
+            batch: "J23-612".to_string(),
+            input_roll_id: 1,
+            consumed_weight: consumed.to_string(),
+            // A stale scan; the transfer still moves the stored roll
+            material_number: "30000951".to_string(),
+            production_order: "220012069".to_string(),
+        },
+        remnant_weight,
+        remnant_meter: 4100.0,
+        post_return: true,
+    };
+
+    let too_much = Remnant::end_input_roll(&conn, &sap, &payload("400", 180.0), Some(1)).await;
+    assert!(matches!(
+        too_much,
+        Err(rusqlite::Error::InvalidParameterName(_))
+    ));
+    assert!(sap.state().goods_issues.is_empty());
+
+    // Without a return store there is nothing to transfer; the rejected end writes nothing
+    let unconfigured = FakeSapGateway::new(FakeSapState::default());
+    assert!(
+        Remnant::end_input_roll(&conn, &unconfigured, &payload("340", 180.0), Some(1))
+            .await
+            .is_err()
+    );
+    let (consumed, remnants): (Option<String>, i32) = conn
+        .query_row(
+            "SELECT consumed_weight, (SELECT COUNT(*) FROM remnants) FROM input_rolls WHERE id = 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!((consumed, remnants), (None, 0));
+
+    let ended = Remnant::end_input_roll(&conn, &sap, &payload("340", 180.0), Some(1))
+        .await
+        .unwrap();
+    assert!(ended.material_document.is_some());
+    let remnant = ended.remnant;
+    assert_eq!((remnant.weight, remnant.meter), (180.0, 4100.0));
+    assert!(remnant.return_document.is_some());
+    assert_eq!(remnant.return_location.as_deref(), Some("RM01"));
+    assert_eq!(remnant.storage_location.as_deref(), Some("RM01"));
+    {
+        let state = sap.state();
+        assert_eq!(state.goods_issues.len(), 2);
+        assert_eq!(state.goods_issues[0].movement_type, None);
+        assert_eq!(state.goods_issues[0].quantity, "340");
+        assert_eq!(state.goods_issues[1].movement_type.as_deref(), Some("311"));
+        assert_eq!(
+            state.goods_issues[1].receiving_storage_location.as_deref(),
+            Some("RM01")
+        );
+        assert_eq!(state.goods_issues[1].quantity, "180");
+        assert_eq!(state.goods_issues[1].material, "30000950");
+        assert_eq!(state.goods_issues[1].order, "220012061");
+        assert_eq!(
+            state.goods_issues[1].header_text,
+            format!("GR-RM{}", remnant.id)
+        );
+    }
+    assert!(
+        Remnant::end_input_roll(&conn, &sap, &payload("340", 180.0), Some(1))
+            .await
+            .is_err()
+    );
+
+    let details = remnant.label_details(&conn).unwrap();
+    assert_eq!(details.output_batch, "J23-612");
+    assert_eq!(Remnant::record_print(&conn, remnant.id).unwrap(), 0);
+    assert_eq!(Remnant::record_print(&conn, remnant.id).unwrap(), 1);
+
+    let filter = RemnantFilterPayload {
+        material_number: Some("30000950".to_string()),
+    };
+    assert_eq!(Remnant::available(&conn, &filter).unwrap().len(), 1);
+    let reload = InputRollCreatePayload {
+        job_id: 2,
+        batch: String::new(),
+        material_document: None,
+        material_number: String::new(),
+        start_meter: 0.0,
+        start_weight: String::new(),
+        consumed_weight: None,
+        output_batch: None,
+        remnant_id: Some(remnant.id),
+        bom_override: None,
+    };
+    let roll = InputRoll::create(&mut conn, &reload, 1).unwrap();
+    assert_eq!(roll.batch, "J23-612");
+    assert_eq!(roll.start_weight, "180KG");
+    assert_eq!(roll.start_meter, 4100.0);
+    assert!(Remnant::available(&conn, &filter).unwrap().is_empty());
+    assert!(InputRoll::create(&mut conn, &reload, 1).is_err());
+
+    // The reloaded remnant is issued from the return store it was moved to
+    let end = EndInputRollPayload {
+        id: roll.id,
+        input_roll_id: roll.id,
+        batch: "J23-612".to_string(),
+        consumed_weight: "150".to_string(),
+        material_number: "30000950".to_string(),
+        production_order: "220012062".to_string(),
+        ..payload("150", 0.0).end
+    };
+    InputRoll::end_input_roll(&conn, &sap, &end, Some(1))
+        .await
+        .unwrap();
+    assert_eq!(sap.state().goods_issues[2].storage_location, "RM01");
+
+    roll.delete(&conn, &sap, Some(1)).await.unwrap();
+    assert_eq!(Remnant::available(&conn, &filter).unwrap().len(), 1);
+}
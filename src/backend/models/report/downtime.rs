@@ -1,15 +1,24 @@
 use chrono::Local;
-use rusqlite::{params, Connection, Result};
+use rusqlite::{params, Connection, OptionalExtension, Result};
 use serde::{Serialize, Deserialize};
-use crate::backend::models::FilterResponse;
+use crate::backend::models::{parse_local_datetime, AuditLog, FilterResponse, Job, Shift};
 
+const DOWNTIME_SELECT: &str = "SELECT d.id, d.shift_id, d.machine_id, m.name, d.job_id, d.start_time, d.end_time, d.duration_minutes, d.downtime_reason_id, d.created_by, d.created_at, d.updated_at
+     FROM downtimes d
+     LEFT JOIN machines m ON d.machine_id = m.id";
+
+/// A machine stop. Downtimes can be opened from the shop floor without an end time and
+/// closed later; `duration_minutes` is always worked out here from the two times.
 #[derive(Debug, Serialize)]
 pub struct Downtime {
     pub id: i32,
     pub shift_id: i32,
+    pub machine_id: Option<i32>,
+    pub machine_name: Option<String>,
+    pub job_id: Option<i32>,
     pub start_time: String,
-    pub end_time: String,
-    pub duration_minutes: i32,
+    pub end_time: Option<String>,
+    pub duration_minutes: Option<i32>,
     pub downtime_reason_id: i32,
     pub created_by: i32,
     pub created_at: String,
@@ -18,10 +27,14 @@ pub struct Downtime {
 
 #[derive(Deserialize)]
 pub struct DowntimeCreatePayload {
-    pub shift_id: i32,
+    /// Only used when the shift calendar does not cover `start_time`
+    pub shift_id: Option<i32>,
+    pub machine_id: i32,
+    /// Defaults to the job running on the machine at `start_time`
+    pub job_id: Option<i32>,
     pub start_time: String,
-    pub end_time: String,
-    pub duration_minutes: i32,
+    /// Leave empty to open the downtime and close it later
+    pub end_time: Option<String>,
     pub downtime_reason_id: i32,
 }
 
@@ -29,15 +42,28 @@ pub struct DowntimeCreatePayload {
 pub struct DowntimePayload {
     pub id: i32,
     pub shift_id: Option<i32>,
+    pub machine_id: Option<i32>,
+    pub job_id: Option<i32>,
     pub start_time: Option<String>,
     pub end_time: Option<String>,
-    pub duration_minutes: Option<i32>,
     pub downtime_reason_id: Option<i32>,
 }
 
+#[derive(Deserialize)]
+pub struct DowntimeClosePayload {
+    pub id: i32,
+    /// Defaults to now
+    pub end_time: Option<String>,
+}
+
 #[derive(Deserialize)]
 pub struct DowntimeFilterPayload {
     pub shift_id: Option<String>,
+    pub machine_id: Option<String>,
+    pub section_id: Option<String>,
+    pub job_id: Option<String>,
+    /// `true` for downtimes that are still open, `false` for closed ones
+    pub open: Option<String>,
     pub downtime_reason_id: Option<String>,
     pub start_date: Option<String>,
     pub end_date: Option<String>,
@@ -46,96 +72,223 @@ pub struct DowntimeFilterPayload {
     pub page: Option<String>,
 }
 
+fn invalid(msg: &str) -> rusqlite::Error {
+    rusqlite::Error::InvalidParameterName(msg.to_string())
+}
+
+fn non_empty(value: &Option<String>) -> Option<String> {
+    value.as_ref().map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+/// Whole minutes from `start` to `end`; errors when either time is unreadable or the
+/// downtime would end before it starts.
+fn minutes_between(conn: &Connection, start: &str, end: &str) -> Result<i32> {
+    let minutes: Option<i32> = conn.query_row(
+        "SELECT CAST(ROUND((julianday(?2) - julianday(?1)) * 1440) AS INTEGER)",
+        params![start, end],
+        |row| row.get(0),
+    )?;
+    match minutes {
+        None => Err(invalid("Invalid start or end time")),
+        Some(m) if m < 0 => Err(invalid("End time must be after start time")),
+        Some(m) => Ok(m),
+    }
+}
+
+/// The job running on a machine at the given time.
+fn active_job(conn: &Connection, machine_id: i32, at: &str) -> Result<Option<i32>> {
+    conn.query_row(
+        "SELECT id FROM jobs
+         WHERE machine_id = ?1 AND julianday(start_datetime) <= julianday(?2)
+         AND (end_datetime IS NULL OR julianday(end_datetime) >= julianday(?2))
+         ORDER BY start_datetime DESC, id DESC LIMIT 1",
+        params![machine_id, at],
+        |row| row.get(0),
+    ).optional()
+}
+
+/// A machine can only be down once at a time; open downtimes run until they are closed.
+fn check_overlap(conn: &Connection, id: Option<i32>, machine_id: i32, start: &str, end: Option<&str>) -> Result<()> {
+    let clash: Option<String> = conn.query_row(
+        "SELECT start_time FROM downtimes
+         WHERE machine_id = ?1 AND (?2 IS NULL OR id <> ?2)
+         AND julianday(start_time) < julianday(COALESCE(?4, '9999-12-31'))
+         AND julianday(COALESCE(end_time, '9999-12-31')) > julianday(?3)
+         LIMIT 1",
+        params![machine_id, id, start, end],
+        |row| row.get(0),
+    ).optional()?;
+    match clash {
+        Some(other) => Err(rusqlite::Error::InvalidParameterName(format!("Machine already has a downtime starting {} in that period", other))),
+        None => Ok(()),
+    }
+}
+
+fn resolve_job(conn: &Connection, machine_id: i32, job_id: Option<i32>, start: &str) -> Result<Option<i32>> {
+    match job_id {
+        Some(job_id) => {
+            let job_machine: Option<i32> = conn.query_row(
+                "SELECT machine_id FROM jobs WHERE id = ?1",
+                params![job_id],
+                |row| row.get(0),
+            ).optional()?.flatten();
+            if job_machine != Some(machine_id) {
+                return Err(invalid("Job does not run on this machine"));
+            }
+            Ok(Some(job_id))
+        }
+        None => active_job(conn, machine_id, start),
+    }
+}
+
 impl Downtime {
+    fn from_row(row: &rusqlite::Row) -> Result<Self> {
+        Ok(Downtime {
+            id: row.get(0)?,
+            shift_id: row.get(1)?,
+            machine_id: row.get(2)?,
+            machine_name: row.get(3)?,
+            job_id: row.get(4)?,
+            start_time: row.get(5)?,
+            end_time: row.get(6)?,
+            duration_minutes: row.get(7)?,
+            downtime_reason_id: row.get(8)?,
+            created_by: row.get(9)?,
+            created_at: row.get(10)?,
+            updated_at: row.get(11)?,
+        })
+    }
+
     pub fn create(conn: &Connection, data: &DowntimeCreatePayload, user_id: i32) -> Result<Self> {
         let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let start_time = data.start_time.trim();
+        let end_time = non_empty(&data.end_time);
+        let duration_minutes = match &end_time {
+            Some(end) => Some(minutes_between(conn, start_time, end)?),
+            // Still rejects an unreadable start time
+            None => {
+                minutes_between(conn, start_time, start_time)?;
+                None
+            }
+        };
+        check_overlap(conn, None, data.machine_id, start_time, end_time.as_deref())?;
+        let job_id = resolve_job(conn, data.machine_id, data.job_id, start_time)?;
+        if let Some(job_id) = job_id {
+            Job::ensure_open(conn, job_id)?;
+        }
+        let started = parse_local_datetime(start_time).ok_or_else(|| invalid("Invalid start or end time"))?;
+        let shift = Shift::resolve(conn, started, data.shift_id)?;
+
         conn.execute(
-            "INSERT INTO downtimes (shift_id, start_time, end_time, duration_minutes, downtime_reason_id, created_by, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-            params![data.shift_id, data.start_time, data.end_time, data.duration_minutes, data.downtime_reason_id, user_id, now, now],
+            "INSERT INTO downtimes (shift_id, machine_id, job_id, start_time, end_time, duration_minutes, downtime_reason_id, created_by, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![shift.shift_id, data.machine_id, job_id, start_time, end_time, duration_minutes, data.downtime_reason_id, user_id, now, now],
         )?;
         let id = conn.last_insert_rowid() as i32;
-        Ok(Downtime {
-            id,
-            shift_id: data.shift_id,
-            start_time: data.start_time.clone(),
-            end_time: data.end_time.clone(),
-            duration_minutes: data.duration_minutes,
-            downtime_reason_id: data.downtime_reason_id,
-            created_by: user_id,
-            created_at: now.clone(),
-            updated_at: now.clone(),
-        })
+        let record = Self::find_by_id(conn, id)?;
+        AuditLog::created(conn, Some(user_id), "downtimes", id, &record)?;
+        Ok(record)
     }
 
-    pub fn update(&mut self, conn: &Connection, data: &DowntimePayload) -> Result<()> {
+    pub fn update(&mut self, conn: &Connection, data: &DowntimePayload, user_id: Option<i32>) -> Result<()> {
+        let before = AuditLog::snapshot(self);
         let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-        if let Some(shift_id) = data.shift_id {
-            conn.execute("UPDATE downtimes SET shift_id = ?1 WHERE id = ?2", params![shift_id, self.id])?;
-            self.shift_id = shift_id;
-        }
-        if let Some(start_time) = &data.start_time {
-            conn.execute("UPDATE downtimes SET start_time = ?1 WHERE id = ?2", params![start_time, self.id])?;
-            self.start_time = start_time.clone();
-        }
-        if let Some(end_time) = &data.end_time {
-            conn.execute("UPDATE downtimes SET end_time = ?1 WHERE id = ?2", params![end_time, self.id])?;
-            self.end_time = end_time.clone();
+        let start_time = non_empty(&data.start_time).unwrap_or_else(|| self.start_time.clone());
+        let end_time = non_empty(&data.end_time).or_else(|| self.end_time.clone());
+        let machine_id = data.machine_id.or(self.machine_id);
+        let duration_minutes = match &end_time {
+            Some(end) => Some(minutes_between(conn, &start_time, end)?),
+            None => None,
+        };
+        let job_id = match machine_id {
+            Some(machine_id) => {
+                check_overlap(conn, Some(self.id), machine_id, &start_time, end_time.as_deref())?;
+                if data.job_id.is_some() || data.machine_id.is_some() || data.start_time.is_some() {
+                    resolve_job(conn, machine_id, data.job_id, &start_time)?
+                } else {
+                    self.job_id
+                }
+            }
+            None => self.job_id,
+        };
+        if job_id != self.job_id && let Some(job_id) = job_id {
+            Job::ensure_open(conn, job_id)?;
         }
-        if let Some(duration_minutes) = data.duration_minutes {
-            conn.execute("UPDATE downtimes SET duration_minutes = ?1 WHERE id = ?2", params![duration_minutes, self.id])?;
-            self.duration_minutes = duration_minutes;
+        // A moved start can fall in another shift; the calendar decides as on create
+        let shift_id = if start_time != self.start_time {
+            let started = parse_local_datetime(&start_time).ok_or_else(|| invalid("Invalid start or end time"))?;
+            Shift::resolve(conn, started, data.shift_id.or(Some(self.shift_id)))?.shift_id
+        } else {
+            data.shift_id.unwrap_or(self.shift_id)
+        };
+
+        conn.execute(
+            "UPDATE downtimes SET shift_id = ?1, machine_id = ?2, job_id = ?3, start_time = ?4, end_time = ?5, duration_minutes = ?6, downtime_reason_id = ?7, updated_at = ?8 WHERE id = ?9",
+            params![
+                shift_id,
+                machine_id,
+                job_id,
+                start_time,
+                end_time,
+                duration_minutes,
+                data.downtime_reason_id.unwrap_or(self.downtime_reason_id),
+                now,
+                self.id
+            ],
+        )?;
+        *self = Self::find_by_id(conn, self.id)?;
+        AuditLog::updated(conn, user_id, "downtimes", self.id, &before, self)?;
+        Ok(())
+    }
+
+    /// Ends an open downtime, at `end_time` or now.
+    pub fn close(&mut self, conn: &Connection, end_time: Option<String>, user_id: Option<i32>) -> Result<()> {
+        if self.end_time.is_some() {
+            return Err(invalid("Downtime is already closed"));
         }
-        if let Some(downtime_reason_id) = data.downtime_reason_id {
-            conn.execute("UPDATE downtimes SET downtime_reason_id = ?1 WHERE id = ?2", params![downtime_reason_id, self.id])?;
-            self.downtime_reason_id = downtime_reason_id;
+        let before = AuditLog::snapshot(self);
+        let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let end_time = non_empty(&end_time).unwrap_or_else(|| now.clone());
+        let duration_minutes = minutes_between(conn, &self.start_time, &end_time)?;
+        if let Some(machine_id) = self.machine_id {
+            check_overlap(conn, Some(self.id), machine_id, &self.start_time, Some(&end_time))?;
         }
-        conn.execute("UPDATE downtimes SET updated_at = ?1 WHERE id = ?2", params![now, self.id])?;
+        conn.execute(
+            "UPDATE downtimes SET end_time = ?1, duration_minutes = ?2, updated_at = ?3 WHERE id = ?4",
+            params![end_time, duration_minutes, now, self.id],
+        )?;
+        self.end_time = Some(end_time);
+        self.duration_minutes = Some(duration_minutes);
         self.updated_at = now;
+        AuditLog::updated(conn, user_id, "downtimes", self.id, &before, self)?;
         Ok(())
     }
 
-    pub fn delete(&self, conn: &Connection) -> Result<()> {
+    pub fn delete(&self, conn: &Connection, user_id: Option<i32>) -> Result<()> {
         conn.execute("DELETE FROM downtimes WHERE id = ?1", params![self.id])?;
+        AuditLog::deleted(conn, user_id, "downtimes", self.id, self)?;
         Ok(())
     }
 
     pub fn find_by_id(conn: &Connection, id: i32) -> Result<Self> {
-        let mut stmt = conn.prepare("SELECT * FROM downtimes WHERE id = ?1")?;
-        stmt.query_row(params![id], |row| Ok(Downtime {
-            id: row.get(0)?,
-            shift_id: row.get(1)?,
-            start_time: row.get(2)?,
-            end_time: row.get(3)?,
-            duration_minutes: row.get(4)?,
-            downtime_reason_id: row.get(5)?,
-            created_by: row.get(6)?,
-            created_at: row.get(7)?,
-            updated_at: row.get(8)?,
-        }))
+        let mut stmt = conn.prepare(&format!("{} WHERE d.id = ?1", DOWNTIME_SELECT))?;
+        stmt.query_row(params![id], Self::from_row)
     }
 
     pub fn all(conn: &Connection) -> Result<Vec<Self>> {
-        let mut stmt = conn.prepare("SELECT * FROM downtimes ORDER BY created_at DESC")?;
-        let downtimes = stmt.query_map([], |row| Ok(Downtime {
-            id: row.get(0)?,
-            shift_id: row.get(1)?,
-            start_time: row.get(2)?,
-            end_time: row.get(3)?,
-            duration_minutes: row.get(4)?,
-            downtime_reason_id: row.get(5)?,
-            created_by: row.get(6)?,
-            created_at: row.get(7)?,
-            updated_at: row.get(8)?,
-        }))?.collect::<Result<Vec<_>, _>>()?;
+        let mut stmt = conn.prepare(&format!("{} ORDER BY d.created_at DESC", DOWNTIME_SELECT))?;
+        let downtimes = stmt.query_map([], Self::from_row)?.collect::<Result<Vec<_>, _>>()?;
         Ok(downtimes)
     }
 
     pub fn filter(conn: &Connection, filter: &DowntimeFilterPayload) -> Result<FilterResponse<Self>> {
-        let mut count_query = "SELECT COUNT(*) FROM downtimes WHERE 1=1".to_string();
-        let mut data_query = "SELECT * FROM downtimes WHERE 1=1".to_string();
+        let mut count_query = "SELECT COUNT(*) FROM downtimes d LEFT JOIN machines m ON d.machine_id = m.id WHERE 1=1".to_string();
+        let mut data_query = format!("{} WHERE 1=1", DOWNTIME_SELECT);
         let mut params_vec: Vec<&dyn rusqlite::ToSql> = vec![];
 
         let mut shift_ids: Vec<i32> = vec![];
+        let mut machine_ids: Vec<i32> = vec![];
+        let mut section_ids: Vec<i32> = vec![];
+        let mut job_ids: Vec<i32> = vec![];
         let mut downtime_reason_ids: Vec<i32> = vec![];
         let mut created_bys: Vec<i32> = vec![];
         let mut start_dates: Vec<String> = vec![];
@@ -147,8 +300,39 @@ impl Downtime {
             if let Ok(parsed) = val.parse::<i32>() {
                 shift_ids.push(parsed);
                 params_vec.push(shift_ids.last().unwrap());
-                count_query.push_str(" AND shift_id = ?");
-                data_query.push_str(" AND shift_id = ?");
+                count_query.push_str(" AND d.shift_id = ?");
+                data_query.push_str(" AND d.shift_id = ?");
+            }
+        }
+
+        if let Some(val) = &filter.machine_id && let Ok(parsed) = val.parse::<i32>() {
+            machine_ids.push(parsed);
+            params_vec.push(machine_ids.last().unwrap());
+            count_query.push_str(" AND d.machine_id = ?");
+            data_query.push_str(" AND d.machine_id = ?");
+        }
+
+        if let Some(val) = &filter.section_id && let Ok(parsed) = val.parse::<i32>() {
+            section_ids.push(parsed);
+            params_vec.push(section_ids.last().unwrap());
+            count_query.push_str(" AND m.section_id = ?");
+            data_query.push_str(" AND m.section_id = ?");
+        }
+
+        if let Some(val) = &filter.job_id && let Ok(parsed) = val.parse::<i32>() {
+            job_ids.push(parsed);
+            params_vec.push(job_ids.last().unwrap());
+            count_query.push_str(" AND d.job_id = ?");
+            data_query.push_str(" AND d.job_id = ?");
+        }
+
+        if let Some(val) = &filter.open {
+            if val == "true" {
+                count_query.push_str(" AND d.end_time IS NULL");
+                data_query.push_str(" AND d.end_time IS NULL");
+            } else if val == "false" {
+                count_query.push_str(" AND d.end_time IS NOT NULL");
+                data_query.push_str(" AND d.end_time IS NOT NULL");
             }
         }
 
@@ -156,8 +340,8 @@ impl Downtime {
             if let Ok(parsed) = val.parse::<i32>() {
                 downtime_reason_ids.push(parsed);
                 params_vec.push(downtime_reason_ids.last().unwrap());
-                count_query.push_str(" AND downtime_reason_id = ?");
-                data_query.push_str(" AND downtime_reason_id = ?");
+                count_query.push_str(" AND d.downtime_reason_id = ?");
+                data_query.push_str(" AND d.downtime_reason_id = ?");
             }
         }
 
@@ -165,8 +349,8 @@ impl Downtime {
             if let Ok(parsed) = val.parse::<i32>() {
                 created_bys.push(parsed);
                 params_vec.push(created_bys.last().unwrap());
-                count_query.push_str(" AND created_by = ?");
-                data_query.push_str(" AND created_by = ?");
+                count_query.push_str(" AND d.created_by = ?");
+                data_query.push_str(" AND d.created_by = ?");
             }
         }
 
@@ -174,8 +358,8 @@ impl Downtime {
             if !val.is_empty() {
                 start_dates.push(val.clone());
                 params_vec.push(start_dates.last().unwrap());
-                count_query.push_str(" AND date(created_at) >= date(?)");
-                data_query.push_str(" AND date(created_at) >= date(?)");
+                count_query.push_str(" AND date(d.start_time) >= date(?)");
+                data_query.push_str(" AND date(d.start_time) >= date(?)");
             }
         }
 
@@ -183,14 +367,14 @@ impl Downtime {
             if !val.is_empty() {
                 end_dates.push(val.clone());
                 params_vec.push(end_dates.last().unwrap());
-                count_query.push_str(" AND date(created_at) <= date(?)");
-                data_query.push_str(" AND date(created_at) <= date(?)");
+                count_query.push_str(" AND date(d.start_time) <= date(?)");
+                data_query.push_str(" AND date(d.start_time) <= date(?)");
             }
         }
 
         let total_count: i32 = conn.query_row(&count_query, params_vec.as_slice(), |row| row.get(0))?;
 
-        data_query.push_str(" ORDER BY created_at DESC");
+        data_query.push_str(" ORDER BY d.created_at DESC");
 
         if let (Some(page), Some(per_page)) = (&filter.page, &filter.per_page) {
             if let (Ok(page_val), Ok(per_page_val)) = (page.parse::<i32>(), per_page.parse::<i32>()) {
@@ -206,19 +390,7 @@ impl Downtime {
         }
 
         let mut stmt = conn.prepare(&data_query)?;
-        let rows = stmt.query_map(params_vec.as_slice(), |row| {
-            Ok(Downtime {
-                id: row.get(0)?,
-                shift_id: row.get(1)?,
-                start_time: row.get(2)?,
-                end_time: row.get(3)?,
-                duration_minutes: row.get(4)?,
-                downtime_reason_id: row.get(5)?,
-                created_by: row.get(6)?,
-                created_at: row.get(7)?,
-                updated_at: row.get(8)?,
-            })
-        })?;
+        let rows = stmt.query_map(params_vec.as_slice(), Self::from_row)?;
 
         let data = rows.collect::<Result<Vec<_>, _>>()?;
 
@@ -229,3 +401,85 @@ impl Downtime {
     }
 }
 
+
+#[test]
+fn test_open_close_and_overlap_per_machine() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::manager::migrations::migrate_up(&mut conn, None).unwrap();
+    conn.execute_batch(
+        "INSERT INTO users (id, full_name) VALUES (1, 'Operator');
+         INSERT INTO machines (id, name, label) VALUES (1, 'Printer 1', 'P1'), (2, 'Printer 2', 'P2');
+         INSERT INTO shifts (id, name) VALUES (1, 'Day');
+         INSERT INTO downtime_reasons (id, name) VALUES (1, 'Web break');
+         INSERT INTO jobs (id, machine_id, shift_id, created_by, start_datetime) VALUES (1, 1, 1, 1, '2026-01-09 07:00:00');",
+    )
+    .unwrap();
+
+    let payload = |machine_id, start: &str, end: Option<&str>| DowntimeCreatePayload {
+        shift_id: Some(1),
+        machine_id,
+        job_id: None,
+        start_time: start.to_string(),
+        end_time: end.map(str::to_string),
+        downtime_reason_id: 1,
+    };
+    let mut open = Downtime::create(&conn, &payload(1, "2026-01-09T08:00", None), 1).unwrap();
+    assert_eq!(open.job_id, Some(1));
+    assert!(open.duration_minutes.is_none());
+
+    // The machine is still down, but another machine can stop at the same time
+    assert!(Downtime::create(&conn, &payload(1, "2026-01-09T09:00", Some("2026-01-09T09:10")), 1).is_err());
+    let other = Downtime::create(&conn, &payload(2, "2026-01-09T09:00", Some("2026-01-09T09:10")), 1).unwrap();
+    assert_eq!(other.duration_minutes, Some(10));
+    assert!(other.job_id.is_none());
+
+    assert!(open.close(&conn, Some("2026-01-09T07:30".to_string()), Some(1)).is_err());
+    open.close(&conn, Some("2026-01-09T08:45".to_string()), Some(1)).unwrap();
+    assert_eq!(Downtime::find_by_id(&conn, open.id).unwrap().duration_minutes, Some(45));
+    assert!(open.close(&conn, None, Some(1)).is_err());
+
+    let filter = DowntimeFilterPayload {
+        shift_id: None,
+        machine_id: Some("1".to_string()),
+        section_id: None,
+        job_id: None,
+        open: Some("false".to_string()),
+        downtime_reason_id: None,
+        start_date: None,
+        end_date: None,
+        created_by: None,
+        per_page: None,
+        page: None,
+    };
+    let result = Downtime::filter(&conn, &filter).unwrap();
+    assert_eq!(result.total_count, 1);
+    assert_eq!(result.data[0].machine_name.as_deref(), Some("Printer 1"));
+
+    // Dates filter on when the machine stopped, not when it was entered
+    let on_the_day = DowntimeFilterPayload {
+        start_date: Some("2026-01-09".to_string()),
+        end_date: Some("2026-01-09".to_string()),
+        ..filter
+    };
+    assert_eq!(Downtime::filter(&conn, &on_the_day).unwrap().total_count, 1);
+
+    // Moving the start re-resolves the shift; a closed job cannot take the downtime
+    conn.execute_batch(
+        "INSERT INTO shifts (id, name, start_time, end_time) VALUES (2, 'Night', '19:00', '07:00');
+         INSERT INTO jobs (id, machine_id, shift_id, created_by, start_datetime, end_datetime) VALUES (2, 1, 1, 1, '2026-01-08 07:00:00', '2026-01-08 18:00:00');",
+    )
+    .unwrap();
+    let moved = DowntimePayload {
+        id: open.id,
+        shift_id: None,
+        machine_id: None,
+        job_id: None,
+        start_time: Some("2026-01-09T06:00".to_string()),
+        end_time: None,
+        downtime_reason_id: None,
+    };
+    open.update(&conn, &moved, Some(1)).unwrap();
+    assert_eq!(open.shift_id, 2);
+    let relinked = DowntimePayload { job_id: Some(2), start_time: None, ..moved };
+    assert!(open.update(&conn, &relinked, Some(1)).is_err());
+}
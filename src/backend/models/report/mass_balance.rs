@@ -0,0 +1,475 @@
+use rusqlite::{Connection, Result, params};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+
+/// Unaccounted loss allowed when neither the request nor `MASS_BALANCE_TOLERANCE` sets one
+const DEFAULT_TOLERANCE_PCT: f64 = 2.0;
+
+#[derive(Deserialize)]
+pub struct MassBalanceFilterPayload {
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
+    pub machine_id: Option<String>,
+    pub section_id: Option<String>,
+    pub production_order: Option<String>,
+    /// Unaccounted weight allowed, in percent of the input consumed
+    pub tolerance: Option<String>,
+    /// `true` to list only jobs outside the tolerance
+    pub flagged: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct MassBalanceJobQuery {
+    pub id: i32,
+    pub tolerance: Option<String>,
+}
+
+/// Input consumed against net output and recorded scrap. Rolls scrapped by QA count as
+/// scrap only, through the `scraps` row their disposition books.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Balance {
+    pub input_kg: f64,
+    pub output_kg: f64,
+    pub scrap_kg: f64,
+    pub unaccounted_kg: f64,
+    /// Output over input; `None` without input
+    pub yield_pct: Option<f64>,
+    pub unaccounted_pct: Option<f64>,
+}
+
+impl Balance {
+    fn add(&mut self, other: &Balance) {
+        self.input_kg += other.input_kg;
+        self.output_kg += other.output_kg;
+        self.scrap_kg += other.scrap_kg;
+    }
+
+    fn finish(&mut self) {
+        let pct = |num: f64, den: f64| (den > 0.0).then(|| round(num / den * 100.0));
+        self.input_kg = round(self.input_kg);
+        self.output_kg = round(self.output_kg);
+        self.scrap_kg = round(self.scrap_kg);
+        self.unaccounted_kg = round(self.input_kg - self.output_kg - self.scrap_kg);
+        self.yield_pct = pct(self.output_kg, self.input_kg);
+        self.unaccounted_pct = pct(self.unaccounted_kg, self.input_kg);
+    }
+
+    fn outside(&self, tolerance_pct: f64) -> bool {
+        match self.unaccounted_pct {
+            Some(pct) => pct.abs() > tolerance_pct,
+            // Output or scrap with nothing consumed is unexplained too
+            None => self.output_kg + self.scrap_kg > 0.0,
+        }
+    }
+}
+
+fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+#[derive(Debug, Serialize)]
+pub struct JobBalance {
+    pub job_id: i32,
+    pub production_order: Option<String>,
+    pub machine_id: Option<i32>,
+    pub machine: Option<String>,
+    pub section: Option<String>,
+    pub day: Option<String>,
+    pub input_rolls: i32,
+    pub output_rolls: i32,
+    /// Input rolls without a consumed weight and output rolls not yet weighed
+    pub pending_rolls: i32,
+    /// The job has ended and every roll is weighed, so its balance is final
+    pub complete: bool,
+    #[serde(flatten)]
+    pub balance: Balance,
+    /// Only complete jobs are flagged
+    pub out_of_tolerance: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrderBalance {
+    pub production_order: String,
+    pub jobs: i32,
+    pub complete: bool,
+    #[serde(flatten)]
+    pub balance: Balance,
+    pub out_of_tolerance: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MassBalanceReport {
+    pub tolerance_pct: f64,
+    pub jobs: Vec<JobBalance>,
+    pub orders: Vec<OrderBalance>,
+    pub total: Balance,
+    pub flagged: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BalanceInputRoll {
+    pub id: i32,
+    pub batch: Option<String>,
+    pub material_number: Option<String>,
+    pub start_weight: Option<String>,
+    pub consumed_weight: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BalanceOutputRoll {
+    pub id: i32,
+    pub output_batch: Option<String>,
+    pub final_meter: Option<f64>,
+    pub final_weight: Option<f64>,
+    pub qa_status: Option<String>,
+    /// False for rolls scrapped by QA, which are in the scrap instead
+    pub counted: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BalanceScrap {
+    pub id: i32,
+    pub time: Option<String>,
+    pub scrap_type: Option<String>,
+    pub weight_kg: f64,
+    pub notes: Option<String>,
+}
+
+/// One job's balance with the rolls and scrap behind it.
+#[derive(Debug, Serialize)]
+pub struct MassBalanceDetail {
+    pub tolerance_pct: f64,
+    pub job: JobBalance,
+    pub inputs: Vec<BalanceInputRoll>,
+    pub outputs: Vec<BalanceOutputRoll>,
+    pub scraps: Vec<BalanceScrap>,
+}
+
+const JOB_SELECT: &str = "SELECT j.id, j.production_order, j.machine_id, m.name, s.name, COALESCE(j.production_date, date(j.start_datetime)), j.end_datetime IS NOT NULL, \
+     (SELECT COUNT(*) FROM input_rolls ir WHERE ir.job_id = j.id), \
+     (SELECT COUNT(*) FROM output_rolls o JOIN input_rolls ir ON o.input_roll_id = ir.id WHERE ir.job_id = j.id), \
+     (SELECT COUNT(*) FROM input_rolls ir WHERE ir.job_id = j.id AND ir.consumed_weight IS NULL) \
+        + (SELECT COUNT(*) FROM output_rolls o JOIN input_rolls ir ON o.input_roll_id = ir.id WHERE ir.job_id = j.id AND COALESCE(o.final_weight, 0) <= 0), \
+     (SELECT COALESCE(SUM(CAST(ir.consumed_weight AS REAL)), 0) FROM input_rolls ir WHERE ir.job_id = j.id), \
+     (SELECT COALESCE(SUM(CAST(o.final_weight AS REAL)), 0) FROM output_rolls o JOIN input_rolls ir ON o.input_roll_id = ir.id WHERE ir.job_id = j.id AND COALESCE(o.qa_status, '') <> 'scrapped'), \
+     (SELECT COALESCE(SUM(CAST(sc.weight_kg AS REAL)), 0) FROM scraps sc WHERE sc.job_id = j.id) \
+     FROM jobs j \
+     LEFT JOIN machines m ON j.machine_id = m.id \
+     LEFT JOIN sections s ON COALESCE(j.section_id, m.section_id) = s.id \
+     WHERE 1=1";
+
+fn job_from_row(row: &rusqlite::Row, tolerance_pct: f64) -> Result<JobBalance> {
+    let ended: bool = row.get(6)?;
+    let pending_rolls: i32 = row.get(9)?;
+    let mut balance = Balance {
+        input_kg: row.get(10)?,
+        output_kg: row.get(11)?,
+        scrap_kg: row.get(12)?,
+        ..Default::default()
+    };
+    balance.finish();
+    let complete = ended && pending_rolls == 0;
+    Ok(JobBalance {
+        job_id: row.get(0)?,
+        production_order: row.get(1)?,
+        machine_id: row.get(2)?,
+        machine: row.get(3)?,
+        section: row.get(4)?,
+        day: row.get(5)?,
+        input_rolls: row.get(7)?,
+        output_rolls: row.get(8)?,
+        pending_rolls,
+        complete,
+        out_of_tolerance: complete && balance.outside(tolerance_pct),
+        balance,
+    })
+}
+
+/// Tolerance from the request, else `MASS_BALANCE_TOLERANCE`, else 2%.
+fn tolerance(requested: Option<&str>) -> Result<f64> {
+    let configured = env::var("MASS_BALANCE_TOLERANCE").ok();
+    match requested
+        .or(configured.as_deref())
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+    {
+        None => Ok(DEFAULT_TOLERANCE_PCT),
+        Some(value) => value
+            .trim_end_matches('%')
+            .parse::<f64>()
+            .ok()
+            .filter(|t| *t >= 0.0)
+            .ok_or_else(|| {
+                rusqlite::Error::InvalidParameterName(format!("Bad tolerance '{}'", value))
+            }),
+    }
+}
+
+impl MassBalanceReport {
+    pub fn calculate(conn: &Connection, filter: &MassBalanceFilterPayload) -> Result<Self> {
+        let tolerance_pct = tolerance(filter.tolerance.as_deref())?;
+        let mut query = JOB_SELECT.to_string();
+        let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = vec![];
+
+        if let Some(val) = filter.start_date.as_ref().filter(|v| !v.is_empty()) {
+            query.push_str(" AND COALESCE(j.production_date, date(j.start_datetime)) >= date(?)");
+            params_vec.push(Box::new(val.clone()));
+        }
+        if let Some(val) = filter.end_date.as_ref().filter(|v| !v.is_empty()) {
+            query.push_str(" AND COALESCE(j.production_date, date(j.start_datetime)) <= date(?)");
+            params_vec.push(Box::new(val.clone()));
+        }
+        for (column, value) in [
+            ("j.machine_id", &filter.machine_id),
+            ("COALESCE(j.section_id, m.section_id)", &filter.section_id),
+        ] {
+            if let Some(id) = value.as_ref().and_then(|v| v.parse::<i32>().ok()) {
+                query.push_str(&format!(" AND {} = ?", column));
+                params_vec.push(Box::new(id));
+            }
+        }
+        if let Some(val) = filter
+            .production_order
+            .as_ref()
+            .filter(|v| !v.trim().is_empty())
+        {
+            query.push_str(" AND j.production_order = ?");
+            params_vec.push(Box::new(val.trim().to_string()));
+        }
+        query.push_str(" ORDER BY j.start_datetime, j.id");
+
+        let mut stmt = conn.prepare(&query)?;
+        let mut jobs = stmt
+            .query_map(
+                rusqlite::params_from_iter(params_vec.iter().map(|p| p.as_ref())),
+                |row| job_from_row(row, tolerance_pct),
+            )?
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut orders: BTreeMap<String, OrderBalance> = BTreeMap::new();
+        let mut total = Balance::default();
+        for job in &jobs {
+            total.add(&job.balance);
+            let Some(po) = job.production_order.clone().filter(|po| !po.is_empty()) else {
+                continue;
+            };
+            let order = orders.entry(po.clone()).or_insert_with(|| OrderBalance {
+                production_order: po,
+                jobs: 0,
+                complete: true,
+                balance: Balance::default(),
+                out_of_tolerance: false,
+            });
+            order.jobs += 1;
+            order.complete &= job.complete;
+            order.out_of_tolerance |= job.out_of_tolerance;
+            order.balance.add(&job.balance);
+        }
+        let mut orders: Vec<OrderBalance> = orders.into_values().collect();
+        orders.iter_mut().for_each(|o| o.balance.finish());
+        total.finish();
+
+        let flagged = jobs.iter().filter(|j| j.out_of_tolerance).count() as i32;
+        if filter.flagged.as_deref() == Some("true") {
+            jobs.retain(|j| j.out_of_tolerance);
+            orders.retain(|o| o.out_of_tolerance);
+        }
+        Ok(MassBalanceReport {
+            tolerance_pct,
+            jobs,
+            orders,
+            total,
+            flagged,
+        })
+    }
+
+    pub fn job_detail(conn: &Connection, query: &MassBalanceJobQuery) -> Result<MassBalanceDetail> {
+        let tolerance_pct = tolerance(query.tolerance.as_deref())?;
+        let job = conn.query_row(
+            &format!("{} AND j.id = ?1", JOB_SELECT),
+            params![query.id],
+            |row| job_from_row(row, tolerance_pct),
+        )?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, batch, material_number, CAST(start_weight AS TEXT), CAST(consumed_weight AS REAL) \
+             FROM input_rolls WHERE job_id = ?1 ORDER BY id",
+        )?;
+        let inputs = stmt
+            .query_map(params![query.id], |row| {
+                Ok(BalanceInputRoll {
+                    id: row.get(0)?,
+                    batch: row.get(1)?,
+                    material_number: row.get(2)?,
+                    start_weight: row.get(3)?,
+                    consumed_weight: row.get(4)?,
+                })
+            })?
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT o.id, o.output_batch, CAST(o.final_meter AS REAL), CAST(o.final_weight AS REAL), o.qa_status \
+             FROM output_rolls o JOIN input_rolls ir ON o.input_roll_id = ir.id \
+             WHERE ir.job_id = ?1 ORDER BY o.id",
+        )?;
+        let outputs = stmt
+            .query_map(params![query.id], |row| {
+                let qa_status: Option<String> = row.get(4)?;
+                Ok(BalanceOutputRoll {
+                    id: row.get(0)?,
+                    output_batch: row.get(1)?,
+                    final_meter: row.get(2)?,
+                    final_weight: row.get(3)?,
+                    counted: qa_status.as_deref() != Some("scrapped"),
+                    qa_status,
+                })
+            })?
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT sc.id, sc.time, st.name, CAST(sc.weight_kg AS REAL), sc.notes \
+             FROM scraps sc LEFT JOIN scrap_types st ON sc.scrap_type_id = st.id \
+             WHERE sc.job_id = ?1 ORDER BY sc.time, sc.id",
+        )?;
+        let scraps = stmt
+            .query_map(params![query.id], |row| {
+                Ok(BalanceScrap {
+                    id: row.get(0)?,
+                    time: row.get(1)?,
+                    scrap_type: row.get(2)?,
+                    weight_kg: row.get(3)?,
+                    notes: row.get(4)?,
+                })
+            })?
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(MassBalanceDetail {
+            tolerance_pct,
+            job,
+            inputs,
+            outputs,
+            scraps,
+        })
+    }
+}
+
+#[test]
+fn test_mass_balance_per_job_and_order() {
+    use crate::backend::models::{DispositionPayload, RollDisposition};
+
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::manager::migrations::migrate_up(&mut conn, None).unwrap();
+    conn.execute_batch(
+        "INSERT INTO users (id, full_name) VALUES (1, 'Operator');
+         INSERT INTO sections (id, name) VALUES (1, 'Printing'), (2, 'Lamination');
+         INSERT INTO machines (id, name, label, section_id) VALUES (1, 'Printer 1', 'P1', 1), (2, 'Laminator 1', 'L1', 2);
+         INSERT INTO shifts (id, name) VALUES (1, 'Day');
+         INSERT INTO scrap_types (id, name) VALUES (1, 'Edge trim');
+         INSERT INTO jobs (id, machine_id, shift_id, created_by, production_order, start_datetime, end_datetime) VALUES
+             (1, 1, 1, 1, '220012061', '2026-01-09 07:00:00', '2026-01-09 11:00:00'),
+             (2, 2, 1, 1, '220012061', '2026-01-09 12:00:00', '2026-01-09 15:00:00'),
+             (3, 1, 1, 1, '220012099', '2026-01-09 16:00:00', NULL);
+         INSERT INTO input_rolls (id, job_id, batch, created_by, start_weight, consumed_weight) VALUES
+             (1, 1, 'J23-612', 1, '520KG', 500), (2, 2, 'J23-613', 1, '400KG', 400), (3, 3, 'J23-614', 1, '300KG', NULL);
+         INSERT INTO output_rolls (id, input_roll_id, output_batch, final_meter, final_weight, flag_count, qa_status, created_by, from_input_batch) VALUES
+             (1, 1, 'A', 6000, 300, 0, NULL, 1, 'J23-612'), (2, 1, 'B', 3000, 180, 1, 'on_hold', 1, 'J23-612'), (3, 2, 'C', 8000, 350, 0, NULL, 1, 'J23-613');
+         INSERT INTO scraps (shift_id, time, scrap_type_id, weight_kg, created_by, job_id) VALUES (1, '2026-01-09 09:30:00', 1, 10, 1, 1);",
+    )
+    .unwrap();
+
+    // Scrap recorded without a job goes to the one the operator is running
+    let scrap = crate::backend::models::Scrap::create(
+        &conn,
+        &crate::backend::models::ScrapCreatePayload {
+            shift_id: 1,
+            time: "2026-01-09 13:00:00".to_string(),
+            scrap_type_id: 1,
+            weight_kg: 20.0,
+            notes: None,
+            job_id: None,
+        },
+        1,
+    )
+    .unwrap();
+    assert_eq!(scrap.job_id, Some(2));
+
+    // Scrapping roll B moves its 180 kg from output to scrap
+    RollDisposition::create(
+        &mut conn,
+        &DispositionPayload {
+            output_roll_id: 2,
+            action: "scrap".to_string(),
+            notes: "Misregister".to_string(),
+            scrap_type_id: Some(1),
+        },
+        1,
+    )
+    .unwrap();
+
+    let filter = |tolerance: Option<&str>| MassBalanceFilterPayload {
+        start_date: Some("2026-01-09".to_string()),
+        end_date: Some("2026-01-09".to_string()),
+        machine_id: None,
+        section_id: None,
+        production_order: None,
+        tolerance: tolerance.map(str::to_string),
+        flagged: None,
+    };
+    let report = MassBalanceReport::calculate(&conn, &filter(Some("3"))).unwrap();
+    assert_eq!(report.jobs.len(), 3);
+    let printing = &report.jobs[0];
+    assert_eq!(
+        (
+            printing.balance.input_kg,
+            printing.balance.output_kg,
+            printing.balance.scrap_kg
+        ),
+        (500.0, 300.0, 190.0)
+    );
+    assert_eq!(printing.balance.unaccounted_kg, 10.0);
+    assert_eq!(printing.balance.yield_pct, Some(60.0));
+    assert!(printing.complete && !printing.out_of_tolerance);
+
+    let laminating = &report.jobs[1];
+    assert_eq!(laminating.balance.unaccounted_kg, 30.0);
+    assert_eq!(laminating.balance.unaccounted_pct, Some(7.5));
+    assert!(laminating.out_of_tolerance);
+
+    // Still running with an unweighed input, so not judged yet
+    assert!(!report.jobs[2].complete && !report.jobs[2].out_of_tolerance);
+    assert_eq!(report.flagged, 1);
+
+    assert_eq!(report.orders.len(), 2);
+    let order = &report.orders[0];
+    assert_eq!(order.production_order, "220012061");
+    assert_eq!((order.jobs, order.balance.input_kg), (2, 900.0));
+    assert_eq!(order.balance.unaccounted_kg, 40.0);
+    assert!(order.out_of_tolerance);
+    assert_eq!(report.total.input_kg, 900.0);
+
+    let loose = MassBalanceReport::calculate(&conn, &filter(Some("10"))).unwrap();
+    assert_eq!(loose.flagged, 0);
+    assert!(MassBalanceReport::calculate(&conn, &filter(Some("lots"))).is_err());
+
+    let mut by_section = filter(None);
+    by_section.section_id = Some("2".to_string());
+    by_section.flagged = Some("true".to_string());
+    let flagged = MassBalanceReport::calculate(&conn, &by_section).unwrap();
+    assert_eq!(flagged.jobs.len(), 1);
+    assert_eq!(flagged.jobs[0].job_id, 2);
+
+    let detail = MassBalanceReport::job_detail(
+        &conn,
+        &MassBalanceJobQuery {
+            id: 1,
+            tolerance: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(detail.inputs.len(), 1);
+    assert_eq!(detail.outputs.len(), 2);
+    assert!(!detail.outputs[1].counted);
+    assert_eq!(detail.scraps.len(), 2);
+    assert_eq!(detail.scraps[0].scrap_type.as_deref(), Some("Edge trim"));
+}
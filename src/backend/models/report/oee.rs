@@ -0,0 +1,325 @@
+use chrono::Local;
+use rusqlite::{Connection, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Deserialize)]
+pub struct OeeFilterPayload {
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
+    /// Comma separated list of `machine`, `shift`, `day` and `section`; defaults to `machine`
+    pub group_by: Option<String>,
+    pub machine_id: Option<String>,
+    pub shift_id: Option<String>,
+    pub section_id: Option<String>,
+}
+
+/// Availability, performance and quality for one group of jobs. The ratios are `None` when
+/// there is nothing to divide by: no run time, no rated speed or no output.
+#[derive(Debug, Default, Serialize)]
+pub struct OeeRow {
+    pub machine_id: Option<i32>,
+    pub machine: Option<String>,
+    pub shift: Option<String>,
+    pub day: Option<String>,
+    pub section: Option<String>,
+    pub jobs: i32,
+    pub planned_minutes: f64,
+    pub downtime_minutes: f64,
+    pub run_minutes: f64,
+    pub meters: f64,
+    /// Meters the machines could have run at rated speed, for jobs whose machine has one
+    pub target_meters: f64,
+    pub good_meters: f64,
+    pub flagged_meters: f64,
+    pub availability: Option<f64>,
+    pub performance: Option<f64>,
+    pub quality: Option<f64>,
+    pub oee: Option<f64>,
+    #[serde(skip)]
+    rated_meters: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OeeReport {
+    pub group_by: Vec<String>,
+    pub rows: Vec<OeeRow>,
+    pub total: OeeRow,
+}
+
+struct JobRun {
+    id: i32,
+    machine_id: Option<i32>,
+    machine: Option<String>,
+    rated_speed: Option<f64>,
+    shift_id: Option<i32>,
+    shift: Option<String>,
+    section: Option<String>,
+    day: String,
+    created_by: Option<i32>,
+    start: f64,
+    end: f64,
+    good_meters: f64,
+    flagged_meters: f64,
+    downtime_minutes: f64,
+}
+
+const MINUTES_PER_DAY: f64 = 1440.0;
+const GROUPS: [&str; 4] = ["machine", "shift", "day", "section"];
+
+impl OeeRow {
+    fn add(&mut self, job: &JobRun) {
+        let planned = ((job.end - job.start) * MINUTES_PER_DAY).max(0.0);
+        let downtime = job.downtime_minutes.min(planned);
+        let run = planned - downtime;
+        let meters = job.good_meters + job.flagged_meters;
+        self.jobs += 1;
+        self.planned_minutes += planned;
+        self.downtime_minutes += downtime;
+        self.run_minutes += run;
+        self.meters += meters;
+        self.good_meters += job.good_meters;
+        self.flagged_meters += job.flagged_meters;
+        if let Some(speed) = job.rated_speed.filter(|s| *s > 0.0) {
+            self.target_meters += run * speed;
+            self.rated_meters += meters;
+        }
+    }
+
+    fn finish(&mut self) {
+        let ratio = |num: f64, den: f64| (den > 0.0).then(|| round(num / den));
+        self.planned_minutes = round(self.planned_minutes);
+        self.downtime_minutes = round(self.downtime_minutes);
+        self.run_minutes = round(self.run_minutes);
+        self.target_meters = round(self.target_meters);
+        self.availability = ratio(self.run_minutes, self.planned_minutes);
+        self.performance = ratio(self.rated_meters, self.target_meters);
+        self.quality = ratio(self.good_meters, self.meters);
+        self.oee = match (self.availability, self.performance, self.quality) {
+            (Some(a), Some(p), Some(q)) => Some(round(a * p * q)),
+            _ => None,
+        };
+    }
+}
+
+fn round(value: f64) -> f64 {
+    (value * 10000.0).round() / 10000.0
+}
+
+impl OeeReport {
+    /// Planned time is the span of each job (to now for running jobs) and availability is
+    /// what is left after the job's downtime. Performance compares metres produced with the
+    /// machine's rated speed over the run time, and quality is unflagged metres over all
+    /// metres.
+    pub fn calculate(conn: &Connection, filter: &OeeFilterPayload) -> Result<Self> {
+        let group_by: Vec<String> = filter
+            .group_by
+            .as_deref()
+            .unwrap_or("machine")
+            .split(',')
+            .map(|g| g.trim().to_lowercase())
+            .filter(|g| !g.is_empty())
+            .collect();
+        if let Some(bad) = group_by.iter().find(|g| !GROUPS.contains(&g.as_str())) {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Cannot group OEE by '{}'",
+                bad
+            )));
+        }
+
+        let mut jobs = load_jobs(conn, filter)?;
+        charge_downtimes(conn, &mut jobs)?;
+
+        let mut groups: BTreeMap<Vec<String>, OeeRow> = BTreeMap::new();
+        let mut total = OeeRow::default();
+        for job in &jobs {
+            let key: Vec<String> = group_by
+                .iter()
+                .map(|g| match g.as_str() {
+                    "machine" => job.machine.clone().unwrap_or_default(),
+                    "shift" => job.shift.clone().unwrap_or_default(),
+                    "day" => job.day.clone(),
+                    _ => job.section.clone().unwrap_or_default(),
+                })
+                .collect();
+            let row = groups.entry(key).or_insert_with(|| {
+                let by = |name: &str| group_by.iter().any(|g| g == name);
+                OeeRow {
+                    machine_id: job.machine_id.filter(|_| by("machine")),
+                    machine: job.machine.clone().filter(|_| by("machine")),
+                    shift: job.shift.clone().filter(|_| by("shift")),
+                    day: by("day").then(|| job.day.clone()),
+                    section: job.section.clone().filter(|_| by("section")),
+                    ..Default::default()
+                }
+            });
+            row.add(job);
+            total.add(job);
+        }
+
+        let mut rows: Vec<OeeRow> = groups.into_values().collect();
+        rows.iter_mut().for_each(OeeRow::finish);
+        total.finish();
+        Ok(OeeReport {
+            group_by,
+            rows,
+            total,
+        })
+    }
+}
+
+fn load_jobs(conn: &Connection, filter: &OeeFilterPayload) -> Result<Vec<JobRun>> {
+    let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let mut query = "SELECT j.id, j.machine_id, m.name, CAST(m.rated_speed AS REAL), j.shift_id, sh.name, s.name, COALESCE(j.production_date, date(j.start_datetime)), j.created_by, \
+         julianday(j.start_datetime), julianday(COALESCE(j.end_datetime, ?)), \
+         (SELECT COALESCE(SUM(CAST(o.final_meter AS REAL)), 0) FROM output_rolls o JOIN input_rolls ir ON o.input_roll_id = ir.id WHERE ir.job_id = j.id AND COALESCE(o.flag_count, 0) = 0), \
+         (SELECT COALESCE(SUM(CAST(o.final_meter AS REAL)), 0) FROM output_rolls o JOIN input_rolls ir ON o.input_roll_id = ir.id WHERE ir.job_id = j.id AND COALESCE(o.flag_count, 0) > 0) \
+         FROM jobs j \
+         LEFT JOIN machines m ON j.machine_id = m.id \
+         LEFT JOIN shifts sh ON j.shift_id = sh.id \
+         LEFT JOIN sections s ON COALESCE(j.section_id, m.section_id) = s.id \
+         WHERE j.start_datetime IS NOT NULL"
+        .to_string();
+    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(now)];
+
+    if let Some(val) = filter.start_date.as_ref().filter(|v| !v.is_empty()) {
+        query.push_str(" AND COALESCE(j.production_date, date(j.start_datetime)) >= date(?)");
+        params_vec.push(Box::new(val.clone()));
+    }
+    if let Some(val) = filter.end_date.as_ref().filter(|v| !v.is_empty()) {
+        query.push_str(" AND COALESCE(j.production_date, date(j.start_datetime)) <= date(?)");
+        params_vec.push(Box::new(val.clone()));
+    }
+    for (column, value) in [
+        ("j.machine_id", &filter.machine_id),
+        ("j.shift_id", &filter.shift_id),
+        ("COALESCE(j.section_id, m.section_id)", &filter.section_id),
+    ] {
+        if let Some(id) = value.as_ref().and_then(|v| v.parse::<i32>().ok()) {
+            query.push_str(&format!(" AND {} = ?", column));
+            params_vec.push(Box::new(id));
+        }
+    }
+    query.push_str(" ORDER BY j.start_datetime, j.id");
+
+    let mut stmt = conn.prepare(&query)?;
+    stmt.query_map(
+        rusqlite::params_from_iter(params_vec.iter().map(|p| p.as_ref())),
+        |row| {
+            Ok(JobRun {
+                id: row.get(0)?,
+                machine_id: row.get(1)?,
+                machine: row.get(2)?,
+                rated_speed: row.get(3)?,
+                shift_id: row.get(4)?,
+                shift: row.get(5)?,
+                section: row.get(6)?,
+                day: row.get(7)?,
+                created_by: row.get(8)?,
+                start: row.get(9)?,
+                end: row.get(10)?,
+                good_meters: row.get(11)?,
+                flagged_meters: row.get(12)?,
+                downtime_minutes: 0.0,
+            })
+        },
+    )?
+    .collect()
+}
+
+/// Adds each downtime to its job, or else to the job running on its machine when the stop
+/// began. Downtimes logged before they were tied to machines go to the job of the same
+/// operator and shift. Open downtimes count up to now.
+fn charge_downtimes(conn: &Connection, jobs: &mut [JobRun]) -> Result<()> {
+    let (Some(first), Some(last)) = (
+        jobs.iter().map(|j| j.start).reduce(f64::min),
+        jobs.iter().map(|j| j.end).reduce(f64::max),
+    ) else {
+        return Ok(());
+    };
+    let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let mut stmt = conn.prepare(
+        "SELECT job_id, machine_id, shift_id, created_by, julianday(start_time), \
+         COALESCE(duration_minutes, (julianday(?3) - julianday(start_time)) * 1440, 0) \
+         FROM downtimes WHERE julianday(start_time) BETWEEN ?1 AND ?2",
+    )?;
+    let downtimes = stmt
+        .query_map(rusqlite::params![first, last, now], |row| {
+            Ok((
+                row.get::<_, Option<i32>>(0)?,
+                row.get::<_, Option<i32>>(1)?,
+                row.get::<_, Option<i32>>(2)?,
+                row.get::<_, Option<i32>>(3)?,
+                row.get::<_, f64>(4)?,
+                row.get::<_, f64>(5)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    for (job_id, machine_id, shift_id, created_by, at, minutes) in downtimes {
+        let running = |j: &&mut JobRun| j.start <= at && at <= j.end;
+        let job = match (job_id, machine_id) {
+            (Some(job_id), _) => jobs.iter_mut().find(|j| j.id == job_id),
+            (None, Some(machine_id)) => jobs
+                .iter_mut()
+                .filter(running)
+                .find(|j| j.machine_id == Some(machine_id)),
+            (None, None) => jobs
+                .iter_mut()
+                .filter(running)
+                .find(|j| j.shift_id == shift_id && j.created_by == created_by),
+        };
+        if let Some(job) = job {
+            job.downtime_minutes += minutes.max(0.0);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_oee_by_machine_and_shift() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::manager::migrations::migrate_up(&mut conn, None).unwrap();
+    conn.execute_batch(
+        "INSERT INTO users (id, full_name) VALUES (1, 'Operator');
+         INSERT INTO sections (id, name) VALUES (1, 'Printing');
+         INSERT INTO machines (id, name, label, section_id, rated_speed) VALUES (1, 'Printer 1', 'P1', 1, 100.0), (2, 'Printer 2', 'P2', 1, NULL);
+         INSERT INTO shifts (id, name) VALUES (1, 'Day'), (2, 'Night');
+         INSERT INTO jobs (id, machine_id, shift_id, created_by, section_id, start_datetime, end_datetime) VALUES
+             (1, 1, 1, 1, 1, '2026-01-09 07:00:00', '2026-01-09 11:00:00'),
+             (2, 2, 2, 1, 1, '2026-01-09 19:00:00', '2026-01-09 21:00:00');
+         INSERT INTO input_rolls (id, job_id, batch, created_by, start_weight) VALUES (1, 1, 'J23-612', 1, '520KG'), (2, 2, 'J23-613', 1, '520KG');
+         INSERT INTO output_rolls (input_roll_id, output_batch, final_meter, flag_count) VALUES (1, 'A', 9000, 0), (1, 'B', 1800, 1), (2, 'C', 6000, 0);
+         INSERT INTO downtimes (shift_id, start_time, end_time, duration_minutes, created_by) VALUES
+             (1, '2026-01-09T08:00', '2026-01-09T09:00', 60, 1),
+             (1, '2026-01-10T08:00', '2026-01-10T09:00', 45, 1);",
+    )
+    .unwrap();
+
+    let filter = |group_by: &str| OeeFilterPayload {
+        start_date: Some("2026-01-09".to_string()),
+        end_date: Some("2026-01-09".to_string()),
+        group_by: Some(group_by.to_string()),
+        machine_id: None,
+        shift_id: None,
+        section_id: None,
+    };
+    let report = OeeReport::calculate(&conn, &filter("machine")).unwrap();
+    assert_eq!(report.rows.len(), 2);
+    let printer = &report.rows[0];
+    assert_eq!(printer.machine.as_deref(), Some("Printer 1"));
+    assert_eq!(printer.planned_minutes, 240.0);
+    assert_eq!(printer.downtime_minutes, 60.0);
+    assert_eq!(printer.availability, Some(0.75));
+    assert_eq!(printer.performance, Some(0.6));
+    assert_eq!(printer.quality, Some(round(9000.0 / 10800.0)));
+    assert!(report.rows[1].performance.is_none());
+    assert!(report.rows[1].oee.is_none());
+    assert_eq!(report.total.meters, 16800.0);
+
+    let by_day_shift = OeeReport::calculate(&conn, &filter("day,shift")).unwrap();
+    assert_eq!(by_day_shift.rows.len(), 2);
+    assert_eq!(by_day_shift.rows[0].day.as_deref(), Some("2026-01-09"));
+    assert!(by_day_shift.rows[0].machine.is_none());
+    assert!(OeeReport::calculate(&conn, &filter("operator")).is_err());
+}
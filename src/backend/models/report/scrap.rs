@@ -1,7 +1,7 @@
 use chrono::Local;
-use rusqlite::{params, Connection, Result};
+use rusqlite::{params, Connection, OptionalExtension, Result};
 use serde::{Serialize, Deserialize};
-use crate::backend::models::FilterResponse;
+use crate::backend::models::{AuditLog, FilterResponse};
 
 #[derive(Debug, Serialize)]
 pub struct Scrap {
@@ -14,6 +14,8 @@ pub struct Scrap {
     pub created_by: i32,
     pub created_at: String,
     pub updated_at: String,
+    /// Job the scrap came from, for the mass balance
+    pub job_id: Option<i32>,
 }
 
 #[derive(Deserialize)]
@@ -23,6 +25,9 @@ pub struct ScrapCreatePayload {
     pub scrap_type_id: i32,
     pub weight_kg: f64,
     pub notes: Option<String>,
+    /// Defaults to the job the operator was running on that shift at `time`
+    #[serde(default)]
+    pub job_id: Option<i32>,
 }
 
 #[derive(Deserialize)]
@@ -33,6 +38,7 @@ pub struct ScrapPayload {
     pub scrap_type_id: Option<i32>,
     pub weight_kg: Option<f64>,
     pub notes: Option<String>,
+    pub job_id: Option<i32>,
 }
 
 #[derive(Deserialize)]
@@ -46,15 +52,31 @@ pub struct ScrapFilterPayload {
     pub page: Option<String>,
 }
 
+/// The job the operator was running on the shift at the given time.
+pub(crate) fn operator_job(conn: &Connection, shift_id: i32, user_id: i32, at: &str) -> Result<Option<i32>> {
+    conn.query_row(
+        "SELECT id FROM jobs
+         WHERE shift_id = ?1 AND created_by = ?2 AND julianday(start_datetime) <= julianday(?3)
+         AND (end_datetime IS NULL OR julianday(end_datetime) >= julianday(?3))
+         ORDER BY start_datetime DESC, id DESC LIMIT 1",
+        params![shift_id, user_id, at],
+        |row| row.get(0),
+    ).optional()
+}
+
 impl Scrap {
     pub fn create(conn: &Connection, data: &ScrapCreatePayload, user_id: i32) -> Result<Self> {
         let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let job_id = match data.job_id {
+            Some(job_id) => Some(job_id),
+            None => operator_job(conn, data.shift_id, user_id, &data.time)?,
+        };
         conn.execute(
-            "INSERT INTO scraps (shift_id, time, scrap_type_id, weight_kg, notes, created_by, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-            params![data.shift_id, data.time, data.scrap_type_id, data.weight_kg, data.notes, user_id, now, now],
+            "INSERT INTO scraps (shift_id, time, scrap_type_id, weight_kg, notes, created_by, created_at, updated_at, job_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![data.shift_id, data.time, data.scrap_type_id, data.weight_kg, data.notes, user_id, now, now, job_id],
         )?;
         let id = conn.last_insert_rowid() as i32;
-        Ok(Scrap {
+        let record = Scrap {
             id,
             shift_id: data.shift_id,
             time: data.time.clone(),
@@ -64,10 +86,14 @@ impl Scrap {
             created_by: user_id,
             created_at: now.clone(),
             updated_at: now.clone(),
-        })
+            job_id,
+        };
+        AuditLog::created(conn, Some(user_id), "scraps", id, &record)?;
+        Ok(record)
     }
 
-    pub fn update(&mut self, conn: &Connection, data: &ScrapPayload) -> Result<()> {
+    pub fn update(&mut self, conn: &Connection, data: &ScrapPayload, user_id: Option<i32>) -> Result<()> {
+        let before = AuditLog::snapshot(self);
         let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
         if let Some(shift_id) = data.shift_id {
             conn.execute("UPDATE scraps SET shift_id = ?1 WHERE id = ?2", params![shift_id, self.id])?;
@@ -89,13 +115,19 @@ impl Scrap {
             conn.execute("UPDATE scraps SET notes = ?1 WHERE id = ?2", params![notes, self.id])?;
             self.notes = Some(notes.clone());
         }
+        if let Some(job_id) = data.job_id {
+            conn.execute("UPDATE scraps SET job_id = ?1 WHERE id = ?2", params![job_id, self.id])?;
+            self.job_id = Some(job_id);
+        }
         conn.execute("UPDATE scraps SET updated_at = ?1 WHERE id = ?2", params![now, self.id])?;
         self.updated_at = now;
+        AuditLog::updated(conn, user_id, "scraps", self.id, &before, self)?;
         Ok(())
     }
 
-    pub fn delete(&self, conn: &Connection) -> Result<()> {
+    pub fn delete(&self, conn: &Connection, user_id: Option<i32>) -> Result<()> {
         conn.execute("DELETE FROM scraps WHERE id = ?1", params![self.id])?;
+        AuditLog::deleted(conn, user_id, "scraps", self.id, self)?;
         Ok(())
     }
 
@@ -111,6 +143,7 @@ impl Scrap {
             created_by: row.get(6)?,
             created_at: row.get(7)?,
             updated_at: row.get(8)?,
+            job_id: row.get(9)?,
         }))
     }
 
@@ -126,6 +159,7 @@ impl Scrap {
             created_by: row.get(6)?,
             created_at: row.get(7)?,
             updated_at: row.get(8)?,
+            job_id: row.get(9)?,
         }))?.collect::<Result<Vec<_>, _>>()?;
         Ok(scraps)
     }
@@ -217,6 +251,7 @@ impl Scrap {
                 created_by: row.get(6)?,
                 created_at: row.get(7)?,
                 updated_at: row.get(8)?,
+                job_id: row.get(9)?,
             })
         })?;
 
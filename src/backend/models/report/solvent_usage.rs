@@ -1,7 +1,7 @@
 use chrono::Local;
 use rusqlite::{params, Connection, Result};
 use serde::{Serialize, Deserialize};
-use crate::backend::models::FilterResponse;
+use crate::backend::models::{AuditLog, FilterResponse};
 
 #[derive(Debug, Serialize)]
 pub struct ActualSolventUsage {
@@ -48,7 +48,7 @@ impl ActualSolventUsage {
             params![data.shift_id, data.solvent_type_id, data.kgs_issued, user_id, now, now],
         )?;
         let id = conn.last_insert_rowid() as i32;
-        Ok(ActualSolventUsage {
+        let record = ActualSolventUsage {
             id,
             shift_id: data.shift_id,
             solvent_type_id: data.solvent_type_id,
@@ -56,10 +56,13 @@ impl ActualSolventUsage {
             created_by: user_id,
             created_at: now.clone(),
             updated_at: now.clone(),
-        })
+        };
+        AuditLog::created(conn, Some(user_id), "solvent_usages", id, &record)?;
+        Ok(record)
     }
 
-    pub fn update(&mut self, conn: &Connection, data: &ActualSolventUsagePayload) -> Result<()> {
+    pub fn update(&mut self, conn: &Connection, data: &ActualSolventUsagePayload, user_id: Option<i32>) -> Result<()> {
+        let before = AuditLog::snapshot(self);
         let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
         if let Some(shift_id) = data.shift_id {
             conn.execute("UPDATE solvent_usages SET shift_id = ?1 WHERE id = ?2", params![shift_id, self.id])?;
@@ -75,11 +78,13 @@ impl ActualSolventUsage {
         }
         conn.execute("UPDATE solvent_usages SET updated_at = ?1 WHERE id = ?2", params![now, self.id])?;
         self.updated_at = now;
+        AuditLog::updated(conn, user_id, "solvent_usages", self.id, &before, self)?;
         Ok(())
     }
 
-    pub fn delete(&self, conn: &Connection) -> Result<()> {
+    pub fn delete(&self, conn: &Connection, user_id: Option<i32>) -> Result<()> {
         conn.execute("DELETE FROM solvent_usages WHERE id = ?1", params![self.id])?;
+        AuditLog::deleted(conn, user_id, "solvent_usages", self.id, self)?;
         Ok(())
     }
 
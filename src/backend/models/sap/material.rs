@@ -1,6 +1,6 @@
 use rusqlite::{params, Connection, Result};
 use serde::{Serialize, Deserialize};
-use crate::backend::models::FilterResponse;
+use crate::backend::models::{AuditLog, FilterResponse};
 use std::collections::HashMap;
 
 #[derive(Debug, Serialize)]
@@ -40,26 +40,31 @@ pub struct MaterialFilterPayload {
 }
 
 impl Material {
-    pub fn create(conn: &Connection, data: &MaterialCreatePayload) -> Result<Self> {
+    pub fn create(conn: &Connection, data: &MaterialCreatePayload, user_id: Option<i32>) -> Result<Self> {
         conn.execute(
             "INSERT INTO materials (code, key, value, created_at) VALUES (?1, 'Loading...', 'Loading...', datetime('now'))",
             params![data.code],
         )?;
         let id = conn.last_insert_rowid() as i32;
         
-        Self::find_by_id(conn, id)
+        let material = Self::find_by_id(conn, id)?;
+        AuditLog::created(conn, user_id, "materials", id, &material)?;
+        Ok(material)
     }
 
-    pub fn update(&mut self, conn: &Connection, data: &MaterialPayload) -> Result<()> {
+    pub fn update(&mut self, conn: &Connection, data: &MaterialPayload, user_id: Option<i32>) -> Result<()> {
+        let before = AuditLog::snapshot(self);
         if let Some(code) = &data.code {
             conn.execute("UPDATE materials SET code = ?1 WHERE id = ?2", params![code, self.id])?;
             self.code = code.clone();
         }
+        AuditLog::updated(conn, user_id, "materials", self.id, &before, self)?;
         Ok(())
     }
 
-    pub fn delete(&self, conn: &Connection) -> Result<()> {
+    pub fn delete(&self, conn: &Connection, user_id: Option<i32>) -> Result<()> {
         conn.execute("DELETE FROM materials WHERE id = ?1", params![self.id])?;
+        AuditLog::deleted(conn, user_id, "materials", self.id, self)?;
         Ok(())
     }
 
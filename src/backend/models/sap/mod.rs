@@ -1,5 +1,9 @@
 pub mod material;
+pub mod outbox;
 pub mod process_order;
+pub mod reversal;
 
 pub use material::*;
+pub use outbox::*;
 pub use process_order::*;
+pub use reversal::*;
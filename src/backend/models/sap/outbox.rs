@@ -0,0 +1,441 @@
+use std::env;
+
+use chrono::{Duration, Local};
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::models::{AuditLog, FilterResponse, SapReversal};
+use crate::sap::{PostingError, SapPosting};
+
+const DEFAULT_MAX_ATTEMPTS: i32 = 8;
+const BASE_BACKOFF_SECS: i64 = 30;
+const MAX_BACKOFF_SECS: i64 = 3600;
+// A message left in `posting` this long was interrupted mid-request
+const STALE_POSTING_MINUTES: i64 = 10;
+
+#[derive(Debug, Serialize)]
+pub struct SapOutbox {
+    pub id: i32,
+    pub kind: String,
+    pub idempotency_key: String,
+    pub payload: serde_json::Value,
+    pub status: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub document: Option<String>,
+    pub ref_model: Option<String>,
+    pub ref_id: Option<i32>,
+    pub created_by: Option<i32>,
+    pub next_attempt_at: String,
+    pub posted_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Deserialize)]
+pub struct SapOutboxFilterPayload {
+    pub status: Option<String>,
+    pub kind: Option<String>,
+    pub idempotency_key: Option<String>,
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
+    pub per_page: Option<String>,
+    pub page: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SapOutboxStats {
+    pub pending: i32,
+    pub posting: i32,
+    pub posted: i32,
+    pub failed: i32,
+    pub cancelled: i32,
+}
+
+fn now() -> String {
+    Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+fn max_attempts() -> i32 {
+    env::var("SAP_OUTBOX_MAX_ATTEMPTS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_ATTEMPTS)
+}
+
+const SELECT: &str = "SELECT id, kind, idempotency_key, payload, status, attempts, last_error, document, ref_model, ref_id, created_by, next_attempt_at, posted_at, created_at, updated_at FROM sap_outbox";
+
+impl SapOutbox {
+    fn from_row(row: &rusqlite::Row) -> Result<Self> {
+        let payload: String = row.get(3)?;
+        Ok(SapOutbox {
+            id: row.get(0)?,
+            kind: row.get(1)?,
+            idempotency_key: row.get(2)?,
+            payload: serde_json::from_str(&payload).unwrap_or(serde_json::Value::Null),
+            status: row.get(4)?,
+            attempts: row.get(5)?,
+            last_error: row.get(6)?,
+            document: row.get(7)?,
+            ref_model: row.get(8)?,
+            ref_id: row.get(9)?,
+            created_by: row.get(10)?,
+            next_attempt_at: row.get(11)?,
+            posted_at: row.get(12)?,
+            created_at: row.get(13)?,
+            updated_at: row.get(14)?,
+        })
+    }
+
+    pub fn find_by_id(conn: &Connection, id: i32) -> Result<Self> {
+        conn.query_row(
+            &format!("{} WHERE id = ?1", SELECT),
+            params![id],
+            Self::from_row,
+        )
+    }
+
+    pub fn find_by_key(conn: &Connection, key: &str) -> Result<Self> {
+        conn.query_row(
+            &format!("{} WHERE idempotency_key = ?1", SELECT),
+            params![key],
+            Self::from_row,
+        )
+    }
+
+    pub fn posting(&self) -> Result<SapPosting> {
+        serde_json::from_value(self.payload.clone())
+            .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))
+    }
+
+    /// Queues a posting under `key`. Enqueuing the same key again only refreshes the payload of
+    /// a message still waiting as `pending`; posted and in-flight messages are left alone, so a
+    /// repeated operator action can never post twice, and failed or cancelled messages only go
+    /// back to `pending` through [`SapOutbox::retry`].
+    pub fn enqueue(
+        conn: &Connection,
+        key: &str,
+        posting: &SapPosting,
+        reference: Option<(&str, i32)>,
+        user_id: Option<i32>,
+    ) -> Result<Self> {
+        let now = now();
+        let payload = serde_json::to_string(posting)
+            .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+        conn.execute(
+            "INSERT INTO sap_outbox (kind, idempotency_key, payload, status, attempts, ref_model, ref_id, created_by, next_attempt_at, created_at, updated_at) \
+             VALUES (?1, ?2, ?3, 'pending', 0, ?4, ?5, ?6, ?7, ?7, ?7) \
+             ON CONFLICT(idempotency_key) DO UPDATE SET payload = excluded.payload, status = 'pending', attempts = 0, last_error = NULL, next_attempt_at = excluded.next_attempt_at, updated_at = excluded.updated_at \
+             WHERE sap_outbox.status = 'pending'",
+            params![posting.kind(), key, payload, reference.map(|r| r.0), reference.map(|r| r.1), user_id, now],
+        )?;
+        Self::find_by_key(conn, key)
+    }
+
+    /// Moves a due message to `posting` so no other worker picks it up. Returns `None` when the
+    /// message is not due or was claimed elsewhere.
+    pub fn claim(conn: &Connection, id: i32) -> Result<Option<Self>> {
+        let claimed = conn.execute(
+            "UPDATE sap_outbox SET status = 'posting', attempts = attempts + 1, updated_at = ?1 WHERE id = ?2 AND status = 'pending'",
+            params![now(), id],
+        )?;
+        if claimed == 0 {
+            return Ok(None);
+        }
+        Self::find_by_id(conn, id).optional()
+    }
+
+    pub fn due_ids(conn: &Connection, limit: i32) -> Result<Vec<i32>> {
+        let mut stmt = conn.prepare(
+            "SELECT id FROM sap_outbox WHERE status = 'pending' AND next_attempt_at <= ?1 ORDER BY next_attempt_at, id LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![now(), limit], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    /// Records the outcome of a claimed message. Rejected postings are retried with exponential
+    /// backoff until `SAP_OUTBOX_MAX_ATTEMPTS` is reached; one SAP may have booked fails at
+    /// once, so it is only sent again after someone checked SAP and retried it.
+    pub fn complete(
+        &mut self,
+        conn: &Connection,
+        result: std::result::Result<Option<String>, PostingError>,
+    ) -> Result<()> {
+        let now = now();
+        match result {
+            Ok(document) => {
+                conn.execute(
+                    "UPDATE sap_outbox SET status = 'posted', document = ?1, last_error = NULL, posted_at = ?2, updated_at = ?2 WHERE id = ?3",
+                    params![document, now, self.id],
+                )?;
+                match (self.ref_model.as_deref(), self.ref_id, &document) {
+                    (Some("input_rolls"), Some(ref_id), Some(document)) => {
+                        conn.execute(
+                            "UPDATE input_rolls SET material_document = ?1, updated_at = ?2 WHERE id = ?3",
+                            params![document, now, ref_id],
+                        )?;
+                    }
+                    // The transfer moved the remnant to the return store
+                    (Some("remnants"), Some(ref_id), Some(document)) => {
+                        conn.execute(
+                            "UPDATE remnants SET return_document = ?1, storage_location = COALESCE(return_location, storage_location) WHERE id = ?2",
+                            params![document, ref_id],
+                        )?;
+                    }
+                    // Roll reversals come back without a document
+                    (Some("sap_reversals"), Some(ref_id), document) => {
+                        SapReversal::reversed(conn, ref_id, document.as_deref())?;
+                    }
+                    _ => {}
+                }
+                self.status = "posted".into();
+                self.document = document;
+                self.last_error = None;
+                self.posted_at = Some(now.clone());
+            }
+            Err(error) => {
+                let (status, error) = match error {
+                    PostingError::Unconfirmed(e) => (
+                        "failed",
+                        format!(
+                            "{}; SAP may have posted it, check for header text {} before retrying",
+                            e, self.idempotency_key
+                        ),
+                    ),
+                    PostingError::Rejected(e) if self.attempts >= max_attempts() => ("failed", e),
+                    PostingError::Rejected(e) => ("pending", e),
+                };
+                let delay =
+                    (BASE_BACKOFF_SECS << (self.attempts - 1).clamp(0, 16)).min(MAX_BACKOFF_SECS);
+                let next_attempt_at = (Local::now() + Duration::seconds(delay))
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string();
+                conn.execute(
+                    "UPDATE sap_outbox SET status = ?1, last_error = ?2, next_attempt_at = ?3, updated_at = ?4 WHERE id = ?5",
+                    params![status, error, next_attempt_at, now, self.id],
+                )?;
+                self.status = status.into();
+                self.last_error = Some(error);
+                self.next_attempt_at = next_attempt_at;
+            }
+        }
+        self.updated_at = now;
+        Ok(())
+    }
+
+    /// Fails messages stuck in `posting` after a crash or restart. SAP may or may not have
+    /// received them, so they need a manual check before being retried.
+    pub fn recover_interrupted(conn: &Connection) -> Result<usize> {
+        let cutoff = (Local::now() - Duration::minutes(STALE_POSTING_MINUTES))
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string();
+        conn.execute(
+            "UPDATE sap_outbox SET status = 'failed', last_error = 'Interrupted while posting, check SAP for header text ' || idempotency_key || ' before retrying', updated_at = ?1 WHERE status = 'posting' AND updated_at <= ?2",
+            params![now(), cutoff],
+        )
+    }
+
+    pub fn retry(&mut self, conn: &Connection, user_id: Option<i32>) -> Result<()> {
+        if !matches!(self.status.as_str(), "failed" | "cancelled" | "pending") {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Cannot retry a {} message",
+                self.status
+            )));
+        }
+        let before = AuditLog::snapshot(self);
+        let now = now();
+        conn.execute(
+            "UPDATE sap_outbox SET status = 'pending', attempts = 0, next_attempt_at = ?1, updated_at = ?1 WHERE id = ?2",
+            params![now, self.id],
+        )?;
+        self.status = "pending".into();
+        self.attempts = 0;
+        self.next_attempt_at = now.clone();
+        self.updated_at = now;
+        AuditLog::updated(conn, user_id, "sap_outbox", self.id, &before, self)
+    }
+
+    /// Replaces the payload of a failed or cancelled message without queuing it again, so a
+    /// later [`SapOutbox::retry`] posts the corrected values.
+    pub fn replace_payload(
+        &mut self,
+        conn: &Connection,
+        posting: &SapPosting,
+        user_id: Option<i32>,
+    ) -> Result<()> {
+        if !matches!(self.status.as_str(), "failed" | "cancelled") {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Cannot replace the payload of a {} message",
+                self.status
+            )));
+        }
+        let before = AuditLog::snapshot(self);
+        let payload = serde_json::to_value(posting)
+            .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+        let now = now();
+        conn.execute(
+            "UPDATE sap_outbox SET payload = ?1, updated_at = ?2 WHERE id = ?3",
+            params![payload.to_string(), now, self.id],
+        )?;
+        self.payload = payload;
+        self.updated_at = now;
+        AuditLog::updated(conn, user_id, "sap_outbox", self.id, &before, self)
+    }
+
+    pub fn cancel(&mut self, conn: &Connection, user_id: Option<i32>) -> Result<()> {
+        if !matches!(self.status.as_str(), "failed" | "pending") {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Cannot cancel a {} message",
+                self.status
+            )));
+        }
+        let before = AuditLog::snapshot(self);
+        let now = now();
+        conn.execute(
+            "UPDATE sap_outbox SET status = 'cancelled', updated_at = ?1 WHERE id = ?2",
+            params![now, self.id],
+        )?;
+        self.status = "cancelled".into();
+        self.updated_at = now;
+        AuditLog::updated(conn, user_id, "sap_outbox", self.id, &before, self)
+    }
+
+    pub fn stats(conn: &Connection) -> Result<SapOutboxStats> {
+        let count = |status: &str| -> Result<i32> {
+            conn.query_row(
+                "SELECT COUNT(*) FROM sap_outbox WHERE status = ?1",
+                params![status],
+                |row| row.get(0),
+            )
+        };
+        Ok(SapOutboxStats {
+            pending: count("pending")?,
+            posting: count("posting")?,
+            posted: count("posted")?,
+            failed: count("failed")?,
+            cancelled: count("cancelled")?,
+        })
+    }
+
+    pub fn filter(
+        conn: &Connection,
+        filter: &SapOutboxFilterPayload,
+    ) -> Result<FilterResponse<Self>> {
+        let mut count_query = "SELECT COUNT(*) FROM sap_outbox WHERE 1=1".to_string();
+        let mut data_query = format!("{} WHERE 1=1", SELECT);
+        let mut params_vec: Vec<&dyn rusqlite::ToSql> = vec![];
+
+        let mut values: Vec<String> = vec![];
+        let mut pages: Vec<i32> = vec![];
+        let mut per_pages: Vec<i32> = vec![];
+
+        let conditions = [
+            (&filter.status, " AND status = ?"),
+            (&filter.kind, " AND kind = ?"),
+            (
+                &filter.idempotency_key,
+                " AND idempotency_key LIKE '%' || ? || '%'",
+            ),
+            (&filter.start_date, " AND date(created_at) >= date(?)"),
+            (&filter.end_date, " AND date(created_at) <= date(?)"),
+        ];
+        for (value, clause) in &conditions {
+            if let Some(val) = value
+                && !val.is_empty()
+            {
+                values.push(val.clone());
+                count_query.push_str(clause);
+                data_query.push_str(clause);
+            }
+        }
+        for val in &values {
+            params_vec.push(val);
+        }
+
+        let total_count: i32 =
+            conn.query_row(&count_query, params_vec.as_slice(), |row| row.get(0))?;
+
+        data_query.push_str(" ORDER BY created_at DESC, id DESC");
+
+        if let (Some(page), Some(per_page)) = (&filter.page, &filter.per_page)
+            && let (Ok(page_val), Ok(per_page_val)) = (page.parse::<i32>(), per_page.parse::<i32>())
+            && per_page_val > 0
+        {
+            pages.push((page_val - 1) * per_page_val);
+            per_pages.push(per_page_val);
+            data_query.push_str(" LIMIT ? OFFSET ?");
+            params_vec.push(per_pages.last().unwrap());
+            params_vec.push(pages.last().unwrap());
+        }
+
+        let mut stmt = conn.prepare(&data_query)?;
+        let rows = stmt.query_map(params_vec.as_slice(), Self::from_row)?;
+        let data = rows.collect::<Result<Vec<_>, _>>()?;
+
+        Ok(FilterResponse { total_count, data })
+    }
+}
+
+#[test]
+fn test_outbox_retries_and_never_reposts() {
+    use crate::sap::RollData;
+
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::manager::migrations::migrate_up(&mut conn, None).unwrap();
+
+    let posting = |weight: &str| {
+        SapPosting::RollConfirmation(RollData {
+            weight: weight.to_string(),
+            meter: "1000".to_string(),
+            batch: "26001001001".to_string(),
+            production_order: "220012061".to_string(),
+        })
+    };
+    let first = SapOutbox::enqueue(&conn, "RC-OR1", &posting("50"), None, None).unwrap();
+    let again = SapOutbox::enqueue(&conn, "RC-OR1", &posting("51"), None, None).unwrap();
+    assert_eq!(first.id, again.id);
+    assert_eq!(again.payload["weight"], "51");
+
+    let mut claimed = SapOutbox::claim(&conn, first.id).unwrap().unwrap();
+    assert!(SapOutbox::claim(&conn, first.id).unwrap().is_none());
+    claimed
+        .complete(
+            &conn,
+            Err(PostingError::Rejected("connection refused".into())),
+        )
+        .unwrap();
+    assert_eq!(claimed.status, "pending");
+    assert!(claimed.next_attempt_at > claimed.updated_at);
+    assert!(SapOutbox::due_ids(&conn, 10).unwrap().is_empty());
+
+    let mut claimed = SapOutbox::claim(&conn, first.id).unwrap().unwrap();
+    assert_eq!(claimed.attempts, 2);
+    claimed.complete(&conn, Ok(None)).unwrap();
+
+    let after = SapOutbox::enqueue(&conn, "RC-OR1", &posting("60"), None, None).unwrap();
+    assert_eq!(after.status, "posted");
+    assert_eq!(after.payload["weight"], "51");
+    assert!(SapOutbox::claim(&conn, first.id).unwrap().is_none());
+
+    // A timeout may have posted the message, so it waits for a manual check instead of a resend
+    let sent = SapOutbox::enqueue(&conn, "RC-OR3", &posting("80"), None, None).unwrap();
+    let mut claimed = SapOutbox::claim(&conn, sent.id).unwrap().unwrap();
+    claimed
+        .complete(&conn, Err(PostingError::Unconfirmed("timed out".into())))
+        .unwrap();
+    assert_eq!(claimed.status, "failed");
+    assert!(claimed.last_error.as_deref().unwrap().contains("RC-OR3"));
+    assert!(SapOutbox::due_ids(&conn, 10).unwrap().is_empty());
+
+    // Failed and cancelled messages only go back to pending through an explicit retry
+    let mut held = SapOutbox::enqueue(&conn, "RC-OR2", &posting("70"), None, None).unwrap();
+    held.cancel(&conn, None).unwrap();
+    let again = SapOutbox::enqueue(&conn, "RC-OR2", &posting("71"), None, None).unwrap();
+    assert_eq!(again.status, "cancelled");
+    assert_eq!(again.payload["weight"], "70");
+    held.retry(&conn, None).unwrap();
+    assert_eq!(
+        SapOutbox::find_by_id(&conn, held.id).unwrap().status,
+        "pending"
+    );
+}
@@ -0,0 +1,476 @@
+use chrono::Local;
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::models::{AuditLog, SapOutbox};
+use crate::sap::SapPosting;
+
+/// Reversal of a posting SAP already accepted, after the local record was corrected or
+/// deleted. It keeps the original document next to the reversal, and the corrected value is
+/// re-posted under `repost_key` once the reversal went through.
+#[derive(Debug, Serialize)]
+pub struct SapReversal {
+    pub id: i32,
+    pub ref_model: String,
+    pub ref_id: i32,
+    pub original_key: String,
+    pub original_document: Option<String>,
+    pub reversal_key: String,
+    pub reversal_document: Option<String>,
+    /// Outbox message posting the reversal and its status
+    pub message_id: Option<i32>,
+    pub status: Option<String>,
+    pub reason: Option<String>,
+    pub reversed_at: Option<String>,
+    pub repost_key: Option<String>,
+    pub repost_status: Option<String>,
+    pub repost_document: Option<String>,
+    pub created_by: Option<i32>,
+    pub created_at: String,
+}
+
+#[derive(Deserialize)]
+pub struct SapReversalFilterPayload {
+    pub ref_model: Option<String>,
+    pub ref_id: Option<i32>,
+    /// Only reversals whose corrected value has not been re-posted
+    pub awaiting_repost: Option<bool>,
+}
+
+const SELECT: &str = "SELECT r.id, r.ref_model, r.ref_id, r.original_key, r.original_document, r.reversal_key, r.reversal_document, rv.id, rv.status, r.reason, r.reversed_at, r.repost_key, rp.status, rp.document, r.created_by, r.created_at \
+     FROM sap_reversals r \
+     LEFT JOIN sap_outbox rv ON rv.idempotency_key = r.reversal_key \
+     LEFT JOIN sap_outbox rp ON rp.idempotency_key = r.repost_key";
+
+/// Key of a record's first posting, as the model that posts it enqueues it
+fn initial_key(ref_model: &str, ref_id: i32) -> Option<String> {
+    match ref_model {
+        "input_rolls" => Some(format!("GI-IR{}", ref_id)),
+        "output_rolls" => Some(format!("RC-OR{}", ref_id)),
+        _ => None,
+    }
+}
+
+/// Replaces a posted quantity only when its value changed, so "498.50" stays as posted
+fn keep_or_replace(posted: &mut String, current: f64) {
+    if posted.trim().parse::<f64>().ok() != Some(current) {
+        *posted = current.to_string();
+    }
+}
+
+impl SapReversal {
+    fn from_row(row: &rusqlite::Row) -> Result<Self> {
+        Ok(SapReversal {
+            id: row.get(0)?,
+            ref_model: row.get(1)?,
+            ref_id: row.get(2)?,
+            original_key: row.get(3)?,
+            original_document: row.get(4)?,
+            reversal_key: row.get(5)?,
+            reversal_document: row.get(6)?,
+            message_id: row.get(7)?,
+            status: row.get(8)?,
+            reason: row.get(9)?,
+            reversed_at: row.get(10)?,
+            repost_key: row.get(11)?,
+            repost_status: row.get(12)?,
+            repost_document: row.get(13)?,
+            created_by: row.get(14)?,
+            created_at: row.get(15)?,
+        })
+    }
+
+    pub fn find_by_id(conn: &Connection, id: i32) -> Result<Self> {
+        conn.query_row(
+            &format!("{} WHERE r.id = ?1", SELECT),
+            params![id],
+            Self::from_row,
+        )
+    }
+
+    pub fn filter(conn: &Connection, filter: &SapReversalFilterPayload) -> Result<Vec<Self>> {
+        let mut query = format!("{} WHERE 1=1", SELECT);
+        let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+        if let Some(ref_model) = filter.ref_model.as_deref().filter(|m| !m.is_empty()) {
+            query.push_str(" AND r.ref_model = ?");
+            params_vec.push(Box::new(ref_model.to_string()));
+        }
+        if let Some(ref_id) = filter.ref_id {
+            query.push_str(" AND r.ref_id = ?");
+            params_vec.push(Box::new(ref_id));
+        }
+        if filter.awaiting_repost == Some(true) {
+            query.push_str(" AND r.repost_key IS NULL");
+        }
+        query.push_str(" ORDER BY r.id DESC");
+        let mut stmt = conn.prepare(&query)?;
+        stmt.query_map(
+            params_vec
+                .iter()
+                .map(|b| &**b)
+                .collect::<Vec<_>>()
+                .as_slice(),
+            Self::from_row,
+        )?
+        .collect()
+    }
+
+    /// Key the record's value is currently posted under: its first posting, or the re-post of
+    /// its latest correction.
+    pub fn current_key(conn: &Connection, ref_model: &str, ref_id: i32) -> Result<Option<String>> {
+        let reposted: Option<String> = conn
+            .query_row(
+                "SELECT repost_key FROM sap_reversals WHERE ref_model = ?1 AND ref_id = ?2 AND repost_key IS NOT NULL ORDER BY id DESC LIMIT 1",
+                params![ref_model, ref_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(reposted.or_else(|| initial_key(ref_model, ref_id)))
+    }
+
+    /// What should be posted for the record now: `original` with the record's current values,
+    /// `None` once the record is gone.
+    fn corrected_posting(
+        conn: &Connection,
+        ref_model: &str,
+        ref_id: i32,
+        original: &SapPosting,
+    ) -> Result<Option<SapPosting>> {
+        match (ref_model, original) {
+            ("input_rolls", SapPosting::GoodsIssue { .. }) => {
+                let consumed: Option<Option<f64>> = conn
+                    .query_row(
+                        "SELECT CAST(consumed_weight AS REAL) FROM input_rolls WHERE id = ?1",
+                        params![ref_id],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                let Some(consumed) = consumed.flatten() else {
+                    return Ok(None);
+                };
+                let mut corrected = original.clone();
+                if let SapPosting::GoodsIssue { quantity, .. } = &mut corrected {
+                    keep_or_replace(quantity, consumed);
+                }
+                Ok(Some(corrected))
+            }
+            ("output_rolls", SapPosting::RollConfirmation(data)) => conn
+                .query_row(
+                    "SELECT output_batch, CAST(final_weight AS REAL), CAST(final_meter AS REAL) FROM output_rolls WHERE id = ?1",
+                    params![ref_id],
+                    |row| {
+                        let mut corrected = data.clone();
+                        corrected.batch = row.get(0)?;
+                        keep_or_replace(&mut corrected.weight, row.get(1)?);
+                        keep_or_replace(&mut corrected.meter, row.get(2)?);
+                        Ok(SapPosting::RollConfirmation(corrected))
+                    },
+                )
+                .optional(),
+            _ => Ok(None),
+        }
+    }
+
+    /// Brings SAP in line after a record was corrected or deleted locally. A posting SAP has
+    /// not accepted yet is refreshed with the current values, or cancelled when the record is
+    /// gone; an accepted one that no longer matches is reversed, and the corrected value waits
+    /// for [`SapReversal::repost`]. Returns the reversal when one was queued.
+    pub fn correct(
+        conn: &Connection,
+        ref_model: &str,
+        ref_id: i32,
+        reason: &str,
+        user_id: Option<i32>,
+    ) -> Result<Option<Self>> {
+        let Some(key) = Self::current_key(conn, ref_model, ref_id)? else {
+            return Ok(None);
+        };
+        let Some(mut message) = SapOutbox::find_by_key(conn, &key).optional()? else {
+            return Ok(None);
+        };
+        let original = message.posting()?;
+        let corrected = Self::corrected_posting(conn, ref_model, ref_id, &original)?;
+        match message.status.as_str() {
+            "posting" => Err(rusqlite::Error::InvalidParameterName(format!(
+                "{} is being posted to SAP, correct it again in a moment",
+                key
+            ))),
+            "posted" => {
+                let unchanged = corrected
+                    .as_ref()
+                    .and_then(|posting| serde_json::to_value(posting).ok())
+                    .is_some_and(|payload| payload == message.payload);
+                if unchanged {
+                    return Ok(None);
+                }
+                Self::reverse(conn, &message, ref_model, ref_id, reason, user_id).map(Some)
+            }
+            _ => {
+                match corrected {
+                    // Failed and cancelled messages keep their status until an admin retries them
+                    Some(posting) if message.status != "pending" => {
+                        message.replace_payload(conn, &posting, user_id)?
+                    }
+                    Some(posting) => {
+                        SapOutbox::enqueue(
+                            conn,
+                            &key,
+                            &posting,
+                            Some((ref_model, ref_id)),
+                            message.created_by,
+                        )?;
+                    }
+                    None if message.status != "cancelled" => message.cancel(conn, user_id)?,
+                    None => {}
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// Queues the reversal of a posted message. Reversing the same posting twice returns the
+    /// first reversal.
+    fn reverse(
+        conn: &Connection,
+        message: &SapOutbox,
+        ref_model: &str,
+        ref_id: i32,
+        reason: &str,
+        user_id: Option<i32>,
+    ) -> Result<Self> {
+        let existing: Option<i32> = conn
+            .query_row(
+                "SELECT id FROM sap_reversals WHERE original_key = ?1",
+                params![message.idempotency_key],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(id) = existing {
+            return Self::find_by_id(conn, id);
+        }
+
+        let reversal = match message.posting()? {
+            SapPosting::GoodsIssue { posting_date, .. }
+            | SapPosting::GoodsReturn { posting_date, .. } => {
+                let document = message.document.clone().ok_or_else(|| {
+                    rusqlite::Error::InvalidParameterName(format!(
+                        "{} has no material document to cancel",
+                        message.idempotency_key
+                    ))
+                })?;
+                // Documents are numbered per year, the year the original was posted in
+                let year = [Some(posting_date.as_str()), message.posted_at.as_deref()]
+                    .into_iter()
+                    .flatten()
+                    .map(|date| date.chars().take(4).collect::<String>())
+                    .find(|year| year.len() == 4 && year.chars().all(|c| c.is_ascii_digit()))
+                    .unwrap_or_else(|| Local::now().format("%Y").to_string());
+                SapPosting::DocumentCancellation { document, year }
+            }
+            SapPosting::RollConfirmation(data) => SapPosting::RollReversal(data),
+            _ => {
+                return Err(rusqlite::Error::InvalidParameterName(format!(
+                    "{} is itself a reversal",
+                    message.idempotency_key
+                )));
+            }
+        };
+
+        let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let reversal_key = format!("REV-{}", message.idempotency_key);
+        conn.execute(
+            "INSERT INTO sap_reversals (ref_model, ref_id, original_key, original_document, reversal_key, reason, created_by, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                ref_model,
+                ref_id,
+                message.idempotency_key,
+                message.document,
+                reversal_key,
+                reason,
+                user_id,
+                now
+            ],
+        )?;
+        let id = conn.last_insert_rowid() as i32;
+        SapOutbox::enqueue(
+            conn,
+            &reversal_key,
+            &reversal,
+            Some(("sap_reversals", id)),
+            user_id,
+        )?;
+        let record = Self::find_by_id(conn, id)?;
+        AuditLog::created(conn, user_id, "sap_reversals", id, &record)?;
+        Ok(record)
+    }
+
+    /// Called when SAP accepted the reversal: keeps its document and clears the original one
+    /// from the record, which is no longer posted.
+    pub fn reversed(conn: &Connection, id: i32, document: Option<&str>) -> Result<()> {
+        let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "UPDATE sap_reversals SET reversal_document = ?1, reversed_at = ?2 WHERE id = ?3",
+            params![document, now, id],
+        )?;
+        let reversal = Self::find_by_id(conn, id)?;
+        if reversal.ref_model == "input_rolls" {
+            conn.execute(
+                "UPDATE input_rolls SET material_document = NULL, updated_at = ?1 WHERE id = ?2 AND material_document = ?3",
+                params![now, reversal.ref_id, reversal.original_document],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Posts the record's corrected value after its reversal went through, under a new key so
+    /// the outbox never mistakes it for the reversed posting.
+    pub fn repost(&mut self, conn: &Connection, user_id: Option<i32>) -> Result<SapOutbox> {
+        let invalid = |msg: String| rusqlite::Error::InvalidParameterName(msg);
+        if let Some(key) = &self.repost_key {
+            return Err(invalid(format!("Already re-posted as {}", key)));
+        }
+        if self.status.as_deref() != Some("posted") {
+            return Err(invalid(format!(
+                "The reversal of {} is {}, re-post once SAP has accepted it",
+                self.original_key,
+                self.status.as_deref().unwrap_or("missing")
+            )));
+        }
+        let original = SapOutbox::find_by_key(conn, &self.original_key)?.posting()?;
+        let corrected = Self::corrected_posting(conn, &self.ref_model, self.ref_id, &original)?
+            .ok_or_else(|| {
+                invalid(format!(
+                    "Nothing to re-post, {} {} was deleted",
+                    self.ref_model.trim_end_matches('s').replace('_', " "),
+                    self.ref_id
+                ))
+            })?;
+        let base = match self.original_key.rsplit_once("-R") {
+            Some((base, n)) if !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()) => base,
+            _ => self.original_key.as_str(),
+        };
+        let key = format!("{}-R{}", base, self.id);
+
+        let before = AuditLog::snapshot(self);
+        let message = SapOutbox::enqueue(
+            conn,
+            &key,
+            &corrected,
+            Some((&self.ref_model, self.ref_id)),
+            user_id,
+        )?;
+        conn.execute(
+            "UPDATE sap_reversals SET repost_key = ?1 WHERE id = ?2",
+            params![key, self.id],
+        )?;
+        *self = Self::find_by_id(conn, self.id)?;
+        AuditLog::updated(conn, user_id, "sap_reversals", self.id, &before, self)?;
+        Ok(message)
+    }
+}
+
+#[actix_web::test]
+async fn test_reversal_of_corrected_postings() {
+    use crate::backend::models::{EndInputRollPayload, InputRoll, InputRollUpdatePayload};
+    use crate::sap::{FakeSapGateway, FakeSapState, deliver_now};
+
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::manager::migrations::migrate_up(&mut conn, None).unwrap();
+    conn.execute_batch(
+        "INSERT INTO users (id, full_name) VALUES (1, 'Operator');
+         INSERT INTO jobs (id, production_order) VALUES (1, '220012061');
+         INSERT INTO input_rolls (id, job_id, batch, material_number, created_by, start_meter, start_weight) VALUES (1, 1, 'J23-612', '30000950', 1, 0, '520KG');",
+    )
+    .unwrap();
+    let sap = FakeSapGateway::new(FakeSapState::default());
+    let end = EndInputRollPayload {
+        id: 1,
+        weight_unit: "KG".to_string(),
+        posting_date: "2026-01-09T00:00:00".to_string(),
+        batch: "J23-612".to_string(),
+        input_roll_id: 1,
+        consumed_weight: "498.5".to_string(),
+        material_number: "30000950".to_string(),
+        production_order: "220012061".to_string(),
+    };
+    let original = InputRoll::end_input_roll(&conn, &sap, &end, Some(1))
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Nothing to reverse while the posted weight still matches
+    assert!(
+        SapReversal::correct(&conn, "input_rolls", 1, "check", Some(1))
+            .unwrap()
+            .is_none()
+    );
+
+    let mut roll = InputRoll::find_by_id(&conn, 1).unwrap();
+    let fix = InputRollUpdatePayload {
+        id: 1,
+        batch: None,
+        material_document: None,
+        material_number: None,
+        start_meter: None,
+        start_weight: None,
+        consumed_weight: Some(480.0),
+    };
+    roll.update(&conn, &sap, &fix, Some(1)).await.unwrap();
+    // The update sent the reversal; correcting again finds the same one
+    let reversal = SapReversal::correct(&conn, "input_rolls", 1, "Weight corrected", Some(1))
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        reversal.original_document.as_deref(),
+        Some(original.as_str())
+    );
+    assert_eq!(sap.state().cancelled_documents, vec![original.clone()]);
+    let mut reversal = SapReversal::find_by_id(&conn, reversal.id).unwrap();
+    assert!(reversal.reversal_document.is_some());
+    assert!(
+        InputRoll::find_by_id(&conn, 1)
+            .unwrap()
+            .material_document
+            .is_none()
+    );
+
+    let message = reversal.repost(&conn, Some(1)).unwrap();
+    assert_eq!(message.idempotency_key, "GI-IR1-R1");
+    assert_eq!(message.payload["quantity"], "480");
+    assert!(reversal.repost(&conn, Some(1)).is_err());
+    let message = deliver_now(&conn, &sap, message.id).await.unwrap();
+    assert_eq!(
+        InputRoll::find_by_id(&conn, 1).unwrap().material_document,
+        message.document
+    );
+    assert_eq!(
+        SapReversal::current_key(&conn, "input_rolls", 1).unwrap(),
+        Some("GI-IR1-R1".to_string())
+    );
+
+    // Deleting the roll reverses the re-posted issue, with nothing left to re-post
+    // Nothing is deleted while the posting is on its way to SAP
+    conn.execute(
+        "UPDATE sap_outbox SET status = 'posting' WHERE idempotency_key = 'GI-IR1-R1'",
+        [],
+    )
+    .unwrap();
+    assert!(matches!(
+        roll.delete(&conn, &sap, Some(1)).await,
+        Err(rusqlite::Error::InvalidParameterName(_))
+    ));
+    assert!(InputRoll::find_by_id(&conn, 1).is_ok());
+    conn.execute(
+        "UPDATE sap_outbox SET status = 'posted' WHERE idempotency_key = 'GI-IR1-R1'",
+        [],
+    )
+    .unwrap();
+    roll.delete(&conn, &sap, Some(1)).await.unwrap();
+    let mut reversal = SapReversal::correct(&conn, "input_rolls", 1, "Input roll deleted", Some(1))
+        .unwrap()
+        .unwrap();
+    assert_eq!(reversal.original_key, "GI-IR1-R1");
+    assert!(reversal.reversal_document.is_some());
+    assert!(reversal.repost(&conn, Some(1)).is_err());
+    assert_eq!(sap.state().cancelled_documents.len(), 2);
+}
@@ -0,0 +1,286 @@
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime};
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::models::{AuditLog, Shift};
+
+/// Shifts per day assumed for shifts without times, as batch numbers were built before the
+/// calendar existed.
+const LEGACY_SHIFTS_PER_DAY: i32 = 2;
+
+/// The shift running at a point in time and the production day it belongs to.
+#[derive(Debug, Clone, Serialize)]
+pub struct ShiftSlot {
+    pub shift_id: i32,
+    pub name: String,
+    pub sequence: i32,
+    /// The day the shift started on; a night shift past midnight still counts to it
+    pub production_date: NaiveDate,
+    pub start: Option<NaiveDateTime>,
+    pub end: Option<NaiveDateTime>,
+    pub shifts_per_day: i32,
+    /// Name of the plant holiday covering this shift, if any
+    pub holiday: Option<String>,
+}
+
+impl ShiftSlot {
+    /// Running shift number within the year, e.g. the 3rd shift of 2 January on a 3-shift
+    /// pattern is 6.
+    pub fn number_in_year(&self) -> i32 {
+        (self.production_date.ordinal() as i32 - 1) * self.shifts_per_day + self.sequence
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlantHoliday {
+    pub id: i32,
+    pub date: String,
+    pub name: String,
+    /// `None` closes the whole day
+    pub shift_id: Option<i32>,
+    pub shift_name: Option<String>,
+    pub created_by: Option<i32>,
+    pub created_at: String,
+}
+
+#[derive(Deserialize)]
+pub struct PlantHolidayCreatePayload {
+    pub date: String,
+    pub name: String,
+    pub shift_id: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct CurrentShiftPayload {
+    /// Defaults to now
+    pub at: Option<String>,
+}
+
+/// Accepts the datetime formats the forms and SAP send, in plant local time.
+pub fn parse_local_datetime(value: &str) -> Option<NaiveDateTime> {
+    let value = value.trim();
+    [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+}
+
+impl Shift {
+    /// The calendar shift running at `now`, or `None` when no timed shift covers it.
+    pub fn current(conn: &Connection, now: NaiveDateTime) -> Result<Option<ShiftSlot>> {
+        let shifts = Self::timed(conn)?;
+        let shifts_per_day = shifts.len() as i32;
+        let mut found: Option<ShiftSlot> = None;
+        for (position, (shift, start_time, end_time)) in shifts.iter().enumerate() {
+            for day in [now.date() - Duration::days(1), now.date()] {
+                let start = day.and_time(*start_time);
+                let end = if end_time <= start_time {
+                    (day + Duration::days(1)).and_time(*end_time)
+                } else {
+                    day.and_time(*end_time)
+                };
+                if start > now || now >= end {
+                    continue;
+                }
+                if found
+                    .as_ref()
+                    .is_some_and(|slot| slot.start.is_some_and(|s| s >= start))
+                {
+                    continue;
+                }
+                found = Some(ShiftSlot {
+                    shift_id: shift.id,
+                    name: shift.name.clone(),
+                    sequence: shift.sequence.unwrap_or(position as i32 + 1),
+                    production_date: day,
+                    start: Some(start),
+                    end: Some(end),
+                    shifts_per_day,
+                    holiday: None,
+                });
+            }
+        }
+        if let Some(slot) = found.as_mut() {
+            slot.holiday = PlantHoliday::covering(conn, slot.production_date, slot.shift_id)?;
+        }
+        Ok(found)
+    }
+
+    /// The shift to book work at `at` against. The calendar wins; a shift picked by the
+    /// operator is only used when no timed shift covers `at`.
+    pub fn resolve(
+        conn: &Connection,
+        at: NaiveDateTime,
+        requested: Option<i32>,
+    ) -> Result<ShiftSlot> {
+        if let Some(slot) = Self::current(conn, at)? {
+            return Ok(slot);
+        }
+        let Some(shift_id) = requested else {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "No shift is scheduled at {}",
+                at.format("%Y-%m-%d %H:%M")
+            )));
+        };
+        let shift = Self::find_by_id(conn, shift_id)?;
+        let shifts_per_day: i32 =
+            conn.query_row("SELECT COUNT(*) FROM shifts WHERE active = 1", [], |row| {
+                row.get(0)
+            })?;
+        let production_date = at.date();
+        Ok(ShiftSlot {
+            shift_id: shift.id,
+            sequence: shift.sequence.unwrap_or(shift.id),
+            name: shift.name,
+            production_date,
+            start: None,
+            end: None,
+            shifts_per_day: shifts_per_day.max(LEGACY_SHIFTS_PER_DAY),
+            holiday: PlantHoliday::covering(conn, production_date, shift_id)?,
+        })
+    }
+
+    /// Active shifts with both times set, in production-day order.
+    fn timed(conn: &Connection) -> Result<Vec<(Shift, NaiveTime, NaiveTime)>> {
+        let shifts = Self::all(conn)?;
+        Ok(shifts
+            .into_iter()
+            .filter(|shift| shift.active)
+            .filter_map(|shift| {
+                let start =
+                    NaiveTime::parse_from_str(shift.start_time.as_deref()?, "%H:%M").ok()?;
+                let end = NaiveTime::parse_from_str(shift.end_time.as_deref()?, "%H:%M").ok()?;
+                Some((shift, start, end))
+            })
+            .collect())
+    }
+}
+
+impl PlantHoliday {
+    pub fn create(
+        conn: &Connection,
+        data: &PlantHolidayCreatePayload,
+        user_id: Option<i32>,
+    ) -> Result<Self> {
+        let date = NaiveDate::parse_from_str(data.date.trim(), "%Y-%m-%d").map_err(|_| {
+            rusqlite::Error::InvalidParameterName(format!("Invalid holiday date '{}'", data.date))
+        })?;
+        let name = data.name.trim();
+        if name.is_empty() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Holiday name is required".to_string(),
+            ));
+        }
+        let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "INSERT INTO plant_holidays (date, name, shift_id, created_by, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![date.to_string(), name, data.shift_id, user_id, now],
+        )?;
+        let id = conn.last_insert_rowid() as i32;
+        let record = Self::find_by_id(conn, id)?;
+        AuditLog::created(conn, user_id, "plant_holidays", id, &record)?;
+        Ok(record)
+    }
+
+    pub fn delete(&self, conn: &Connection, user_id: Option<i32>) -> Result<()> {
+        conn.execute("DELETE FROM plant_holidays WHERE id = ?1", params![self.id])?;
+        AuditLog::deleted(conn, user_id, "plant_holidays", self.id, self)?;
+        Ok(())
+    }
+
+    fn from_row(row: &rusqlite::Row) -> Result<Self> {
+        Ok(PlantHoliday {
+            id: row.get(0)?,
+            date: row.get(1)?,
+            name: row.get(2)?,
+            shift_id: row.get(3)?,
+            shift_name: row.get(4)?,
+            created_by: row.get(5)?,
+            created_at: row.get(6)?,
+        })
+    }
+
+    pub fn find_by_id(conn: &Connection, id: i32) -> Result<Self> {
+        conn.query_row(
+            "SELECT h.id, h.date, h.name, h.shift_id, s.name, h.created_by, h.created_at \
+             FROM plant_holidays h LEFT JOIN shifts s ON h.shift_id = s.id WHERE h.id = ?1",
+            params![id],
+            Self::from_row,
+        )
+    }
+
+    pub fn all(conn: &Connection) -> Result<Vec<Self>> {
+        let mut stmt = conn.prepare(
+            "SELECT h.id, h.date, h.name, h.shift_id, s.name, h.created_by, h.created_at \
+             FROM plant_holidays h LEFT JOIN shifts s ON h.shift_id = s.id ORDER BY h.date DESC, h.id",
+        )?;
+        stmt.query_map([], Self::from_row)?.collect()
+    }
+
+    /// Name of the holiday closing `date` for the whole plant or for `shift_id`.
+    pub fn covering(conn: &Connection, date: NaiveDate, shift_id: i32) -> Result<Option<String>> {
+        conn.query_row(
+            "SELECT name FROM plant_holidays WHERE date = ?1 AND (shift_id IS NULL OR shift_id = ?2) ORDER BY shift_id IS NULL LIMIT 1",
+            params![date.to_string(), shift_id],
+            |row| row.get(0),
+        )
+        .optional()
+    }
+}
+
+#[test]
+fn test_shift_calendar_resolution() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::manager::migrations::migrate_up(&mut conn, None).unwrap();
+    let at = |s: &str| parse_local_datetime(s).unwrap();
+    let date = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+
+    // Untimed shifts fall back to the operator's pick with the old 2-shift numbering
+    conn.execute_batch("INSERT INTO shifts (id, name) VALUES (1, 'Day'), (2, 'Night');")
+        .unwrap();
+    assert!(Shift::resolve(&conn, at("2026-01-02 08:00"), None).is_err());
+    let legacy = Shift::resolve(&conn, at("2026-01-02 20:00"), Some(2)).unwrap();
+    assert_eq!(legacy.number_in_year(), 4);
+
+    conn.execute_batch(
+        "UPDATE shifts SET start_time = '07:00', end_time = '19:00', sequence = 1 WHERE id = 1;
+         UPDATE shifts SET start_time = '19:00', end_time = '07:00', sequence = 2 WHERE id = 2;",
+    )
+    .unwrap();
+    let night = Shift::resolve(&conn, at("2026-01-02T03:30"), Some(1)).unwrap();
+    assert_eq!(night.shift_id, 2);
+    assert_eq!(night.production_date, date("2026-01-01"));
+    assert_eq!(night.number_in_year(), 2);
+    assert_eq!(
+        Shift::current(&conn, at("2026-01-02 19:00"))
+            .unwrap()
+            .unwrap()
+            .production_date,
+        date("2026-01-02")
+    );
+
+    conn.execute_batch(
+        "UPDATE shifts SET start_time = '06:00', end_time = '14:00' WHERE id = 1;
+         UPDATE shifts SET name = 'Afternoon', start_time = '14:00', end_time = '22:00' WHERE id = 2;
+         INSERT INTO shifts (id, name, start_time, end_time, sequence) VALUES (3, 'Night', '22:00', '06:00', 3);
+         INSERT INTO plant_holidays (date, name, created_at) VALUES ('2026-01-02', 'New Year', '2026-01-01');",
+    )
+    .unwrap();
+    let third = Shift::current(&conn, at("2026-01-03 05:59:59"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(third.shift_id, 3);
+    assert_eq!(third.number_in_year(), 6);
+    assert_eq!(third.holiday.as_deref(), Some("New Year"));
+    assert!(
+        Shift::current(&conn, at("2026-01-03 06:00"))
+            .unwrap()
+            .unwrap()
+            .holiday
+            .is_none()
+    );
+}
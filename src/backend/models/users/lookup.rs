@@ -1,10 +1,18 @@
 use rusqlite::{Connection, Result, params};
 use serde::{Deserialize, Serialize};
 
+use crate::backend::models::AuditLog;
+
 #[derive(Debug, Serialize)]
 pub struct Shift {
     pub id: i32,
     pub name: String,
+    /// `HH:MM`; a shift whose end is not after its start runs past midnight
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+    /// Position in the production day (1 = first shift), used in batch numbers
+    pub sequence: Option<i32>,
+    pub active: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -48,6 +56,24 @@ pub struct LookupPayload {
     pub id: i32,
     pub name: Option<String>,
 }
+
+#[derive(Deserialize)]
+pub struct ShiftCreatePayload {
+    pub name: String,
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+    pub sequence: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct ShiftPayload {
+    pub id: i32,
+    pub name: Option<String>,
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+    pub sequence: Option<i32>,
+    pub active: Option<bool>,
+}
 #[derive(Debug, Serialize)]
 pub struct POCodeSection {
     pub po_code_id: i32,
@@ -89,55 +115,127 @@ impl Shift {
         Ok(false)
     }
 
-    pub fn create(conn: &Connection, data: &LookupCreatePayload) -> Result<Self> {
-        conn.execute("INSERT INTO shifts (name) VALUES (?1)", params![data.name])?;
+    pub fn create(
+        conn: &Connection,
+        data: &ShiftCreatePayload,
+        user_id: Option<i32>,
+    ) -> Result<Self> {
+        let start_time = shift_time(data.start_time.as_deref())?;
+        let end_time = shift_time(data.end_time.as_deref())?;
+        if start_time.is_some() != end_time.is_some() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "A shift needs both a start and an end time".to_string(),
+            ));
+        }
+        conn.execute(
+            "INSERT INTO shifts (name, start_time, end_time, sequence, active) VALUES (?1, ?2, ?3, ?4, 1)",
+            params![data.name, start_time, end_time, data.sequence],
+        )?;
         let id = conn.last_insert_rowid() as i32;
-        Ok(Shift {
+        let record = Shift {
             id,
             name: data.name.clone(),
-        })
+            start_time,
+            end_time,
+            sequence: data.sequence,
+            active: true,
+        };
+        AuditLog::created(conn, user_id, "shifts", id, &record)?;
+        Ok(record)
     }
 
-    pub fn update(&mut self, conn: &Connection, data: &LookupPayload) -> Result<()> {
+    pub fn update(
+        &mut self,
+        conn: &Connection,
+        data: &ShiftPayload,
+        user_id: Option<i32>,
+    ) -> Result<()> {
+        let before = AuditLog::snapshot(self);
         if let Some(name) = &data.name {
-            conn.execute(
-                "UPDATE shifts SET name = ?1 WHERE id = ?2",
-                params![name, self.id],
-            )?;
             self.name = name.clone();
         }
+        if data.start_time.is_some() {
+            self.start_time = shift_time(data.start_time.as_deref())?;
+        }
+        if data.end_time.is_some() {
+            self.end_time = shift_time(data.end_time.as_deref())?;
+        }
+        if data.sequence.is_some() {
+            self.sequence = data.sequence;
+        }
+        if let Some(active) = data.active {
+            self.active = active;
+        }
+        if self.start_time.is_some() != self.end_time.is_some() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "A shift needs both a start and an end time".to_string(),
+            ));
+        }
+        conn.execute(
+            "UPDATE shifts SET name = ?1, start_time = ?2, end_time = ?3, sequence = ?4, active = ?5 WHERE id = ?6",
+            params![
+                self.name,
+                self.start_time,
+                self.end_time,
+                self.sequence,
+                self.active,
+                self.id
+            ],
+        )?;
+        AuditLog::updated(conn, user_id, "shifts", self.id, &before, self)?;
         Ok(())
     }
 
-    pub fn delete(&self, conn: &Connection) -> Result<()> {
+    pub fn delete(&self, conn: &Connection, user_id: Option<i32>) -> Result<()> {
         conn.execute("DELETE FROM shifts WHERE id = ?1", params![self.id])?;
+        AuditLog::deleted(conn, user_id, "shifts", self.id, self)?;
         Ok(())
     }
 
-    pub fn find_by_id(conn: &Connection, id: i32) -> Result<Self> {
-        let mut stmt = conn.prepare("SELECT * FROM shifts WHERE id = ?1")?;
-        stmt.query_row(params![id], |row| {
-            Ok(Shift {
-                id: row.get(0)?,
-                name: row.get(1)?,
-            })
+    fn from_row(row: &rusqlite::Row) -> Result<Self> {
+        Ok(Shift {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            start_time: row.get(2)?,
+            end_time: row.get(3)?,
+            sequence: row.get(4)?,
+            active: row.get(5)?,
         })
     }
 
+    pub fn find_by_id(conn: &Connection, id: i32) -> Result<Self> {
+        let mut stmt = conn.prepare(
+            "SELECT id, name, start_time, end_time, sequence, active FROM shifts WHERE id = ?1",
+        )?;
+        stmt.query_row(params![id], Self::from_row)
+    }
+
     pub fn all(conn: &Connection) -> Result<Vec<Self>> {
-        let mut stmt = conn.prepare("SELECT * FROM shifts ORDER BY name")?;
+        let mut stmt = conn.prepare(
+            "SELECT id, name, start_time, end_time, sequence, active FROM shifts ORDER BY COALESCE(sequence, id), name",
+        )?;
         let shifts = stmt
-            .query_map([], |row| {
-                Ok(Shift {
-                    id: row.get(0)?,
-                    name: row.get(1)?,
-                })
-            })?
+            .query_map([], Self::from_row)?
             .collect::<Result<Vec<_>, _>>()?;
         Ok(shifts)
     }
 }
 
+/// Normalises an `HH:MM` shift boundary; blank clears it.
+fn shift_time(value: Option<&str>) -> Result<Option<String>> {
+    match value.map(str::trim).filter(|v| !v.is_empty()) {
+        Some(v) => chrono::NaiveTime::parse_from_str(v, "%H:%M")
+            .map(|t| Some(t.format("%H:%M").to_string()))
+            .map_err(|_| {
+                rusqlite::Error::InvalidParameterName(format!(
+                    "Invalid shift time '{}', expected HH:MM",
+                    v
+                ))
+            }),
+        None => Ok(None),
+    }
+}
+
 impl Colour {
     pub fn has_related_records(conn: &Connection, colour_id: i32) -> Result<bool> {
         let count: i32 = conn.query_row(
@@ -148,16 +246,28 @@ impl Colour {
         Ok(count > 0)
     }
 
-    pub fn create(conn: &Connection, data: &LookupCreatePayload) -> Result<Self> {
+    pub fn create(
+        conn: &Connection,
+        data: &LookupCreatePayload,
+        user_id: Option<i32>,
+    ) -> Result<Self> {
         conn.execute("INSERT INTO colours (name) VALUES (?1)", params![data.name])?;
         let id = conn.last_insert_rowid() as i32;
-        Ok(Colour {
+        let record = Colour {
             id,
             name: data.name.clone(),
-        })
+        };
+        AuditLog::created(conn, user_id, "colours", id, &record)?;
+        Ok(record)
     }
 
-    pub fn update(&mut self, conn: &Connection, data: &LookupPayload) -> Result<()> {
+    pub fn update(
+        &mut self,
+        conn: &Connection,
+        data: &LookupPayload,
+        user_id: Option<i32>,
+    ) -> Result<()> {
+        let before = AuditLog::snapshot(self);
         if let Some(name) = &data.name {
             conn.execute(
                 "UPDATE colours SET name = ?1 WHERE id = ?2",
@@ -165,11 +275,13 @@ impl Colour {
             )?;
             self.name = name.clone();
         }
+        AuditLog::updated(conn, user_id, "colours", self.id, &before, self)?;
         Ok(())
     }
 
-    pub fn delete(&self, conn: &Connection) -> Result<()> {
+    pub fn delete(&self, conn: &Connection, user_id: Option<i32>) -> Result<()> {
         conn.execute("DELETE FROM colours WHERE id = ?1", params![self.id])?;
+        AuditLog::deleted(conn, user_id, "colours", self.id, self)?;
         Ok(())
     }
 
@@ -207,19 +319,31 @@ impl SolventType {
         Ok(count > 0)
     }
 
-    pub fn create(conn: &Connection, data: &LookupCreatePayload) -> Result<Self> {
+    pub fn create(
+        conn: &Connection,
+        data: &LookupCreatePayload,
+        user_id: Option<i32>,
+    ) -> Result<Self> {
         conn.execute(
             "INSERT INTO solvent_types (name) VALUES (?1)",
             params![data.name],
         )?;
         let id = conn.last_insert_rowid() as i32;
-        Ok(SolventType {
+        let record = SolventType {
             id,
             name: data.name.clone(),
-        })
+        };
+        AuditLog::created(conn, user_id, "solvent_types", id, &record)?;
+        Ok(record)
     }
 
-    pub fn update(&mut self, conn: &Connection, data: &LookupPayload) -> Result<()> {
+    pub fn update(
+        &mut self,
+        conn: &Connection,
+        data: &LookupPayload,
+        user_id: Option<i32>,
+    ) -> Result<()> {
+        let before = AuditLog::snapshot(self);
         if let Some(name) = &data.name {
             conn.execute(
                 "UPDATE solvent_types SET name = ?1 WHERE id = ?2",
@@ -227,11 +351,13 @@ impl SolventType {
             )?;
             self.name = name.clone();
         }
+        AuditLog::updated(conn, user_id, "solvent_types", self.id, &before, self)?;
         Ok(())
     }
 
-    pub fn delete(&self, conn: &Connection) -> Result<()> {
+    pub fn delete(&self, conn: &Connection, user_id: Option<i32>) -> Result<()> {
         conn.execute("DELETE FROM solvent_types WHERE id = ?1", params![self.id])?;
+        AuditLog::deleted(conn, user_id, "solvent_types", self.id, self)?;
         Ok(())
     }
 
@@ -269,19 +395,31 @@ impl ScrapType {
         Ok(count > 0)
     }
 
-    pub fn create(conn: &Connection, data: &LookupCreatePayload) -> Result<Self> {
+    pub fn create(
+        conn: &Connection,
+        data: &LookupCreatePayload,
+        user_id: Option<i32>,
+    ) -> Result<Self> {
         conn.execute(
             "INSERT INTO scrap_types (name) VALUES (?1)",
             params![data.name],
         )?;
         let id = conn.last_insert_rowid() as i32;
-        Ok(ScrapType {
+        let record = ScrapType {
             id,
             name: data.name.clone(),
-        })
+        };
+        AuditLog::created(conn, user_id, "scrap_types", id, &record)?;
+        Ok(record)
     }
 
-    pub fn update(&mut self, conn: &Connection, data: &LookupPayload) -> Result<()> {
+    pub fn update(
+        &mut self,
+        conn: &Connection,
+        data: &LookupPayload,
+        user_id: Option<i32>,
+    ) -> Result<()> {
+        let before = AuditLog::snapshot(self);
         if let Some(name) = &data.name {
             conn.execute(
                 "UPDATE scrap_types SET name = ?1 WHERE id = ?2",
@@ -289,11 +427,13 @@ impl ScrapType {
             )?;
             self.name = name.clone();
         }
+        AuditLog::updated(conn, user_id, "scrap_types", self.id, &before, self)?;
         Ok(())
     }
 
-    pub fn delete(&self, conn: &Connection) -> Result<()> {
+    pub fn delete(&self, conn: &Connection, user_id: Option<i32>) -> Result<()> {
         conn.execute("DELETE FROM scrap_types WHERE id = ?1", params![self.id])?;
+        AuditLog::deleted(conn, user_id, "scrap_types", self.id, self)?;
         Ok(())
     }
 
@@ -331,19 +471,31 @@ impl DowntimeReason {
         Ok(count > 0)
     }
 
-    pub fn create(conn: &Connection, data: &LookupCreatePayload) -> Result<Self> {
+    pub fn create(
+        conn: &Connection,
+        data: &LookupCreatePayload,
+        user_id: Option<i32>,
+    ) -> Result<Self> {
         conn.execute(
             "INSERT INTO downtime_reasons (name) VALUES (?1)",
             params![data.name],
         )?;
         let id = conn.last_insert_rowid() as i32;
-        Ok(DowntimeReason {
+        let record = DowntimeReason {
             id,
             name: data.name.clone(),
-        })
+        };
+        AuditLog::created(conn, user_id, "downtime_reasons", id, &record)?;
+        Ok(record)
     }
 
-    pub fn update(&mut self, conn: &Connection, data: &LookupPayload) -> Result<()> {
+    pub fn update(
+        &mut self,
+        conn: &Connection,
+        data: &LookupPayload,
+        user_id: Option<i32>,
+    ) -> Result<()> {
+        let before = AuditLog::snapshot(self);
         if let Some(name) = &data.name {
             conn.execute(
                 "UPDATE downtime_reasons SET name = ?1 WHERE id = ?2",
@@ -351,14 +503,16 @@ impl DowntimeReason {
             )?;
             self.name = name.clone();
         }
+        AuditLog::updated(conn, user_id, "downtime_reasons", self.id, &before, self)?;
         Ok(())
     }
 
-    pub fn delete(&self, conn: &Connection) -> Result<()> {
+    pub fn delete(&self, conn: &Connection, user_id: Option<i32>) -> Result<()> {
         conn.execute(
             "DELETE FROM downtime_reasons WHERE id = ?1",
             params![self.id],
         )?;
+        AuditLog::deleted(conn, user_id, "downtime_reasons", self.id, self)?;
         Ok(())
     }
 
@@ -403,20 +557,29 @@ impl FlagReason {
         conn: &Connection,
         data: &LookupCreatePayload,
         section_id: Option<i32>,
+        user_id: Option<i32>,
     ) -> Result<Self> {
         conn.execute(
             "INSERT INTO flag_reasons (name, section_id) VALUES (?1, ?2)",
             params![data.name, section_id],
         )?;
         let id = conn.last_insert_rowid() as i32;
-        Ok(FlagReason {
+        let record = FlagReason {
             id,
             name: data.name.clone(),
             section_id,
-        })
+        };
+        AuditLog::created(conn, user_id, "flag_reasons", id, &record)?;
+        Ok(record)
     }
 
-    pub fn update(&mut self, conn: &Connection, data: &LookupPayload) -> Result<()> {
+    pub fn update(
+        &mut self,
+        conn: &Connection,
+        data: &LookupPayload,
+        user_id: Option<i32>,
+    ) -> Result<()> {
+        let before = AuditLog::snapshot(self);
         if let Some(name) = &data.name {
             conn.execute(
                 "UPDATE flag_reasons SET name = ?1 WHERE id = ?2",
@@ -424,11 +587,13 @@ impl FlagReason {
             )?;
             self.name = name.clone();
         }
+        AuditLog::updated(conn, user_id, "flag_reasons", self.id, &before, self)?;
         Ok(())
     }
 
-    pub fn delete(&self, conn: &Connection) -> Result<()> {
+    pub fn delete(&self, conn: &Connection, user_id: Option<i32>) -> Result<()> {
         conn.execute("DELETE FROM flag_reasons WHERE id = ?1", params![self.id])?;
+        AuditLog::deleted(conn, user_id, "flag_reasons", self.id, self)?;
         Ok(())
     }
 
@@ -477,19 +642,39 @@ impl FlagReason {
 }
 
 impl POCodeSection {
-    pub fn create(conn: &Connection, po_code_id: i32, section_id: i32) -> Result<()> {
+    pub fn create(
+        conn: &Connection,
+        po_code_id: i32,
+        section_id: i32,
+        user_id: Option<i32>,
+    ) -> Result<()> {
         conn.execute(
             "INSERT INTO po_code_sections (po_code_id, section_id) VALUES (?1, ?2)",
             params![po_code_id, section_id],
         )?;
+        let record = POCodeSection {
+            po_code_id,
+            section_id,
+        };
+        AuditLog::created(conn, user_id, "po_code_sections", po_code_id, &record)?;
         Ok(())
     }
 
-    pub fn delete(conn: &Connection, po_code_id: i32, section_id: i32) -> Result<()> {
+    pub fn delete(
+        conn: &Connection,
+        po_code_id: i32,
+        section_id: i32,
+        user_id: Option<i32>,
+    ) -> Result<()> {
         conn.execute(
             "DELETE FROM po_code_sections WHERE po_code_id = ?1 AND section_id = ?2",
             params![po_code_id, section_id],
         )?;
+        let record = POCodeSection {
+            po_code_id,
+            section_id,
+        };
+        AuditLog::deleted(conn, user_id, "po_code_sections", po_code_id, &record)?;
         Ok(())
     }
 
@@ -536,21 +721,33 @@ impl POCode {
         Ok(false)
     }
 
-    pub fn create(conn: &Connection, data: &LookupCreatePayload) -> Result<Self> {
+    pub fn create(
+        conn: &Connection,
+        data: &LookupCreatePayload,
+        user_id: Option<i32>,
+    ) -> Result<Self> {
         let now = chrono::Utc::now().naive_utc().to_string();
         conn.execute(
             "INSERT INTO po_codes (name, created_at) VALUES (?1, ?2, ?3)",
             params![data.name, "", now],
         )?;
         let id = conn.last_insert_rowid() as i32;
-        Ok(POCode {
+        let record = POCode {
             id,
             name: data.name.clone(),
             created_at: now,
-        })
+        };
+        AuditLog::created(conn, user_id, "po_codes", id, &record)?;
+        Ok(record)
     }
 
-    pub fn update(&mut self, conn: &Connection, data: &LookupPayload) -> Result<()> {
+    pub fn update(
+        &mut self,
+        conn: &Connection,
+        data: &LookupPayload,
+        user_id: Option<i32>,
+    ) -> Result<()> {
+        let before = AuditLog::snapshot(self);
         if let Some(name) = &data.name {
             conn.execute(
                 "UPDATE po_codes SET name = ?1 WHERE id = ?2",
@@ -558,11 +755,13 @@ impl POCode {
             )?;
             self.name = name.clone();
         }
+        AuditLog::updated(conn, user_id, "po_codes", self.id, &before, self)?;
         Ok(())
     }
 
-    pub fn delete(&self, conn: &Connection) -> Result<()> {
+    pub fn delete(&self, conn: &Connection, user_id: Option<i32>) -> Result<()> {
         conn.execute("DELETE FROM po_codes WHERE id = ?1", params![self.id])?;
+        AuditLog::deleted(conn, user_id, "po_codes", self.id, self)?;
         Ok(())
     }
 
@@ -1,6 +1,8 @@
 use rusqlite::{Connection, OptionalExtension, Result, params, params_from_iter};
 use serde::{Deserialize, Serialize};
 
+use crate::backend::models::AuditLog;
+
 #[derive(Debug, Serialize)]
 pub struct Permission {
     pub id: i32,
@@ -42,7 +44,7 @@ impl Permission {
         tx.commit()?;
         Ok(())
     }
-    pub fn create(conn: &Connection, p: &PermissionPayload) -> Result<Self> {
+    pub fn create(conn: &Connection, p: &PermissionPayload, user_id: Option<i32>) -> Result<Self> {
         conn.execute(
             "INSERT OR IGNORE INTO content_type (model) VALUES (?1)",
             params![p.model],
@@ -98,7 +100,7 @@ impl Permission {
                 conn.execute(&sql_rp, params_from_iter(params_vec))?;
             }
 
-            return Ok(Permission {
+            let record = Permission {
                 id: keep_id,
                 role_id: p.role_id,
                 model: p.model.clone(),
@@ -106,7 +108,17 @@ impl Permission {
                 can_read: p.can_read,
                 can_update: p.can_update,
                 can_delete: p.can_delete,
-            });
+            };
+            AuditLog::record(
+                conn,
+                user_id,
+                "permissions",
+                keep_id,
+                "update",
+                None,
+                Some(&AuditLog::snapshot(&record)),
+            )?;
+            return Ok(record);
         }
 
         conn.execute(
@@ -128,7 +140,7 @@ impl Permission {
             params![p.role_id, id],
         )?;
 
-        Ok(Permission {
+        let record = Permission {
             id,
             role_id: p.role_id,
             model: p.model.clone(),
@@ -136,10 +148,18 @@ impl Permission {
             can_read: p.can_read,
             can_update: p.can_update,
             can_delete: p.can_delete,
-        })
+        };
+        AuditLog::created(conn, user_id, "permissions", id, &record)?;
+        Ok(record)
     }
 
-    pub fn update(&mut self, conn: &Connection, p: &PermissionUpdatePayload) -> Result<()> {
+    pub fn update(
+        &mut self,
+        conn: &Connection,
+        p: &PermissionUpdatePayload,
+        user_id: Option<i32>,
+    ) -> Result<()> {
+        let before = AuditLog::snapshot(self);
         if let Some(can_create) = p.can_create {
             conn.execute(
                 "UPDATE permissions SET can_create = ?1 WHERE id = ?2",
@@ -168,15 +188,17 @@ impl Permission {
             )?;
             self.can_delete = can_delete;
         }
+        AuditLog::updated(conn, user_id, "permissions", self.id, &before, self)?;
         Ok(())
     }
 
-    pub fn delete(conn: &Connection, id: i32) -> Result<()> {
+    pub fn delete(conn: &Connection, id: i32, user_id: Option<i32>) -> Result<()> {
         conn.execute(
             "DELETE FROM role_permissions WHERE permission_id = ?1",
             params![id],
         )?;
         conn.execute("DELETE FROM permissions WHERE id = ?1", params![id])?;
+        AuditLog::record(conn, user_id, "permissions", id, "delete", None, None)?;
         Ok(())
     }
 
@@ -1,6 +1,8 @@
 use rusqlite::{Connection, Result, params};
 use serde::{Deserialize, Serialize};
 
+use crate::backend::models::AuditLog;
+
 #[derive(Debug, Serialize)]
 pub struct Role {
     pub id: i32,
@@ -9,20 +11,28 @@ pub struct Role {
 }
 
 impl Role {
-    pub fn create(conn: &Connection, r: &RolePayload) -> Result<Self> {
+    pub fn create(conn: &Connection, r: &RolePayload, user_id: Option<i32>) -> Result<Self> {
         conn.execute(
             "INSERT INTO roles (name, description) VALUES (?1, ?2)",
             params![r.name, r.description],
         )?;
         let id = conn.last_insert_rowid() as i32;
-        Ok(Role {
+        let record = Role {
             id,
             name: r.name.clone(),
             description: r.description.clone(),
-        })
+        };
+        AuditLog::created(conn, user_id, "roles", id, &record)?;
+        Ok(record)
     }
 
-    pub fn update(&mut self, conn: &Connection, r: &RoleUpdatePayload) -> Result<()> {
+    pub fn update(
+        &mut self,
+        conn: &Connection,
+        r: &RoleUpdatePayload,
+        user_id: Option<i32>,
+    ) -> Result<()> {
+        let before = AuditLog::snapshot(self);
         if let Some(name) = &r.name {
             conn.execute(
                 "UPDATE roles SET name = ?1 WHERE id = ?2",
@@ -37,10 +47,11 @@ impl Role {
             )?;
             self.description = Some(description.clone());
         }
+        AuditLog::updated(conn, user_id, "roles", self.id, &before, self)?;
         Ok(())
     }
 
-    pub fn delete(&self, conn: &mut Connection) -> Result<()> {
+    pub fn delete(&self, conn: &mut Connection, user_id: Option<i32>) -> Result<()> {
         let tx = conn.transaction()?;
 
         tx.execute(
@@ -65,6 +76,7 @@ impl Role {
 
         tx.execute("DELETE FROM roles WHERE id = ?1", params![self.id])?;
 
+        AuditLog::deleted(&tx, user_id, "roles", self.id, self)?;
         tx.commit()?;
         Ok(())
     }
@@ -1,6 +1,6 @@
 use rusqlite::{params, Connection, Result};
 use serde::{Serialize, Deserialize};
-use crate::backend::models::FilterResponse;
+use crate::backend::models::{AuditLog, BatchPattern, FilterResponse};
 
 #[derive(Debug, Serialize)]
 pub struct Section {
@@ -10,6 +10,8 @@ pub struct Section {
     pub user_count: i32,
     pub job_count: i32,
     pub po_code_ids: Vec<i32>,
+    /// Output batch format for the section's machines; `None` uses the default
+    pub batch_pattern: Option<String>,
 }
 #[derive(Debug, Serialize)]
 pub struct SectionStats {
@@ -22,12 +24,15 @@ pub struct SectionStats {
 #[derive(Deserialize)]
 pub struct SectionCreatePayload {
     pub name: String,
+    pub batch_pattern: Option<String>,
 }
 
 #[derive(Deserialize)]
 pub struct SectionPayload {
     pub id: i32,
     pub name: Option<String>,
+    /// Blank resets to the default pattern
+    pub batch_pattern: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -45,20 +50,33 @@ pub struct SectionFilterPayload {
     pub page: Option<String>,
 }
 
+/// A trimmed pattern that parses, or `None` for blank.
+fn batch_pattern(value: &Option<String>) -> Result<Option<String>> {
+    match value.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+        Some(pattern) => {
+            BatchPattern::parse(pattern)?;
+            Ok(Some(pattern.to_string()))
+        }
+        None => Ok(None),
+    }
+}
+
 impl Section {
-    pub fn create(conn: &Connection, data: &SectionCreatePayload) -> Result<Self> {
+    pub fn create(conn: &Connection, data: &SectionCreatePayload, user_id: Option<i32>) -> Result<Self> {
+        let batch_pattern = batch_pattern(&data.batch_pattern)?;
         conn.execute(
-            "INSERT INTO sections (name) VALUES (?1)",
-            params![data.name],
+            "INSERT INTO sections (name, batch_pattern) VALUES (?1, ?2)",
+            params![data.name, batch_pattern],
         )?;
         let id = conn.last_insert_rowid() as i32;
         
         let mut stmt = conn.prepare(
-            "SELECT s.*,
+            "SELECT s.id, s.name,
              COUNT(DISTINCT m.id) as machine_count,
              COUNT(DISTINCT us.user_id) as user_count,
              COUNT(DISTINCT j.id) as job_count,
-             GROUP_CONCAT(DISTINCT spc.po_code_id) as po_code_ids
+             GROUP_CONCAT(DISTINCT spc.po_code_id) as po_code_ids,
+             s.batch_pattern
              FROM sections s
              LEFT JOIN machines m ON s.id = m.section_id
              LEFT JOIN user_sections us ON s.id = us.section_id
@@ -68,7 +86,7 @@ impl Section {
              GROUP BY s.id"
         )?;
         
-        stmt.query_row(params![id], |row| {
+        let section = stmt.query_row(params![id], |row| {
             let po_code_ids_str: Option<String> = row.get(5)?;
             let po_code_ids = po_code_ids_str
                 .map(|s| s.split(',')
@@ -83,23 +101,34 @@ impl Section {
                 user_count: row.get(3)?,
                 job_count: row.get(4)?,
                 po_code_ids,
+                batch_pattern: row.get(6)?,
             })
-        })
+        })?;
+        AuditLog::created(conn, user_id, "sections", id, &section)?;
+        Ok(section)
     }
 
-    pub fn update(&mut self, conn: &Connection, data: &SectionPayload) -> Result<()> {
+    pub fn update(&mut self, conn: &Connection, data: &SectionPayload, user_id: Option<i32>) -> Result<()> {
+        let before = AuditLog::snapshot(self);
         if let Some(name) = &data.name {
             conn.execute("UPDATE sections SET name = ?1 WHERE id = ?2", params![name, self.id])?;
             self.name = name.clone();
         }
-        
+        if data.batch_pattern.is_some() {
+            let batch_pattern = batch_pattern(&data.batch_pattern)?;
+            conn.execute("UPDATE sections SET batch_pattern = ?1 WHERE id = ?2", params![batch_pattern, self.id])?;
+            self.batch_pattern = batch_pattern;
+        }
+
+        AuditLog::updated(conn, user_id, "sections", self.id, &before, self)?;
         Ok(())
     }
 
-    pub fn delete(&self, conn: &Connection) -> Result<()> {
+    pub fn delete(&self, conn: &Connection, user_id: Option<i32>) -> Result<()> {
         conn.execute("DELETE FROM user_sections WHERE section_id = ?1", params![self.id])?;
         conn.execute("UPDATE machines SET section_id = NULL WHERE section_id = ?1", params![self.id])?;
         conn.execute("DELETE FROM sections WHERE id = ?1", params![self.id])?;
+        AuditLog::deleted(conn, user_id, "sections", self.id, self)?;
         Ok(())
     }
 
@@ -114,11 +143,12 @@ impl Section {
 
     pub fn find_by_id(conn: &Connection, id: i32) -> Result<Self> {
         let mut stmt = conn.prepare(
-            "SELECT s.*,
+            "SELECT s.id, s.name,
              COUNT(DISTINCT m.id) as machine_count,
              COUNT(DISTINCT us.user_id) as user_count,
              COUNT(DISTINCT j.id) as job_count,
-             GROUP_CONCAT(DISTINCT spc.po_code_id) as po_code_ids
+             GROUP_CONCAT(DISTINCT spc.po_code_id) as po_code_ids,
+             s.batch_pattern
              FROM sections s
              LEFT JOIN machines m ON s.id = m.section_id
              LEFT JOIN user_sections us ON s.id = us.section_id
@@ -143,17 +173,19 @@ impl Section {
                 user_count: row.get(3)?,
                 job_count: row.get(4)?,
                 po_code_ids,
+                batch_pattern: row.get(6)?,
             })
         })
     }
 
     pub fn all(conn: &Connection) -> Result<Vec<Self>> {
         let mut stmt = conn.prepare(
-            "SELECT s.*,
+            "SELECT s.id, s.name,
              COUNT(DISTINCT m.id) as machine_count,
              COUNT(DISTINCT us.user_id) as user_count,
              COUNT(DISTINCT j.id) as job_count,
-             GROUP_CONCAT(DISTINCT spc.po_code_id) as po_code_ids
+             GROUP_CONCAT(DISTINCT spc.po_code_id) as po_code_ids,
+             s.batch_pattern
              FROM sections s
              LEFT JOIN machines m ON s.id = m.section_id
              LEFT JOIN user_sections us ON s.id = us.section_id
@@ -177,6 +209,7 @@ impl Section {
                 user_count: row.get(3)?,
                 job_count: row.get(4)?,
                 po_code_ids,
+                batch_pattern: row.get(6)?,
             })
         })?.collect::<Result<Vec<_>, _>>()?;
         Ok(sections)
@@ -198,11 +231,12 @@ impl Section {
 
     pub fn filter(conn: &Connection, filter: &SectionFilterPayload) -> Result<FilterResponse<Self>> {
         let mut count_query = "SELECT COUNT(*) FROM sections s WHERE 1=1".to_string();
-        let mut data_query = "SELECT s.*,
+        let mut data_query = "SELECT s.id, s.name,
                              COUNT(DISTINCT m.id) as machine_count,
                              COUNT(DISTINCT us.user_id) as user_count,
                              COUNT(DISTINCT j.id) as job_count,
-                             GROUP_CONCAT(DISTINCT spc.po_code_id) as po_code_ids
+                             GROUP_CONCAT(DISTINCT spc.po_code_id) as po_code_ids,
+                             s.batch_pattern
                              FROM sections s
                              LEFT JOIN machines m ON s.id = m.section_id
                              LEFT JOIN user_sections us ON s.id = us.section_id
@@ -277,6 +311,7 @@ impl Section {
                 user_count: row.get(3)?,
                 job_count: row.get(4)?,
                 po_code_ids,
+                batch_pattern: row.get(6)?,
             })
         })?;
 
@@ -288,7 +323,8 @@ impl Section {
         })
     }
     
-    pub fn update_po_codes(&self, conn: &Connection, data: &SectionPoCodesPayload) -> Result<()> {
+    pub fn update_po_codes(&self, conn: &Connection, data: &SectionPoCodesPayload, user_id: Option<i32>) -> Result<()> {
+        let before = AuditLog::snapshot(self);
         conn.execute("DELETE FROM po_code_sections WHERE section_id = ?1", params![self.id])?;
         
         for po_code_id in &data.po_code_ids {
@@ -297,7 +333,10 @@ impl Section {
                 params![self.id, po_code_id],
             )?;
         }
-        
+
+        let mut after = before.clone();
+        after["po_code_ids"] = AuditLog::snapshot(&data.po_code_ids);
+        AuditLog::record(conn, user_id, "sections", self.id, "update", Some(&before), Some(&after))?;
         Ok(())
     }
 }
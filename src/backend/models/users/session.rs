@@ -0,0 +1,99 @@
+use crate::backend::models::AuditLog;
+use rusqlite::{Connection, Result, params};
+use serde::{Deserialize, Serialize};
+
+/// A server-side login session. The session key itself is never exposed.
+#[derive(Debug, Serialize)]
+pub struct UserSession {
+    pub id: i32,
+    pub user_id: Option<i32>,
+    pub user_name: Option<String>,
+    pub created_at: String,
+    pub last_seen: String,
+    pub expires_at: String,
+}
+
+#[derive(Deserialize)]
+pub struct SessionFilterPayload {
+    pub user_id: Option<String>,
+}
+
+/// Revokes a single session by `id`, or every session of `user_id`.
+#[derive(Deserialize)]
+pub struct SessionRevokePayload {
+    pub id: Option<i32>,
+    pub user_id: Option<i32>,
+}
+
+impl UserSession {
+    /// Signed-in sessions the store would still accept: not expired and seen within
+    /// `idle_timeout`.
+    pub fn active(
+        conn: &Connection,
+        filter: &SessionFilterPayload,
+        idle_timeout: chrono::Duration,
+    ) -> Result<Vec<Self>> {
+        let now = chrono::Local::now();
+        let idle_cutoff = (now - idle_timeout).format("%Y-%m-%d %H:%M:%S").to_string();
+        let now = now.format("%Y-%m-%d %H:%M:%S").to_string();
+        let mut query = "SELECT s.id, s.user_id, u.full_name, s.created_at, s.last_seen, s.expires_at FROM sessions s LEFT JOIN users u ON s.user_id = u.id WHERE s.expires_at > ?1 AND s.last_seen > ?2 AND s.user_id IS NOT NULL".to_string();
+        let mut params_vec: Vec<&dyn rusqlite::ToSql> = vec![&now, &idle_cutoff];
+
+        let user_id = filter.user_id.as_ref().and_then(|v| v.parse::<i32>().ok());
+        if let Some(user_id) = &user_id {
+            query.push_str(" AND s.user_id = ?3");
+            params_vec.push(user_id);
+        }
+        query.push_str(" ORDER BY s.last_seen DESC");
+
+        let mut stmt = conn.prepare(&query)?;
+        let rows = stmt.query_map(params_vec.as_slice(), Self::from_row)?;
+        rows.collect()
+    }
+
+    pub fn revoke(
+        conn: &Connection,
+        payload: &SessionRevokePayload,
+        user_id: Option<i32>,
+    ) -> Result<usize> {
+        let sessions: Vec<Self> = match (payload.id, payload.user_id) {
+            (Some(id), _) => {
+                let mut stmt = conn.prepare(
+                    "SELECT s.id, s.user_id, u.full_name, s.created_at, s.last_seen, s.expires_at FROM sessions s LEFT JOIN users u ON s.user_id = u.id WHERE s.id = ?1",
+                )?;
+                stmt.query_map(params![id], Self::from_row)?
+                    .collect::<Result<_>>()?
+            }
+            // Every session of the user, idle ones included
+            (None, Some(owner)) => {
+                let mut stmt = conn.prepare(
+                    "SELECT s.id, s.user_id, u.full_name, s.created_at, s.last_seen, s.expires_at FROM sessions s LEFT JOIN users u ON s.user_id = u.id WHERE s.user_id = ?1",
+                )?;
+                stmt.query_map(params![owner], Self::from_row)?
+                    .collect::<Result<_>>()?
+            }
+            (None, None) => {
+                return Err(rusqlite::Error::InvalidParameterName(
+                    "Either id or user_id is required".into(),
+                ));
+            }
+        };
+
+        for session in &sessions {
+            conn.execute("DELETE FROM sessions WHERE id = ?1", params![session.id])?;
+            AuditLog::deleted(conn, user_id, "sessions", session.id, session)?;
+        }
+        Ok(sessions.len())
+    }
+
+    fn from_row(row: &rusqlite::Row) -> Result<Self> {
+        Ok(UserSession {
+            id: row.get(0)?,
+            user_id: row.get(1)?,
+            user_name: row.get(2)?,
+            created_at: row.get(3)?,
+            last_seen: row.get(4)?,
+            expires_at: row.get(5)?,
+        })
+    }
+}
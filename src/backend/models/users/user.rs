@@ -1,7 +1,52 @@
-use chrono::Local;
+use argon2::{
+    Argon2,
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+};
+use chrono::{Duration, Local};
 use rusqlite::{Connection, Result, params};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::env;
+
+use crate::backend::models::AuditLog;
+
+const DEFAULT_MAX_FAILED_LOGINS: i32 = 5;
+const DEFAULT_LOCKOUT_MINUTES: i64 = 15;
+
+/// Hashes a password with Argon2id and a random salt, returning a PHC string.
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::encode_b64(&rand::random::<[u8; 16]>())
+        .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))
+}
+
+/// Returns `(matches, needs_rehash)`. Anything that is not a PHC string is treated as a
+/// legacy unsalted SHA-256 hex digest and always needs rehashing.
+fn verify_password(password: &str, stored: &str) -> (bool, bool) {
+    match PasswordHash::new(stored) {
+        Ok(parsed) => (
+            Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok(),
+            false,
+        ),
+        Err(_) => {
+            let mut hasher = Sha256::new();
+            hasher.update(password);
+            (format!("{:x}", hasher.finalize()) == stored, true)
+        }
+    }
+}
+
+fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+    env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
 
 #[derive(Debug, Serialize)]
 pub struct User {
@@ -100,11 +145,9 @@ impl User {
         Ok(total_count)
     }
 
-    pub fn create(conn: &Connection, u: &UserCreatePayload) -> Result<User> {
+    pub fn create(conn: &Connection, u: &UserCreatePayload, user_id: Option<i32>) -> Result<User> {
         let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-        let mut hasher = Sha256::new();
-        hasher.update(u.password.as_ref().expect("Password is required"));
-        let hashed_password = format!("{:x}", hasher.finalize());
+        let hashed_password = hash_password(u.password.as_ref().expect("Password is required"))?;
         conn.execute(
             "INSERT INTO users (full_name, staffid, password, phone_number, status, role_id, page_id, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
             params![u.full_name, u.staffid, hashed_password, u.phone_number, u.status, u.role_id, u.page_id, now, now],
@@ -131,7 +174,7 @@ impl User {
             )
             .unwrap_or_else(|_| "".to_string());
 
-        Ok(User {
+        let record = User {
             id,
             full_name: u.full_name.clone(),
             staffid: u.staffid.clone(),
@@ -144,10 +187,18 @@ impl User {
             created_at: now.clone(),
             updated_at: now.clone(),
             section_ids,
-        })
+        };
+        AuditLog::created(conn, user_id, "users", id, &record)?;
+        Ok(record)
     }
 
-    pub fn update(&mut self, conn: &Connection, u: &UserPayload) -> Result<()> {
+    pub fn update(
+        &mut self,
+        conn: &Connection,
+        u: &UserPayload,
+        user_id: Option<i32>,
+    ) -> Result<()> {
+        let before = AuditLog::snapshot(self);
         let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
         if let Some(full_name) = &u.full_name {
             conn.execute(
@@ -164,11 +215,10 @@ impl User {
             self.staffid = staffid.clone();
         }
         if let Some(password) = &u.password {
-            let mut hasher = Sha256::new();
-            hasher.update(password);
-            let hashed_password = format!("{:x}", hasher.finalize());
+            // A password reset by an admin also lifts any lockout
+            let hashed_password = hash_password(password)?;
             conn.execute(
-                "UPDATE users SET password = ?1 WHERE id = ?2",
+                "UPDATE users SET password = ?1, failed_logins = 0, locked_until = NULL WHERE id = ?2",
                 params![hashed_password, self.id],
             )?;
             self.password = hashed_password;
@@ -224,15 +274,30 @@ impl User {
         )?;
         self.updated_at = now;
         self.password = "******".to_string();
+        // Passwords are masked on both sides, so flag the change explicitly
+        let mut after = AuditLog::snapshot(self);
+        if u.password.is_some() {
+            after["password"] = "changed".into();
+        }
+        AuditLog::record(
+            conn,
+            user_id,
+            "users",
+            self.id,
+            "update",
+            Some(&before),
+            Some(&after),
+        )?;
         Ok(())
     }
 
-    pub fn delete(&self, conn: &Connection) -> Result<()> {
+    pub fn delete(&self, conn: &Connection, user_id: Option<i32>) -> Result<()> {
         conn.execute(
             "DELETE FROM user_sections WHERE user_id = ?1",
             params![self.id],
         )?;
         conn.execute("DELETE FROM users WHERE id = ?1", params![self.id])?;
+        AuditLog::deleted(conn, user_id, "users", self.id, self)?;
         Ok(())
     }
 
@@ -318,29 +383,81 @@ impl User {
     }
 
     pub fn signin(conn: &Connection, payload: &SigninPayload) -> Result<SignInResponse> {
-        let mut hasher = Sha256::new();
-        hasher.update(&payload.password);
-        let hashed_password = format!("{:x}", hasher.finalize());
+        let (id, stored_password, mut failed_logins, locked_until): (
+            i32,
+            Option<String>,
+            i32,
+            Option<String>,
+        ) = conn.query_row(
+            "SELECT id, password, failed_logins, locked_until FROM users WHERE lower(staffid) = lower(?1)",
+            params![&payload.staffid],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )?;
+
+        let now = Local::now();
+        let now_str = now.format("%Y-%m-%d %H:%M:%S").to_string();
+        if let Some(until) = &locked_until {
+            if until.as_str() > now_str.as_str() {
+                return Err(rusqlite::Error::InvalidParameterName(format!(
+                    "Account locked after too many failed attempts, try again after {}",
+                    until
+                )));
+            }
+            // The previous lockout has expired, start counting again
+            failed_logins = 0;
+        }
+
+        let (matches, needs_rehash) = stored_password
+            .as_deref()
+            .map(|stored| verify_password(&payload.password, stored))
+            .unwrap_or((false, false));
+
+        if !matches {
+            failed_logins += 1;
+            let locked_until = (failed_logins
+                >= env_or("MAX_FAILED_LOGINS", DEFAULT_MAX_FAILED_LOGINS))
+            .then(|| {
+                (now + Duration::minutes(env_or("LOGIN_LOCKOUT_MINUTES", DEFAULT_LOCKOUT_MINUTES)))
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string()
+            });
+            conn.execute(
+                "UPDATE users SET failed_logins = ?1, locked_until = ?2 WHERE id = ?3",
+                params![failed_logins, locked_until, id],
+            )?;
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+
+        if needs_rehash {
+            conn.execute(
+                "UPDATE users SET password = ?1 WHERE id = ?2",
+                params![hash_password(&payload.password)?, id],
+            )?;
+        }
+        conn.execute(
+            "UPDATE users SET failed_logins = 0, locked_until = NULL WHERE id = ?1",
+            params![id],
+        )?;
+
         let mut stmt = conn.prepare(
             "SELECT u.staffid, r.name AS role, u.id, u.page_id
             FROM users u
             LEFT JOIN roles r ON u.role_id = r.id
-            WHERE lower(u.staffid) = lower(?1) AND u.password = ?2",
+            WHERE u.id = ?1",
         )?;
-        let signin_response =
-            stmt.query_row(params![&payload.staffid, &hashed_password], |row| {
-                Ok(SignInResponse {
-                    staffid: row.get(0)?,
-                    role: row.get::<_, String>(1).unwrap_or_default(),
-                    whois: format!(
-                        "{} {}",
-                        row.get::<_, String>(0)?,
-                        row.get::<_, String>(1).unwrap_or_default()
-                    ),
-                    id: row.get(2)?,
-                    page_id: row.get(3)?,
-                })
-            })?;
+        let signin_response = stmt.query_row(params![id], |row| {
+            Ok(SignInResponse {
+                staffid: row.get(0)?,
+                role: row.get::<_, String>(1).unwrap_or_default(),
+                whois: format!(
+                    "{} {}",
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1).unwrap_or_default()
+                ),
+                id: row.get(2)?,
+                page_id: row.get(3)?,
+            })
+        })?;
         Ok(signin_response)
     }
 
@@ -539,3 +656,47 @@ pub struct UserSectionPayload {
     pub user_id: i32,
     pub section_id: i32,
 }
+
+#[test]
+fn test_signin_upgrades_legacy_hash_and_locks_out() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::manager::migrations::migrate_up(&mut conn, None).unwrap();
+
+    conn.execute("INSERT INTO roles (id, name) VALUES (1, 'admin')", [])
+        .unwrap();
+    let mut hasher = Sha256::new();
+    hasher.update("secret");
+    conn.execute(
+        "INSERT INTO users (full_name, staffid, password, status, role_id, page_id) VALUES ('Op', 'OP1', ?1, 'active', 1, '')",
+        params![format!("{:x}", hasher.finalize())],
+    )
+    .unwrap();
+    let signin = |password: &str| {
+        User::signin(
+            &conn,
+            &SigninPayload {
+                staffid: "op1".to_string(),
+                password: password.to_string(),
+            },
+        )
+    };
+
+    assert!(signin("secret").is_ok());
+    let stored: String = conn
+        .query_row(
+            "SELECT password FROM users WHERE staffid = 'OP1'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert!(stored.starts_with("$argon2id$"));
+    assert!(signin("secret").is_ok());
+
+    for _ in 0..DEFAULT_MAX_FAILED_LOGINS {
+        assert!(signin("wrong").is_err());
+    }
+    assert!(matches!(
+        signin("secret"),
+        Err(rusqlite::Error::InvalidParameterName(_))
+    ));
+}
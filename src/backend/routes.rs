@@ -137,6 +137,27 @@ pub fn init_routes(
             ),
     );
 
+    // Session routes
+    cfg.service(
+        web::scope("/api/sessions")
+            .service(
+                web::resource("")
+                    .wrap(CheckRead {
+                        model: "sessions",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::get().to(all_sessions)),
+            )
+            .service(
+                web::resource("/revoke")
+                    .wrap(CheckDelete {
+                        model: "sessions",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::delete().to(revoke_sessions)),
+            ),
+    );
+
     // Auth routes
     cfg.service(
         web::scope("/auth")
@@ -195,6 +216,22 @@ pub fn init_routes(
                         conn_data: conn_data.clone(),
                     })
                     .route(web::get().to(filter_jobs_with_input_rolls)),
+            )
+            .service(
+                web::resource("/close")
+                    .wrap(CheckUpdate {
+                        model: "jobs",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::post().to(close_job)),
+            )
+            .service(
+                web::resource("/summary")
+                    .wrap(CheckRead {
+                        model: "jobs",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::get().to(job_summary)),
             ),
     );
 
@@ -264,6 +301,72 @@ pub fn init_routes(
                         conn_data: conn_data.clone(),
                     })
                     .route(web::post().to(end_input_roll)),
+            )
+            .service(
+                web::resource("/end-with-remnant")
+                    .wrap(CheckUpdate {
+                        model: "input_rolls",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::post().to(end_input_roll_with_remnant)),
+            )
+            .service(
+                web::resource("/bom-check")
+                    .wrap(CheckRead {
+                        model: "input_rolls",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::get().to(check_bom)),
+            ),
+    );
+
+    // BOM alternative routes
+    cfg.service(
+        web::scope("/api/bom-alternatives")
+            .service(
+                web::resource("/filter")
+                    .wrap(CheckRead {
+                        model: "bom_alternatives",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::get().to(filter_bom_alternatives)),
+            )
+            .service(
+                web::resource("/create")
+                    .wrap(CheckCreate {
+                        model: "bom_alternatives",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::post().to(create_bom_alternative)),
+            )
+            .service(
+                web::resource("/delete")
+                    .wrap(CheckDelete {
+                        model: "bom_alternatives",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::delete().to(delete_bom_alternative)),
+            ),
+    );
+
+    // Remnant routes
+    cfg.service(
+        web::scope("/api/remnants")
+            .service(
+                web::resource("/available")
+                    .wrap(CheckRead {
+                        model: "remnants",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::get().to(available_remnants)),
+            )
+            .service(
+                web::resource("/label")
+                    .wrap(CheckRead {
+                        model: "remnants",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::get().to(remnant_label)),
             ),
     );
 
@@ -310,6 +413,14 @@ pub fn init_routes(
                     })
                     .route(web::get().to(filter_output_rolls)),
             )
+            .service(
+                web::resource("/available")
+                    .wrap(CheckRead {
+                        model: "output_rolls",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::get().to(available_output_rolls)),
+            )
             .service(
                 web::resource("/details")
                     .wrap(CheckRead {
@@ -317,6 +428,51 @@ pub fn init_routes(
                         conn_data: conn_data.clone(),
                     })
                     .route(web::get().to(output_roll_details)),
+            )
+            .service(
+                web::resource("/label")
+                    .wrap(CheckRead {
+                        model: "output_rolls",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::get().to(output_roll_label)),
+            )
+            .service(
+                web::resource("/disposition")
+                    .wrap(CheckUpdate {
+                        model: "qa",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::post().to(dispose_output_roll)),
+            )
+            .service(
+                web::resource("/dispositions")
+                    .wrap(CheckRead {
+                        model: "qa",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::get().to(output_roll_dispositions)),
+            ),
+    );
+
+    // Roll genealogy routes
+    cfg.service(
+        web::scope("/api/trace")
+            .service(
+                web::resource("/backward")
+                    .wrap(CheckRead {
+                        model: "output_rolls",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::get().to(trace_backward)),
+            )
+            .service(
+                web::resource("/forward")
+                    .wrap(CheckRead {
+                        model: "output_rolls",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::get().to(trace_forward)),
             ),
     );
 
@@ -347,6 +503,14 @@ pub fn init_routes(
                     })
                     .route(web::put().to(update_downtime)),
             )
+            .service(
+                web::resource("/close")
+                    .wrap(CheckUpdate {
+                        model: "downtimes",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::post().to(close_downtime)),
+            )
             .service(
                 web::resource("/delete")
                     .wrap(CheckDelete {
@@ -365,6 +529,39 @@ pub fn init_routes(
             ),
     );
 
+    // OEE routes
+    cfg.service(
+        web::scope("/api/oee").service(
+            web::resource("")
+                .wrap(CheckRead {
+                    model: "oee",
+                    conn_data: conn_data.clone(),
+                })
+                .route(web::get().to(oee_report)),
+        ),
+    );
+
+    // Mass balance routes
+    cfg.service(
+        web::scope("/api/reports/mass-balance")
+            .service(
+                web::resource("")
+                    .wrap(CheckRead {
+                        model: "mass_balance",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::get().to(mass_balance_report)),
+            )
+            .service(
+                web::resource("/job")
+                    .wrap(CheckRead {
+                        model: "mass_balance",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::get().to(mass_balance_job)),
+            ),
+    );
+
     // Scrap routes
     cfg.service(
         web::scope("/api/scraps")
@@ -556,6 +753,38 @@ pub fn init_routes(
                     })
                     .route(web::delete().to(delete_shift)),
             )
+            .service(
+                web::resource("/shifts/current")
+                    .wrap(CheckRead {
+                        model: "shifts",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::get().to(current_shift)),
+            )
+            .service(
+                web::resource("/holidays")
+                    .wrap(CheckRead {
+                        model: "shifts",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::get().to(all_holidays)),
+            )
+            .service(
+                web::resource("/holidays/create")
+                    .wrap(CheckCreate {
+                        model: "shifts",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::post().to(create_holiday)),
+            )
+            .service(
+                web::resource("/holidays/delete")
+                    .wrap(CheckDelete {
+                        model: "shifts",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::delete().to(delete_holiday)),
+            )
             .service(
                 web::resource("/colours")
                     .wrap(CheckRead {
@@ -937,6 +1166,71 @@ pub fn init_routes(
         ),
     );
 
+    // Audit routes
+    cfg.service(
+        web::scope("/api/audit").service(
+            web::resource("/filter")
+                .wrap(CheckRead {
+                    model: "audit",
+                    conn_data: conn_data.clone(),
+                })
+                .route(web::get().to(filter_audit_logs)),
+        ),
+    );
+
+    // SAP outbox routes
+    cfg.service(
+        web::scope("/api/sap-outbox")
+            .service(
+                web::resource("/filter")
+                    .wrap(CheckRead {
+                        model: "sap_outbox",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::get().to(filter_sap_outbox)),
+            )
+            .service(
+                web::resource("/stats")
+                    .wrap(CheckRead {
+                        model: "sap_outbox",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::get().to(sap_outbox_stats)),
+            )
+            .service(
+                web::resource("/retry")
+                    .wrap(CheckUpdate {
+                        model: "sap_outbox",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::put().to(retry_sap_outbox)),
+            )
+            .service(
+                web::resource("/cancel")
+                    .wrap(CheckUpdate {
+                        model: "sap_outbox",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::put().to(cancel_sap_outbox)),
+            )
+            .service(
+                web::resource("/reversals")
+                    .wrap(CheckRead {
+                        model: "sap_outbox",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::get().to(filter_sap_reversals)),
+            )
+            .service(
+                web::resource("/reversals/repost")
+                    .wrap(CheckUpdate {
+                        model: "sap_outbox",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::put().to(repost_sap_reversal)),
+            ),
+    );
+
     // Others routes
     cfg.service(
         web::scope("")
@@ -948,7 +1242,14 @@ pub fn init_routes(
                     })
                     .route(web::get().to(home_page)),
             )
-            .service(web::resource("/upload/{name}").route(web::post().to(upload_app)))
+            .service(
+                web::resource("/upload/{name}")
+                    .wrap(CheckCreate {
+                        model: "releases",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::post().to(upload_app)),
+            )
             .service(web::resource("/download/{name}").route(web::get().to(download_app)))
             .service(
                 web::resource("/users")
@@ -1054,6 +1355,14 @@ pub fn init_routes(
                     })
                     .route(web::get().to(consumable_page)),
             )
+            .service(
+                web::resource("/sap-outbox")
+                    .wrap(CheckRead {
+                        model: "sap_outbox",
+                        conn_data: conn_data.clone(),
+                    })
+                    .route(web::get().to(sap_outbox_page)),
+            )
             .service(
                 web::resource("/settings")
                     .wrap(CheckRead {
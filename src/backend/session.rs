@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::{env, fs, io, path::Path};
+
+use actix_session::storage::{
+    CookieSessionStore, LoadError, SaveError, SessionKey, SessionStore, UpdateError,
+    generate_session_key,
+};
+use actix_web::cookie::{Key, time::Duration};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::Local;
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{OptionalExtension, params};
+
+const KEY_FILE: &str = "data/session.key";
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Loads the cookie signing key from `SESSION_KEY` (base64, at least 64 bytes) or from
+/// `data/session.key`, generating and persisting a new key on first start.
+pub fn load_or_create_key() -> io::Result<Key> {
+    let decode = |encoded: &str| -> io::Result<Key> {
+        let bytes = STANDARD.decode(encoded.trim()).map_err(io::Error::other)?;
+        Key::try_from(bytes.as_slice()).map_err(io::Error::other)
+    };
+
+    if let Ok(encoded) = env::var("SESSION_KEY") {
+        return decode(&encoded);
+    }
+    if Path::new(KEY_FILE).exists() {
+        return decode(&fs::read_to_string(KEY_FILE)?);
+    }
+
+    let key = Key::generate();
+    fs::create_dir_all("data")?;
+    // Anyone who can read the key can forge a session, so only the service user may
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(KEY_FILE)?
+        .write_all(STANDARD.encode(key.master()).as_bytes())?;
+    log::info!("Generated new session key in {}", KEY_FILE);
+    Ok(key)
+}
+
+#[derive(Clone)]
+pub struct SessionSettings {
+    pub server_side: bool,
+    pub cookie_secure: bool,
+    pub ttl: chrono::Duration,
+    pub idle_timeout: chrono::Duration,
+}
+
+impl SessionSettings {
+    /// `SESSION_STORE=sqlite` keeps sessions in the local database so they can be listed and
+    /// revoked; anything else keeps the signed cookie store.
+    pub fn from_env() -> Self {
+        let minutes = |key: &str, default: i64| {
+            env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<i64>().ok())
+                .filter(|v| *v > 0)
+                .unwrap_or(default)
+        };
+        SessionSettings {
+            server_side: env::var("SESSION_STORE").is_ok_and(|v| v.eq_ignore_ascii_case("sqlite")),
+            cookie_secure: env::var("SESSION_COOKIE_SECURE").is_ok_and(|v| v == "true" || v == "1"),
+            ttl: chrono::Duration::minutes(minutes("SESSION_TTL_MINUTES", 12 * 60)),
+            idle_timeout: chrono::Duration::minutes(minutes("SESSION_IDLE_MINUTES", 60)),
+        }
+    }
+
+    pub fn store(&self, pool: &Pool<SqliteConnectionManager>) -> AppSessionStore {
+        if self.server_side {
+            AppSessionStore::Sqlite(SqliteSessionStore {
+                pool: pool.clone(),
+                ttl: self.ttl,
+                idle_timeout: self.idle_timeout,
+            })
+        } else {
+            AppSessionStore::Cookie(CookieSessionStore::default())
+        }
+    }
+}
+
+/// Session store picked at startup from [`SessionSettings`].
+pub enum AppSessionStore {
+    Cookie(CookieSessionStore),
+    Sqlite(SqliteSessionStore),
+}
+
+impl SessionStore for AppSessionStore {
+    async fn load(
+        &self,
+        session_key: &SessionKey,
+    ) -> Result<Option<HashMap<String, String>>, LoadError> {
+        match self {
+            AppSessionStore::Cookie(store) => store.load(session_key).await,
+            AppSessionStore::Sqlite(store) => store.load(session_key).await,
+        }
+    }
+
+    async fn save(
+        &self,
+        session_state: HashMap<String, String>,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        match self {
+            AppSessionStore::Cookie(store) => store.save(session_state, ttl).await,
+            AppSessionStore::Sqlite(store) => store.save(session_state, ttl).await,
+        }
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: HashMap<String, String>,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        match self {
+            AppSessionStore::Cookie(store) => store.update(session_key, session_state, ttl).await,
+            AppSessionStore::Sqlite(store) => store.update(session_key, session_state, ttl).await,
+        }
+    }
+
+    async fn update_ttl(&self, session_key: &SessionKey, ttl: &Duration) -> anyhow::Result<()> {
+        match self {
+            AppSessionStore::Cookie(store) => store.update_ttl(session_key, ttl).await,
+            AppSessionStore::Sqlite(store) => store.update_ttl(session_key, ttl).await,
+        }
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> anyhow::Result<()> {
+        match self {
+            AppSessionStore::Cookie(store) => store.delete(session_key).await,
+            AppSessionStore::Sqlite(store) => store.delete(session_key).await,
+        }
+    }
+}
+
+/// Server-side sessions in the `sessions` table. A session ends `ttl` after it was created or
+/// after `idle_timeout` without a request, whichever comes first.
+#[derive(Clone)]
+pub struct SqliteSessionStore {
+    pool: Pool<SqliteConnectionManager>,
+    ttl: chrono::Duration,
+    idle_timeout: chrono::Duration,
+}
+
+fn user_id_of(state: &HashMap<String, String>) -> Option<i32> {
+    state.get("user_id").and_then(|v| v.parse().ok())
+}
+
+impl SqliteSessionStore {
+    fn now() -> String {
+        Local::now().format(TIME_FORMAT).to_string()
+    }
+
+    fn idle_cutoff(&self) -> String {
+        (Local::now() - self.idle_timeout)
+            .format(TIME_FORMAT)
+            .to_string()
+    }
+
+    async fn load(
+        &self,
+        session_key: &SessionKey,
+    ) -> Result<Option<HashMap<String, String>>, LoadError> {
+        let conn = self.pool.get().map_err(|e| LoadError::Other(e.into()))?;
+        let now = Self::now();
+        let state: Option<String> = conn
+            .query_row(
+                "SELECT state FROM sessions WHERE session_key = ?1 AND expires_at > ?2 AND last_seen > ?3",
+                params![session_key.as_ref(), now, self.idle_cutoff()],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| LoadError::Other(e.into()))?;
+
+        match state {
+            Some(state) => {
+                conn.execute(
+                    "UPDATE sessions SET last_seen = ?1 WHERE session_key = ?2",
+                    params![now, session_key.as_ref()],
+                )
+                .map_err(|e| LoadError::Other(e.into()))?;
+                serde_json::from_str(&state)
+                    .map(Some)
+                    .map_err(|e| LoadError::Deserialization(e.into()))
+            }
+            None => {
+                conn.execute(
+                    "DELETE FROM sessions WHERE session_key = ?1",
+                    params![session_key.as_ref()],
+                )
+                .map_err(|e| LoadError::Other(e.into()))?;
+                Ok(None)
+            }
+        }
+    }
+
+    async fn save(
+        &self,
+        session_state: HashMap<String, String>,
+        _ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        let conn = self.pool.get().map_err(|e| SaveError::Other(e.into()))?;
+        let state = serde_json::to_string(&session_state)
+            .map_err(|e| SaveError::Serialization(e.into()))?;
+        let now = Self::now();
+        let expires_at = (Local::now() + self.ttl).format(TIME_FORMAT).to_string();
+
+        conn.execute(
+            "DELETE FROM sessions WHERE expires_at <= ?1 OR last_seen <= ?2",
+            params![now, self.idle_cutoff()],
+        )
+        .map_err(|e| SaveError::Other(e.into()))?;
+
+        let session_key = generate_session_key();
+        conn.execute(
+            "INSERT INTO sessions (session_key, user_id, state, created_at, last_seen, expires_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![session_key.as_ref(), user_id_of(&session_state), state, now, now, expires_at],
+        )
+        .map_err(|e| SaveError::Other(e.into()))?;
+        Ok(session_key)
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: HashMap<String, String>,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        let conn = self.pool.get().map_err(|e| UpdateError::Other(e.into()))?;
+        let state = serde_json::to_string(&session_state)
+            .map_err(|e| UpdateError::Serialization(e.into()))?;
+        let updated = conn
+            .execute(
+                "UPDATE sessions SET state = ?1, user_id = ?2, last_seen = ?3 WHERE session_key = ?4",
+                params![state, user_id_of(&session_state), Self::now(), session_key.as_ref()],
+            )
+            .map_err(|e| UpdateError::Other(e.into()))?;
+
+        if updated > 0 {
+            return Ok(session_key);
+        }
+        // The session was revoked or expired after this request loaded it. Start an empty one so
+        // the stale state cannot log the user back in.
+        drop(conn);
+        self.save(HashMap::new(), ttl).await.map_err(|e| match e {
+            SaveError::Serialization(e) => UpdateError::Serialization(e),
+            SaveError::Other(e) => UpdateError::Other(e),
+        })
+    }
+
+    async fn update_ttl(&self, session_key: &SessionKey, _ttl: &Duration) -> anyhow::Result<()> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE sessions SET last_seen = ?1 WHERE session_key = ?2",
+            params![Self::now(), session_key.as_ref()],
+        )?;
+        Ok(())
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> anyhow::Result<()> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "DELETE FROM sessions WHERE session_key = ?1",
+            params![session_key.as_ref()],
+        )?;
+        Ok(())
+    }
+}
+
+#[actix_web::test]
+async fn test_revoked_session_is_not_restored_by_update() {
+    use crate::backend::models::{SessionRevokePayload, UserSession};
+
+    let pool = Pool::builder()
+        .max_size(1)
+        .build(SqliteConnectionManager::memory())
+        .unwrap();
+    {
+        let mut conn = pool.get().unwrap();
+        crate::manager::migrations::migrate_up(&mut conn, None).unwrap();
+        conn.execute(
+            "INSERT INTO users (id, full_name) VALUES (1, 'Operator')",
+            [],
+        )
+        .unwrap();
+    }
+    let store = SqliteSessionStore {
+        pool: pool.clone(),
+        ttl: chrono::Duration::hours(1),
+        idle_timeout: chrono::Duration::hours(1),
+    };
+    let ttl = Duration::hours(1);
+    let state = HashMap::from([("user_id".to_string(), "1".to_string())]);
+
+    let key = store.save(state.clone(), &ttl).await.unwrap();
+    assert_eq!(store.load(&key).await.unwrap(), Some(state.clone()));
+    let key = store.update(key, state.clone(), &ttl).await.unwrap();
+    assert_eq!(store.load(&key).await.unwrap(), Some(state.clone()));
+
+    // Listed only while the store would still accept it
+    let filter = crate::backend::models::SessionFilterPayload { user_id: None };
+    let listed = |idle| UserSession::active(&pool.get().unwrap(), &filter, idle);
+    assert_eq!(listed(chrono::Duration::hours(1)).unwrap().len(), 1);
+    assert!(listed(chrono::Duration::zero()).unwrap().is_empty());
+
+    let revoked = UserSession::revoke(
+        &pool.get().unwrap(),
+        &SessionRevokePayload {
+            id: None,
+            user_id: Some(1),
+        },
+        None,
+    )
+    .unwrap();
+    assert_eq!(revoked, 1);
+    assert_eq!(store.load(&key).await.unwrap(), None);
+
+    // A request that loaded the session before the revocation writes its state back
+    let key = store.update(key, state, &ttl).await.unwrap();
+    let restored = store.load(&key).await.unwrap().unwrap();
+    assert!(user_id_of(&restored).is_none());
+    let logged_in: i32 = pool
+        .get()
+        .unwrap()
+        .query_row(
+            "SELECT COUNT(*) FROM sessions WHERE user_id IS NOT NULL",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(logged_in, 0);
+}
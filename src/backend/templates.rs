@@ -1,13 +1,19 @@
 use actix_files::NamedFile;
 use actix_multipart::Multipart;
 use actix_session::Session;
-use actix_web::{HttpRequest, HttpResponse, Responder, Result};
+use actix_web::{HttpRequest, HttpResponse, Responder, Result, web};
 use askama::Template;
 use futures_util::StreamExt as _;
 use rand::distr::{Alphanumeric, SampleString};
-use std::{fs::File, io::Write, path::Path};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use serde_json::json;
+use std::path::Path;
 use tokio::fs;
 
+use crate::backend::models::AuditLog;
+use crate::hardware::update::{self, MANIFEST_NAME, ReleaseManifest, SERVICE_FILE, UPDATER_FILE};
+
 #[derive(Template)]
 #[template(path = "home.html")]
 struct HomeTemplate {
@@ -65,6 +71,11 @@ struct SectionTemplate {
     user_name: String,
 }
 #[derive(Template)]
+#[template(path = "admin/sap-outbox.html")]
+struct SapOutboxTemplate {
+    user_name: String,
+}
+#[derive(Template)]
 #[template(path = "production.html")]
 struct ProductionTemplate {
     user_name: String,
@@ -173,6 +184,16 @@ pub async fn section_page(session: Session) -> impl Responder {
         .body(SectionTemplate { user_name }.render().unwrap())
 }
 
+pub async fn sap_outbox_page(session: Session) -> impl Responder {
+    let user_name = session
+        .get::<String>("user_name")
+        .unwrap_or(None)
+        .unwrap_or_default();
+    HttpResponse::Ok()
+        .content_type("text/html")
+        .body(SapOutboxTemplate { user_name }.render().unwrap())
+}
+
 pub async fn production_page(session: Session) -> impl Responder {
     let user_name = session
         .get::<String>("user_name")
@@ -258,46 +279,78 @@ pub async fn logout(session: Session) -> impl Responder {
         .finish()
 }
 
+/// Files a release is made of; nothing else is served from or written to `static/release`.
+const RELEASE_FILES: [&str; 3] = [SERVICE_FILE, UPDATER_FILE, MANIFEST_NAME];
+
 pub async fn download_app(req: HttpRequest) -> Result<NamedFile> {
-    let query: String = req.match_info().get("name").unwrap_or("").to_string();
-    let file_path = format!("./static/release/{}", query);
-    let file_path = if Path::new(&file_path).exists() {
-        file_path
-    } else {
-        "./static/empty.txt".to_string()
-    };
+    let name = req.match_info().get("name").unwrap_or("");
+    let file_path = format!("./static/release/{}", name);
+    if !RELEASE_FILES.contains(&name) || !Path::new(&file_path).exists() {
+        return Err(actix_web::error::ErrorNotFound("No such release file"));
+    }
     Ok(NamedFile::open_async(file_path).await?)
 }
 
-pub async fn upload_app(req: HttpRequest, mut payload: Multipart) -> Result<HttpResponse> {
+pub async fn upload_app(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    req: HttpRequest,
+    mut payload: Multipart,
+    session: Session,
+) -> Result<HttpResponse> {
     let name = req.match_info().get("name").unwrap_or("").to_string();
-    if name.is_empty() {
-        return Ok(HttpResponse::BadRequest().body("Missing file name"));
+    if !RELEASE_FILES.contains(&name.as_str()) {
+        return Ok(HttpResponse::BadRequest().body(format!(
+            "Release files are {}",
+            RELEASE_FILES.join(", ")
+        )));
     }
 
-    let item = match payload.next().await {
+    let mut field = match payload.next().await {
         Some(Ok(field)) => field,
         Some(Err(_)) | None => return Ok(HttpResponse::BadRequest().body("No file uploaded")),
     };
-    let mut field = item;
-
-    let filepath = format!("./static/release/{}", name);
-    let mut f = match File::create(&filepath) {
-        Ok(f) => f,
-        Err(_) => return Ok(HttpResponse::InternalServerError().body("Failed to create file")),
-    };
-
+    let mut data = Vec::new();
     while let Some(chunk) = field.next().await {
-        let data = match chunk {
-            Ok(c) => c,
+        match chunk {
+            Ok(c) => data.extend_from_slice(&c),
             Err(_) => {
                 return Ok(HttpResponse::InternalServerError().body("Failed to read file chunk"));
             }
+        }
+    }
+
+    // Agents verify the signature anyway, but a manifest they would reject is refused here
+    if name == MANIFEST_NAME {
+        let manifest: ReleaseManifest = match serde_json::from_slice(&data) {
+            Ok(m) => m,
+            Err(e) => return Ok(HttpResponse::BadRequest().body(format!("Bad manifest: {}", e))),
         };
-        if f.write_all(&data).is_err() {
-            return Ok(HttpResponse::InternalServerError().body("Failed to write file"));
+        if let Ok(key) = update::public_key()
+            && let Err(e) = manifest.verify(&key)
+        {
+            return Ok(HttpResponse::BadRequest().body(e));
         }
     }
 
+    let filepath = format!("./static/release/{}", name);
+    let tmp = format!("{}.part", filepath);
+    let written = fs::create_dir_all("./static/release").await.is_ok()
+        && fs::write(&tmp, &data).await.is_ok()
+        && fs::rename(&tmp, &filepath).await.is_ok();
+    if !written {
+        return Ok(HttpResponse::InternalServerError().body("Failed to write file"));
+    }
+
+    let user_id: Option<i32> = session.get("user_id").unwrap_or(None);
+    if let Ok(conn) = conn_data.get() {
+        let _ = AuditLog::created(
+            &conn,
+            user_id,
+            "releases",
+            0,
+            &json!({ "file": name, "sha256": update::sha256_hex(&data), "size": data.len() }),
+        );
+    }
+
     Ok(HttpResponse::Ok().body("Upload successful"))
 }
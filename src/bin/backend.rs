@@ -2,10 +2,47 @@ use actix_web::web;
 use dotenvy::dotenv;
 use insignia_mes::backend::app::start_backend;
 use insignia_mes::manager::db::{connect_local_db, init_local_db};
-use insignia_mes::sap::{sync_material_codes, sync_process_orders, sync_scrap_data};
+use insignia_mes::manager::migrations::{
+    current_version, latest_version, migrate_down, migrate_up, status,
+};
+use insignia_mes::sap::{
+    SapSettings, gateway_from_env, process_sap_outbox, sync_material_codes, sync_process_orders,
+    sync_scrap_data,
+};
 use log::LevelFilter;
 use std::fs::OpenOptions;
-use std::{fs, process};
+use std::{env, fs, process};
+
+fn migrate_command(db_file: &str, args: &[String]) -> rusqlite::Result<()> {
+    let mut conn = rusqlite::Connection::open(db_file)?;
+    let target = args.get(1).and_then(|v| v.parse::<i32>().ok());
+    match args.first().map(|s| s.as_str()) {
+        Some("status") | None => {
+            println!(
+                "Schema version: {} (latest {})",
+                current_version(&conn)?,
+                latest_version()
+            );
+            for m in status(&conn)? {
+                match m.applied_at {
+                    Some(at) => println!("  [x] {:>3} {} (applied {})", m.version, m.name, at),
+                    None => println!("  [ ] {:>3} {}", m.version, m.name),
+                }
+            }
+        }
+        Some("up") => {
+            let applied = migrate_up(&mut conn, target)?;
+            println!("Applied {} migration(s): {:?}", applied.len(), applied);
+        }
+        Some("down") => {
+            let target = target.unwrap_or(current_version(&conn)? - 1);
+            let reverted = migrate_down(&mut conn, target)?;
+            println!("Reverted {} migration(s): {:?}", reverted.len(), reverted);
+        }
+        Some(other) => println!("Unknown migrate command '{}', use status|up|down", other),
+    }
+    Ok(())
+}
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -13,6 +50,12 @@ async fn main() -> std::io::Result<()> {
     let db_file = "data/local.db";
     let pid_file = "data/backend.pid";
     fs::create_dir_all("data").expect("Failed to create backups directory");
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(|s| s.as_str()) == Some("migrate") {
+        return migrate_command(db_file, &args[1..]).map_err(std::io::Error::other);
+    }
+
     let access_log = OpenOptions::new()
         .create(true)
         .append(true)
@@ -69,8 +112,14 @@ async fn main() -> std::io::Result<()> {
     let local_pool =
         connect_local_db(db_file).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
 
+    let sap_settings = SapSettings::load().map_err(|e| {
+        log::error!("Invalid SAP settings: {}", e);
+        std::io::Error::other(e)
+    })?;
+    let sap = gateway_from_env(sap_settings);
     let local_pool_clone = local_pool.clone();
     let local_pool_clone2 = local_pool.clone();
+    let sap_clone = sap.clone();
     tokio::spawn(async move {
         loop {
             if let Err(e) = sync_scrap_data(&local_pool_clone).await {
@@ -81,18 +130,31 @@ async fn main() -> std::io::Result<()> {
     });
     tokio::spawn(async move {
         loop {
-            if let Err(e) = sync_process_orders(&local_pool_clone2).await {
+            if let Err(e) = sync_process_orders(&local_pool_clone2, sap_clone.as_ref()).await {
                 log::warn!("Failed to sync process order: {}", e);
             }
-            if let Err(e) = sync_material_codes(&local_pool_clone2).await {
+            if let Err(e) = sync_material_codes(&local_pool_clone2, sap_clone.as_ref()).await {
                 log::warn!("Failed to sync materials: {}", e);
             }
             tokio::time::sleep(tokio::time::Duration::from_secs(600)).await;
         }
     });
 
+    let local_pool_clone3 = local_pool.clone();
+    let sap_clone2 = sap.clone();
+    tokio::spawn(async move {
+        loop {
+            match process_sap_outbox(&local_pool_clone3, sap_clone2.as_ref()).await {
+                Ok(0) => {}
+                Ok(posted) => log::info!("Posted {} queued SAP message(s)", posted),
+                Err(e) => log::warn!("Failed to process SAP outbox: {}", e),
+            }
+            tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
+        }
+    });
+
     let local_pool_data = web::Data::new(local_pool);
-    start_backend(local_pool_data, 80)
+    start_backend(local_pool_data, web::Data::from(sap), 80)
         .await
         .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
 }
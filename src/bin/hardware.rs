@@ -4,6 +4,7 @@ use anyhow::Context;
 use actix_files::Files;
 use std::{io, fs, process};
 use actix_web::{web, App, HttpServer};
+use insignia_mes::hardware::devices::DeviceManager;
 use insignia_mes::hardware::routes::init_routes;
 
 
@@ -18,6 +19,8 @@ async fn main() -> io::Result<()> {
         }
     }
     let _ = fs::write(pid_file, process::id().to_string()).context("Failed to write PID file");
+    // Open the scale and scanner now so the first weight request finds them connected
+    DeviceManager::global();
     let server = HttpServer::new(move || {
         App::new()
             .wrap(Cors::permissive())
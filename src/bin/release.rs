@@ -0,0 +1,87 @@
+use insignia_mes::hardware::update::{
+    MANIFEST_NAME, ReleaseFile, ReleaseManifest, SERVICE_FILE, UPDATER_FILE,
+};
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::{env, fs, process};
+
+const USAGE: &str = "usage:
+  release keygen <key-file>
+  release sign <key-file> <version> <agent-exe> <updater-exe> [out-dir]
+
+keygen writes a new signing key and prints the public key to build the hardware agent with
+(INSIGNIA_RELEASE_PUBLIC_KEY). sign copies the binaries to out-dir (static/release by
+default) with a signed manifest.json.";
+
+fn keygen(key_file: &str) -> Result<(), String> {
+    if Path::new(key_file).exists() {
+        return Err(format!("{} already exists", key_file));
+    }
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+        .map_err(|_| "Failed to generate a key".to_string())?;
+    let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).map_err(|e| e.to_string())?;
+    fs::write(key_file, pkcs8.as_ref()).map_err(|e| e.to_string())?;
+    println!(
+        "Signing key written to {}, keep it off the server",
+        key_file
+    );
+    println!(
+        "INSIGNIA_RELEASE_PUBLIC_KEY={}",
+        base64::Engine::encode(
+            &base64::prelude::BASE64_STANDARD,
+            key_pair.public_key().as_ref()
+        )
+    );
+    Ok(())
+}
+
+fn sign(key_file: &str, version: &str, app: &str, updater: &str, out: &str) -> Result<(), String> {
+    let pkcs8 = fs::read(key_file).map_err(|e| format!("{}: {}", key_file, e))?;
+    let key_pair =
+        Ed25519KeyPair::from_pkcs8(&pkcs8).map_err(|e| format!("{}: {}", key_file, e))?;
+    let app = fs::read(app).map_err(|e| format!("{}: {}", app, e))?;
+    let updater = fs::read(updater).map_err(|e| format!("{}: {}", updater, e))?;
+
+    let mut manifest = ReleaseManifest {
+        version: version.trim().to_string(),
+        files: BTreeMap::from([
+            (SERVICE_FILE.to_string(), ReleaseFile::of(&app)),
+            (UPDATER_FILE.to_string(), ReleaseFile::of(&updater)),
+        ]),
+        signature: String::new(),
+    };
+    manifest.sign(&key_pair);
+
+    let out = Path::new(out);
+    fs::create_dir_all(out).map_err(|e| e.to_string())?;
+    fs::write(out.join(SERVICE_FILE), &app).map_err(|e| e.to_string())?;
+    fs::write(out.join(UPDATER_FILE), &updater).map_err(|e| e.to_string())?;
+    let json = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+    fs::write(out.join(MANIFEST_NAME), json).map_err(|e| e.to_string())?;
+    println!("Release {} signed into {}", manifest.version, out.display());
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args.as_slice() {
+        ["keygen", key_file] => keygen(key_file),
+        ["sign", key_file, version, app, updater] => {
+            sign(key_file, version, app, updater, "static/release")
+        }
+        ["sign", key_file, version, app, updater, out] => {
+            sign(key_file, version, app, updater, out)
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
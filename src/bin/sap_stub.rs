@@ -0,0 +1,11 @@
+use dotenvy::dotenv;
+use insignia_mes::sap::stub::run_stub_server;
+use std::env;
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    dotenv().ok();
+    let addr = env::var("SAP_STUB_ADDR").unwrap_or_else(|_| "127.0.0.1:8089".to_string());
+    println!("SAP stub listening on http://{}", addr);
+    run_stub_server(&addr).await
+}
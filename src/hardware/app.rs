@@ -1,63 +1,151 @@
+use crate::hardware::devices::{Device, DeviceManager};
+use crate::hardware::printer::{JobStatus, PrintQueue, PrinterSettings};
+use crate::hardware::simulated::queue_scan;
+use crate::hardware::update;
 use crate::manager::config::*;
 use actix_web::{HttpResponse, Responder, web};
 use base64::prelude::*;
 use chrono::Local;
-use reqwest;
+use futures_util::StreamExt;
 use serde::Deserialize;
 use serde_json::json;
-use std::{fs, path::Path};
+use std::fs;
+use std::time::Duration;
 #[cfg(windows)]
 use std::{ffi::CString, ptr};
+use tokio::sync::broadcast::error::RecvError;
 #[cfg(windows)]
 use winapi::um::processthreadsapi::{CreateProcessA, PROCESS_INFORMATION, STARTUPINFOA};
 
-pub async fn run_update(
-    base_url: web::Query<std::collections::HashMap<String, String>>,
-) -> impl Responder {
-    let result = (|| -> Result<String, Box<dyn std::error::Error>> {
-        let base_url = base_url
-            .get("base_url")
-            .ok_or("Missing base_url parameter")?;
-        if !Path::new("update.exe").exists() {
-            download_update(base_url)?;
+#[derive(Deserialize)]
+pub struct UpdateQuery {
+    /// Release server, used when `UPDATE_BASE_URL` is not set
+    pub base_url: Option<String>,
+}
+
+/// Starts `update.exe`, which stops the agent and swaps in `new_app.exe`.
+#[cfg(windows)]
+fn launch_updater() -> Result<(), String> {
+    let cmd = CString::new(update::UPDATER_EXE).map_err(|e| e.to_string())?;
+    let started = unsafe {
+        let mut startup_info: STARTUPINFOA = std::mem::zeroed();
+        let mut process_info: PROCESS_INFORMATION = std::mem::zeroed();
+        startup_info.cb = std::mem::size_of::<STARTUPINFOA>() as u32;
+        CreateProcessA(
+            ptr::null(),
+            cmd.as_ptr() as *mut i8,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            0,
+            0,
+            ptr::null_mut(),
+            ptr::null(),
+            &mut startup_info,
+            &mut process_info,
+        )
+    };
+    if started == 0 {
+        return Err(format!(
+            "Failed to start {}: {}",
+            update::UPDATER_EXE,
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+/// There is no updater to swap the binary outside Windows.
+#[cfg(not(windows))]
+fn launch_updater() -> Result<(), String> {
+    Err(UPDATER_UNSUPPORTED.to_string())
+}
+
+const UPDATER_UNSUPPORTED: &str = "Automatic updates are only supported on Windows";
+
+pub async fn run_update(query: web::Query<UpdateQuery>) -> impl Responder {
+    let result = web::block(move || -> Result<String, String> {
+        if !cfg!(windows) {
+            return Err(UPDATER_UNSUPPORTED.to_string());
         }
-        download_app(base_url)?;
+        let base_url = update::base_url(query.base_url.as_deref())?;
+        let manifest = update::stage_update(&base_url)?;
         fs::write(
             "data/last_update.txt",
             Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
-        )?;
-        #[cfg(windows)]
-        unsafe {
-            let cmd = CString::new("update.exe").unwrap();
-            let mut startup_info: STARTUPINFOA = std::mem::zeroed();
-            let mut process_info: PROCESS_INFORMATION = std::mem::zeroed();
-            startup_info.cb = std::mem::size_of::<STARTUPINFOA>() as u32;
-            CreateProcessA(
-                ptr::null(),
-                cmd.as_ptr() as *mut i8,
-                ptr::null_mut(),
-                ptr::null_mut(),
-                0,
-                0,
-                ptr::null_mut(),
-                ptr::null(),
-                &mut startup_info,
-                &mut process_info,
-            );
-        }
-        #[cfg(not(windows))]
-        {
-            // On non-Windows, just skip process creation or implement alternative if needed
-        }
-        Ok("Update started - application will restart shortly...".to_string())
-    })();
+        )
+        .map_err(|e| e.to_string())?;
+        launch_updater()?;
+        Ok(format!(
+            "Updating to {} - application will restart shortly...",
+            manifest.version
+        ))
+    })
+    .await;
 
     match result {
-        Ok(msg) => HttpResponse::Ok().body(msg),
+        Ok(Ok(msg)) => HttpResponse::Ok().body(msg),
+        Ok(Err(e)) => HttpResponse::InternalServerError().body(format!("Update failed: {}", e)),
         Err(e) => HttpResponse::InternalServerError().body(format!("Update failed: {}", e)),
     }
 }
 
+pub async fn rollback_update() -> impl Responder {
+    if !cfg!(windows) {
+        return HttpResponse::Ok()
+            .json(json!({ "success": false, "message": UPDATER_UNSUPPORTED }));
+    }
+    let staged = web::block(|| {
+        update::stage_rollback().and_then(|version| {
+            launch_updater()?;
+            Ok(version)
+        })
+    })
+    .await;
+    match staged {
+        Ok(Ok(version)) => HttpResponse::Ok().json(json!({
+            "success": true,
+            "message": format!("Rolling back to {} - application will restart shortly...", version),
+            "version": version
+        })),
+        Ok(Err(e)) => HttpResponse::Ok().json(json!({ "success": false, "message": e })),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "success": false,
+            "message": e.to_string()
+        })),
+    }
+}
+
+/// Installed version against the signed release on the server.
+pub async fn get_version(query: web::Query<UpdateQuery>) -> HttpResponse {
+    let installed = update::installed_version();
+    let previous = update::previous_version();
+    let available = web::block(move || {
+        update::base_url(query.base_url.as_deref()).and_then(|url| update::fetch_manifest(&url))
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|result| result);
+
+    match available {
+        Ok(manifest) => HttpResponse::Ok().json(json!({
+            "success": true,
+            "installed": installed,
+            "available": manifest.version,
+            "update_available":
+                update::compare_versions(&manifest.version, &installed) == std::cmp::Ordering::Greater,
+            "previous": previous
+        })),
+        Err(e) => HttpResponse::Ok().json(json!({
+            "success": false,
+            "message": e,
+            "installed": installed,
+            "available": null,
+            "update_available": false,
+            "previous": previous
+        })),
+    }
+}
+
 pub async fn get_last_update() -> impl Responder {
     match fs::read_to_string("data/last_update.txt") {
         Ok(content) => HttpResponse::Ok().body(content),
@@ -65,97 +153,114 @@ pub async fn get_last_update() -> impl Responder {
     }
 }
 
-pub fn download_app(base_url: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let url = format!("{}/download/service", base_url);
-    let mut response = reqwest::blocking::get(url)?;
-    if !response.status().is_success() {
-        return Err(format!("Failed to download app: {}", response.status()).into());
+#[derive(Deserialize)]
+pub struct PrintData {
+    /// Base64 PDF, ZPL or EPL
+    pub pdf_data: String,
+    /// Printer profile name; the default profile when missing
+    pub printer: Option<String>,
+    /// `false` returns as soon as the job is queued
+    pub wait: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct PrinterQuery {
+    pub name: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct PrintJobQuery {
+    pub id: u64,
+}
+
+#[derive(Deserialize)]
+pub struct WaitQuery {
+    /// Seconds to wait, capped at two minutes
+    pub timeout: Option<u64>,
+}
+
+impl WaitQuery {
+    fn duration(&self, default: u64) -> Duration {
+        Duration::from_secs(self.timeout.unwrap_or(default).clamp(1, 120))
     }
-    let mut file = fs::File::create("new_app.exe")?;
-    response.copy_to(&mut file)?;
-    Ok(())
 }
 
-pub fn download_update(base_url: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let url = format!("{}/download/update", base_url);
-    let mut response = reqwest::blocking::get(url)?;
-    if !response.status().is_success() {
-        return Err(format!("Failed to download updater: {}", response.status()).into());
+/// Waits for the next code from the scanner instead of holding the port for the request.
+pub async fn scan_qrcode(query: web::Query<WaitQuery>) -> impl Responder {
+    let timeout = query.duration(30);
+    match web::block(move || DeviceManager::global().wait_for_scan(timeout)).await {
+        Ok(Ok(scan)) => HttpResponse::Ok().json(json!({
+            "success": true,
+            "message": "QR code scanned successfully",
+            "data": scan.data
+        })),
+        Ok(Err(message)) => HttpResponse::RequestTimeout().json(json!({
+            "success": false,
+            "message": message,
+            "data": null
+        })),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
-    let mut file = fs::File::create("update.exe")?;
-    response.copy_to(&mut file)?;
-    Ok(())
 }
 
 #[derive(Deserialize)]
-pub struct PrintData {
-    pub pdf_data: String,
+pub struct SimulatedScan {
+    pub code: String,
 }
 
-pub async fn scan_qrcode() -> impl Responder {
-    let scanner_config = AppConfig::scanner();
-
-    if scanner_config.port_name.is_empty() {
+/// Queues a code for the simulated scanner, for testing the scan flow without hardware.
+pub async fn simulate_scan(data: web::Json<SimulatedScan>) -> HttpResponse {
+    if AppConfig::scanner().kind != DeviceKind::Simulated {
         return HttpResponse::BadRequest().json(json!({
             "success": false,
-            "message": "Scanner configuration not found",
-            "data": null
+            "message": "The scanner is not simulated"
         }));
     }
+    if data.code.trim().is_empty() {
+        return HttpResponse::BadRequest().json(json!({
+            "success": false,
+            "message": "Code is required"
+        }));
+    }
+    queue_scan(&data.code);
+    HttpResponse::Ok().json(json!({
+        "success": true,
+        "message": "Scan queued"
+    }))
+}
 
-    match serialport::new(&scanner_config.port_name, scanner_config.baud_rate)
-        .timeout(std::time::Duration::from_millis(1000))
-        .open()
-    {
-        Ok(mut port) => {
-            let mut buf = [0u8; 1024];
-            let mut temp = Vec::new();
-
-            loop {
-                match port.read(&mut buf) {
-                    Ok(n) if n > 0 => {
-                        temp.extend_from_slice(&buf[..n]);
-                        while let Some(pos) = temp.iter().position(|&b| b == b'\n' || b == b'\r') {
-                            let line_bytes = temp.drain(..=pos).collect::<Vec<u8>>();
-                            let line = String::from_utf8_lossy(&line_bytes)
-                                .trim_end_matches(&['\r', '\n'][..])
-                                .to_string();
-
-                            if !line.is_empty() {
-                                return HttpResponse::Ok().json(json!({
-                                    "success": true,
-                                    "message": "QR code scanned successfully",
-                                    "data": line
-                                }));
-                            }
-                        }
-                    }
-                    Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
-                    Err(_) => {
-                        return HttpResponse::BadRequest().json(json!({
-                            "success": false,
-                            "message": "Scanner disconnected",
-                            "data": null
-                        }));
-                    }
-                    Ok(_) => continue,
-                }
+/// Server-sent events: device status, live weights and scans as they happen.
+pub async fn device_events() -> HttpResponse {
+    let manager = DeviceManager::global();
+    let receiver = manager.subscribe();
+    let initial = futures_util::stream::iter(
+        manager
+            .snapshot()
+            .into_iter()
+            .map(|event| Ok::<_, actix_web::Error>(event.to_sse())),
+    );
+    let live = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match tokio::time::timeout(Duration::from_secs(15), receiver.recv()).await {
+                Ok(Ok(event)) => return Some((Ok(event.to_sse()), receiver)),
+                // A slow browser missed some weights; the next one supersedes them anyway
+                Ok(Err(RecvError::Lagged(_))) => continue,
+                Ok(Err(RecvError::Closed)) => return None,
+                Err(_) => return Some((Ok(web::Bytes::from_static(b": ping\n\n")), receiver)),
             }
         }
-        Err(_) => {
-            return HttpResponse::BadRequest().json(json!({
-                "success": false,
-                "message": "Scanner disconnected",
-                "data": null
-            }));
-        }
-    }
+    });
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(initial.chain(live))
 }
 
 pub async fn get_settings() -> HttpResponse {
     let config = serde_json::json!({
         "scanner": AppConfig::scanner(),
         "scale": AppConfig::scale(),
+        "printers": PrinterSettings::load().unwrap_or_default(),
     });
     HttpResponse::Ok().json(config)
 }
@@ -165,48 +270,89 @@ pub async fn update_settings(new_config: web::Json<serde_json::Value>) -> HttpRe
         serde_json::from_value::<DeviceConfig>(new_config["scanner"].clone()).unwrap_or_default();
     let scale =
         serde_json::from_value::<DeviceConfig>(new_config["scale"].clone()).unwrap_or_default();
+    let printers = if new_config["printers"].is_null() {
+        PrinterSettings::load().unwrap_or_default()
+    } else {
+        let printers =
+            match serde_json::from_value::<PrinterSettings>(new_config["printers"].clone()) {
+                Ok(printers) => printers,
+                Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+            };
+        if let Err(e) = printers.save() {
+            return HttpResponse::BadRequest().body(e);
+        }
+        printers
+    };
     AppConfig::save_to_env(scanner.clone(), scale.clone());
-    HttpResponse::Ok().json(serde_json::json!({ "scanner": scanner, "scale": scale }))
+    DeviceManager::global().reload();
+    HttpResponse::Ok()
+        .json(serde_json::json!({ "scanner": scanner, "scale": scale, "printers": printers }))
 }
 
-pub async fn test_scanner_connection() -> HttpResponse {
-    match AppConfig::test_scanner_connection() {
-        Ok(_) => HttpResponse::Ok().json(serde_json::json!({
-            "success": true,
-            "message": "Scanner connection successful"
-        })),
-        Err(e) => HttpResponse::BadRequest().json(serde_json::json!({
+/// Reopens the device with the saved settings and reports whether it connected.
+async fn test_device(device: Device) -> HttpResponse {
+    let manager = DeviceManager::global();
+    let generation = manager.reload();
+    let status = match web::block(move || {
+        manager.wait_for_status(device, generation, Duration::from_secs(5))
+    })
+    .await
+    {
+        Ok(status) => status,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    if !status.connected {
+        return HttpResponse::BadRequest().json(serde_json::json!({
             "success": false,
-            "message": e
-        })),
+            "message": status.message
+        }));
     }
+    HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": format!("{} connection successful", device.label())
+    }))
+}
+
+pub async fn test_scanner_connection() -> HttpResponse {
+    test_device(Device::Scanner).await
 }
 
 pub async fn test_scale_connection() -> HttpResponse {
-    let scale = AppConfig::get_scale();
-    match scale.test_connection() {
-        Ok(_) => HttpResponse::Ok().json(serde_json::json!({
-            "success": true,
-            "message": "Scale connection successful"
-        })),
-        Err(e) => HttpResponse::BadRequest().json(serde_json::json!({
-            "success": false,
-            "message": e
-        })),
-    }
+    test_device(Device::Scale).await
 }
 
-pub async fn test_printer_connection() -> HttpResponse {
-    if check_printer() {
-        return HttpResponse::Ok().json(serde_json::json!({
+pub async fn test_printer_connection(query: web::Query<PrinterQuery>) -> HttpResponse {
+    let settings = match PrinterSettings::load() {
+        Ok(settings) => settings,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "success": false,
+                "message": e
+            }));
+        }
+    };
+    let profile = match settings.find(query.name.as_deref()) {
+        Ok(profile) => profile.clone(),
+        Err(e) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "success": false,
+                "message": e
+            }));
+        }
+    };
+    // Probing shells out or opens a socket, so keep it off the async workers
+    match web::block(move || profile.probe()).await {
+        Ok(status) if status.online => HttpResponse::Ok().json(serde_json::json!({
             "success": true,
-            "message": "Printer connection successful"
-        }));
-    } else {
-        return HttpResponse::BadRequest().json(serde_json::json!({
+            "message": status.message,
+            "status": status
+        })),
+        Ok(status) => HttpResponse::BadRequest().json(serde_json::json!({
             "success": false,
-            "message": "Printer not found"
-        }));
+            "message": status.message,
+            "status": status
+        })),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
 }
 
@@ -217,39 +363,75 @@ pub async fn print_qrcode(data: web::Json<PrintData>) -> HttpResponse {
     } else {
         pdf_data
     };
-    match BASE64_STANDARD.decode(clean_data) {
-        Ok(decoded_data) => {
-            match print_pdf(&decoded_data) {
-                Ok(_) => HttpResponse::Ok().json(serde_json::json!({
-                    "success": true,
-                    "message": "Print successfully"
-                })),
-                // Err(_) => HttpResponse::Ok().json(serde_json::json!({
-                //     "success": true,
-                //     "message": "Print successfully"
-                // })),
-                Err(e) => HttpResponse::InternalServerError().body(format!("Print failed: {}", e)),
-            }
-        }
-        Err(e) => HttpResponse::BadRequest().body(format!("Invalid base64: {}", e)),
+    let decoded_data = match BASE64_STANDARD.decode(clean_data) {
+        Ok(decoded_data) => decoded_data,
+        Err(e) => return HttpResponse::BadRequest().body(format!("Invalid base64: {}", e)),
+    };
+    let profile = match PrinterSettings::load()
+        .and_then(|settings| settings.find(data.printer.as_deref()).cloned())
+    {
+        Ok(profile) => profile,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+
+    let queue = PrintQueue::global();
+    let job = queue.submit(&profile, decoded_data);
+    if data.wait == Some(false) {
+        return HttpResponse::Accepted().json(serde_json::json!({
+            "success": true,
+            "message": "Print job queued",
+            "job": job
+        }));
+    }
+    let id = job.id;
+    let job = match web::block(move || queue.wait(id, std::time::Duration::from_secs(60))).await {
+        Ok(Some(job)) => job,
+        Ok(None) => job,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    match job.status {
+        JobStatus::Done => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "message": "Print successfully",
+            "job": job
+        })),
+        JobStatus::Failed => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": format!("Print failed: {}", job.message.clone().unwrap_or_default()),
+            "job": job
+        })),
+        _ => HttpResponse::Accepted().json(serde_json::json!({
+            "success": true,
+            "message": "Print job is still waiting for the printer",
+            "job": job
+        })),
+    }
+}
+
+pub async fn print_jobs() -> HttpResponse {
+    HttpResponse::Ok().json(PrintQueue::global().jobs())
+}
+
+pub async fn print_job(query: web::Query<PrintJobQuery>) -> HttpResponse {
+    match PrintQueue::global().job(query.id) {
+        Some(job) => HttpResponse::Ok().json(job),
+        None => HttpResponse::NotFound().body("Print job not found"),
     }
 }
 
-pub async fn get_scale_weight() -> HttpResponse {
-    let scale_config = AppConfig::scale();
-    let scale = Scale::new(scale_config.port_name, scale_config.baud_rate);
-    match scale.get_weight() {
-        Ok(weight) => HttpResponse::Ok().json(serde_json::json!({
+/// The next weight to pass the scale's stable-readings rule.
+pub async fn get_scale_weight(query: web::Query<WaitQuery>) -> HttpResponse {
+    let timeout = query.duration(5);
+    match web::block(move || DeviceManager::global().wait_for_weight(timeout)).await {
+        Ok(Ok(sample)) => HttpResponse::Ok().json(serde_json::json!({
             "success": true,
-            "weight": weight
+            "weight": sample.kg,
+            "reading": sample.reading
         })),
-        Err(e) => HttpResponse::BadRequest().json(serde_json::json!({
+        Ok(Err(e)) => HttpResponse::BadRequest().json(serde_json::json!({
             "success": false,
             "message": e
         })),
-        // Err(_) => HttpResponse::Ok().json(serde_json::json!({
-        //     "success": true,
-        //     "weight": 8.5
-        // })),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
 }
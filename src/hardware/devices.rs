@@ -0,0 +1,576 @@
+use crate::hardware::scale::{Reading, ScaleStream, StableFilter, unstable_or_silent};
+use crate::hardware::simulated::{ScaleSimulation, SimulatedScale, SimulatedScanner};
+use crate::manager::config::{AppConfig, DeviceConfig, DeviceKind};
+use actix_web::web::Bytes;
+use chrono::Local;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+const PORT_TIMEOUT: Duration = Duration::from_millis(500);
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+/// Scans kept for clients that reconnect
+const SCAN_HISTORY: usize = 50;
+
+pub trait DevicePort: Read + Write + Send {}
+
+impl<T: Read + Write + Send + ?Sized> DevicePort for T {}
+
+pub fn open_port(device: Device, config: &DeviceConfig) -> Result<Box<dyn DevicePort>, String> {
+    if config.kind == DeviceKind::Simulated {
+        return Ok(match device {
+            Device::Scale => {
+                let simulation = ScaleSimulation::parse(&config.simulation)
+                    .map_err(|e| format!("Invalid scale simulation: {}", e))?;
+                Box::new(SimulatedScale::new(config.protocol, simulation))
+            }
+            Device::Scanner => Box::new(SimulatedScanner::new()),
+        });
+    }
+    let port = serialport::new(&config.port_name, config.baud_rate)
+        .timeout(PORT_TIMEOUT)
+        .open()
+        .map_err(|e| format!("Cannot open {}: {}", config.port_name, e))?;
+    Ok(Box::new(port))
+}
+
+/// Status shown while a device is connected and reading cleanly.
+fn connected_message(device: Device, config: &DeviceConfig) -> String {
+    match config.kind {
+        DeviceKind::Serial => format!("Connected on {}", config.port_name),
+        DeviceKind::Simulated => format!("Simulated {}", device.label().to_lowercase()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Device {
+    Scale,
+    Scanner,
+}
+
+impl Device {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Device::Scale => "Scale",
+            Device::Scanner => "Scanner",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceStatus {
+    pub device: Device,
+    pub connected: bool,
+    pub message: String,
+    /// Settings generation this status was reached with
+    #[serde(skip)]
+    pub generation: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WeightSample {
+    pub seq: u64,
+    pub reading: Reading,
+    pub kg: f32,
+    /// Passed the required number of consecutive stable readings
+    pub confirmed: bool,
+    pub at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Scan {
+    pub id: u64,
+    pub data: String,
+    pub at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum DeviceEvent {
+    Weight(WeightSample),
+    Scan(Scan),
+    Status(DeviceStatus),
+}
+
+impl DeviceEvent {
+    /// One server-sent event; the event name lets the browser listen per type.
+    pub fn to_sse(&self) -> Bytes {
+        let name = match self {
+            DeviceEvent::Weight(_) => "weight",
+            DeviceEvent::Scan(_) => "scan",
+            DeviceEvent::Status(_) => "status",
+        };
+        let data = serde_json::to_string(self).unwrap_or_default();
+        Bytes::from(format!("event: {}\ndata: {}\n\n", name, data))
+    }
+}
+
+struct DeviceState {
+    scale: DeviceStatus,
+    scanner: DeviceStatus,
+    weight: Option<WeightSample>,
+    confirmed: Option<WeightSample>,
+    scans: VecDeque<Scan>,
+    next_seq: u64,
+}
+
+impl DeviceState {
+    fn status(&mut self, device: Device) -> &mut DeviceStatus {
+        match device {
+            Device::Scale => &mut self.scale,
+            Device::Scanner => &mut self.scanner,
+        }
+    }
+}
+
+/// Keeps the scale and scanner ports open on background threads, reopening them when they
+/// are unplugged or the settings change, and fans readings out to waiting requests and
+/// event-stream subscribers.
+pub struct DeviceManager {
+    state: Mutex<DeviceState>,
+    changed: Condvar,
+    events: broadcast::Sender<DeviceEvent>,
+    generation: AtomicU64,
+}
+
+static MANAGER: Lazy<Arc<DeviceManager>> = Lazy::new(|| {
+    let manager = Arc::new(DeviceManager::new());
+    manager.spawn();
+    manager
+});
+
+impl Default for DeviceManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeviceManager {
+    /// The agent's manager; the device threads start on first use.
+    pub fn global() -> &'static Arc<DeviceManager> {
+        &MANAGER
+    }
+
+    pub fn new() -> Self {
+        let status = |device| DeviceStatus {
+            device,
+            connected: false,
+            message: "Starting".to_string(),
+            generation: 0,
+        };
+        DeviceManager {
+            state: Mutex::new(DeviceState {
+                scale: status(Device::Scale),
+                scanner: status(Device::Scanner),
+                weight: None,
+                confirmed: None,
+                scans: VecDeque::new(),
+                next_seq: 1,
+            }),
+            changed: Condvar::new(),
+            events: broadcast::channel(256).0,
+            generation: AtomicU64::new(1),
+        }
+    }
+
+    fn spawn(self: &Arc<Self>) {
+        let manager = self.clone();
+        thread::spawn(move || manager.run_scale());
+        let manager = self.clone();
+        thread::spawn(move || manager.run_scanner());
+    }
+
+    fn lock(&self) -> MutexGuard<'_, DeviceState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn publish(&self, event: DeviceEvent) {
+        // No subscribers is not an error
+        let _ = self.events.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<DeviceEvent> {
+        self.events.subscribe()
+    }
+
+    /// Current device states and the last weight, sent to new subscribers first.
+    pub fn snapshot(&self) -> Vec<DeviceEvent> {
+        let state = self.lock();
+        let mut events = vec![
+            DeviceEvent::Status(state.scale.clone()),
+            DeviceEvent::Status(state.scanner.clone()),
+        ];
+        if let Some(weight) = &state.weight {
+            events.push(DeviceEvent::Weight(weight.clone()));
+        }
+        events
+    }
+
+    pub fn status(&self, device: Device) -> DeviceStatus {
+        self.lock().status(device).clone()
+    }
+
+    /// Makes the device threads reopen their ports with the saved settings; returns the
+    /// generation to wait for.
+    pub fn reload(&self) -> u64 {
+        self.generation.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    fn current_generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    pub fn set_status(&self, device: Device, connected: bool, message: String, generation: u64) {
+        let status = {
+            let mut state = self.lock();
+            let status = state.status(device);
+            if status.connected == connected
+                && status.message == message
+                && status.generation == generation
+            {
+                return;
+            }
+            *status = DeviceStatus {
+                device,
+                connected,
+                message,
+                generation,
+            };
+            status.clone()
+        };
+        self.changed.notify_all();
+        self.publish(DeviceEvent::Status(status));
+    }
+
+    pub fn record_weight(&self, reading: Reading, confirmed: bool) {
+        let sample = {
+            let mut state = self.lock();
+            let changed = state
+                .weight
+                .as_ref()
+                .is_none_or(|last| last.reading != reading || last.confirmed != confirmed);
+            // Continuous scales repeat themselves many times a second; only changes go out,
+            // but every confirmed reading still answers a waiting request
+            if !changed && !confirmed {
+                return;
+            }
+            let sample = WeightSample {
+                seq: state.next_seq,
+                reading,
+                kg: reading.kg(),
+                confirmed,
+                at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            };
+            state.next_seq += 1;
+            if confirmed {
+                state.confirmed = Some(sample.clone());
+            }
+            state.weight = Some(sample.clone());
+            changed.then_some(sample)
+        };
+        self.changed.notify_all();
+        if let Some(sample) = sample {
+            self.publish(DeviceEvent::Weight(sample));
+        }
+    }
+
+    pub fn record_scan(&self, data: &str) -> Scan {
+        let scan = {
+            let mut state = self.lock();
+            let scan = Scan {
+                id: state.next_seq,
+                data: data.to_string(),
+                at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            };
+            state.next_seq += 1;
+            if state.scans.len() >= SCAN_HISTORY {
+                state.scans.pop_front();
+            }
+            state.scans.push_back(scan.clone());
+            scan
+        };
+        self.changed.notify_all();
+        self.publish(DeviceEvent::Scan(scan.clone()));
+        scan
+    }
+
+    /// Blocks until `done` picks something out of the state or the timeout passes.
+    fn wait_until<T>(
+        &self,
+        timeout: Duration,
+        mut done: impl FnMut(&DeviceState) -> Option<T>,
+    ) -> Result<T, MutexGuard<'_, DeviceState>> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.lock();
+        loop {
+            if let Some(value) = done(&state) {
+                return Ok(value);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(state);
+            }
+            state = self
+                .changed
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    /// The next weight to pass the stable-readings rule after the call.
+    pub fn wait_for_weight(&self, timeout: Duration) -> Result<WeightSample, String> {
+        let after = self.lock().next_seq;
+        self.wait_until(timeout, |state| {
+            state.confirmed.clone().filter(|sample| sample.seq >= after)
+        })
+        .map_err(|state| {
+            if !state.scale.connected {
+                state.scale.message.clone()
+            } else {
+                unstable_or_silent(state.weight.as_ref().is_some_and(|w| w.seq >= after))
+            }
+        })
+    }
+
+    /// The next code scanned after the call.
+    pub fn wait_for_scan(&self, timeout: Duration) -> Result<Scan, String> {
+        let after = self.lock().next_seq;
+        self.wait_until(timeout, |state| {
+            state.scans.iter().find(|scan| scan.id >= after).cloned()
+        })
+        .map_err(|state| {
+            if !state.scanner.connected {
+                state.scanner.message.clone()
+            } else {
+                "No code was scanned".to_string()
+            }
+        })
+    }
+
+    /// The device's status once its thread has tried the settings of `generation`.
+    pub fn wait_for_status(
+        &self,
+        device: Device,
+        generation: u64,
+        timeout: Duration,
+    ) -> DeviceStatus {
+        match self.wait_until(timeout, |state| {
+            let status = match device {
+                Device::Scale => &state.scale,
+                Device::Scanner => &state.scanner,
+            };
+            (status.generation >= generation).then(|| status.clone())
+        }) {
+            Ok(status) => status,
+            Err(mut state) => state.status(device).clone(),
+        }
+    }
+
+    /// Waits out the reconnect delay, cut short by a settings change.
+    fn pause(&self, generation: u64) {
+        let deadline = Instant::now() + RECONNECT_DELAY;
+        while Instant::now() < deadline && self.current_generation() == generation {
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    /// Opens the device's port with the current settings, reporting why not.
+    fn connect(
+        &self,
+        device: Device,
+        config: &DeviceConfig,
+        generation: u64,
+    ) -> Option<Box<dyn DevicePort>> {
+        if !config.is_configured() {
+            self.set_status(
+                device,
+                false,
+                format!("{} port not configured", device.label()),
+                generation,
+            );
+            return None;
+        }
+        match open_port(device, config) {
+            Ok(port) => {
+                self.set_status(device, true, connected_message(device, config), generation);
+                Some(port)
+            }
+            Err(e) => {
+                self.set_status(device, false, e, generation);
+                None
+            }
+        }
+    }
+
+    fn run_scale(&self) {
+        loop {
+            let generation = self.current_generation();
+            let config = AppConfig::scale();
+            let Some(mut port) = self.connect(Device::Scale, &config, generation) else {
+                self.pause(generation);
+                continue;
+            };
+            let mut stream = ScaleStream::new(config.protocol);
+            let mut filter = StableFilter::new(config.stable_readings);
+            let mut frame_error = false;
+            while self.current_generation() == generation {
+                match stream.poll(&mut *port) {
+                    Ok(results) => {
+                        for result in results {
+                            match result {
+                                Ok(reading) => {
+                                    // A good reading after a garbled frame clears the error
+                                    if frame_error {
+                                        frame_error = false;
+                                        self.set_status(
+                                            Device::Scale,
+                                            true,
+                                            connected_message(Device::Scale, &config),
+                                            generation,
+                                        );
+                                    }
+                                    let confirmed = filter.push(reading).is_some();
+                                    self.record_weight(reading, confirmed);
+                                }
+                                Err(e) => {
+                                    filter.reset();
+                                    frame_error = true;
+                                    self.set_status(Device::Scale, true, e, generation);
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        self.set_status(Device::Scale, false, e, generation);
+                        self.pause(generation);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    fn run_scanner(&self) {
+        loop {
+            let generation = self.current_generation();
+            let config = AppConfig::scanner();
+            let Some(mut port) = self.connect(Device::Scanner, &config, generation) else {
+                self.pause(generation);
+                continue;
+            };
+            let mut buffer = Vec::new();
+            let mut chunk = [0u8; 1024];
+            while self.current_generation() == generation {
+                match port.read(&mut chunk) {
+                    Ok(n) if n > 0 => {
+                        buffer.extend_from_slice(&chunk[..n]);
+                        while let Some(pos) = buffer.iter().position(|&b| b == b'\n' || b == b'\r')
+                        {
+                            let line: Vec<u8> = buffer.drain(..=pos).collect();
+                            let line = String::from_utf8_lossy(&line).trim().to_string();
+                            if !line.is_empty() {
+                                self.record_scan(&line);
+                            }
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) if e.kind() == ErrorKind::TimedOut => {}
+                    Err(e) => {
+                        self.set_status(
+                            Device::Scanner,
+                            false,
+                            format!("Scanner disconnected: {}", e),
+                            generation,
+                        );
+                        self.pause(generation);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_device_manager_waits_and_events() {
+    use crate::hardware::scale::WeightUnit;
+
+    let manager = Arc::new(DeviceManager::new());
+    let mut events = manager.subscribe();
+    let reading = |weight, stable| Reading {
+        weight,
+        unit: WeightUnit::Kg,
+        stable,
+        net: false,
+    };
+
+    // Nothing yet: the request times out with the device's state
+    manager.set_status(Device::Scale, true, "Connected on COM3".to_string(), 1);
+    manager.record_weight(reading(10.0, false), false);
+    let err = manager
+        .wait_for_weight(Duration::from_millis(50))
+        .unwrap_err();
+    assert_eq!(err, "No weight data found");
+
+    // Weights recorded after the request started are returned
+    let scale = manager.clone();
+    let feeder = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        scale.record_weight(reading(12.5, false), false);
+        scale.record_weight(reading(12.5, true), true);
+        scale.record_scan("26018P1001");
+    });
+    let sample = manager.wait_for_weight(Duration::from_secs(2)).unwrap();
+    assert_eq!((sample.kg, sample.confirmed), (12.5, true));
+    feeder.join().unwrap();
+    assert!(manager.wait_for_scan(Duration::from_millis(50)).is_err());
+
+    let scanner = manager.clone();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        scanner.record_scan("J23-612");
+    });
+    assert_eq!(
+        manager.wait_for_scan(Duration::from_secs(2)).unwrap().data,
+        "J23-612"
+    );
+
+    manager.set_status(Device::Scale, false, "Cannot open COM3".to_string(), 2);
+    assert_eq!(
+        manager
+            .wait_for_weight(Duration::from_millis(10))
+            .unwrap_err(),
+        "Cannot open COM3"
+    );
+    assert!(
+        !manager
+            .wait_for_status(Device::Scale, 2, Duration::from_millis(10))
+            .connected
+    );
+
+    let mut names = vec![];
+    while let Ok(event) = events.try_recv() {
+        let sse = String::from_utf8(event.to_sse().to_vec()).unwrap();
+        names.push(sse.lines().next().unwrap().to_string());
+    }
+    assert_eq!(
+        names,
+        [
+            "event: status",
+            "event: weight",
+            "event: weight",
+            "event: weight",
+            "event: scan",
+            "event: scan",
+            "event: status"
+        ]
+    );
+}
@@ -1,2 +1,7 @@
 pub mod app;
+pub mod devices;
+pub mod printer;
 pub mod routes;
+pub mod scale;
+pub mod simulated;
+pub mod update;
@@ -0,0 +1,623 @@
+use chrono::Local;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Component, Path};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use std::{env, fs, thread};
+
+const SETTINGS_FILE: &str = "data/printers.json";
+const PRINTS_DIR: &str = "data/prints";
+const RAW_PORT: u16 = 9100;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// Finished jobs kept for the status endpoints
+const JOB_HISTORY: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PrinterKind {
+    /// ZPL/EPL sent as-is to a network printer, normally on port 9100
+    Raw,
+    /// `lp` on Linux; PDFs go through the queue's driver, anything else with `-o raw`
+    Cups,
+    /// `data/pdf.exe` (SumatraPDF) on Windows
+    Sumatra,
+    /// Writes each job to a file under `target` (`data/prints` by default) for testing
+    Simulated,
+}
+
+impl Default for PrinterKind {
+    fn default() -> Self {
+        if cfg!(windows) {
+            PrinterKind::Sumatra
+        } else {
+            PrinterKind::Cups
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrinterProfile {
+    pub name: String,
+    pub kind: PrinterKind,
+    /// `host[:port]` for raw printers, the CUPS queue or the Windows printer name; empty uses
+    /// the system default printer
+    pub target: String,
+    /// Comma-separated `lp -o` options or the SumatraPDF `-print-settings`
+    pub options: String,
+}
+
+/// Named printers for this station, from `data/printers.json` (or `PRINTER_SETTINGS_FILE`).
+/// Without the file the station prints to the system default printer as it always has.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrinterSettings {
+    /// Profile used when a print request names no printer
+    pub default: String,
+    pub printers: Vec<PrinterProfile>,
+}
+
+impl Default for PrinterSettings {
+    fn default() -> Self {
+        PrinterSettings {
+            default: "default".to_string(),
+            printers: vec![PrinterProfile {
+                name: "default".to_string(),
+                kind: PrinterKind::default(),
+                target: String::new(),
+                options: "portrait".to_string(),
+            }],
+        }
+    }
+}
+
+impl PrinterSettings {
+    fn path() -> String {
+        env::var("PRINTER_SETTINGS_FILE").unwrap_or_else(|_| SETTINGS_FILE.to_string())
+    }
+
+    pub fn load() -> Result<Self, String> {
+        let path = Self::path();
+        if !Path::new(&path).exists() {
+            return Ok(PrinterSettings::default());
+        }
+        let raw = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?;
+        let settings: PrinterSettings =
+            serde_json::from_str(&raw).map_err(|e| format!("{}: {}", path, e))?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        self.validate()?;
+        let raw = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(Self::path(), raw).map_err(|e| format!("Failed to save printers: {}", e))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, printer) in self.printers.iter().enumerate() {
+            if printer.name.trim().is_empty() {
+                return Err("Every printer needs a name".to_string());
+            }
+            if !printer
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(format!(
+                    "Printer name '{}' may only use letters, digits, '-' and '_'",
+                    printer.name.escape_debug()
+                ));
+            }
+            if self.printers[..i].iter().any(|p| p.name == printer.name) {
+                return Err(format!("Printer '{}' is listed twice", printer.name));
+            }
+            if let Some(field) = [&printer.name, &printer.target].into_iter().find(|f| {
+                f.chars()
+                    .any(|c| c.is_control() || matches!(c, '$' | '"' | '`'))
+            }) {
+                return Err(format!(
+                    "Printer '{}' has an invalid character in '{}'",
+                    printer.name.escape_debug(),
+                    field.escape_debug()
+                ));
+            }
+            if printer.kind == PrinterKind::Simulated && !under_data(printer.target.trim()) {
+                return Err(format!(
+                    "Simulated printer '{}' must write inside data/",
+                    printer.name
+                ));
+            }
+            if printer.kind == PrinterKind::Raw && printer.target.trim().is_empty() {
+                return Err(format!("Raw printer '{}' needs a host", printer.name));
+            }
+        }
+        if !self.printers.is_empty() && self.find(None).is_err() {
+            return Err(format!(
+                "Default printer '{}' is not configured",
+                self.default
+            ));
+        }
+        Ok(())
+    }
+
+    /// The named profile, or the default one.
+    pub fn find(&self, name: Option<&str>) -> Result<&PrinterProfile, String> {
+        let name = name.filter(|n| !n.is_empty()).unwrap_or(&self.default);
+        self.printers
+            .iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| format!("Printer '{}' is not configured", name))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PrinterStatus {
+    pub name: String,
+    pub kind: PrinterKind,
+    pub online: bool,
+    pub message: String,
+}
+
+fn is_pdf(data: &[u8]) -> bool {
+    data.starts_with(b"%PDF")
+}
+
+impl PrinterProfile {
+    /// Checks the printer can take a job without printing anything.
+    pub fn probe(&self) -> PrinterStatus {
+        let result = match self.kind {
+            PrinterKind::Raw => self.raw_address().and_then(|address| {
+                TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)
+                    .map(|_| format!("Reachable at {}", address))
+                    .map_err(|e| format!("{} is unreachable: {}", address, e))
+            }),
+            PrinterKind::Cups => self.cups_status(),
+            PrinterKind::Sumatra => self.windows_status(),
+            PrinterKind::Simulated => Ok(format!("Writes jobs to {}", self.prints_dir())),
+        };
+        let (online, message) = match result {
+            Ok(message) => (true, message),
+            Err(message) => (false, message),
+        };
+        PrinterStatus {
+            name: self.name.clone(),
+            kind: self.kind,
+            online,
+            message,
+        }
+    }
+
+    pub fn print(&self, data: &[u8]) -> Result<(), String> {
+        match self.kind {
+            PrinterKind::Raw => {
+                if is_pdf(data) {
+                    return Err(format!(
+                        "{} is a ZPL/EPL printer and cannot print PDF",
+                        self.name
+                    ));
+                }
+                let address = self.raw_address()?;
+                let mut stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)
+                    .map_err(|e| format!("{} is unreachable: {}", address, e))?;
+                stream
+                    .set_write_timeout(Some(Duration::from_secs(10)))
+                    .map_err(|e| e.to_string())?;
+                stream
+                    .write_all(data)
+                    .and_then(|_| stream.flush())
+                    .map_err(|e| format!("Sending to {} failed: {}", address, e))
+            }
+            PrinterKind::Cups => self.print_cups(data),
+            PrinterKind::Sumatra => self.print_sumatra(data),
+            PrinterKind::Simulated => self.print_file(data),
+        }
+    }
+
+    fn prints_dir(&self) -> String {
+        if self.target.trim().is_empty() {
+            PRINTS_DIR.to_string()
+        } else {
+            self.target.trim().to_string()
+        }
+    }
+
+    fn print_file(&self, data: &[u8]) -> Result<(), String> {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        let dir = self.prints_dir();
+        fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir, e))?;
+        let path = Path::new(&dir).join(format!(
+            "{}-{}-{}.{}",
+            self.name,
+            Local::now().format("%Y%m%d-%H%M%S"),
+            NEXT.fetch_add(1, Ordering::SeqCst),
+            if is_pdf(data) { "pdf" } else { "zpl" }
+        ));
+        fs::write(&path, data).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn raw_address(&self) -> Result<std::net::SocketAddr, String> {
+        let target = self.target.trim();
+        let target = if target.contains(':') {
+            target.to_string()
+        } else {
+            format!("{}:{}", target, RAW_PORT)
+        };
+        target
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addresses| addresses.next())
+            .ok_or_else(|| format!("Cannot resolve printer address {}", target))
+    }
+
+    fn cups_queue(&self) -> Result<String, String> {
+        if !self.target.is_empty() {
+            return Ok(self.target.clone());
+        }
+        // "system default destination: Zebra_ZT230"
+        let output = Command::new("lpstat")
+            .arg("-d")
+            .output()
+            .map_err(|_| "CUPS is not installed".to_string())?;
+        String::from_utf8_lossy(&output.stdout)
+            .split_once(':')
+            .map(|(_, queue)| queue.trim().to_string())
+            .filter(|queue| !queue.is_empty())
+            .ok_or_else(|| "No default CUPS printer".to_string())
+    }
+
+    fn cups_status(&self) -> Result<String, String> {
+        let queue = self.cups_queue()?;
+        let output = Command::new("lpstat")
+            .args(["-p", &queue])
+            .output()
+            .map_err(|_| "CUPS is not installed".to_string())?;
+        let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if !output.status.success() || text.is_empty() {
+            return Err(format!("CUPS queue {} not found", queue));
+        }
+        let first_line = text.lines().next().unwrap_or_default().to_string();
+        if text.contains("disabled") {
+            Err(first_line)
+        } else {
+            Ok(first_line)
+        }
+    }
+
+    fn print_cups(&self, data: &[u8]) -> Result<(), String> {
+        let mut command = Command::new("lp");
+        if !self.target.is_empty() {
+            command.args(["-d", &self.target]);
+        }
+        if !is_pdf(data) {
+            command.args(["-o", "raw"]);
+        }
+        for option in self
+            .options
+            .split(',')
+            .map(str::trim)
+            .filter(|o| !o.is_empty())
+        {
+            command.args(["-o", option]);
+        }
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|_| "CUPS is not installed".to_string())?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(data)
+                .map_err(|e| format!("Sending to lp failed: {}", e))?;
+        }
+        let output = child.wait_with_output().map_err(|e| e.to_string())?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+        }
+    }
+
+    fn print_sumatra(&self, data: &[u8]) -> Result<(), String> {
+        if !is_pdf(data) {
+            return Err(format!("{} prints PDF only", self.name));
+        }
+        let data_dir = Path::new("data");
+        let pdf_path = data_dir.join("temp_print.pdf");
+        let pdf_exe_path = data_dir.join("pdf.exe");
+        if !pdf_exe_path.exists() {
+            return Err("SumatraPDF not found".to_string());
+        }
+        fs::write(&pdf_path, data).map_err(|e| format!("Failed to save PDF: {}", e))?;
+
+        let mut command = Command::new(pdf_exe_path);
+        if self.target.is_empty() {
+            command.arg("-print-to-default");
+        } else {
+            command.args(["-print-to", &self.target]);
+        }
+        let settings = if self.options.is_empty() {
+            "portrait"
+        } else {
+            &self.options
+        };
+        let output = command
+            .arg(&pdf_path)
+            .args(["-print-settings", settings, "-exit-when-done", "-silent"])
+            .output()
+            .map_err(|e| format!("Print failed: {}", e));
+        let _ = fs::remove_file(pdf_path);
+
+        if output?.status.success() {
+            Ok(())
+        } else {
+            Err("Print job failed".to_string())
+        }
+    }
+
+    fn windows_status(&self) -> Result<String, String> {
+        if !Path::new("data").join("pdf.exe").exists() {
+            return Err("SumatraPDF not found".to_string());
+        }
+        // The printer name reaches PowerShell through the environment so it
+        // is only ever compared as data, never parsed as part of the script.
+        let mut command = Command::new("powershell");
+        command.env("INSIGNIA_PRINTER", &self.target).args([
+            "-NoProfile",
+            "-Command",
+            "$n = $env:INSIGNIA_PRINTER; \
+             $p = if ($n) { Get-CimInstance Win32_Printer | Where-Object { $_.Name -eq $n } } \
+             else { Get-CimInstance Win32_Printer -Filter 'Default=True' }; \
+             $p | ForEach-Object { \"$($_.Name)|$($_.WorkOffline)\" }",
+        ]);
+        #[cfg(windows)]
+        {
+            use std::os::windows::process::CommandExt;
+            command.creation_flags(0x08000000); // CREATE_NO_WINDOW
+        }
+        let output = command
+            .output()
+            .map_err(|e| format!("Cannot query printers: {}", e))?;
+        let text = String::from_utf8_lossy(&output.stdout);
+        match text.trim().lines().next().and_then(|l| l.split_once('|')) {
+            Some((name, offline)) if offline.trim().eq_ignore_ascii_case("true") => {
+                Err(format!("{} is offline", name))
+            }
+            Some((name, _)) => Ok(format!("{} is ready", name)),
+            None if self.target.is_empty() => Err("No default printer".to_string()),
+            None => Err(format!("Printer {} not found", self.target)),
+        }
+    }
+}
+
+/// Whether a simulated printer's folder stays inside `data/`; empty means
+/// the default [`PRINTS_DIR`].
+fn under_data(target: &str) -> bool {
+    let mut components = Path::new(target).components();
+    target.is_empty()
+        || (components.next() == Some(Component::Normal("data".as_ref()))
+            && components.all(|c| matches!(c, Component::Normal(_))))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Printing,
+    Done,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PrintJob {
+    pub id: u64,
+    pub printer: String,
+    pub bytes: usize,
+    pub status: JobStatus,
+    pub message: Option<String>,
+    pub created_at: String,
+    pub finished_at: Option<String>,
+}
+
+impl PrintJob {
+    pub fn finished(&self) -> bool {
+        matches!(self.status, JobStatus::Done | JobStatus::Failed)
+    }
+}
+
+#[derive(Default)]
+struct JobBoard {
+    jobs: Mutex<VecDeque<PrintJob>>,
+    changed: Condvar,
+}
+
+impl JobBoard {
+    fn update(&self, id: u64, status: JobStatus, message: Option<String>) {
+        let mut jobs = self.jobs.lock().unwrap();
+        if let Some(job) = jobs.iter_mut().find(|j| j.id == id) {
+            job.status = status;
+            job.message = message;
+            if job.finished() {
+                job.finished_at = Some(Local::now().format("%Y-%m-%d %H:%M:%S").to_string());
+            }
+        }
+        self.changed.notify_all();
+    }
+}
+
+/// Jobs are printed one at a time by a single worker thread, so two labels never interleave
+/// on the same printer and callers get a status for each.
+pub struct PrintQueue {
+    board: Arc<JobBoard>,
+    sender: Sender<(u64, PrinterProfile, Vec<u8>)>,
+    next_id: AtomicU64,
+}
+
+static QUEUE: Lazy<PrintQueue> = Lazy::new(PrintQueue::start);
+
+impl PrintQueue {
+    pub fn global() -> &'static PrintQueue {
+        &QUEUE
+    }
+
+    pub fn start() -> Self {
+        let board = Arc::new(JobBoard::default());
+        let (sender, receiver) = mpsc::channel::<(u64, PrinterProfile, Vec<u8>)>();
+        let worker = board.clone();
+        thread::spawn(move || {
+            for (id, profile, data) in receiver {
+                worker.update(id, JobStatus::Printing, None);
+                match profile.print(&data) {
+                    Ok(()) => worker.update(id, JobStatus::Done, None),
+                    Err(e) => worker.update(id, JobStatus::Failed, Some(e)),
+                }
+            }
+        });
+        PrintQueue {
+            board,
+            sender,
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    pub fn submit(&self, profile: &PrinterProfile, data: Vec<u8>) -> PrintJob {
+        let job = PrintJob {
+            id: self.next_id.fetch_add(1, Ordering::SeqCst),
+            printer: profile.name.clone(),
+            bytes: data.len(),
+            status: JobStatus::Queued,
+            message: None,
+            created_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            finished_at: None,
+        };
+        {
+            let mut jobs = self.board.jobs.lock().unwrap();
+            while jobs.len() >= JOB_HISTORY {
+                match jobs.iter().position(|j| j.finished()) {
+                    Some(oldest) => jobs.remove(oldest),
+                    None => break,
+                };
+            }
+            jobs.push_back(job.clone());
+        }
+        if self.sender.send((job.id, profile.clone(), data)).is_err() {
+            self.board.update(
+                job.id,
+                JobStatus::Failed,
+                Some("Print queue is not running".to_string()),
+            );
+        }
+        job
+    }
+
+    pub fn job(&self, id: u64) -> Option<PrintJob> {
+        let jobs = self.board.jobs.lock().unwrap();
+        jobs.iter().find(|j| j.id == id).cloned()
+    }
+
+    /// Newest first.
+    pub fn jobs(&self) -> Vec<PrintJob> {
+        let jobs = self.board.jobs.lock().unwrap();
+        jobs.iter().rev().cloned().collect()
+    }
+
+    /// Blocks until the job has printed or failed, or the timeout passes.
+    pub fn wait(&self, id: u64, timeout: Duration) -> Option<PrintJob> {
+        let deadline = Instant::now() + timeout;
+        let mut jobs = self.board.jobs.lock().unwrap();
+        loop {
+            let job = jobs.iter().find(|j| j.id == id).cloned()?;
+            let now = Instant::now();
+            if job.finished() || now >= deadline {
+                return Some(job);
+            }
+            jobs = self
+                .board
+                .changed
+                .wait_timeout(jobs, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+}
+
+#[test]
+fn test_raw_printer_queue_and_probe() {
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let received = thread::spawn(move || {
+        let mut data = vec![];
+        listener.accept().unwrap().0.read_to_end(&mut data).unwrap();
+        data
+    });
+    let zebra = PrinterProfile {
+        name: "zebra".to_string(),
+        kind: PrinterKind::Raw,
+        target: address.to_string(),
+        options: String::new(),
+    };
+    let queue = PrintQueue::start();
+
+    let pdf = queue.submit(&zebra, b"%PDF-1.5".to_vec());
+    let zpl = queue.submit(&zebra, b"^XA^FDroll^FS^XZ".to_vec());
+    let pdf = queue.wait(pdf.id, Duration::from_secs(5)).unwrap();
+    assert_eq!(pdf.status, JobStatus::Failed);
+    assert!(pdf.message.unwrap().contains("cannot print PDF"));
+    let zpl = queue.wait(zpl.id, Duration::from_secs(5)).unwrap();
+    assert_eq!(zpl.status, JobStatus::Done);
+    assert_eq!(received.join().unwrap(), b"^XA^FDroll^FS^XZ");
+    assert_eq!(queue.jobs().len(), 2);
+
+    // Nothing listens on the port any more
+    assert!(!zebra.probe().online);
+
+    let mut settings = PrinterSettings {
+        default: "zebra".to_string(),
+        printers: vec![zebra.clone()],
+    };
+    assert!(settings.validate().is_ok());
+    assert_eq!(settings.find(Some("")).unwrap().name, "zebra");
+    assert!(settings.find(Some("office")).is_err());
+    settings.printers.push(zebra);
+    assert!(settings.validate().is_err());
+    settings.printers.pop();
+    settings.printers[0].target = "$(Start-Process calc)".to_string();
+    assert!(settings.validate().is_err());
+    settings.printers[0].target = "\\\\server\\Zebra \"Bay 1\"".to_string();
+    assert!(settings.validate().is_err());
+    settings.printers[0].target = "\\\\server\\Zebra Bay 1".to_string();
+    assert!(settings.validate().is_ok());
+    settings.printers[0].name = "../zebra".to_string();
+    settings.default = "../zebra".to_string();
+    assert!(settings.validate().is_err());
+    settings.printers[0].name = "zebra".to_string();
+    settings.default = "zebra".to_string();
+    settings.printers[0].kind = PrinterKind::Simulated;
+    for target in ["", "data/prints", "data/bench/zebra"] {
+        settings.printers[0].target = target.to_string();
+        assert!(settings.validate().is_ok(), "{}", target);
+    }
+    for target in [
+        "/etc",
+        "data/../src",
+        "../data",
+        "datas/prints",
+        "C:\\Windows",
+    ] {
+        settings.printers[0].target = target.to_string();
+        assert!(settings.validate().is_err(), "{}", target);
+    }
+    settings.default = "office".to_string();
+    assert!(settings.validate().is_err());
+}
@@ -18,8 +18,14 @@ pub fn init_routes(cfg: &mut web::ServiceConfig) {
         web::scope("/api/app")
             .route("/scan", web::post().to(scan_qrcode))
             .route("/print", web::post().to(print_qrcode))
+            .route("/print/jobs", web::get().to(print_jobs))
+            .route("/print/job", web::get().to(print_job))
             .route("/weight", web::get().to(get_scale_weight))
+            .route("/events", web::get().to(device_events))
+            .route("/simulate/scan", web::post().to(simulate_scan))
             .route("/last_update", web::get().to(get_last_update))
             .route("/update", web::get().to(run_update))
+            .route("/version", web::get().to(get_version))
+            .route("/rollback", web::post().to(rollback_update))
     );
 }
@@ -0,0 +1,432 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::io::{ErrorKind, Read, Write};
+use std::time::{Duration, Instant};
+
+/// Garbage longer than this without a frame boundary is dropped
+const MAX_BUFFER: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScaleProtocol {
+    /// First number on each line, as scales were read before protocols could be chosen. The
+    /// indicator sends no motion flag, so only repeated equal readings show it is stable.
+    #[default]
+    Text,
+    /// Mettler Toledo continuous output: STX, status words A-C, weight and tare, CR
+    Toledo,
+    /// MT-SICS, polled with `SI`; replies `S S` when stable and `S D` while moving
+    Sics,
+    /// `ST,GS,+0012.34kg` style lines: ST/US stable or unstable, GS/NT gross or net
+    Generic,
+}
+
+impl ScaleProtocol {
+    pub fn parse(value: &str) -> Self {
+        match value.trim().to_lowercase().as_str() {
+            "toledo" => ScaleProtocol::Toledo,
+            "sics" => ScaleProtocol::Sics,
+            "generic" => ScaleProtocol::Generic,
+            _ => ScaleProtocol::Text,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScaleProtocol::Text => "text",
+            ScaleProtocol::Toledo => "toledo",
+            ScaleProtocol::Sics => "sics",
+            ScaleProtocol::Generic => "generic",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WeightUnit {
+    Kg,
+    Lb,
+    G,
+}
+
+impl WeightUnit {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "kg" => Some(WeightUnit::Kg),
+            "lb" | "lbs" => Some(WeightUnit::Lb),
+            "g" => Some(WeightUnit::G),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Reading {
+    pub weight: f32,
+    pub unit: WeightUnit,
+    pub stable: bool,
+    /// Net of tare; gross otherwise
+    pub net: bool,
+}
+
+impl Reading {
+    pub fn kg(&self) -> f32 {
+        match self.unit {
+            WeightUnit::Kg => self.weight,
+            WeightUnit::Lb => self.weight * 0.453_592_37,
+            WeightUnit::G => self.weight / 1000.0,
+        }
+    }
+}
+
+/// Takes the next complete frame off the buffer.
+pub fn next_frame(protocol: ScaleProtocol, buffer: &mut Vec<u8>) -> Option<Vec<u8>> {
+    loop {
+        if protocol == ScaleProtocol::Toledo {
+            let start = buffer.iter().position(|&b| b == 0x02)?;
+            buffer.drain(..start);
+            let end = buffer.iter().position(|&b| b == b'\r')?;
+            let frame: Vec<u8> = buffer.drain(..=end).collect();
+            return Some(frame[1..frame.len() - 1].to_vec());
+        }
+        let end = buffer.iter().position(|&b| b == b'\r' || b == b'\n')?;
+        let frame: Vec<u8> = buffer.drain(..=end).collect();
+        if frame.len() > 1 {
+            return Some(frame[..frame.len() - 1].to_vec());
+        }
+    }
+}
+
+/// `Ok(None)` for lines that carry no weight (echoes, acknowledgements, noise).
+pub fn parse_frame(protocol: ScaleProtocol, frame: &[u8]) -> Result<Option<Reading>, String> {
+    match protocol {
+        ScaleProtocol::Toledo => parse_toledo(frame),
+        _ => {
+            let text = String::from_utf8_lossy(frame);
+            match protocol {
+                ScaleProtocol::Sics => parse_sics(text.trim()),
+                ScaleProtocol::Generic => parse_generic(text.trim()),
+                _ => Ok(parse_text(&text)),
+            }
+        }
+    }
+}
+
+fn parse_text(text: &str) -> Option<Reading> {
+    let re = Regex::new(r"(?i)(-?\d+(?:\.\d+)?)\s*(kg|lbs?|g)?\b").unwrap();
+    let caps = re.captures(text)?;
+    Some(Reading {
+        weight: caps[1].parse().ok()?,
+        unit: caps
+            .get(2)
+            .and_then(|u| WeightUnit::parse(u.as_str()))
+            .unwrap_or(WeightUnit::Kg),
+        stable: true,
+        net: false,
+    })
+}
+
+fn parse_generic(text: &str) -> Result<Option<Reading>, String> {
+    let re = Regex::new(
+        r"(?i)^(ST|US|OL)\s*,?\s*(GS|NT|TR)?\s*,?\s*([+-]?)\s*(\d+(?:\.\d+)?)\s*,?\s*([a-z]+)?",
+    )
+    .unwrap();
+    let Some(caps) = re.captures(text) else {
+        return Ok(None);
+    };
+    let status = caps[1].to_uppercase();
+    if status == "OL" {
+        return Err("Scale is overloaded".to_string());
+    }
+    let weight: f32 = caps[4]
+        .parse()
+        .map_err(|_| format!("Bad weight in '{}'", text))?;
+    Ok(Some(Reading {
+        weight: if &caps[3] == "-" { -weight } else { weight },
+        unit: caps
+            .get(5)
+            .and_then(|u| WeightUnit::parse(u.as_str()))
+            .unwrap_or(WeightUnit::Kg),
+        stable: status == "ST",
+        net: caps
+            .get(2)
+            .is_some_and(|m| m.as_str().eq_ignore_ascii_case("NT")),
+    }))
+}
+
+/// SICS weight replies carry no gross/net flag, so readings are reported as gross.
+fn parse_sics(text: &str) -> Result<Option<Reading>, String> {
+    let fields: Vec<&str> = text.split_whitespace().collect();
+    match fields.as_slice() {
+        ["S", "S" | "D", weight, rest @ ..] => {
+            let weight: f32 = weight
+                .parse()
+                .map_err(|_| format!("Bad weight in '{}'", text))?;
+            Ok(Some(Reading {
+                weight,
+                unit: rest
+                    .first()
+                    .and_then(|u| WeightUnit::parse(u))
+                    .unwrap_or(WeightUnit::Kg),
+                stable: fields[1] == "S",
+                net: false,
+            }))
+        }
+        ["S", "+"] => Err("Scale is overloaded".to_string()),
+        ["S", "-"] => Err("Scale is underloaded".to_string()),
+        ["ES"] | ["ET"] | ["EL"] => Err(format!("Scale rejected the command ({})", text)),
+        // "S I": busy, ask again
+        _ => Ok(None),
+    }
+}
+
+fn parse_toledo(frame: &[u8]) -> Result<Option<Reading>, String> {
+    if frame.len() < 9 {
+        return Ok(None);
+    }
+    let (a, b, c) = (frame[0], frame[1], frame[2]);
+    let digits = String::from_utf8_lossy(&frame[3..9]);
+    let Ok(raw) = digits.trim().parse::<u32>() else {
+        return Ok(None);
+    };
+    if b & 0b100 != 0 {
+        return Err("Scale is out of range".to_string());
+    }
+    // Status A bits 0-2 place the decimal point: 0 = XXXX00 ... 2 = XXXXXX ... 7 = X.XXXXX
+    let places = (a & 0b111) as i32 - 2;
+    let weight = if places > 0 {
+        raw as f32 / 10f32.powi(places)
+    } else {
+        raw as f32 * 10f32.powi(-places)
+    };
+    let unit = match (c & 0b111, b & 0b1_0000 != 0) {
+        (1, _) => WeightUnit::G,
+        (_, true) => WeightUnit::Kg,
+        (_, false) => WeightUnit::Lb,
+    };
+    Ok(Some(Reading {
+        weight: if b & 0b10 != 0 { -weight } else { weight },
+        unit,
+        stable: b & 0b1000 == 0,
+        net: b & 0b1 != 0,
+    }))
+}
+
+/// Passes a reading once `required` stable readings in a row agree on the weight.
+pub struct StableFilter {
+    required: u32,
+    last: Option<Reading>,
+    count: u32,
+}
+
+impl StableFilter {
+    pub fn new(required: u32) -> Self {
+        StableFilter {
+            required: required.max(1),
+            last: None,
+            count: 0,
+        }
+    }
+
+    pub fn push(&mut self, reading: Reading) -> Option<Reading> {
+        if !reading.stable {
+            self.reset();
+            return None;
+        }
+        self.count = match self.last {
+            Some(last) if last == reading => self.count + 1,
+            _ => 1,
+        };
+        self.last = Some(reading);
+        (self.count >= self.required).then_some(reading)
+    }
+
+    pub fn reset(&mut self) {
+        self.last = None;
+        self.count = 0;
+    }
+}
+
+/// Incremental reader over an open port; SICS scales are polled with `SI` as replies arrive.
+pub struct ScaleStream {
+    protocol: ScaleProtocol,
+    buffer: Vec<u8>,
+    awaiting_reply: bool,
+}
+
+impl ScaleStream {
+    pub fn new(protocol: ScaleProtocol) -> Self {
+        ScaleStream {
+            protocol,
+            buffer: Vec::new(),
+            awaiting_reply: false,
+        }
+    }
+
+    /// One read from the port and the frames it completed; nothing when the read timed out.
+    /// `Err` means the port is gone and has to be reopened.
+    pub fn poll<P: Read + Write + ?Sized>(
+        &mut self,
+        port: &mut P,
+    ) -> Result<Vec<Result<Reading, String>>, String> {
+        if self.protocol == ScaleProtocol::Sics && !self.awaiting_reply {
+            port.write_all(b"SI\r\n")
+                .and_then(|_| port.flush())
+                .map_err(|e| format!("Scale disconnected: {}", e))?;
+            self.awaiting_reply = true;
+        }
+        let mut chunk = [0u8; 256];
+        match port.read(&mut chunk) {
+            Ok(0) => return Err("Scale stopped sending".to_string()),
+            Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+            Err(e) if e.kind() == ErrorKind::TimedOut => {
+                self.awaiting_reply = false;
+                return Ok(vec![]);
+            }
+            Err(e) => return Err(format!("Scale disconnected: {}", e)),
+        }
+        let mut readings = vec![];
+        while let Some(frame) = next_frame(self.protocol, &mut self.buffer) {
+            self.awaiting_reply = false;
+            if let Some(result) = parse_frame(self.protocol, &frame).transpose() {
+                readings.push(result);
+            }
+        }
+        if self.buffer.len() > MAX_BUFFER {
+            self.buffer.clear();
+        }
+        Ok(readings)
+    }
+}
+
+/// Reads until the filter passes a reading or the timeout runs out.
+pub fn read_stable<P: Read + Write + ?Sized>(
+    port: &mut P,
+    protocol: ScaleProtocol,
+    required: u32,
+    timeout: Duration,
+) -> Result<Reading, String> {
+    let deadline = Instant::now() + timeout;
+    let mut stream = ScaleStream::new(protocol);
+    let mut filter = StableFilter::new(required);
+    let mut last_error = None;
+    let mut moving = false;
+
+    while Instant::now() < deadline {
+        for result in stream.poll(port)? {
+            match result {
+                Ok(reading) => {
+                    last_error = None;
+                    if let Some(reading) = filter.push(reading) {
+                        return Ok(reading);
+                    }
+                    moving = true;
+                }
+                Err(e) => {
+                    filter.reset();
+                    last_error = Some(e);
+                }
+            }
+        }
+    }
+    Err(last_error.unwrap_or_else(|| unstable_or_silent(moving)))
+}
+
+pub fn unstable_or_silent(moving: bool) -> String {
+    if moving {
+        "Weight is not stable, make sure nothing is moving on the scale".to_string()
+    } else {
+        "No weight data found".to_string()
+    }
+}
+
+#[test]
+fn test_scale_protocols_and_stable_readings() {
+    use std::collections::VecDeque;
+
+    struct Port {
+        chunks: VecDeque<Vec<u8>>,
+        writes: usize,
+    }
+    impl Read for Port {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.chunks.pop_front() {
+                Some(chunk) => {
+                    buf[..chunk.len()].copy_from_slice(&chunk);
+                    Ok(chunk.len())
+                }
+                None => Err(ErrorKind::TimedOut.into()),
+            }
+        }
+    }
+    impl Write for Port {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.writes += 1;
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let port = |chunks: &[&[u8]]| Port {
+        chunks: chunks.iter().map(|c| c.to_vec()).collect(),
+        writes: 0,
+    };
+    let timeout = Duration::from_millis(200);
+
+    // Toledo: 2 decimals (A=0x24), kg and net (B=0x31), weight 001234, tare 000100
+    let reading = parse_frame(ScaleProtocol::Toledo, b"\x24\x31\x20001234000100").unwrap();
+    assert_eq!(
+        reading,
+        Some(Reading {
+            weight: 12.34,
+            unit: WeightUnit::Kg,
+            stable: true,
+            net: true
+        })
+    );
+    // Motion and lb
+    let moving = parse_frame(ScaleProtocol::Toledo, b"\x24\x28\x20001234000000").unwrap();
+    assert!(moving.is_some_and(|r| !r.stable && r.unit == WeightUnit::Lb));
+
+    let generic = parse_frame(ScaleProtocol::Generic, b"ST,NT,-0012.50 lb")
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        (generic.weight, generic.net, generic.stable),
+        (-12.5, true, true)
+    );
+    assert!((generic.kg() + 5.67).abs() < 0.01);
+    assert!(parse_frame(ScaleProtocol::Generic, b"OL,GS,+9999.99kg").is_err());
+    assert_eq!(parse_frame(ScaleProtocol::Generic, b"hello").unwrap(), None);
+
+    // Moving weights never pass; two equal stable frames do
+    let mut generic_port = port(&[
+        b"US,GS,+0010.10kg\r\nST,GS,+0010.",
+        b"20kg\r\nST,GS,+0010.30kg\r\n",
+        b"ST,GS,+0010.30kg\r\n",
+    ]);
+    let reading = read_stable(&mut generic_port, ScaleProtocol::Generic, 2, timeout).unwrap();
+    assert_eq!((reading.weight, reading.net), (10.3, false));
+
+    let mut unstable = port(&[b"US,GS,+0010.10kg\r\nUS,GS,+0010.20kg\r\n"]);
+    let err = read_stable(&mut unstable, ScaleProtocol::Generic, 1, timeout).unwrap_err();
+    assert!(err.contains("not stable"));
+
+    let mut sics = port(&[b"S D      4.10 kg\r\n", b"S I\r\n", b"S S      4.12 kg\r\n"]);
+    let reading = read_stable(&mut sics, ScaleProtocol::Sics, 1, timeout).unwrap();
+    assert_eq!(reading.weight, 4.12);
+    assert_eq!(sics.writes, 3);
+
+    let mut toledo = port(&[b"\x02\x24\x31\x20001234000100\r\x02\x24\x31\x20001234000100\r"]);
+    let reading = read_stable(&mut toledo, ScaleProtocol::Toledo, 2, timeout).unwrap();
+    assert_eq!(reading.weight, 12.34);
+
+    let mut text = port(&[b"  8.50 kg\r\n"]);
+    assert_eq!(
+        read_stable(&mut text, ScaleProtocol::Text, 1, timeout)
+            .unwrap()
+            .weight,
+        8.5
+    );
+}
@@ -0,0 +1,362 @@
+use crate::hardware::scale::ScaleProtocol;
+use once_cell::sync::Lazy;
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read, Write};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// Pause between simulated frames, about what a continuous indicator sends
+const FRAME_INTERVAL: Duration = Duration::from_millis(100);
+/// Stable readings the random simulation holds each weight for
+const RANDOM_HOLD: u32 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Step {
+    pub weight: f32,
+    pub stable: bool,
+    pub count: u32,
+}
+
+/// What the simulated scale shows, from `SCALE_SIMULATION`:
+///
+/// - `fixed:12.5` (or just `12.5`): always that weight, stable
+/// - `random:10..20`: a new weight in the range every couple of seconds, moving in between
+/// - `script:~3x4,12.5x10,~0x2,0x10`: each step `[~]weight[xcount]`, `~` for unstable
+///   readings; the script repeats
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScaleSimulation {
+    Fixed(f32),
+    Random { min: f32, max: f32 },
+    Script(Vec<Step>),
+}
+
+impl ScaleSimulation {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let spec = spec.trim();
+        let number = |value: &str| {
+            value
+                .trim()
+                .parse::<f32>()
+                .map_err(|_| format!("'{}' is not a weight", value.trim()))
+        };
+        let (kind, rest) = spec.split_once(':').unwrap_or(("fixed", spec));
+        match kind.trim().to_lowercase().as_str() {
+            "fixed" if rest.trim().is_empty() => Ok(ScaleSimulation::Fixed(0.0)),
+            "fixed" => Ok(ScaleSimulation::Fixed(number(rest)?)),
+            "random" => {
+                let (min, max) = rest
+                    .split_once("..")
+                    .ok_or_else(|| "Random simulation needs a range like 10..20".to_string())?;
+                let (min, max) = (number(min)?, number(max)?);
+                if min > max {
+                    return Err(format!("Empty weight range {}..{}", min, max));
+                }
+                Ok(ScaleSimulation::Random { min, max })
+            }
+            "script" => {
+                let steps = rest
+                    .split(',')
+                    .filter(|step| !step.trim().is_empty())
+                    .map(|step| {
+                        let step = step.trim();
+                        let (weight, count) = step.split_once('x').unwrap_or((step, "1"));
+                        let count = count
+                            .trim()
+                            .parse::<u32>()
+                            .ok()
+                            .filter(|c| *c > 0)
+                            .ok_or_else(|| format!("Bad repeat count in '{}'", step))?;
+                        Ok(Step {
+                            weight: number(weight.trim().trim_start_matches('~'))?,
+                            stable: !weight.trim().starts_with('~'),
+                            count,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                if steps.is_empty() {
+                    return Err("Script simulation needs at least one step".to_string());
+                }
+                Ok(ScaleSimulation::Script(steps))
+            }
+            other => Err(format!("Unknown scale simulation '{}'", other)),
+        }
+    }
+}
+
+/// Scale port that answers in the configured protocol, so the real drivers and stability
+/// rules are exercised.
+pub struct SimulatedScale {
+    protocol: ScaleProtocol,
+    simulation: ScaleSimulation,
+    steps: VecDeque<Step>,
+    tick: u32,
+    requests: usize,
+    out: Vec<u8>,
+    interval: Duration,
+}
+
+impl SimulatedScale {
+    pub fn new(protocol: ScaleProtocol, simulation: ScaleSimulation) -> Self {
+        SimulatedScale {
+            protocol,
+            simulation,
+            steps: VecDeque::new(),
+            tick: 0,
+            requests: 0,
+            out: Vec::new(),
+            interval: FRAME_INTERVAL,
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    fn refill(&mut self) {
+        match &self.simulation {
+            ScaleSimulation::Fixed(weight) => self.steps.push_back(Step {
+                weight: *weight,
+                stable: true,
+                count: 1,
+            }),
+            ScaleSimulation::Random { min, max } => {
+                let weight = if min == max {
+                    *min
+                } else {
+                    rand::random_range(*min..*max)
+                };
+                let weight = (weight * 100.0).round() / 100.0;
+                self.steps.push_back(Step {
+                    weight,
+                    stable: false,
+                    count: 3,
+                });
+                self.steps.push_back(Step {
+                    weight,
+                    stable: true,
+                    count: RANDOM_HOLD,
+                });
+            }
+            ScaleSimulation::Script(steps) => self.steps.extend(steps.iter().copied()),
+        }
+    }
+
+    fn next_reading(&mut self) -> (f32, bool) {
+        if self.steps.is_empty() {
+            self.refill();
+        }
+        let step = self.steps.front_mut().expect("refill adds a step");
+        // A moving load never reads the same twice, which is all a plain-text scale shows
+        self.tick = self.tick.wrapping_add(1);
+        let jitter = if step.stable {
+            0.0
+        } else {
+            (self.tick % 5) as f32 * 0.07 - 0.14
+        };
+        let reading = (step.weight + jitter, step.stable);
+        step.count -= 1;
+        if step.count == 0 {
+            self.steps.pop_front();
+        }
+        reading
+    }
+}
+
+/// One frame for the reading in the indicator's format.
+pub fn encode_frame(protocol: ScaleProtocol, weight: f32, stable: bool) -> Vec<u8> {
+    match protocol {
+        ScaleProtocol::Text => format!("{:.2} kg\r\n", weight).into_bytes(),
+        ScaleProtocol::Generic => format!(
+            "{},GS,{:+09.2}kg\r\n",
+            if stable { "ST" } else { "US" },
+            weight
+        )
+        .into_bytes(),
+        ScaleProtocol::Sics => format!(
+            "S {} {:>10.2} kg\r\n",
+            if stable { "S" } else { "D" },
+            weight
+        )
+        .into_bytes(),
+        ScaleProtocol::Toledo => {
+            // Two decimals, kg, motion and sign in status word B
+            let mut status_b = 0x30;
+            if !stable {
+                status_b |= 0x08;
+            }
+            if weight < 0.0 {
+                status_b |= 0x02;
+            }
+            let hundredths = (weight.abs() * 100.0).round() as u32 % 1_000_000;
+            let mut frame = vec![0x02, 0x24, status_b, 0x20];
+            frame.extend(format!("{:06}000000\r", hundredths).into_bytes());
+            frame
+        }
+    }
+}
+
+impl Read for SimulatedScale {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.out.is_empty() {
+            thread::sleep(self.interval);
+            // SICS scales only answer when polled
+            if self.protocol == ScaleProtocol::Sics {
+                if self.requests == 0 {
+                    return Err(ErrorKind::TimedOut.into());
+                }
+                self.requests -= 1;
+            }
+            let (weight, stable) = self.next_reading();
+            self.out = encode_frame(self.protocol, weight, stable);
+        }
+        let n = buf.len().min(self.out.len());
+        buf[..n].copy_from_slice(&self.out[..n]);
+        self.out.drain(..n);
+        Ok(n)
+    }
+}
+
+impl Write for SimulatedScale {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if buf.starts_with(b"SI") {
+            self.requests += 1;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+static SCAN_QUEUE: Lazy<Mutex<VecDeque<String>>> = Lazy::new(Default::default);
+
+/// Queues a code for the simulated scanner to read next.
+pub fn queue_scan(code: &str) {
+    SCAN_QUEUE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push_back(code.trim().to_string());
+}
+
+/// Scanner port that reads the codes queued with [`queue_scan`], one line each.
+pub struct SimulatedScanner {
+    out: Vec<u8>,
+}
+
+impl SimulatedScanner {
+    pub fn new() -> Self {
+        SimulatedScanner { out: Vec::new() }
+    }
+}
+
+impl Default for SimulatedScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Read for SimulatedScanner {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.out.is_empty() {
+            let next = SCAN_QUEUE
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .pop_front();
+            match next {
+                Some(code) => self.out = format!("{}\r\n", code).into_bytes(),
+                None => {
+                    thread::sleep(FRAME_INTERVAL);
+                    return Err(ErrorKind::TimedOut.into());
+                }
+            }
+        }
+        let n = buf.len().min(self.out.len());
+        buf[..n].copy_from_slice(&self.out[..n]);
+        self.out.drain(..n);
+        Ok(n)
+    }
+}
+
+impl Write for SimulatedScanner {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_simulated_scale_scanner_and_printer() {
+    use crate::hardware::printer::{PrinterKind, PrinterProfile};
+    use crate::hardware::scale::read_stable;
+
+    assert_eq!(
+        ScaleSimulation::parse("12.5").unwrap(),
+        ScaleSimulation::Fixed(12.5)
+    );
+    assert!(ScaleSimulation::parse("random:20..10").is_err());
+    assert!(ScaleSimulation::parse("script:").is_err());
+    assert!(ScaleSimulation::parse("wobbly:1").is_err());
+
+    // Moving readings first, then the stable weight passes the three-reading rule in every
+    // protocol the drivers speak
+    let script = ScaleSimulation::parse("script:~3x4,12.5x3,~0x2").unwrap();
+    for protocol in [
+        ScaleProtocol::Text,
+        ScaleProtocol::Generic,
+        ScaleProtocol::Sics,
+        ScaleProtocol::Toledo,
+    ] {
+        let mut scale =
+            SimulatedScale::new(protocol, script.clone()).with_interval(Duration::from_millis(1));
+        let reading = read_stable(&mut scale, protocol, 3, Duration::from_secs(2)).unwrap();
+        assert_eq!(reading.weight, 12.5, "{:?}", protocol);
+    }
+
+    let random = ScaleSimulation::parse("random:10..20").unwrap();
+    let mut scale =
+        SimulatedScale::new(ScaleProtocol::Generic, random).with_interval(Duration::from_millis(1));
+    let reading = read_stable(
+        &mut scale,
+        ScaleProtocol::Generic,
+        5,
+        Duration::from_secs(2),
+    )
+    .unwrap();
+    assert!((10.0..=20.0).contains(&reading.weight));
+
+    let mut scanner = SimulatedScanner::new();
+    let mut buf = [0u8; 64];
+    assert!(scanner.read(&mut buf).is_err());
+    queue_scan("26018P1001");
+    let n = scanner.read(&mut buf).unwrap();
+    assert_eq!(&buf[..n], b"26018P1001\r\n");
+
+    let dir = tempfile::tempdir().unwrap();
+    let printer = PrinterProfile {
+        name: "bench".to_string(),
+        kind: PrinterKind::Simulated,
+        target: dir.path().to_string_lossy().to_string(),
+        options: String::new(),
+    };
+    assert!(printer.probe().online);
+    printer.print(b"^XA^XZ").unwrap();
+    printer.print(b"%PDF-1.5").unwrap();
+    let mut files: Vec<String> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    files.sort();
+    assert_eq!(files.len(), 2);
+    assert!(
+        files
+            .iter()
+            .any(|f| f.starts_with("bench-") && f.ends_with(".zpl"))
+    );
+    assert!(files.iter().any(|f| f.ends_with(".pdf")));
+}
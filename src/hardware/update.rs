@@ -0,0 +1,286 @@
+use base64::prelude::*;
+use ring::signature::{ED25519, Ed25519KeyPair, UnparsedPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Base64 Ed25519 public key releases must be signed with, fixed at build time. Without it
+/// the agent refuses to update.
+pub const RELEASE_PUBLIC_KEY: Option<&str> = option_env!("INSIGNIA_RELEASE_PUBLIC_KEY");
+
+pub const MANIFEST_NAME: &str = "manifest.json";
+/// Release file holding the agent binary
+pub const SERVICE_FILE: &str = "service";
+/// Release file holding the updater that swaps the binary while the agent is stopped
+pub const UPDATER_FILE: &str = "update";
+
+pub const APP_STAGED: &str = "new_app.exe";
+pub const UPDATER_EXE: &str = "update.exe";
+const PREVIOUS_DIR: &str = "data/previous";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReleaseFile {
+    pub sha256: String,
+    pub size: u64,
+}
+
+impl ReleaseFile {
+    pub fn of(bytes: &[u8]) -> Self {
+        ReleaseFile {
+            sha256: sha256_hex(bytes),
+            size: bytes.len() as u64,
+        }
+    }
+
+    pub fn matches(&self, bytes: &[u8]) -> bool {
+        self.size == bytes.len() as u64 && self.sha256.eq_ignore_ascii_case(&sha256_hex(bytes))
+    }
+}
+
+/// `manifest.json` published next to the release files. The signature covers the version
+/// and every file's hash and size.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReleaseManifest {
+    pub version: String,
+    pub files: BTreeMap<String, ReleaseFile>,
+    /// Base64 Ed25519 signature of [`ReleaseManifest::signed_message`]
+    #[serde(default)]
+    pub signature: String,
+}
+
+impl ReleaseManifest {
+    pub fn signed_message(&self) -> Vec<u8> {
+        let mut message = format!("insignia-release\nversion {}\n", self.version.trim());
+        for (name, file) in &self.files {
+            message.push_str(&format!(
+                "{} {} {}\n",
+                name,
+                file.sha256.to_lowercase(),
+                file.size
+            ));
+        }
+        message.into_bytes()
+    }
+
+    pub fn sign(&mut self, key_pair: &Ed25519KeyPair) {
+        let signature = key_pair.sign(&self.signed_message());
+        self.signature = BASE64_STANDARD.encode(signature.as_ref());
+    }
+
+    pub fn verify(&self, public_key: &[u8]) -> Result<(), String> {
+        let signature = BASE64_STANDARD
+            .decode(self.signature.trim())
+            .map_err(|_| "Release manifest is not signed".to_string())?;
+        UnparsedPublicKey::new(&ED25519, public_key)
+            .verify(&self.signed_message(), &signature)
+            .map_err(|_| "Release manifest signature is invalid".to_string())
+    }
+
+    /// Checks downloaded bytes against the manifest entry for `name`.
+    pub fn check_file(&self, name: &str, bytes: &[u8]) -> Result<(), String> {
+        let file = self
+            .files
+            .get(name)
+            .ok_or_else(|| format!("Release {} has no '{}' file", self.version, name))?;
+        if !file.matches(bytes) {
+            return Err(format!(
+                "'{}' does not match the signed release {}",
+                name, self.version
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+pub fn public_key() -> Result<Vec<u8>, String> {
+    let key = RELEASE_PUBLIC_KEY
+        .filter(|key| !key.trim().is_empty())
+        .ok_or("This build has no release key, updates are disabled")?;
+    BASE64_STANDARD
+        .decode(key.trim())
+        .map_err(|_| "The embedded release key is not valid base64".to_string())
+}
+
+/// Compares dotted versions numerically, so `1.10.0` is newer than `1.9.3`.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parts = |v: &str| -> Vec<u64> {
+        v.trim()
+            .trim_start_matches('v')
+            .split(['.', '-', '+'])
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    };
+    let (a, b) = (parts(a), parts(b));
+    for i in 0..a.len().max(b.len()) {
+        match a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0)) {
+            Ordering::Equal => continue,
+            other => return other,
+        }
+    }
+    Ordering::Equal
+}
+
+/// Version of the running binary, so a staged update only counts once it has been swapped in.
+pub fn installed_version() -> String {
+    env!("CARGO_PKG_VERSION").to_string()
+}
+
+/// Version kept for rollback, if an update has replaced one.
+pub fn previous_version() -> Option<String> {
+    fs::read_to_string(Path::new(PREVIOUS_DIR).join("version.txt"))
+        .map(|v| v.trim().to_string())
+        .ok()
+        .filter(|v| !v.is_empty())
+}
+
+/// The release server from `UPDATE_BASE_URL`, else the one the caller passed.
+pub fn base_url(requested: Option<&str>) -> Result<String, String> {
+    std::env::var("UPDATE_BASE_URL")
+        .ok()
+        .or_else(|| requested.map(str::to_string))
+        .map(|url| url.trim().trim_end_matches('/').to_string())
+        .filter(|url| !url.is_empty())
+        .ok_or_else(|| "No update server, pass base_url or set UPDATE_BASE_URL".to_string())
+}
+
+fn download(base_url: &str, name: &str) -> Result<Vec<u8>, String> {
+    let url = format!("{}/download/{}", base_url, name);
+    let response = reqwest::blocking::get(&url).map_err(|e| format!("{}: {}", url, e))?;
+    if !response.status().is_success() {
+        return Err(format!("{}: {}", url, response.status()));
+    }
+    response
+        .bytes()
+        .map(|b| b.to_vec())
+        .map_err(|e| format!("{}: {}", url, e))
+}
+
+/// Downloads the manifest and checks it is signed with the embedded key.
+pub fn fetch_manifest(base_url: &str) -> Result<ReleaseManifest, String> {
+    let key = public_key()?;
+    let body = download(base_url, MANIFEST_NAME)?;
+    let manifest: ReleaseManifest =
+        serde_json::from_slice(&body).map_err(|e| format!("Bad release manifest: {}", e))?;
+    manifest.verify(&key)?;
+    Ok(manifest)
+}
+
+/// Writes through a temporary file so a failed download never leaves half a binary behind.
+fn write_replacing(path: &str, bytes: &[u8]) -> Result<(), String> {
+    let tmp = format!("{}.part", path);
+    fs::write(&tmp, bytes)
+        .and_then(|_| fs::rename(&tmp, path))
+        .map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+/// Copies the running binary aside with its version so [`stage_rollback`] can restore it.
+fn keep_previous() -> Result<(), String> {
+    let current = std::env::current_exe().map_err(|e| e.to_string())?;
+    fs::create_dir_all(PREVIOUS_DIR).map_err(|e| e.to_string())?;
+    fs::copy(&current, Path::new(PREVIOUS_DIR).join("app.exe"))
+        .map_err(|e| format!("Failed to keep the current version: {}", e))?;
+    fs::write(
+        Path::new(PREVIOUS_DIR).join("version.txt"),
+        installed_version(),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Makes sure `update.exe` is the signed updater, downloading it when missing or altered.
+fn stage_updater(base_url: &str, manifest: &ReleaseManifest) -> Result<(), String> {
+    let current = fs::read(UPDATER_EXE).unwrap_or_default();
+    if manifest.check_file(UPDATER_FILE, &current).is_ok() {
+        return Ok(());
+    }
+    let bytes = download(base_url, UPDATER_FILE)?;
+    manifest.check_file(UPDATER_FILE, &bytes)?;
+    write_replacing(UPDATER_EXE, &bytes)
+}
+
+/// Downloads and verifies a newer release into `new_app.exe`, keeping the running binary for
+/// rollback. The caller starts the updater to swap it in.
+pub fn stage_update(base_url: &str) -> Result<ReleaseManifest, String> {
+    let manifest = fetch_manifest(base_url)?;
+    let installed = installed_version();
+    if compare_versions(&manifest.version, &installed) != Ordering::Greater {
+        return Err(format!("Version {} is already up to date", installed));
+    }
+    let app = download(base_url, SERVICE_FILE)?;
+    manifest.check_file(SERVICE_FILE, &app)?;
+    stage_updater(base_url, &manifest)?;
+    keep_previous()?;
+    write_replacing(APP_STAGED, &app)?;
+    Ok(manifest)
+}
+
+/// Stages the binary kept by the last update, returning the version it goes back to.
+pub fn stage_rollback() -> Result<String, String> {
+    let version = previous_version().ok_or("No previous version to roll back to")?;
+    if !Path::new(UPDATER_EXE).exists() {
+        return Err("The updater is missing, roll back by reinstalling".to_string());
+    }
+    let app = fs::read(Path::new(PREVIOUS_DIR).join("app.exe"))
+        .map_err(|e| format!("Previous version is missing: {}", e))?;
+    write_replacing(APP_STAGED, &app)?;
+    let _ = fs::remove_dir_all(PREVIOUS_DIR);
+    Ok(version)
+}
+
+#[test]
+fn test_release_manifest_signature_and_versions() {
+    let rng = ring::rand::SystemRandom::new();
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+    let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+    let public = ring::signature::KeyPair::public_key(&key_pair)
+        .as_ref()
+        .to_vec();
+
+    let app = b"MZ new agent".to_vec();
+    let mut manifest = ReleaseManifest {
+        version: "1.4.0".to_string(),
+        files: BTreeMap::from([
+            (SERVICE_FILE.to_string(), ReleaseFile::of(&app)),
+            (UPDATER_FILE.to_string(), ReleaseFile::of(b"MZ updater")),
+        ]),
+        signature: String::new(),
+    };
+    assert!(manifest.verify(&public).is_err());
+    manifest.sign(&key_pair);
+    let manifest: ReleaseManifest =
+        serde_json::from_str(&serde_json::to_string(&manifest).unwrap()).unwrap();
+    manifest.verify(&public).unwrap();
+    manifest.check_file(SERVICE_FILE, &app).unwrap();
+    assert!(manifest.check_file(SERVICE_FILE, b"MZ tampered").is_err());
+    assert!(manifest.check_file("other", &app).is_err());
+
+    // A swapped hash or version breaks the signature, as does another key
+    let mut tampered = manifest.clone();
+    tampered
+        .files
+        .insert(SERVICE_FILE.to_string(), ReleaseFile::of(b"MZ tampered"));
+    assert!(tampered.verify(&public).is_err());
+    let mut tampered = manifest.clone();
+    tampered.version = "9.0.0".to_string();
+    assert!(tampered.verify(&public).is_err());
+    let other = Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+    let other = Ed25519KeyPair::from_pkcs8(other.as_ref()).unwrap();
+    assert!(
+        manifest
+            .verify(ring::signature::KeyPair::public_key(&other).as_ref())
+            .is_err()
+    );
+
+    assert_eq!(compare_versions("1.10.0", "1.9.3"), Ordering::Greater);
+    assert_eq!(compare_versions("v1.2", "1.2.0"), Ordering::Equal);
+    assert_eq!(compare_versions("1.2.0", "1.2.1"), Ordering::Less);
+}
@@ -0,0 +1,926 @@
+use chrono::Local;
+use rusqlite::{Connection, OptionalExtension, Result, params};
+
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub up: &'static str,
+    pub down: Option<&'static str>,
+    // (table, column) that only exists once this migration has run. Databases created
+    // before migrations were introduced are checked against it so the step is recorded
+    // instead of failing on an already existing column.
+    pub marker: Option<(&'static str, &'static str)>,
+}
+
+pub struct MigrationStatus {
+    pub version: i32,
+    pub name: &'static str,
+    pub applied_at: Option<String>,
+}
+
+// Append new migrations to the end with the next version number. Never edit a
+// migration that has already shipped; add a new one instead.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "baseline_schema",
+        up: "
+        CREATE TABLE IF NOT EXISTS content_type (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            model TEXT UNIQUE
+        );
+        CREATE TABLE IF NOT EXISTS permissions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            codename TEXT UNIQUE,
+            name TEXT,
+            content_type_id INTEGER,
+            can_create BOOLEAN DEFAULT 0,
+            can_read BOOLEAN DEFAULT 0,
+            can_update BOOLEAN DEFAULT 0,
+            can_delete BOOLEAN DEFAULT 0,
+            FOREIGN KEY (content_type_id) REFERENCES content_type(id)
+        );
+        CREATE TABLE IF NOT EXISTS roles (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT UNIQUE,
+            description TEXT
+        );
+        CREATE TABLE IF NOT EXISTS users (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            full_name TEXT,
+            staffid TEXT,
+            phone_number TEXT,
+            password TEXT,
+            status TEXT,
+            role_id INTEGER,
+            page_id TEXT,
+            created_at DATETIME,
+            updated_at DATETIME,
+            FOREIGN KEY (role_id) REFERENCES roles(id)
+        );
+        CREATE TABLE IF NOT EXISTS role_permissions (
+            role_id INTEGER,
+            permission_id INTEGER,
+            PRIMARY KEY(role_id, permission_id),
+            FOREIGN KEY (role_id) REFERENCES roles(id),
+            FOREIGN KEY (permission_id) REFERENCES permissions(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS sections (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT UNIQUE
+        );
+        CREATE TABLE IF NOT EXISTS po_codes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT UNIQUE,
+            created_at DATETIME
+        );
+        CREATE TABLE IF NOT EXISTS po_code_sections (
+            po_code_id INTEGER,
+            section_id INTEGER,
+            PRIMARY KEY(po_code_id, section_id),
+            FOREIGN KEY(po_code_id) REFERENCES po_codes(id) ON DELETE CASCADE,
+            FOREIGN KEY(section_id) REFERENCES sections(id) ON DELETE CASCADE
+        );
+        CREATE TABLE IF NOT EXISTS process_order (
+            process_order TEXT,
+            posting_date TEXT,
+            shift TEXT,
+            description TEXT,
+            line TEXT,
+            po_code_id INTEGER,
+            material_id INTEGER,
+            FOREIGN KEY (po_code_id) REFERENCES po_codes(id)
+            FOREIGN KEY (material_id) REFERENCES materials(id)
+        );
+        CREATE TABLE IF NOT EXISTS materials (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            code TEXT UNIQUE,
+            key TEXT,
+            value TEXT,
+            created_at DATETIME
+        );
+        CREATE TABLE IF NOT EXISTS materials_value_description (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            value TEXT UNIQUE,
+            desc TEXT
+        );
+        CREATE TABLE IF NOT EXISTS machines (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT UNIQUE,
+            label TEXT,
+            section_id INTEGER,
+            FOREIGN KEY (section_id) REFERENCES sections(id)
+        );
+        CREATE TABLE IF NOT EXISTS user_sections (
+            user_id INTEGER,
+            section_id INTEGER,
+            PRIMARY KEY(user_id, section_id),
+            FOREIGN KEY(user_id) REFERENCES users(id),
+            FOREIGN KEY(section_id) REFERENCES sections(id)
+        );
+        CREATE TABLE IF NOT EXISTS shifts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT UNIQUE
+        );
+        CREATE TABLE IF NOT EXISTS colours (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT UNIQUE
+        );
+        CREATE TABLE IF NOT EXISTS solvent_types (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT UNIQUE
+        );
+        CREATE TABLE IF NOT EXISTS scrap_types (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT UNIQUE
+        );
+        CREATE TABLE IF NOT EXISTS downtime_reasons (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT UNIQUE
+        );
+        CREATE TABLE IF NOT EXISTS flag_reasons (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT UNIQUE,
+            section_id INTEGER,
+            FOREIGN KEY (section_id) REFERENCES sections(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS jobs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            machine_id INTEGER,
+            shift_id INTEGER,
+            created_by INTEGER,
+            production_order TEXT,
+            start_datetime DATETIME DEFAULT CURRENT_TIMESTAMP,
+            end_datetime DATETIME,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (machine_id) REFERENCES machines(id),
+            FOREIGN KEY (shift_id) REFERENCES shifts(id),
+            FOREIGN KEY (created_by) REFERENCES users(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS input_rolls (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            job_id INTEGER,
+            batch TEXT,
+            material_document TEXT DEFAULT NULL,
+            material_number TEXT,
+            start_meter DECIMAL(10,2),
+            created_by INTEGER,
+            start_weight DECIMAL(10,2),
+            is_consumed INTEGER DEFAULT 0,
+            consumed_at DATETIME DEFAULT NULL,
+            consumed_weight DECIMAL(10,2) DEFAULT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (job_id) REFERENCES jobs(id),
+            FOREIGN KEY (created_by) REFERENCES users(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS output_rolls (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            input_roll_id INTEGER,
+            from_input_batch TEXT,
+            output_batch TEXT,
+            final_meter DECIMAL(10,2),
+            flag_reason TEXT,
+            final_weight DECIMAL(10,2),
+            core_weight DECIMAL(10,2),
+            flag_count INTEGER DEFAULT 0,
+            created_by INTEGER,
+            updated_by INTEGER,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (input_roll_id) REFERENCES input_rolls(id),
+            FOREIGN KEY (created_by) REFERENCES users(id),
+            FOREIGN KEY (updated_by) REFERENCES users(id)
+        );
+        CREATE TABLE IF NOT EXISTS downtimes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            shift_id INTEGER,
+            start_time DATETIME,
+            end_time DATETIME,
+            duration_minutes INTEGER,
+            downtime_reason_id INTEGER,
+            created_by INTEGER,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (shift_id) REFERENCES shifts(id),
+            FOREIGN KEY (downtime_reason_id) REFERENCES downtime_reasons(id),
+            FOREIGN KEY (created_by) REFERENCES users(id)
+        );
+        CREATE TABLE IF NOT EXISTS scraps (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            shift_id INTEGER,
+            time DATETIME,
+            scrap_type_id INTEGER,
+            weight_kg DECIMAL(10,2),
+            notes TEXT,
+            created_by INTEGER,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (shift_id) REFERENCES shifts(id),
+            FOREIGN KEY (scrap_type_id) REFERENCES scrap_types(id),
+            FOREIGN KEY (created_by) REFERENCES users(id)
+        );
+        CREATE TABLE IF NOT EXISTS ink_usages (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            shift_id INTEGER,
+            colour_id INTEGER,
+            batch_code TEXT,
+            kgs_issued DECIMAL(10,2),
+            created_by INTEGER,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (shift_id) REFERENCES shifts(id),
+            FOREIGN KEY (colour_id) REFERENCES colours(id),
+            FOREIGN KEY (created_by) REFERENCES users(id)
+        );
+        CREATE TABLE IF NOT EXISTS solvent_usages (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            shift_id INTEGER,
+            solvent_type_id INTEGER,
+            kgs_issued DECIMAL(10,2),
+            created_by INTEGER,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (shift_id) REFERENCES shifts(id),
+            FOREIGN KEY (solvent_type_id) REFERENCES solvent_types(id),
+            FOREIGN KEY (created_by) REFERENCES users(id)
+        );
+
+        DROP TABLE IF EXISTS rolls;
+        DELETE FROM role_permissions WHERE permission_id IN (
+            SELECT p.id FROM permissions p
+            JOIN content_type c ON p.content_type_id = c.id
+            WHERE c.model = 'rolls'
+        );
+        DELETE FROM permissions WHERE content_type_id IN (
+            SELECT id FROM content_type WHERE model = 'rolls'
+        );
+        DELETE FROM content_type WHERE model = 'rolls';
+        ",
+        down: None,
+        marker: None,
+    },
+    Migration {
+        version: 2,
+        name: "jobs_section_id",
+        up: "
+        ALTER TABLE jobs ADD COLUMN section_id INTEGER;
+        UPDATE jobs SET section_id = (SELECT m.section_id FROM machines m WHERE m.id = jobs.machine_id);
+        ",
+        down: Some(
+            "
+        ALTER TABLE jobs DROP COLUMN section_id;
+        ",
+        ),
+        marker: Some(("jobs", "section_id")),
+    },
+    Migration {
+        version: 3,
+        name: "audit_log",
+        up: "
+        CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            user_id INTEGER,
+            model TEXT NOT NULL,
+            row_id INTEGER NOT NULL,
+            action TEXT NOT NULL,
+            changes TEXT NOT NULL,
+            created_at DATETIME NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_audit_log_model_row ON audit_log (model, row_id);
+        CREATE INDEX IF NOT EXISTS idx_audit_log_user ON audit_log (user_id);
+        CREATE INDEX IF NOT EXISTS idx_audit_log_created_at ON audit_log (created_at);
+        ",
+        down: Some(
+            "
+        DROP TABLE IF EXISTS audit_log;
+        ",
+        ),
+        marker: None,
+    },
+    Migration {
+        version: 4,
+        name: "users_failed_logins",
+        up: "
+        ALTER TABLE users ADD COLUMN failed_logins INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE users ADD COLUMN locked_until DATETIME;
+        ",
+        down: Some(
+            "
+        ALTER TABLE users DROP COLUMN locked_until;
+        ALTER TABLE users DROP COLUMN failed_logins;
+        ",
+        ),
+        marker: Some(("users", "failed_logins")),
+    },
+    Migration {
+        version: 5,
+        name: "sessions",
+        up: "
+        CREATE TABLE IF NOT EXISTS sessions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_key TEXT NOT NULL UNIQUE,
+            user_id INTEGER,
+            state TEXT NOT NULL,
+            created_at DATETIME NOT NULL,
+            last_seen DATETIME NOT NULL,
+            expires_at DATETIME NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_sessions_user ON sessions (user_id);
+        ",
+        down: Some(
+            "
+        DROP TABLE IF EXISTS sessions;
+        ",
+        ),
+        marker: None,
+    },
+    Migration {
+        version: 6,
+        name: "sap_outbox",
+        up: "
+        CREATE TABLE IF NOT EXISTS sap_outbox (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            kind TEXT NOT NULL,
+            idempotency_key TEXT NOT NULL UNIQUE,
+            payload TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'pending',
+            attempts INTEGER NOT NULL DEFAULT 0,
+            last_error TEXT,
+            document TEXT,
+            ref_model TEXT,
+            ref_id INTEGER,
+            created_by INTEGER,
+            next_attempt_at DATETIME NOT NULL,
+            posted_at DATETIME,
+            created_at DATETIME NOT NULL,
+            updated_at DATETIME NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_sap_outbox_status ON sap_outbox (status, next_attempt_at);
+        CREATE INDEX IF NOT EXISTS idx_sap_outbox_ref ON sap_outbox (ref_model, ref_id);
+        ",
+        down: Some(
+            "
+        DROP TABLE IF EXISTS sap_outbox;
+        ",
+        ),
+        marker: None,
+    },
+    Migration {
+        version: 7,
+        name: "roll_lineage",
+        up: "
+        CREATE TABLE IF NOT EXISTS roll_lineage (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            parent_batch TEXT NOT NULL,
+            parent_input_roll_id INTEGER,
+            output_roll_id INTEGER NOT NULL,
+            created_at DATETIME NOT NULL,
+            UNIQUE (parent_batch, output_roll_id),
+            FOREIGN KEY (parent_input_roll_id) REFERENCES input_rolls(id) ON DELETE SET NULL,
+            FOREIGN KEY (output_roll_id) REFERENCES output_rolls(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_roll_lineage_parent ON roll_lineage (parent_batch);
+        CREATE INDEX IF NOT EXISTS idx_roll_lineage_parent_roll ON roll_lineage (parent_input_roll_id);
+        CREATE INDEX IF NOT EXISTS idx_roll_lineage_output ON roll_lineage (output_roll_id);
+
+        WITH RECURSIVE split(output_roll_id, job_id, batch, rest, created_at) AS (
+            SELECT o.id, ir.job_id, '', o.from_input_batch || ',', COALESCE(o.created_at, datetime('now'))
+            FROM output_rolls o JOIN input_rolls ir ON o.input_roll_id = ir.id
+            WHERE COALESCE(o.from_input_batch, '') <> ''
+            UNION ALL
+            SELECT output_roll_id, job_id, TRIM(substr(rest, 1, instr(rest, ',') - 1)),
+                   substr(rest, instr(rest, ',') + 1), created_at
+            FROM split WHERE rest <> ''
+        )
+        INSERT OR IGNORE INTO roll_lineage (parent_batch, parent_input_roll_id, output_roll_id, created_at)
+        SELECT s.batch,
+               COALESCE(
+                   (SELECT MAX(ir.id) FROM input_rolls ir WHERE ir.batch = s.batch AND ir.job_id = s.job_id),
+                   (SELECT MAX(ir.id) FROM input_rolls ir WHERE ir.batch = s.batch)
+               ),
+               s.output_roll_id, s.created_at
+        FROM split s WHERE s.batch <> '';
+        ",
+        down: Some(
+            "
+        DROP TABLE IF EXISTS roll_lineage;
+        ",
+        ),
+        marker: None,
+    },
+    Migration {
+        version: 8,
+        name: "output_rolls_as_inputs",
+        up: "
+        ALTER TABLE output_rolls ADD COLUMN consumed_at DATETIME;
+        ALTER TABLE output_rolls ADD COLUMN consumed_job_id INTEGER REFERENCES jobs(id);
+        ALTER TABLE input_rolls ADD COLUMN source_output_roll_id INTEGER REFERENCES output_rolls(id);
+        ",
+        down: Some(
+            "
+        ALTER TABLE input_rolls DROP COLUMN source_output_roll_id;
+        ALTER TABLE output_rolls DROP COLUMN consumed_job_id;
+        ALTER TABLE output_rolls DROP COLUMN consumed_at;
+        ",
+        ),
+        marker: Some(("input_rolls", "source_output_roll_id")),
+    },
+    Migration {
+        version: 9,
+        name: "roll_dispositions",
+        up: "
+        ALTER TABLE output_rolls ADD COLUMN qa_status TEXT;
+        CREATE TABLE IF NOT EXISTS roll_dispositions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            output_roll_id INTEGER NOT NULL,
+            action TEXT NOT NULL,
+            from_status TEXT,
+            to_status TEXT NOT NULL,
+            notes TEXT NOT NULL,
+            scrap_id INTEGER,
+            created_by INTEGER,
+            created_at DATETIME NOT NULL,
+            FOREIGN KEY (output_roll_id) REFERENCES output_rolls(id) ON DELETE CASCADE,
+            FOREIGN KEY (scrap_id) REFERENCES scraps(id) ON DELETE SET NULL,
+            FOREIGN KEY (created_by) REFERENCES users(id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_roll_dispositions_roll ON roll_dispositions (output_roll_id);
+        CREATE INDEX IF NOT EXISTS idx_output_rolls_qa_status ON output_rolls (qa_status);
+        ",
+        down: Some(
+            "
+        DROP TABLE IF EXISTS roll_dispositions;
+        DROP INDEX IF EXISTS idx_output_rolls_qa_status;
+        ALTER TABLE output_rolls DROP COLUMN qa_status;
+        ",
+        ),
+        marker: Some(("output_rolls", "qa_status")),
+    },
+    Migration {
+        version: 10,
+        name: "machines_rated_speed",
+        up: "ALTER TABLE machines ADD COLUMN rated_speed REAL;",
+        down: Some("ALTER TABLE machines DROP COLUMN rated_speed;"),
+        marker: Some(("machines", "rated_speed")),
+    },
+    Migration {
+        version: 11,
+        name: "downtimes_machine_job",
+        up: "
+        ALTER TABLE downtimes ADD COLUMN machine_id INTEGER REFERENCES machines(id);
+        ALTER TABLE downtimes ADD COLUMN job_id INTEGER REFERENCES jobs(id) ON DELETE SET NULL;
+        UPDATE downtimes SET job_id = (
+            SELECT j.id FROM jobs j
+            WHERE j.shift_id = downtimes.shift_id AND j.created_by = downtimes.created_by
+            AND julianday(downtimes.start_time) BETWEEN julianday(j.start_datetime)
+                AND julianday(COALESCE(j.end_datetime, downtimes.start_time))
+            ORDER BY j.id LIMIT 1
+        );
+        UPDATE downtimes SET machine_id = (SELECT machine_id FROM jobs WHERE id = downtimes.job_id)
+        WHERE job_id IS NOT NULL;
+        CREATE INDEX IF NOT EXISTS idx_downtimes_machine ON downtimes (machine_id, start_time);
+        CREATE INDEX IF NOT EXISTS idx_downtimes_job ON downtimes (job_id);
+        ",
+        down: Some(
+            "
+        DROP INDEX IF EXISTS idx_downtimes_job;
+        DROP INDEX IF EXISTS idx_downtimes_machine;
+        ALTER TABLE downtimes DROP COLUMN job_id;
+        ALTER TABLE downtimes DROP COLUMN machine_id;
+        ",
+        ),
+        marker: Some(("downtimes", "machine_id")),
+    },
+    Migration {
+        version: 12,
+        name: "shift_calendar",
+        up: "
+        ALTER TABLE shifts ADD COLUMN start_time TEXT;
+        ALTER TABLE shifts ADD COLUMN end_time TEXT;
+        ALTER TABLE shifts ADD COLUMN sequence INTEGER;
+        ALTER TABLE shifts ADD COLUMN active INTEGER NOT NULL DEFAULT 1;
+        UPDATE shifts SET start_time = '07:00', end_time = '19:00', sequence = 1 WHERE id = 1;
+        UPDATE shifts SET start_time = '19:00', end_time = '07:00', sequence = 2 WHERE id = 2;
+        CREATE TABLE IF NOT EXISTS plant_holidays (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            date TEXT NOT NULL,
+            name TEXT NOT NULL,
+            shift_id INTEGER,
+            created_by INTEGER,
+            created_at DATETIME NOT NULL,
+            FOREIGN KEY (shift_id) REFERENCES shifts(id) ON DELETE CASCADE,
+            FOREIGN KEY (created_by) REFERENCES users(id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_plant_holidays_date ON plant_holidays (date);
+        ALTER TABLE jobs ADD COLUMN production_date TEXT;
+        UPDATE jobs SET production_date = CASE
+            WHEN EXISTS (
+                SELECT 1 FROM shifts s
+                WHERE s.id = jobs.shift_id AND s.end_time <= s.start_time
+                AND time(jobs.start_datetime) < s.end_time
+            ) THEN date(jobs.start_datetime, '-1 day')
+            ELSE date(jobs.start_datetime)
+        END
+        WHERE start_datetime IS NOT NULL;
+        ",
+        down: Some(
+            "
+        ALTER TABLE jobs DROP COLUMN production_date;
+        DROP TABLE IF EXISTS plant_holidays;
+        ALTER TABLE shifts DROP COLUMN active;
+        ALTER TABLE shifts DROP COLUMN sequence;
+        ALTER TABLE shifts DROP COLUMN end_time;
+        ALTER TABLE shifts DROP COLUMN start_time;
+        ",
+        ),
+        marker: Some(("shifts", "start_time")),
+    },
+    Migration {
+        version: 13,
+        name: "batch_sequences",
+        up: "
+        ALTER TABLE sections ADD COLUMN batch_pattern TEXT;
+        CREATE TABLE IF NOT EXISTS batch_sequences (
+            scope TEXT PRIMARY KEY,
+            last_value INTEGER NOT NULL,
+            updated_at DATETIME NOT NULL
+        );
+        -- Counting rows could hand out a batch twice. Labels and SAP already carry those
+        -- batches, so later copies keep them and are only left out of the unique index
+        ALTER TABLE output_rolls ADD COLUMN duplicate_batch INTEGER NOT NULL DEFAULT 0;
+        UPDATE output_rolls SET duplicate_batch = 1
+        WHERE output_batch IS NOT NULL
+        AND id NOT IN (SELECT MIN(id) FROM output_rolls GROUP BY output_batch);
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_output_rolls_output_batch ON output_rolls (output_batch)
+        WHERE duplicate_batch = 0;
+        -- Carry on from the roll numbers already issued in the default format
+        INSERT OR IGNORE INTO batch_sequences (scope, last_value, updated_at)
+        SELECT substr(output_batch, 1, length(output_batch) - 3) || '###',
+               MAX(CAST(substr(output_batch, -3) AS INTEGER)),
+               datetime('now', 'localtime')
+        FROM output_rolls
+        WHERE output_batch GLOB '*[0-9][0-9][0-9]'
+        GROUP BY substr(output_batch, 1, length(output_batch) - 3);
+        ",
+        down: Some(
+            "
+        DROP INDEX IF EXISTS idx_output_rolls_output_batch;
+        ALTER TABLE output_rolls DROP COLUMN duplicate_batch;
+        DROP TABLE IF EXISTS batch_sequences;
+        ALTER TABLE sections DROP COLUMN batch_pattern;
+        ",
+        ),
+        marker: Some(("sections", "batch_pattern")),
+    },
+    Migration {
+        version: 14,
+        name: "label_prints",
+        up: "
+        CREATE TABLE IF NOT EXISTS label_prints (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            output_roll_id INTEGER NOT NULL,
+            format TEXT NOT NULL,
+            reprint INTEGER NOT NULL,
+            printed_by INTEGER,
+            printed_at DATETIME NOT NULL,
+            FOREIGN KEY (output_roll_id) REFERENCES output_rolls(id) ON DELETE CASCADE,
+            FOREIGN KEY (printed_by) REFERENCES users(id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_label_prints_output_roll_id ON label_prints (output_roll_id);
+        ",
+        down: Some("DROP TABLE IF EXISTS label_prints;"),
+        marker: Some(("label_prints", "reprint")),
+    },
+    Migration {
+        version: 15,
+        name: "scraps_job",
+        up: "
+        ALTER TABLE scraps ADD COLUMN job_id INTEGER REFERENCES jobs(id) ON DELETE SET NULL;
+        UPDATE scraps SET job_id = (
+            SELECT ir.job_id FROM roll_dispositions d
+            JOIN output_rolls o ON d.output_roll_id = o.id
+            JOIN input_rolls ir ON o.input_roll_id = ir.id
+            WHERE d.scrap_id = scraps.id
+        );
+        UPDATE scraps SET job_id = (
+            SELECT j.id FROM jobs j
+            WHERE j.shift_id = scraps.shift_id AND j.created_by = scraps.created_by
+            AND julianday(scraps.time) BETWEEN julianday(j.start_datetime)
+                AND julianday(COALESCE(j.end_datetime, scraps.time))
+            ORDER BY j.id LIMIT 1
+        )
+        WHERE job_id IS NULL;
+        CREATE INDEX IF NOT EXISTS idx_scraps_job ON scraps (job_id);
+        ",
+        down: Some(
+            "
+        DROP INDEX IF EXISTS idx_scraps_job;
+        ALTER TABLE scraps DROP COLUMN job_id;
+        ",
+        ),
+        marker: Some(("scraps", "job_id")),
+    },
+    Migration {
+        version: 16,
+        name: "remnants",
+        up: "
+        CREATE TABLE IF NOT EXISTS remnants (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            input_roll_id INTEGER NOT NULL,
+            batch TEXT NOT NULL,
+            material_number TEXT,
+            weight DECIMAL(10,2) NOT NULL,
+            unit TEXT NOT NULL DEFAULT 'KG',
+            meter DECIMAL(10,2) NOT NULL DEFAULT 0,
+            storage_location TEXT,
+            return_document TEXT,
+            label_prints INTEGER NOT NULL DEFAULT 0,
+            consumed_job_id INTEGER,
+            consumed_at DATETIME,
+            created_by INTEGER,
+            created_at DATETIME NOT NULL,
+            FOREIGN KEY (input_roll_id) REFERENCES input_rolls(id) ON DELETE CASCADE,
+            FOREIGN KEY (consumed_job_id) REFERENCES jobs(id) ON DELETE SET NULL,
+            FOREIGN KEY (created_by) REFERENCES users(id)
+        );
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_remnants_input_roll_id ON remnants (input_roll_id);
+        ALTER TABLE input_rolls ADD COLUMN source_remnant_id INTEGER REFERENCES remnants(id);
+        ",
+        down: Some(
+            "
+        ALTER TABLE input_rolls DROP COLUMN source_remnant_id;
+        DROP TABLE IF EXISTS remnants;
+        ",
+        ),
+        marker: Some(("remnants", "label_prints")),
+    },
+    Migration {
+        version: 17,
+        name: "sap_reversals",
+        up: "
+        CREATE TABLE IF NOT EXISTS sap_reversals (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            ref_model TEXT NOT NULL,
+            ref_id INTEGER NOT NULL,
+            original_key TEXT NOT NULL UNIQUE,
+            original_document TEXT,
+            reversal_key TEXT NOT NULL UNIQUE,
+            reversal_document TEXT,
+            reason TEXT,
+            reversed_at DATETIME,
+            repost_key TEXT,
+            created_by INTEGER,
+            created_at DATETIME NOT NULL,
+            FOREIGN KEY (created_by) REFERENCES users(id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_sap_reversals_ref ON sap_reversals (ref_model, ref_id);
+        ",
+        down: Some("DROP TABLE IF EXISTS sap_reversals;"),
+        marker: Some(("sap_reversals", "repost_key")),
+    },
+    Migration {
+        version: 18,
+        name: "bom_conformance",
+        up: "
+        CREATE TABLE IF NOT EXISTS bom_alternatives (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            material_number TEXT NOT NULL,
+            alternative_number TEXT NOT NULL,
+            process_order TEXT,
+            note TEXT,
+            created_by INTEGER,
+            created_at DATETIME NOT NULL,
+            FOREIGN KEY (created_by) REFERENCES users(id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_bom_alternatives_alternative ON bom_alternatives (alternative_number);
+        ALTER TABLE input_rolls ADD COLUMN bom_override_by INTEGER REFERENCES users(id);
+        ALTER TABLE input_rolls ADD COLUMN bom_override_reason TEXT;
+        ",
+        down: Some(
+            "
+        ALTER TABLE input_rolls DROP COLUMN bom_override_reason;
+        ALTER TABLE input_rolls DROP COLUMN bom_override_by;
+        DROP TABLE IF EXISTS bom_alternatives;
+        ",
+        ),
+        marker: Some(("input_rolls", "bom_override_reason")),
+    },
+    Migration {
+        version: 19,
+        name: "remnant_return_location",
+        up: "
+        ALTER TABLE remnants ADD COLUMN return_location TEXT;
+        ",
+        down: Some("ALTER TABLE remnants DROP COLUMN return_location;"),
+        marker: Some(("remnants", "return_location")),
+    },
+];
+
+fn ensure_migrations_table(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            name TEXT,
+            applied_at DATETIME
+        );
+        ",
+    )
+}
+
+fn table_exists(conn: &Connection, table: &str) -> Result<bool> {
+    let count: i32 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        params![table],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+fn column_exists(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let count: i32 = conn.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info(?1) WHERE name = ?2",
+        params![table, column],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+pub fn latest_version() -> i32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+pub fn current_version(conn: &Connection) -> Result<i32> {
+    ensure_migrations_table(conn)?;
+    conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_migrations",
+        [],
+        |row| row.get(0),
+    )
+}
+
+pub fn status(conn: &Connection) -> Result<Vec<MigrationStatus>> {
+    ensure_migrations_table(conn)?;
+    let mut result = Vec::new();
+    for migration in MIGRATIONS {
+        let applied_at: Option<String> = conn
+            .query_row(
+                "SELECT applied_at FROM schema_migrations WHERE version = ?1",
+                params![migration.version],
+                |row| row.get(0),
+            )
+            .optional()?;
+        result.push(MigrationStatus {
+            version: migration.version,
+            name: migration.name,
+            applied_at,
+        });
+    }
+    Ok(result)
+}
+
+/// Applies pending migrations in order up to `target` (or the latest one), each in its
+/// own transaction. Returns the versions that were applied.
+pub fn migrate_up(conn: &mut Connection, target: Option<i32>) -> Result<Vec<i32>> {
+    let target = target.unwrap_or_else(latest_version);
+    let mut version = current_version(conn)?;
+    let legacy_install = version == 0 && table_exists(conn, "users")?;
+    if legacy_install {
+        log::info!(
+            "Existing database without schema_migrations detected, bringing it to version {}",
+            target
+        );
+    }
+
+    let mut applied = Vec::new();
+    for migration in MIGRATIONS {
+        if migration.version <= version || migration.version > target {
+            continue;
+        }
+        let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let tx = conn.transaction()?;
+        let already_present = match migration.marker {
+            Some((table, column)) if legacy_install => column_exists(&tx, table, column)?,
+            _ => false,
+        };
+        if !already_present {
+            tx.execute_batch(migration.up)?;
+        }
+        tx.execute(
+            "INSERT INTO schema_migrations (version, name, applied_at) VALUES (?1, ?2, ?3)",
+            params![migration.version, migration.name, now],
+        )?;
+        tx.commit()?;
+        log::info!(
+            "Applied migration {} ({})",
+            migration.version,
+            migration.name
+        );
+        version = migration.version;
+        applied.push(migration.version);
+    }
+    Ok(applied)
+}
+
+/// Reverts applied migrations newer than `target`, newest first. Returns the versions
+/// that were reverted.
+pub fn migrate_down(conn: &mut Connection, target: i32) -> Result<Vec<i32>> {
+    let version = current_version(conn)?;
+    let mut reverted = Vec::new();
+    for migration in MIGRATIONS.iter().rev() {
+        if migration.version > version || migration.version <= target {
+            continue;
+        }
+        let down = migration.down.ok_or_else(|| {
+            rusqlite::Error::InvalidParameterName(format!(
+                "Migration {} ({}) cannot be reverted",
+                migration.version, migration.name
+            ))
+        })?;
+        let tx = conn.transaction()?;
+        tx.execute_batch(down)?;
+        tx.execute(
+            "DELETE FROM schema_migrations WHERE version = ?1",
+            params![migration.version],
+        )?;
+        tx.commit()?;
+        log::info!(
+            "Reverted migration {} ({})",
+            migration.version,
+            migration.name
+        );
+        reverted.push(migration.version);
+    }
+    Ok(reverted)
+}
+
+#[test]
+fn test_migrations_round_trip() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    assert_eq!(migrate_up(&mut conn, None).unwrap().len(), MIGRATIONS.len());
+    assert_eq!(current_version(&conn).unwrap(), latest_version());
+    assert!(migrate_up(&mut conn, None).unwrap().is_empty());
+
+    migrate_down(&mut conn, 1).unwrap();
+    assert_eq!(current_version(&conn).unwrap(), 1);
+    assert!(!column_exists(&conn, "jobs", "section_id").unwrap());
+    assert!(migrate_down(&mut conn, 0).is_err());
+
+    migrate_up(&mut conn, None).unwrap();
+    assert!(column_exists(&conn, "jobs", "section_id").unwrap());
+}
+
+#[test]
+fn test_duplicate_batches_survive_the_unique_index() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    migrate_up(&mut conn, Some(12)).unwrap();
+    conn.execute_batch(
+        "INSERT INTO output_rolls (id, output_batch, final_meter, final_weight) VALUES
+             (1, '26001P1001', 0, 0), (2, '26001P1001', 0, 0), (3, '26001P1002', 0, 0);",
+    )
+    .unwrap();
+    migrate_up(&mut conn, Some(13)).unwrap();
+
+    let batches: Vec<String> = conn
+        .prepare("SELECT output_batch FROM output_rolls ORDER BY id")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_>>()
+        .unwrap();
+    assert_eq!(batches, vec!["26001P1001", "26001P1001", "26001P1002"]);
+    assert!(
+        conn.execute(
+            "INSERT INTO output_rolls (output_batch, final_meter, final_weight) VALUES ('26001P1002', 0, 0)",
+            [],
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn test_roll_dispositions_leave_historical_rolls_released() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    migrate_up(&mut conn, Some(8)).unwrap();
+    conn.execute(
+        "INSERT INTO output_rolls (id, output_batch, final_meter, final_weight, flag_reason, flag_count) VALUES (1, '26001P1001', 0, 0, '3:Colour', 1)",
+        [],
+    )
+    .unwrap();
+    migrate_up(&mut conn, Some(9)).unwrap();
+
+    let qa_status: Option<String> = conn
+        .query_row(
+            "SELECT qa_status FROM output_rolls WHERE id = 1",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(qa_status, None);
+}
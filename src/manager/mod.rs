@@ -1,3 +1,4 @@
 pub mod db;
 pub mod config;
 pub mod helper;
+pub mod migrations;
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::sap::{
+    GoodsIssue, OrderComponent, RollData, SapFuture, SapGateway, SapProcessOrder, SapSettings, stub,
+};
+
+#[derive(Debug, Clone)]
+pub struct BatchStock {
+    pub material: String,
+    pub storage_location: String,
+    pub batch: String,
+    pub quantity: f64,
+    pub unit: String,
+}
+
+/// Data served by [`FakeSapGateway`] and everything posted to it.
+#[derive(Default)]
+pub struct FakeSapState {
+    pub process_orders: Vec<SapProcessOrder>,
+    pub components: HashMap<String, Vec<OrderComponent>>,
+    pub descriptions: HashMap<String, String>,
+    pub stock: Vec<BatchStock>,
+    pub goods_issues: Vec<GoodsIssue>,
+    pub rolls: Vec<RollData>,
+    pub cancelled_documents: Vec<String>,
+    pub reversed_rolls: Vec<RollData>,
+    /// When set every posting fails with this message, as if SAP were unreachable
+    pub fail_postings: Option<String>,
+}
+
+/// In-process SAP for tests and offline demos.
+#[derive(Default)]
+pub struct FakeSapGateway {
+    state: Mutex<FakeSapState>,
+    settings: SapSettings,
+}
+
+impl FakeSapGateway {
+    pub fn new(state: FakeSapState) -> Self {
+        FakeSapGateway {
+            state: Mutex::new(state),
+            settings: SapSettings::default(),
+        }
+    }
+
+    pub fn with_settings(mut self, settings: SapSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Seeds the fake with the same recorded responses the stub server replays.
+    pub fn from_fixtures() -> Self {
+        let mut state = FakeSapState::default();
+
+        if let Ok(orders) =
+            serde_json::from_value(stub::fixture(stub::PROCESS_ORDERS)["d"]["results"].clone())
+        {
+            state.process_orders = orders;
+        }
+        for order in stub::fixture(stub::PRODUCTION_ORDERS)["d"]["results"]
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            if let (Some(id), Ok(components)) = (
+                order["ManufacturingOrder"].as_str(),
+                serde_json::from_value(order["to_ProductionOrderComponent"]["results"].clone()),
+            ) {
+                state.components.insert(id.to_string(), components);
+            }
+        }
+        for item in stub::fixture(stub::MATERIAL_DESCRIPTIONS)["data"]["mAKTs"]["items"]
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            if let (Some(matnr), Some(maktx)) = (item["MATNR"].as_str(), item["MAKTX"].as_str()) {
+                state
+                    .descriptions
+                    .insert(matnr.trim_start_matches('0').to_string(), maktx.to_string());
+            }
+        }
+        for item in stub::fixture(stub::BATCH_STOCK)["d"]["results"]
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            state.stock.push(BatchStock {
+                material: item["Material"].as_str().unwrap_or_default().to_string(),
+                storage_location: item["StorageLocation"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                batch: item["Batch"].as_str().unwrap_or_default().to_string(),
+                quantity: item["TotalStock"]
+                    .as_str()
+                    .and_then(|v| v.trim().parse().ok())
+                    .unwrap_or(0.0),
+                unit: item["UnitOfMeasure"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+            });
+        }
+
+        Self::new(state)
+    }
+
+    pub fn state(&self) -> MutexGuard<'_, FakeSapState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl SapGateway for FakeSapGateway {
+    fn settings(&self) -> &SapSettings {
+        &self.settings
+    }
+
+    fn process_orders<'a>(&'a self, _posting_date: &'a str) -> SapFuture<'a, Vec<SapProcessOrder>> {
+        let orders = self.state().process_orders.clone();
+        Box::pin(async move { Ok(orders) })
+    }
+
+    fn order_components<'a>(&'a self, order: &'a str) -> SapFuture<'a, Vec<OrderComponent>> {
+        let components = self
+            .state()
+            .components
+            .get(order)
+            .cloned()
+            .unwrap_or_default();
+        Box::pin(async move { Ok(components) })
+    }
+
+    fn material_descriptions(&self, matnrs: Vec<String>) -> SapFuture<'_, HashMap<String, String>> {
+        let state = self.state();
+        let descriptions = matnrs
+            .iter()
+            .filter_map(|m| state.descriptions.get(m).map(|d| (m.clone(), d.clone())))
+            .collect();
+        Box::pin(async move { Ok(descriptions) })
+    }
+
+    fn batch_availability<'a>(
+        &'a self,
+        material: &'a str,
+        storage_location: &'a str,
+    ) -> SapFuture<'a, Vec<(String, String)>> {
+        let batches = self
+            .state()
+            .stock
+            .iter()
+            .filter(|s| {
+                s.material == material && s.storage_location == storage_location && s.quantity > 0.0
+            })
+            .map(|s| (s.batch.clone(), format!("{:.3}{}", s.quantity, s.unit)))
+            .collect();
+        Box::pin(async move { Ok(batches) })
+    }
+
+    fn post_goods_issue<'a>(&'a self, issue: &'a GoodsIssue) -> SapFuture<'a, String> {
+        let mut state = self.state();
+        let result = match &state.fail_postings {
+            Some(e) => Err(e.clone().into()),
+            None => {
+                state.goods_issues.push(issue.clone());
+                Ok(format!(
+                    "49{:08}",
+                    state.goods_issues.len() + state.cancelled_documents.len()
+                ))
+            }
+        };
+        Box::pin(async move { result })
+    }
+
+    fn post_roll<'a>(&'a self, roll: &'a RollData) -> SapFuture<'a, ()> {
+        let mut state = self.state();
+        let result = match &state.fail_postings {
+            Some(e) => Err(e.clone().into()),
+            None => {
+                state.rolls.push(roll.clone());
+                Ok(())
+            }
+        };
+        Box::pin(async move { result })
+    }
+
+    fn cancel_material_document<'a>(
+        &'a self,
+        document: &'a str,
+        _year: &'a str,
+    ) -> SapFuture<'a, String> {
+        let mut state = self.state();
+        let result = match &state.fail_postings {
+            Some(e) => Err(e.clone().into()),
+            None if state.cancelled_documents.iter().any(|d| d == document) => {
+                Err(format!("Material document {} is already cancelled", document).into())
+            }
+            None => {
+                state.cancelled_documents.push(document.to_string());
+                Ok(format!(
+                    "49{:08}",
+                    state.goods_issues.len() + state.cancelled_documents.len()
+                ))
+            }
+        };
+        Box::pin(async move { result })
+    }
+
+    fn reverse_roll<'a>(&'a self, roll: &'a RollData) -> SapFuture<'a, ()> {
+        let mut state = self.state();
+        let result = match &state.fail_postings {
+            Some(e) => Err(e.clone().into()),
+            None => {
+                state.reversed_rolls.push(roll.clone());
+                Ok(())
+            }
+        };
+        Box::pin(async move { result })
+    }
+}
@@ -0,0 +1,277 @@
+use futures_util::future::BoxFuture;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, env, error, sync::Arc};
+
+use crate::sap::{FakeSapGateway, RollData, SapSettings};
+
+pub type SapError = Box<dyn error::Error + Send + Sync>;
+pub type SapFuture<'a, T> = BoxFuture<'a, Result<T, SapError>>;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SapProcessOrder {
+    #[serde(rename = "ProcessOrder")]
+    pub process_order: String,
+    #[serde(rename = "PostingDate")]
+    pub posting_date: String,
+    #[serde(rename = "Shift")]
+    pub shift: String,
+    #[serde(rename = "Material")]
+    pub material: String,
+    #[serde(rename = "MaterialDescription")]
+    pub material_description: String,
+    #[serde(rename = "Line")]
+    pub line: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OrderComponent {
+    #[serde(rename = "MaterialGroup")]
+    pub material_group: String,
+    #[serde(rename = "Material")]
+    pub material: String,
+}
+
+/// Goods issue of a batch against a process order.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GoodsIssue {
+    pub material: String,
+    pub batch: String,
+    pub order: String,
+    pub quantity: String,
+    pub unit: String,
+    pub posting_date: String,
+    pub storage_location: String,
+    /// Reference written to the document header so the posting can be found in SAP
+    pub header_text: String,
+    /// Overrides the plant's issue movement type, as for a returned remnant
+    #[serde(default)]
+    pub movement_type: Option<String>,
+    /// Receiving storage location of a transfer posting
+    #[serde(default)]
+    pub receiving_storage_location: Option<String>,
+}
+
+/// Every call the MES makes to SAP and Fabric. The backend holds one gateway in `web::Data`
+/// so handlers, background sync and the outbox worker can run against the real QA system, the
+/// bundled stub server or [`FakeSapGateway`] in tests.
+pub trait SapGateway: Send + Sync {
+    /// Plant codes used for every call and posting
+    fn settings(&self) -> &SapSettings;
+    /// Process orders posted on `posting_date` (`YYYY-MM-DD`)
+    fn process_orders<'a>(&'a self, posting_date: &'a str) -> SapFuture<'a, Vec<SapProcessOrder>>;
+    /// Components of a production order, empty when SAP does not know the order
+    fn order_components<'a>(&'a self, order: &'a str) -> SapFuture<'a, Vec<OrderComponent>>;
+    /// Material descriptions keyed by material number without leading zeros
+    fn material_descriptions(&self, matnrs: Vec<String>) -> SapFuture<'_, HashMap<String, String>>;
+    /// Batches in stock as `(batch, "<qty><unit>")`
+    fn batch_availability<'a>(
+        &'a self,
+        material: &'a str,
+        storage_location: &'a str,
+    ) -> SapFuture<'a, Vec<(String, String)>>;
+    /// Posts a goods issue (or its return) and returns the material document number
+    fn post_goods_issue<'a>(&'a self, issue: &'a GoodsIssue) -> SapFuture<'a, String>;
+    /// Confirms a weighed output roll
+    fn post_roll<'a>(&'a self, roll: &'a RollData) -> SapFuture<'a, ()>;
+    /// Cancels a posted material document and returns the reversal document number
+    fn cancel_material_document<'a>(
+        &'a self,
+        document: &'a str,
+        year: &'a str,
+    ) -> SapFuture<'a, String>;
+    /// Reverses the confirmation of an output roll
+    fn reverse_roll<'a>(&'a self, roll: &'a RollData) -> SapFuture<'a, ()>;
+}
+
+/// The SAP API Management and Fabric endpoints, configured from the environment.
+#[derive(Clone, Default)]
+pub struct HttpSapGateway {
+    pub(crate) client: Client,
+    pub(crate) settings: SapSettings,
+    pub(crate) pso_url: String,
+    pub(crate) pso_api_key: String,
+    pub(crate) pdo_url: String,
+    pub(crate) pdo_username: String,
+    pub(crate) pdo_password: String,
+    pub(crate) mav_url: String,
+    pub(crate) mav_api_key: String,
+    pub(crate) material_document_url: String,
+    pub(crate) material_document_api_key: String,
+    pub(crate) roll_url: String,
+    pub(crate) roll_api_key: String,
+    pub(crate) fabric_token_url: String,
+    pub(crate) fabric_client_id: String,
+    pub(crate) fabric_client_secret: String,
+    pub(crate) fabric_api_url: String,
+}
+
+impl HttpSapGateway {
+    pub fn from_env(settings: SapSettings) -> Self {
+        let var = |key: &str| env::var(key).unwrap_or_default();
+        let tenant = var("FABRIC_TENANT_ID");
+        HttpSapGateway {
+            client: Client::new(),
+            settings,
+            pso_url: var("SAP_QA_PSO_BASE_URL"),
+            pso_api_key: var("SAP_QA_PSO_APIKey"),
+            pdo_url: var("SAP_QA_PDO_URL"),
+            pdo_username: var("SAP_QA_PDO_USERNAME"),
+            pdo_password: var("SAP_QA_PDO_PASSWORD"),
+            mav_url: var("SAP_QA_MAV_BASE_URL"),
+            mav_api_key: var("SAP_QA_MAV_APIKey"),
+            material_document_url: var("SAP_QA_MATERIAL_INSUANCE_URL"),
+            material_document_api_key: var("SAP_QA_MATERIAL_INSUANCE_APIKEY"),
+            roll_url: var("SAP_QA_ROLL_URL"),
+            roll_api_key: var("SAP_QA_ROLL_APIKEY"),
+            fabric_token_url: env::var("FABRIC_TOKEN_URL").unwrap_or_else(|_| {
+                format!(
+                    "https://login.microsoftonline.com/{}/oauth2/v2.0/token",
+                    tenant
+                )
+            }),
+            fabric_client_id: var("FABRIC_CLIENT_ID"),
+            fabric_client_secret: var("FABRIC_CLIENT_SECRET"),
+            fabric_api_url: var("FABRIC_AUTH_API"),
+        }
+    }
+
+    /// Points every endpoint at one server, such as the stub from `sap::stub`.
+    pub fn with_base_url(base_url: &str) -> Self {
+        let base = base_url.trim_end_matches('/');
+        HttpSapGateway {
+            client: Client::new(),
+            settings: SapSettings::default(),
+            pso_url: format!("{}/pso", base),
+            pso_api_key: "stub".into(),
+            pdo_url: format!("{}/pdo", base),
+            pdo_username: "stub".into(),
+            pdo_password: "stub".into(),
+            mav_url: format!("{}/mav", base),
+            mav_api_key: "stub".into(),
+            material_document_url: format!("{}/material-document", base),
+            material_document_api_key: "stub".into(),
+            roll_url: format!("{}/rolls", base),
+            roll_api_key: "stub".into(),
+            fabric_token_url: format!("{}/fabric/token", base),
+            fabric_client_id: "stub".into(),
+            fabric_client_secret: "stub".into(),
+            fabric_api_url: format!("{}/fabric/graphql", base),
+        }
+    }
+
+    pub fn with_settings(mut self, settings: SapSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+}
+
+impl SapGateway for HttpSapGateway {
+    fn settings(&self) -> &SapSettings {
+        &self.settings
+    }
+
+    fn process_orders<'a>(&'a self, posting_date: &'a str) -> SapFuture<'a, Vec<SapProcessOrder>> {
+        Box::pin(self.fetch_process_orders(posting_date))
+    }
+
+    fn order_components<'a>(&'a self, order: &'a str) -> SapFuture<'a, Vec<OrderComponent>> {
+        Box::pin(self.fetch_order_components(order))
+    }
+
+    fn material_descriptions(&self, matnrs: Vec<String>) -> SapFuture<'_, HashMap<String, String>> {
+        Box::pin(self.fetch_material_descriptions(matnrs))
+    }
+
+    fn batch_availability<'a>(
+        &'a self,
+        material: &'a str,
+        storage_location: &'a str,
+    ) -> SapFuture<'a, Vec<(String, String)>> {
+        Box::pin(self.fetch_batch_availability(material, storage_location))
+    }
+
+    fn post_goods_issue<'a>(&'a self, issue: &'a GoodsIssue) -> SapFuture<'a, String> {
+        Box::pin(self.post_material_document(issue))
+    }
+
+    fn post_roll<'a>(&'a self, roll: &'a RollData) -> SapFuture<'a, ()> {
+        Box::pin(self.post_rolls(roll, false))
+    }
+
+    fn cancel_material_document<'a>(
+        &'a self,
+        document: &'a str,
+        year: &'a str,
+    ) -> SapFuture<'a, String> {
+        Box::pin(self.post_material_document_cancellation(document, year))
+    }
+
+    fn reverse_roll<'a>(&'a self, roll: &'a RollData) -> SapFuture<'a, ()> {
+        Box::pin(self.post_rolls(roll, true))
+    }
+}
+
+/// Builds the gateway selected by `SAP_GATEWAY`: `fake` serves the bundled fixtures in
+/// process, `stub` talks to the stub server at `SAP_STUB_URL`, anything else uses the real
+/// endpoints.
+pub fn gateway_from_env(settings: SapSettings) -> Arc<dyn SapGateway> {
+    match env::var("SAP_GATEWAY").unwrap_or_default().as_str() {
+        "fake" => Arc::new(FakeSapGateway::from_fixtures().with_settings(settings)),
+        "stub" => Arc::new(
+            HttpSapGateway::with_base_url(
+                &env::var("SAP_STUB_URL").unwrap_or_else(|_| "http://127.0.0.1:8089".into()),
+            )
+            .with_settings(settings),
+        ),
+        _ => Arc::new(HttpSapGateway::from_env(settings)),
+    }
+}
+
+/// Pulls the readable message out of an SAP OData error body.
+pub(crate) fn sap_error_message(status: reqwest::StatusCode, body: &str) -> String {
+    match serde_json::from_str::<serde_json::Value>(body) {
+        Ok(json) => json["error"]["message"]["value"]
+            .as_str()
+            .map(|m| m.to_string())
+            .unwrap_or_else(|| body.to_string()),
+        Err(_) => format!("SAP Error {}: {}", status, body),
+    }
+}
+
+/// An error status SAP answered a posting with.
+#[derive(Debug)]
+pub struct SapStatusError {
+    pub status: reqwest::StatusCode,
+    pub message: String,
+}
+
+impl std::fmt::Display for SapStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl error::Error for SapStatusError {}
+
+/// [`sap_error_message`] as an error that keeps the status, so the outbox can tell a rejected
+/// posting from one SAP may have booked anyway.
+pub(crate) fn sap_status_error(status: reqwest::StatusCode, body: &str) -> SapError {
+    Box::new(SapStatusError {
+        status,
+        message: sap_error_message(status, body),
+    })
+}
+
+/// Whether a failed posting may still have been booked in SAP: the request went out but no
+/// clear answer came back, as with a timeout, a 5xx or an unreadable success response.
+/// Sending it again could post it twice.
+pub fn may_have_posted(error: &SapError) -> bool {
+    if let Some(e) = error.downcast_ref::<reqwest::Error>() {
+        return !(e.is_connect() || e.is_builder());
+    }
+    if let Some(e) = error.downcast_ref::<SapStatusError>() {
+        return e.status.is_success() || e.status.is_server_error();
+    }
+    error.is::<serde_json::Error>()
+}
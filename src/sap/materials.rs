@@ -1,117 +1,106 @@
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
-use reqwest;
-use reqwest::Client;
 use rusqlite::params;
 use serde_json::json;
-use std::{collections, env, error};
-
-pub async fn get_material_descriptions(
-    matnrs: Vec<String>,
-) -> Result<collections::HashMap<String, String>, Box<dyn error::Error>> {
-    let client = Client::new();
+use std::{collections, error};
+
+use crate::sap::{HttpSapGateway, OrderComponent, SapError, SapGateway};
+
+impl HttpSapGateway {
+    pub(crate) async fn fetch_material_descriptions(
+        &self,
+        matnrs: Vec<String>,
+    ) -> Result<collections::HashMap<String, String>, SapError> {
+        let token_res = self
+            .client
+            .post(&self.fabric_token_url)
+            .form(&[
+                ("client_id", self.fabric_client_id.as_str()),
+                ("client_secret", self.fabric_client_secret.as_str()),
+                ("scope", "https://api.fabric.microsoft.com/.default"),
+                ("grant_type", "client_credentials"),
+            ])
+            .send()
+            .await?
+            .json::<serde_json::Value>()
+            .await?;
 
-    let token_url = format!(
-        "https://login.microsoftonline.com/{}/oauth2/v2.0/token",
-        env::var("FABRIC_TENANT_ID")?
-    );
-    let token_res = client
-        .post(&token_url)
-        .form(&[
-            ("client_id", env::var("FABRIC_CLIENT_ID")?),
-            ("client_secret", env::var("FABRIC_CLIENT_SECRET")?),
-            (
-                "scope",
-                "https://api.fabric.microsoft.com/.default".to_owned(),
-            ),
-            ("grant_type", "client_credentials".to_owned()),
-        ])
-        .send()
-        .await?
-        .json::<serde_json::Value>()
-        .await?;
-
-    let token = token_res["access_token"].as_str().unwrap();
-
-    let first_value = if matnrs.len() > 0 { matnrs.len() } else { 50 };
-
-    let formatted_matnrs: Vec<String> = matnrs
-        .into_iter()
-        .map(|matnr| format!("{:0>18}", matnr))
-        .collect();
-
-    let query = json!({
-        "query": format!("
-            query ($matnrs: [String!]!) {{
-            mAKTs(first: {}, filter: {{ MATNR: {{ in: $matnrs }} }}) {{
-                items {{ MATNR MAKTX }}
-            }}
-            }}
-        ", first_value),
-        "variables": {
-            "matnrs": formatted_matnrs
-        }
-    });
-
-    let url = env::var("FABRIC_AUTH_API")?;
-
-    let res = client
-        .post(&url)
-        .json(&query)
-        .header("Authorization", format!("Bearer {}", token))
-        .send()
-        .await?
-        .json::<serde_json::Value>()
-        .await?;
-
-    let mut descriptions = std::collections::HashMap::new();
-
-    if let Some(items) = res["data"]["mAKTs"]["items"].as_array() {
-        for item in items {
-            if let (Some(matnr), Some(maktx)) = (item["MATNR"].as_str(), item["MAKTX"].as_str()) {
-                let trimmed_matnr = matnr.trim_start_matches('0');
-                descriptions.insert(trimmed_matnr.to_string(), maktx.to_string());
+        let token = token_res["access_token"]
+            .as_str()
+            .ok_or("No access token from Fabric")?;
+
+        let first_value = if matnrs.len() > 0 { matnrs.len() } else { 50 };
+
+        let formatted_matnrs: Vec<String> = matnrs
+            .into_iter()
+            .map(|matnr| format!("{:0>18}", matnr))
+            .collect();
+
+        let query = json!({
+            "query": format!("
+                query ($matnrs: [String!]!) {{
+                mAKTs(first: {}, filter: {{ MATNR: {{ in: $matnrs }} }}) {{
+                    items {{ MATNR MAKTX }}
+                }}
+                }}
+            ", first_value),
+            "variables": {
+                "matnrs": formatted_matnrs
             }
-        }
-    }
+        });
 
-    Ok(descriptions)
-}
+        let res = self
+            .client
+            .post(&self.fabric_api_url)
+            .json(&query)
+            .header("Authorization", format!("Bearer {}", token))
+            .send()
+            .await?
+            .json::<serde_json::Value>()
+            .await?;
 
-pub async fn sync_material_codes(
-    local_pool: &Pool<SqliteConnectionManager>,
-) -> Result<(), Box<dyn error::Error>> {
-    let user = env::var("SAP_QA_PDO_USERNAME").unwrap_or_default();
-    let pass = env::var("SAP_QA_PDO_PASSWORD").unwrap_or_default();
-    let base_url = env::var("SAP_QA_PDO_URL").unwrap_or_default();
+        let mut descriptions = std::collections::HashMap::new();
 
-    let conn = local_pool.get()?;
-    let codes = get_materials_needing_update(&conn)?;
+        if let Some(items) = res["data"]["mAKTs"]["items"].as_array() {
+            for item in items {
+                if let (Some(matnr), Some(maktx)) = (item["MATNR"].as_str(), item["MAKTX"].as_str())
+                {
+                    let trimmed_matnr = matnr.trim_start_matches('0');
+                    descriptions.insert(trimmed_matnr.to_string(), maktx.to_string());
+                }
+            }
+        }
 
-    for code in codes {
+        Ok(descriptions)
+    }
+
+    pub(crate) async fn fetch_order_components(
+        &self,
+        order: &str,
+    ) -> Result<Vec<OrderComponent>, SapError> {
         let url = format!(
-            "{}?$format=json&$filter=Plant eq 'A710' and ManufacturingOrder eq '{}'",
-            base_url, code
+            "{}?$format=json&$filter=Plant eq '{}' and ManufacturingOrder eq '{}'",
+            self.pdo_url, self.settings.plant, order
         );
         let url = url + "&$expand=to_ProductionOrderComponent,to_ProductionOrderOperation";
 
-        let client = Client::new();
         let mut headers = reqwest::header::HeaderMap::new();
         headers.insert(
             "sap-client",
-            reqwest::header::HeaderValue::from_static("500"),
+            reqwest::header::HeaderValue::from_str(&self.settings.sap_client)?,
         );
 
-        let res = client
+        let res = self
+            .client
             .get(&url)
             .headers(headers)
-            .basic_auth(&user, Some(&pass))
+            .basic_auth(&self.pdo_username, Some(&self.pdo_password))
             .send()
             .await?;
 
         let text = res.text().await?;
         if text.is_empty() {
-            continue;
+            return Ok(vec![]);
         }
 
         let json: serde_json::Value = serde_json::from_str(&text)?;
@@ -123,17 +112,43 @@ pub async fn sync_material_codes(
             .as_array()
             .ok_or("No components array")?;
 
-        let mut keys = Vec::new();
-        let mut value_ids = Vec::new();
-
+        let mut parsed = Vec::new();
         for component in components {
             let material_group = component["MaterialGroup"]
                 .as_str()
                 .ok_or("No MaterialGroup")?;
             let material = component["Material"].as_str().ok_or("No Material")?;
+            parsed.push(OrderComponent {
+                material_group: material_group.to_string(),
+                material: material.to_string(),
+            });
+        }
+        Ok(parsed)
+    }
+}
+
+pub async fn sync_material_codes(
+    local_pool: &Pool<SqliteConnectionManager>,
+    sap: &dyn SapGateway,
+) -> Result<(), Box<dyn error::Error>> {
+    let conn = local_pool.get()?;
+    let codes = get_materials_needing_update(&conn)?;
+
+    for code in codes {
+        let components = sap
+            .order_components(&code)
+            .await
+            .map_err(|e| e.to_string())?;
+        if components.is_empty() {
+            continue;
+        }
 
-            keys.push(material_group.to_string());
-            let value_id = get_or_create_value(&conn, material)?;
+        let mut keys = Vec::new();
+        let mut value_ids = Vec::new();
+
+        for component in components {
+            keys.push(component.material_group);
+            let value_id = get_or_create_value(&conn, &component.material)?;
             value_ids.push(value_id);
         }
 
@@ -144,13 +159,14 @@ pub async fn sync_material_codes(
         update_material(&conn, &code, &keys_str, &value_ids_joined)?;
     }
 
-    update_value_descriptions(local_pool).await?;
+    update_value_descriptions(local_pool, sap).await?;
 
     Ok(())
 }
 
 pub async fn update_value_descriptions(
     local_pool: &Pool<SqliteConnectionManager>,
+    sap: &dyn SapGateway,
 ) -> Result<(), Box<dyn error::Error>> {
     let conn = local_pool.get()?;
 
@@ -174,7 +190,10 @@ pub async fn update_value_descriptions(
         (numeric_values, id_value_map)
     }; // stmt dropped here
 
-    let descriptions = get_material_descriptions(numeric_values).await?;
+    let descriptions = sap
+        .material_descriptions(numeric_values)
+        .await
+        .map_err(|e| e.to_string())?;
 
     for (id, value) in id_value_map {
         if let Some(description) = descriptions.get(&value) {
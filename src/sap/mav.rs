@@ -1,28 +1,34 @@
 use actix_web::{HttpResponse, Responder, web};
-use reqwest::Client;
-use std::{env, error};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
 
-pub async fn get_batch_availability(
-    material: &str,
-    storage_location: &str,
-) -> Result<Vec<(String, String)>, Box<dyn error::Error>> {
-    let base_url = env::var("SAP_QA_MAV_BASE_URL")?;
-    let api_key = env::var("SAP_QA_MAV_APIKey")?;
+use crate::sap::{HttpSapGateway, SapError, SapGateway};
 
-    let url = format!(
-        "{}?$format=json&$filter=Material+eq+'{}'and+StorageLocation+eq+'{}'+and+Plant+eq+'A710'",
-        base_url, material, storage_location
-    );
+impl HttpSapGateway {
+    pub(crate) async fn fetch_batch_availability(
+        &self,
+        material: &str,
+        storage_location: &str,
+    ) -> Result<Vec<(String, String)>, SapError> {
+        let url = format!(
+            "{}?$format=json&$filter=Material+eq+'{}'and+StorageLocation+eq+'{}'+and+Plant+eq+'{}'",
+            self.mav_url, material, storage_location, self.settings.plant
+        );
 
-    let client = Client::new();
-    let res = client
-        .get(&url)
-        .header("APIKey", api_key)
-        .send()
-        .await?
-        .json::<serde_json::Value>()
-        .await?;
+        let res = self
+            .client
+            .get(&url)
+            .header("APIKey", &self.mav_api_key)
+            .send()
+            .await?
+            .json::<serde_json::Value>()
+            .await?;
 
+        Ok(parse_batch_availability(&res))
+    }
+}
+
+fn parse_batch_availability(res: &serde_json::Value) -> Vec<(String, String)> {
     let mut batches = Vec::new();
 
     if let Some(results) = res["d"]["results"].as_array() {
@@ -45,10 +51,14 @@ pub async fn get_batch_availability(
         }
     }
 
-    Ok(batches)
+    batches
 }
 
+/// Batches in stock for a material. The storage location comes from `storage_location`
+/// when given, otherwise from the SAP settings for `section_id`.
 pub async fn get_batch_availability_handler(
+    conn_data: web::Data<Pool<SqliteConnectionManager>>,
+    sap: web::Data<dyn SapGateway>,
     web::Query(params): web::Query<std::collections::HashMap<String, String>>,
 ) -> impl Responder {
     let material_number = match params.get("material_number") {
@@ -56,11 +66,24 @@ pub async fn get_batch_availability_handler(
         None => return HttpResponse::BadRequest().body("Missing material_number parameter"),
     };
     let storage_location = match params.get("storage_location") {
-        Some(num) => num,
-        None => return HttpResponse::BadRequest().body("Missing storage_location parameter"),
+        Some(location) => location.clone(),
+        None => {
+            let conn = match conn_data.get() {
+                Ok(c) => c,
+                Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+            };
+            let section_id = params.get("section_id").and_then(|v| v.parse().ok());
+            match sap.settings().section_storage_location(&conn, section_id) {
+                Ok(location) => location,
+                Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+            }
+        }
     };
 
-    match get_batch_availability(material_number, storage_location).await {
+    match sap
+        .batch_availability(material_number, &storage_location)
+        .await
+    {
         Ok(batches) => HttpResponse::Ok().json(batches),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
@@ -1,7 +1,7 @@
 use chrono::Local;
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::env;
+
+use crate::sap::{GoodsIssue, HttpSapGateway, SapError, sap_status_error};
 
 #[allow(non_snake_case)]
 #[derive(Serialize)]
@@ -14,6 +14,8 @@ struct MaterialDocItem {
     EntryUnit: String,
     ManufacturingOrder: String,
     Batch: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    IssuingOrReceivingStorageLoc: Option<String>,
 }
 
 #[allow(non_snake_case)]
@@ -22,6 +24,7 @@ struct MaterialDocRequest {
     PostingDate: String,
     DocumentDate: String,
     GoodsMovementCode: String,
+    MaterialDocumentHeaderText: String,
     to_MaterialDocumentItem: Vec<MaterialDocItem>,
 }
 
@@ -36,82 +39,112 @@ struct MaterialDocResponseData {
     MaterialDocument: String,
 }
 
-pub async fn post_material_document(
-    material: &str,
-    batch: &str,
-    order: &str,
-    quantity: &str,
-    unit: &str,
-    posting_date: &str,
-    storage_location: &str,
-) -> Result<String, Box<dyn std::error::Error>> {
-    let url = env::var("SAP_QA_MATERIAL_INSUANCE_URL")?;
-    let api_key = env::var("SAP_QA_MATERIAL_INSUANCE_APIKEY")?;
-
-    let now = Local::now().format("%Y-%m-%d").to_string();
-    let date_str = format!("{}T00:00:00", now);
-
-    let request = MaterialDocRequest {
-        PostingDate: posting_date.to_string(),
-        DocumentDate: date_str,
-        GoodsMovementCode: "05".to_string(),
-        to_MaterialDocumentItem: vec![MaterialDocItem {
-            Material: material.to_string(),
-            GoodsMovementType: "261".to_string(),
-            Plant: "A710".to_string(),
-            StorageLocation: storage_location.to_string(),
-            QuantityInEntryUnit: quantity.to_string(),
-            EntryUnit: unit.to_string(),
-            ManufacturingOrder: order.to_string(),
-            Batch: batch.to_string(),
-        }],
-    };
-
-    let client = Client::new();
-    let res = client
-        .post(&url)
-        .header("APIKey", api_key)
-        .header("Content-Type", "application/json")
-        .header("Accept", "application/json")
-        .json(&request)
-        .send()
-        .await?;
-
-    let status = res.status();
-    let response_text = res.text().await?;
-    // println!("POSTING DATE: {}", posting_date.to_string());
-
-    if status.is_success() {
-        let response: MaterialDocResponse = serde_json::from_str(&response_text)?;
-        Ok(response.d.MaterialDocument)
-    } else {
-        if let Ok(json) = serde_json::from_str::<serde_json::Value>(&response_text) {
-            let msg = json["error"]["message"]["value"]
-                .as_str()
-                .unwrap_or(&response_text);
-            Err(msg.into())
+impl HttpSapGateway {
+    pub(crate) async fn post_material_document(
+        &self,
+        issue: &GoodsIssue,
+    ) -> Result<String, SapError> {
+        let now = Local::now().format("%Y-%m-%d").to_string();
+        let date_str = format!("{}T00:00:00", now);
+
+        let request = MaterialDocRequest {
+            PostingDate: issue.posting_date.clone(),
+            DocumentDate: date_str,
+            GoodsMovementCode: self.settings.goods_movement_code.clone(),
+            MaterialDocumentHeaderText: issue.header_text.clone(),
+            to_MaterialDocumentItem: vec![MaterialDocItem {
+                Material: issue.material.clone(),
+                GoodsMovementType: issue
+                    .movement_type
+                    .clone()
+                    .unwrap_or_else(|| self.settings.movement_type.clone()),
+                Plant: self.settings.plant.clone(),
+                StorageLocation: issue.storage_location.clone(),
+                QuantityInEntryUnit: issue.quantity.clone(),
+                EntryUnit: issue.unit.clone(),
+                ManufacturingOrder: issue.order.clone(),
+                Batch: issue.batch.clone(),
+                IssuingOrReceivingStorageLoc: issue.receiving_storage_location.clone(),
+            }],
+        };
+
+        let res = self
+            .client
+            .post(&self.material_document_url)
+            .header("APIKey", &self.material_document_api_key)
+            .header("Content-Type", "application/json")
+            .header("Accept", "application/json")
+            .json(&request)
+            .send()
+            .await?;
+
+        let status = res.status();
+        let response_text = res.text().await?;
+
+        if status.is_success() {
+            let response: MaterialDocResponse = serde_json::from_str(&response_text)?;
+            Ok(response.d.MaterialDocument)
+        } else {
+            Err(sap_status_error(status, &response_text))
+        }
+    }
+
+    /// Calls the `Cancel` function import of the material document service, which posts the
+    /// reversal of every item and returns its document number.
+    pub(crate) async fn post_material_document_cancellation(
+        &self,
+        document: &str,
+        year: &str,
+    ) -> Result<String, SapError> {
+        let service = self
+            .material_document_url
+            .trim_end_matches('/')
+            .rsplit_once('/')
+            .map(|(service, _)| service)
+            .unwrap_or(&self.material_document_url);
+        let res = self
+            .client
+            .post(format!("{}/Cancel", service))
+            .query(&[
+                ("MaterialDocumentYear", format!("'{}'", year)),
+                ("MaterialDocument", format!("'{}'", document)),
+            ])
+            .header("APIKey", &self.material_document_api_key)
+            .header("Accept", "application/json")
+            .send()
+            .await?;
+
+        let status = res.status();
+        let response_text = res.text().await?;
+
+        if status.is_success() {
+            let response: MaterialDocResponse = serde_json::from_str(&response_text)?;
+            Ok(response.d.MaterialDocument)
         } else {
-            Err(format!("SAP Error {}: {}", status, response_text).into())
+            Err(sap_status_error(status, &response_text))
         }
     }
 }
 
-#[tokio::test]
+#[actix_web::test]
 async fn test_post_material_document() {
-    use dotenv::dotenv;
+    let server = crate::sap::stub::spawn_stub_server().await.unwrap();
+    let gateway = HttpSapGateway::with_base_url(&server.url);
 
-    dotenv().ok();
-
-    let result = post_material_document(
-        "30000950",
-        "J23-612",
-        "220012061",
-        "9613.7",
-        "KG",
-        "2026-01-09T00:00:00",
-        "DW01",
-    )
-    .await;
+    let result = gateway
+        .post_material_document(&GoodsIssue {
+            material: "30000950".to_string(),
+            batch: "J23-612".to_string(),
+            order: "220012061".to_string(),
+            quantity: "9613.7".to_string(),
+            unit: "KG".to_string(),
+            posting_date: "2026-01-09T00:00:00".to_string(),
+            storage_location: "DW01".to_string(),
+            header_text: "GI-IR1".to_string(),
+            movement_type: None,
+            receiving_storage_location: None,
+        })
+        .await;
 
     match result {
         Ok(doc) => {
@@ -123,4 +156,29 @@ async fn test_post_material_document() {
             panic!("Test failed: {}", e);
         }
     }
+    {
+        let posted = server.state.material_documents.lock().unwrap();
+        assert_eq!(posted[0]["MaterialDocumentHeaderText"], "GI-IR1");
+        assert_eq!(
+            posted[0]["to_MaterialDocumentItem"][0]["GoodsMovementType"],
+            "261"
+        );
+    }
+
+    let reversal = gateway
+        .post_material_document_cancellation("4900000001", "2026")
+        .await
+        .unwrap();
+    assert_ne!(reversal, "4900000001");
+    // A refused cancellation, like a request that never connected, is safe to send again
+    let refused = gateway
+        .post_material_document_cancellation("4999999999", "2026")
+        .await
+        .unwrap_err();
+    assert!(!crate::sap::may_have_posted(&refused));
+    let unreachable = HttpSapGateway::with_base_url("http://127.0.0.1:1")
+        .post_material_document_cancellation("4900000001", "2026")
+        .await
+        .unwrap_err();
+    assert!(!crate::sap::may_have_posted(&unreachable));
 }
@@ -1,13 +1,21 @@
+pub mod fake;
+pub mod gateway;
 pub mod materials;
 pub mod mav;
 pub mod mins;
+pub mod outbox;
 pub mod pso;
 pub mod rolls;
 pub mod scrap;
+pub mod settings;
+pub mod stub;
 
+pub use fake::*;
+pub use gateway::*;
 pub use materials::*;
 pub use mav::*;
-pub use mins::*;
+pub use outbox::*;
 pub use pso::*;
 pub use rolls::*;
 pub use scrap::*;
+pub use settings::*;
@@ -0,0 +1,219 @@
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::{error, fmt};
+
+use crate::backend::models::SapOutbox;
+use crate::sap::{GoodsIssue, RollData, SapError, SapGateway, may_have_posted};
+
+/// A posting queued in `sap_outbox`, stored as JSON so the worker can replay it.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SapPosting {
+    /// Goods issue (movement type 261) of an input roll to its process order
+    GoodsIssue {
+        material: String,
+        batch: String,
+        order: String,
+        quantity: String,
+        unit: String,
+        posting_date: String,
+        storage_location: String,
+    },
+    /// Transfer of an unused remnant to the return store, with the plant's return movement type
+    /// (311 by default)
+    GoodsReturn {
+        material: String,
+        batch: String,
+        order: String,
+        quantity: String,
+        unit: String,
+        posting_date: String,
+        storage_location: String,
+    },
+    /// Confirmation of a weighed output roll
+    RollConfirmation(RollData),
+    /// Cancellation of a posted goods issue or return
+    DocumentCancellation { document: String, year: String },
+    /// Reversal of a roll confirmation, carrying what was confirmed
+    RollReversal(RollData),
+}
+
+impl SapPosting {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SapPosting::GoodsIssue { .. } => "goods_issue",
+            SapPosting::GoodsReturn { .. } => "goods_return",
+            SapPosting::RollConfirmation(_) => "roll_confirmation",
+            SapPosting::DocumentCancellation { .. } => "document_cancellation",
+            SapPosting::RollReversal(_) => "roll_reversal",
+        }
+    }
+}
+
+/// Why a posting did not go through.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PostingError {
+    /// SAP refused the posting or never received it, so it is safe to send again
+    Rejected(String),
+    /// SAP may have booked the posting before the failure; it needs checking in SAP before
+    /// it is sent again
+    Unconfirmed(String),
+}
+
+impl fmt::Display for PostingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PostingError::Rejected(e) | PostingError::Unconfirmed(e) => f.write_str(e),
+        }
+    }
+}
+
+impl From<SapError> for PostingError {
+    fn from(e: SapError) -> Self {
+        if may_have_posted(&e) {
+            PostingError::Unconfirmed(e.to_string())
+        } else {
+            PostingError::Rejected(e.to_string())
+        }
+    }
+}
+
+/// Sends one posting to SAP, returning the material document when SAP issues one. The
+/// idempotency key goes into the document header text so postings can be reconciled in SAP.
+pub async fn send_posting(
+    sap: &dyn SapGateway,
+    posting: &SapPosting,
+    idempotency_key: &str,
+) -> Result<Option<String>, PostingError> {
+    match posting {
+        SapPosting::GoodsIssue {
+            material,
+            batch,
+            order,
+            quantity,
+            unit,
+            posting_date,
+            storage_location,
+        } => {
+            let issue = GoodsIssue {
+                material: material.clone(),
+                batch: batch.clone(),
+                order: order.clone(),
+                quantity: quantity.clone(),
+                unit: unit.clone(),
+                posting_date: posting_date.clone(),
+                storage_location: storage_location.clone(),
+                header_text: idempotency_key.to_string(),
+                movement_type: None,
+                receiving_storage_location: None,
+            };
+            sap.post_goods_issue(&issue)
+                .await
+                .map(Some)
+                .map_err(PostingError::from)
+        }
+        SapPosting::GoodsReturn {
+            material,
+            batch,
+            order,
+            quantity,
+            unit,
+            posting_date,
+            storage_location,
+        } => {
+            let settings = sap.settings();
+            let issue = GoodsIssue {
+                material: material.clone(),
+                batch: batch.clone(),
+                order: order.clone(),
+                quantity: quantity.clone(),
+                unit: unit.clone(),
+                posting_date: posting_date.clone(),
+                storage_location: storage_location.clone(),
+                header_text: idempotency_key.to_string(),
+                movement_type: Some(settings.return_movement_type.clone()),
+                receiving_storage_location: Some(settings.return_storage_location.clone())
+                    .filter(|location| !location.is_empty()),
+            };
+            sap.post_goods_issue(&issue)
+                .await
+                .map(Some)
+                .map_err(PostingError::from)
+        }
+        SapPosting::RollConfirmation(data) => sap
+            .post_roll(data)
+            .await
+            .map(|_| None)
+            .map_err(PostingError::from),
+        SapPosting::DocumentCancellation { document, year } => sap
+            .cancel_material_document(document, year)
+            .await
+            .map(Some)
+            .map_err(PostingError::from),
+        SapPosting::RollReversal(data) => sap
+            .reverse_roll(data)
+            .await
+            .map(|_| None)
+            .map_err(PostingError::from),
+    }
+}
+
+/// Claims and sends a single message on the caller's connection. Used to post straight away
+/// when the operator acts; anything that fails stays queued for the background worker.
+pub async fn deliver_now(
+    conn: &Connection,
+    sap: &dyn SapGateway,
+    id: i32,
+) -> rusqlite::Result<SapOutbox> {
+    let Some(mut message) = SapOutbox::claim(conn, id)? else {
+        return SapOutbox::find_by_id(conn, id);
+    };
+    let result = match message.posting() {
+        Ok(posting) => send_posting(sap, &posting, &message.idempotency_key).await,
+        Err(e) => Err(PostingError::Rejected(e.to_string())),
+    };
+    message.complete(conn, result)?;
+    Ok(message)
+}
+
+/// Posts every due outbox message, returning how many reached SAP.
+pub async fn process_sap_outbox(
+    local_pool: &Pool<SqliteConnectionManager>,
+    sap: &dyn SapGateway,
+) -> Result<usize, Box<dyn error::Error>> {
+    let due = {
+        let conn = local_pool.get()?;
+        SapOutbox::recover_interrupted(&conn)?;
+        SapOutbox::due_ids(&conn, 20)?
+    };
+
+    let mut posted = 0;
+    for id in due {
+        let claimed = {
+            let conn = local_pool.get()?;
+            SapOutbox::claim(&conn, id)?
+        };
+        let Some(mut message) = claimed else {
+            continue;
+        };
+        let result = match message.posting() {
+            Ok(posting) => send_posting(sap, &posting, &message.idempotency_key).await,
+            Err(e) => Err(PostingError::Rejected(e.to_string())),
+        };
+        if let Err(e) = &result {
+            log::warn!(
+                "SAP outbox message {} ({}) failed: {}",
+                message.id,
+                message.idempotency_key,
+                e
+            );
+        } else {
+            posted += 1;
+        }
+        let conn = local_pool.get()?;
+        message.complete(&conn, result)?;
+    }
+    Ok(posted)
+}
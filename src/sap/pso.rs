@@ -1,10 +1,11 @@
 use chrono::Datelike;
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
-use reqwest;
 use rusqlite::params;
-use serde::{Deserialize, Serialize};
-use std::{env, error};
+use serde::Deserialize;
+use std::error;
+
+use crate::sap::{HttpSapGateway, SapError, SapGateway, SapProcessOrder, sap_error_message};
 
 #[derive(Debug, Deserialize)]
 struct ApiResponse {
@@ -13,31 +14,40 @@ struct ApiResponse {
 
 #[derive(Debug, Deserialize)]
 struct ResponseData {
-    results: Vec<ProcessOrder>,
+    results: Vec<SapProcessOrder>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct ProcessOrder {
-    #[serde(rename = "ProcessOrder")]
-    process_order: String,
-    #[serde(rename = "PostingDate")]
-    posting_date: String,
-    #[serde(rename = "Shift")]
-    shift: String,
-    #[serde(rename = "Material")]
-    material: String,
-    #[serde(rename = "MaterialDescription")]
-    material_description: String,
-    #[serde(rename = "Line")]
-    line: String,
+impl HttpSapGateway {
+    pub(crate) async fn fetch_process_orders(
+        &self,
+        posting_date: &str,
+    ) -> Result<Vec<SapProcessOrder>, SapError> {
+        let url = format!(
+            "{}?$format=json&$filter=OrderType eq '{}' and Plant eq '{}' and PostingDate eq datetime'{}T00:00:00'",
+            self.pso_url, self.settings.order_type, self.settings.plant, posting_date
+        );
+
+        let response = self
+            .client
+            .get(&url)
+            .header("APIKey", &self.pso_api_key)
+            .send()
+            .await?;
+        let status = response.status();
+        let text = response.text().await?;
+
+        if !status.is_success() {
+            return Err(sap_error_message(status, &text).into());
+        }
+        let api_response: ApiResponse = serde_json::from_str(&text)?;
+        Ok(api_response.d.results)
+    }
 }
 
 pub async fn sync_process_orders(
     local_pool: &Pool<SqliteConnectionManager>,
+    sap: &dyn SapGateway,
 ) -> Result<(), Box<dyn error::Error>> {
-    let api_key = env::var("SAP_QA_PSO_APIKey").unwrap_or_default();
-    let base_url = env::var("SAP_QA_PSO_BASE_URL").unwrap_or_default();
-
     let conn = local_pool.get()?;
 
     let _starget_date = get_target_date(&conn)?;
@@ -52,26 +62,18 @@ pub async fn sync_process_orders(
             target_date.month(),
             target_date.day()
         );
-        let url = format!(
-            "{}?$format=json&$filter=OrderType eq 'ZIS1' and Plant eq 'A710' and PostingDate eq datetime'{}T00:00:00'",
-            base_url, formatted_date
-        );
-        // print!("{}", url);
-
-        let client = reqwest::Client::new();
-        let response = client
-            .get(&url)
-            .header("APIKey", api_key.clone())
-            .send()
-            .await?;
-        let status = response.status();
-        let text = response.text().await?;
 
-        if status.is_success() {
-            let api_response: ApiResponse = serde_json::from_str(&text)?;
-            for po in api_response.d.results {
-                extract_and_save_po_data(&conn, &po)?;
+        match sap.process_orders(&formatted_date).await {
+            Ok(orders) => {
+                for po in orders {
+                    extract_and_save_po_data(&conn, &po)?;
+                }
             }
+            Err(e) => log::warn!(
+                "Failed to fetch process orders for {}: {}",
+                formatted_date,
+                e
+            ),
         }
     }
     Ok(())
@@ -95,7 +97,7 @@ fn get_target_date(conn: &rusqlite::Connection) -> rusqlite::Result<chrono::Naiv
 
 fn extract_and_save_po_data(
     conn: &rusqlite::Connection,
-    po: &ProcessOrder,
+    po: &SapProcessOrder,
 ) -> rusqlite::Result<()> {
     let (po_code, rest_description) = extract_po_and_rest(&po.material_description);
 
@@ -6,7 +6,9 @@ use tokio::net::TcpStream;
 use tokio_util::compat::TokioAsyncWriteCompatExt;
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::params;
+use rusqlite::{params, OptionalExtension};
+
+use crate::backend::models::{operator_job, parse_local_datetime, Shift};
 
 
 pub async fn sync_scrap_data(local_pool: &Pool<SqliteConnectionManager>) -> Result<(), Box<dyn error::Error>> {
@@ -73,24 +75,7 @@ pub async fn sync_scrap_data(local_pool: &Pool<SqliteConnectionManager>) -> Resu
 
             if let (Some(shift_num), Some(datetime_str), Some(scrap_type_name), Some(weight), Some(created_by_name)) = 
                 (shift_num, datetime_str, scrap_type_name, weight, created_by_name) {
-                
-                let datetime = parse_datetime(datetime_str)?;
-                
-                let shift_name = match shift_num {
-                    1 => "SHIFT 1",
-                    2 => "SHIFT 2",
-                    _ => "SHIFT 1",
-                };
-                
-                let shift_id = get_or_create_shift(&conn, shift_name)?;
-                let scrap_type_id = get_or_create_scrap_type(&conn, scrap_type_name)?;
-                let user_id = get_or_create_user(&conn, created_by_name)?;
-
-                conn.execute(
-                    "INSERT INTO scraps (shift_id, time, scrap_type_id, weight_kg, created_by, created_at, updated_at)
-                     VALUES (?, ?, ?, ?, ?, datetime('now'), datetime('now'))",
-                    params![shift_id, datetime, scrap_type_id, weight, user_id],
-                )?;
+                insert_scrap(&conn, shift_num, datetime_str, scrap_type_name, weight, created_by_name)?;
             }
         }
     }
@@ -98,6 +83,31 @@ pub async fn sync_scrap_data(local_pool: &Pool<SqliteConnectionManager>) -> Resu
     Ok(())
 }
 
+/// Stores one weighing from the scrap system against the job the operator was running, the
+/// same way scrap entered in the app is attributed.
+fn insert_scrap(
+    conn: &rusqlite::Connection,
+    shift_num: i32,
+    datetime_str: &str,
+    scrap_type_name: &str,
+    weight: f64,
+    created_by_name: &str,
+) -> Result<i64, Box<dyn error::Error>> {
+    let datetime = parse_datetime(datetime_str)?;
+    
+    let shift_id = resolve_shift(conn, &datetime, shift_num)?;
+    let scrap_type_id = get_or_create_scrap_type(conn, scrap_type_name)?;
+    let user_id = get_or_create_user(conn, created_by_name)?;
+    let job_id = operator_job(conn, shift_id as i32, user_id as i32, &datetime)?;
+
+    conn.execute(
+        "INSERT INTO scraps (shift_id, time, scrap_type_id, weight_kg, created_by, created_at, updated_at, job_id)
+         VALUES (?, ?, ?, ?, ?, datetime('now'), datetime('now'), ?)",
+        params![shift_id, datetime, scrap_type_id, weight, user_id, job_id],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
 fn parse_datetime(datetime_str: &str) -> Result<String, Box<dyn error::Error>> {
     let parts: Vec<&str> = datetime_str.split_whitespace().collect();
     if parts.len() != 2 {
@@ -159,6 +169,30 @@ fn parse_datetime(datetime_str: &str) -> Result<String, Box<dyn error::Error>> {
     Ok(format!("{}-{}-{} {}:{}:{}", year, month_num, day, hour_str, minute, second))
 }
 
+/// The calendar shift at the scrap time; the weighing station's own shift number is only
+/// used for times the calendar does not cover.
+fn resolve_shift(conn: &rusqlite::Connection, datetime: &str, shift_num: i32) -> rusqlite::Result<i64> {
+    if let Some(at) = parse_local_datetime(datetime)
+        && let Some(slot) = Shift::current(conn, at)? {
+        return Ok(slot.shift_id as i64);
+    }
+    let by_sequence: Option<i64> = conn.query_row(
+        "SELECT id FROM shifts WHERE sequence = ?1 AND active = 1 ORDER BY id LIMIT 1",
+        params![shift_num],
+        |row| row.get(0),
+    ).optional()?;
+    if let Some(id) = by_sequence {
+        return Ok(id);
+    }
+    let shift_name = match shift_num {
+        1 => "SHIFT 1",
+        2 => "SHIFT 2",
+        3 => "SHIFT 3",
+        _ => "SHIFT 1",
+    };
+    get_or_create_shift(conn, shift_name)
+}
+
 fn get_or_create_shift(conn: &rusqlite::Connection, name: &str) -> rusqlite::Result<i64> {
     let shift_id: Result<i64, _> = conn.query_row(
         "SELECT id FROM shifts WHERE name = ?",
@@ -212,3 +246,33 @@ fn get_or_create_user(conn: &rusqlite::Connection, full_name: &str) -> rusqlite:
     }
 }
 
+#[test]
+fn test_synced_scrap_is_attributed_to_the_operator_job() {
+    let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+    crate::manager::migrations::migrate_up(&mut conn, None).unwrap();
+    conn.execute_batch(
+        "INSERT OR IGNORE INTO roles (id, name) VALUES (1, 'Operator');
+         INSERT INTO users (id, full_name) VALUES (1, 'Jane Operator');
+         INSERT INTO sections (id, name) VALUES (1, 'Printing');
+         INSERT INTO machines (id, name, label, section_id) VALUES (1, 'Printer 1', 'P1', 1);
+         INSERT INTO shifts (id, name, sequence, active) VALUES (1, 'Day', 1, 1);
+         INSERT INTO jobs (id, machine_id, shift_id, created_by, production_order, start_datetime, end_datetime) VALUES
+             (1, 1, 1, 1, '220012061', '2026-01-09 07:00:00', '2026-01-09 11:00:00'),
+             (2, 1, 1, 1, '220012062', '2026-01-09 12:00:00', NULL);",
+    )
+    .unwrap();
+
+    let id = insert_scrap(&conn, 1, "09-JAN-2026 01:30:00PM", "Edge trim", 12.5, "Jane Operator").unwrap();
+    let (time, job_id): (String, Option<i32>) = conn
+        .query_row("SELECT time, job_id FROM scraps WHERE id = ?1", params![id], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap();
+    assert_eq!(time, "2026-01-09 13:30:00");
+    assert_eq!(job_id, Some(2));
+
+    // Nobody was running a job for weighings by an unknown name
+    let id = insert_scrap(&conn, 1, "09-JAN-2026 01:45:00PM", "Edge trim", 3.0, "Somebody Else").unwrap();
+    let job_id: Option<i32> = conn
+        .query_row("SELECT job_id FROM scraps WHERE id = ?1", params![id], |row| row.get(0))
+        .unwrap();
+    assert_eq!(job_id, None);
+}
@@ -0,0 +1,195 @@
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, env, fs, path::Path};
+
+const SETTINGS_FILE: &str = "data/sap.json";
+
+/// Plant-specific SAP codes. Read from `data/sap.json` (or `SAP_SETTINGS_FILE`), then any
+/// `SAP_*` environment variable overrides the file, so one build can be deployed per plant.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SapSettings {
+    pub plant: String,
+    pub order_type: String,
+    pub storage_location: String,
+    pub movement_type: String,
+    /// Transfer type that moves an unused remnant to `return_storage_location`. Only the used
+    /// weight is issued, so 262 would reverse consumption that never happened and is rejected
+    pub return_movement_type: String,
+    /// Store a remnant is transferred to; empty leaves remnants where they were issued from
+    pub return_storage_location: String,
+    pub goods_movement_code: String,
+    pub sap_client: String,
+    /// Storage location by section name, for sections that issue from their own store
+    pub section_storage_locations: HashMap<String, String>,
+}
+
+impl Default for SapSettings {
+    fn default() -> Self {
+        SapSettings {
+            plant: "A710".to_string(),
+            order_type: "ZIS1".to_string(),
+            storage_location: "DW01".to_string(),
+            movement_type: "261".to_string(),
+            return_movement_type: "311".to_string(),
+            return_storage_location: String::new(),
+            goods_movement_code: "05".to_string(),
+            sap_client: "500".to_string(),
+            section_storage_locations: HashMap::new(),
+        }
+    }
+}
+
+impl SapSettings {
+    /// Loads and validates the settings; called once at startup so a bad config stops the
+    /// backend instead of producing rejected postings.
+    pub fn load() -> Result<Self, String> {
+        let path = env::var("SAP_SETTINGS_FILE").unwrap_or_else(|_| SETTINGS_FILE.to_string());
+        let mut settings = if Path::new(&path).exists() {
+            Self::from_file(&path)?
+        } else {
+            SapSettings::default()
+        };
+        settings.apply_env();
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let raw = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        serde_json::from_str(&raw).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    fn apply_env(&mut self) {
+        for (key, field) in [
+            ("SAP_PLANT", &mut self.plant),
+            ("SAP_ORDER_TYPE", &mut self.order_type),
+            ("SAP_STORAGE_LOCATION", &mut self.storage_location),
+            ("SAP_MOVEMENT_TYPE", &mut self.movement_type),
+            ("SAP_RETURN_MOVEMENT_TYPE", &mut self.return_movement_type),
+            (
+                "SAP_RETURN_STORAGE_LOCATION",
+                &mut self.return_storage_location,
+            ),
+            ("SAP_GOODS_MOVEMENT_CODE", &mut self.goods_movement_code),
+            ("SAP_CLIENT", &mut self.sap_client),
+        ] {
+            if let Ok(value) = env::var(key) {
+                *field = value.trim().to_string();
+            }
+        }
+        // SAP_SECTION_STORAGE_LOCATIONS=Printing=DW02,Lamination=DW03
+        if let Ok(value) = env::var("SAP_SECTION_STORAGE_LOCATIONS") {
+            for pair in value.split(',') {
+                if let Some((section, location)) = pair.split_once('=') {
+                    self.section_storage_locations
+                        .insert(section.trim().to_string(), location.trim().to_string());
+                }
+            }
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        check_code("plant", &self.plant, 4, false)?;
+        check_code("order type", &self.order_type, 4, false)?;
+        check_code("storage location", &self.storage_location, 4, false)?;
+        check_code("movement type", &self.movement_type, 3, true)?;
+        check_code("return movement type", &self.return_movement_type, 3, true)?;
+        if matches!(self.return_movement_type.as_str(), "261" | "262") {
+            return Err(format!(
+                "SAP return movement type {} would change the goods issue, remnants are only issued as used and need a transfer type such as 311",
+                self.return_movement_type
+            ));
+        }
+        if !self.return_storage_location.is_empty() {
+            check_code(
+                "return storage location",
+                &self.return_storage_location,
+                4,
+                false,
+            )?;
+        }
+        check_code("goods movement code", &self.goods_movement_code, 2, true)?;
+        check_code("client", &self.sap_client, 3, true)?;
+        for (section, location) in &self.section_storage_locations {
+            check_code(
+                &format!("storage location for {}", section),
+                location,
+                4,
+                false,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Storage location for a section by name, falling back to the plant default
+    pub fn storage_location_for(&self, section: Option<&str>) -> &str {
+        section
+            .and_then(|s| self.section_storage_locations.get(s))
+            .unwrap_or(&self.storage_location)
+    }
+
+    /// Storage location for a section by id
+    pub fn section_storage_location(
+        &self,
+        conn: &Connection,
+        section_id: Option<i32>,
+    ) -> rusqlite::Result<String> {
+        let section: Option<String> = match section_id {
+            Some(id) => conn
+                .query_row(
+                    "SELECT name FROM sections WHERE id = ?1",
+                    params![id],
+                    |row| row.get(0),
+                )
+                .optional()?,
+            None => None,
+        };
+        Ok(self.storage_location_for(section.as_deref()).to_string())
+    }
+}
+
+/// Codes are at most `max_len` alphanumeric characters; numeric codes must be exactly `max_len`
+/// digits, as SAP pads them (movement type 261, client 500).
+fn check_code(name: &str, value: &str, max_len: usize, numeric: bool) -> Result<(), String> {
+    let valid = if numeric {
+        value.len() == max_len && value.chars().all(|c| c.is_ascii_digit())
+    } else {
+        (1..=max_len).contains(&value.len()) && value.chars().all(|c| c.is_ascii_alphanumeric())
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid SAP {}: '{}'", name, value))
+    }
+}
+
+#[test]
+fn test_sap_settings_file_and_section_overrides() {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    fs::write(
+        file.path(),
+        r#"{"plant": "B210", "section_storage_locations": {"Lamination": "DW03"}}"#,
+    )
+    .unwrap();
+    let settings = SapSettings::from_file(file.path().to_str().unwrap()).unwrap();
+    assert!(settings.validate().is_ok());
+    assert_eq!(settings.plant, "B210");
+    assert_eq!(settings.movement_type, "261");
+    assert_eq!(settings.return_movement_type, "311");
+    assert_eq!(settings.storage_location_for(Some("Lamination")), "DW03");
+    assert_eq!(settings.storage_location_for(Some("Printing")), "DW01");
+    assert_eq!(settings.storage_location_for(None), "DW01");
+
+    let reversing = SapSettings {
+        return_movement_type: "262".to_string(),
+        ..settings.clone()
+    };
+    assert!(reversing.validate().is_err());
+
+    let bad = SapSettings {
+        movement_type: "26A".to_string(),
+        ..settings
+    };
+    assert!(bad.validate().is_err());
+}
@@ -0,0 +1,339 @@
+//! Stand-in for the SAP API Management and Fabric endpoints. Replays the recorded responses
+//! in `src/sap/fixtures` and records every posting, so the MES can run and be tested without
+//! the QA gateway. Point the backend at it with `SAP_GATEWAY=stub`.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use actix_web::{App, HttpResponse, HttpServer, Responder, dev::ServerHandle, web};
+use regex::Regex;
+use serde_json::{Value, json};
+
+pub const PROCESS_ORDERS: &str = include_str!("fixtures/process_orders.json");
+pub const PRODUCTION_ORDERS: &str = include_str!("fixtures/production_orders.json");
+pub const BATCH_STOCK: &str = include_str!("fixtures/batch_stock.json");
+pub const MATERIAL_DESCRIPTIONS: &str = include_str!("fixtures/material_descriptions.json");
+
+pub fn fixture(raw: &str) -> Value {
+    serde_json::from_str(raw).unwrap_or(Value::Null)
+}
+
+/// Postings received by the stub, as sent by the client.
+pub struct StubState {
+    pub material_documents: Mutex<Vec<Value>>,
+    pub rolls: Mutex<Vec<Value>>,
+    /// Material documents cancelled through the `Cancel` function import
+    pub cancelled_documents: Mutex<Vec<String>>,
+    next_document: AtomicU64,
+}
+
+impl Default for StubState {
+    fn default() -> Self {
+        StubState {
+            material_documents: Mutex::new(vec![]),
+            rolls: Mutex::new(vec![]),
+            cancelled_documents: Mutex::new(vec![]),
+            next_document: AtomicU64::new(4900000001),
+        }
+    }
+}
+
+fn filter_value(query: &HashMap<String, String>, field: &str) -> Option<String> {
+    let pattern = Regex::new(&format!(r"{}\s+eq\s+'([^']*)'", field)).ok()?;
+    let filter = query.get("$filter")?;
+    pattern.captures(filter).map(|c| c[1].to_string())
+}
+
+fn odata_error(message: &str) -> HttpResponse {
+    HttpResponse::BadRequest().json(json!({
+        "error": { "code": "STUB/400", "message": { "lang": "en", "value": message } }
+    }))
+}
+
+async fn process_orders() -> impl Responder {
+    HttpResponse::Ok().json(fixture(PROCESS_ORDERS))
+}
+
+async fn production_orders(
+    web::Query(query): web::Query<HashMap<String, String>>,
+) -> impl Responder {
+    let order = filter_value(&query, "ManufacturingOrder").unwrap_or_default();
+    let results: Vec<Value> = fixture(PRODUCTION_ORDERS)["d"]["results"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|o| o["ManufacturingOrder"] == order.as_str())
+        .cloned()
+        .collect();
+    HttpResponse::Ok().json(json!({ "d": { "results": results } }))
+}
+
+async fn batch_stock(web::Query(query): web::Query<HashMap<String, String>>) -> impl Responder {
+    let material = filter_value(&query, "Material").unwrap_or_default();
+    let location = filter_value(&query, "StorageLocation").unwrap_or_default();
+    let results: Vec<Value> = fixture(BATCH_STOCK)["d"]["results"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|s| s["Material"] == material.as_str() && s["StorageLocation"] == location.as_str())
+        .cloned()
+        .collect();
+    HttpResponse::Ok().json(json!({ "d": { "results": results } }))
+}
+
+async fn material_document(state: web::Data<StubState>, body: web::Json<Value>) -> impl Responder {
+    let item = &body["to_MaterialDocumentItem"][0];
+    if item["Batch"].as_str().is_none_or(str::is_empty) {
+        return odata_error("Batch is required for material with batch management");
+    }
+    if item["QuantityInEntryUnit"]
+        .as_str()
+        .and_then(|q| q.parse::<f64>().ok())
+        .is_none_or(|q| q <= 0.0)
+    {
+        return odata_error("Enter a quantity greater than zero");
+    }
+
+    let document = state
+        .next_document
+        .fetch_add(1, Ordering::SeqCst)
+        .to_string();
+    state
+        .material_documents
+        .lock()
+        .unwrap()
+        .push(body.into_inner());
+    HttpResponse::Created().json(json!({
+        "d": { "MaterialDocument": document, "MaterialDocumentYear": chrono::Local::now().format("%Y").to_string() }
+    }))
+}
+
+async fn cancel_material_document(
+    state: web::Data<StubState>,
+    web::Query(query): web::Query<HashMap<String, String>>,
+) -> impl Responder {
+    let document = query
+        .get("MaterialDocument")
+        .map(|d| d.trim_matches('\'').to_string())
+        .unwrap_or_default();
+    let issued = document
+        .parse::<u64>()
+        .is_ok_and(|d| (4900000001..state.next_document.load(Ordering::SeqCst)).contains(&d));
+    if !issued {
+        return odata_error(&format!("Material document {} does not exist", document));
+    }
+    let mut cancelled = state.cancelled_documents.lock().unwrap();
+    if cancelled.contains(&document) {
+        return odata_error(&format!(
+            "Material document {} is already cancelled",
+            document
+        ));
+    }
+    cancelled.push(document);
+    let reversal = state
+        .next_document
+        .fetch_add(1, Ordering::SeqCst)
+        .to_string();
+    HttpResponse::Created().json(json!({
+        "d": { "MaterialDocument": reversal, "MaterialDocumentYear": chrono::Local::now().format("%Y").to_string() }
+    }))
+}
+
+async fn rolls(state: web::Data<StubState>, body: web::Json<Value>) -> impl Responder {
+    if body["Batch"].as_str().is_none_or(str::is_empty) {
+        return odata_error("Batch is required");
+    }
+    state.rolls.lock().unwrap().push(body.into_inner());
+    HttpResponse::Created().json(json!({ "d": {} }))
+}
+
+async fn fabric_token() -> impl Responder {
+    HttpResponse::Ok().json(json!({ "token_type": "Bearer", "access_token": "stub-token" }))
+}
+
+async fn fabric_graphql(body: web::Json<Value>) -> impl Responder {
+    let wanted: Vec<&str> = body["variables"]["matnrs"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+    let items: Vec<Value> = fixture(MATERIAL_DESCRIPTIONS)["data"]["mAKTs"]["items"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|i| i["MATNR"].as_str().is_some_and(|m| wanted.contains(&m)))
+        .cloned()
+        .collect();
+    HttpResponse::Ok().json(json!({ "data": { "mAKTs": { "items": items } } }))
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("/pso", web::get().to(process_orders))
+        .route("/pdo", web::get().to(production_orders))
+        .route("/mav", web::get().to(batch_stock))
+        .route("/material-document", web::post().to(material_document))
+        .route("/Cancel", web::post().to(cancel_material_document))
+        .route("/rolls", web::post().to(rolls))
+        .route("/fabric/token", web::post().to(fabric_token))
+        .route("/fabric/graphql", web::post().to(fabric_graphql));
+}
+
+pub struct StubServer {
+    pub url: String,
+    pub state: web::Data<StubState>,
+    handle: ServerHandle,
+}
+
+impl StubServer {
+    pub async fn stop(self) {
+        self.handle.stop(false).await;
+    }
+}
+
+/// Starts the stub on a free local port in the background.
+pub async fn spawn_stub_server() -> std::io::Result<StubServer> {
+    let state = web::Data::new(StubState::default());
+    let app_state = state.clone();
+    let server =
+        HttpServer::new(move || App::new().app_data(app_state.clone()).configure(configure))
+            .workers(1)
+            .bind(("127.0.0.1", 0))?;
+    let url = format!("http://{}", server.addrs()[0]);
+    let server = server.run();
+    let handle = server.handle();
+    actix_web::rt::spawn(server);
+    Ok(StubServer { url, state, handle })
+}
+
+pub async fn run_stub_server(addr: &str) -> std::io::Result<()> {
+    let state = web::Data::new(StubState::default());
+    log::info!("SAP stub listening on http://{}", addr);
+    HttpServer::new(move || App::new().app_data(state.clone()).configure(configure))
+        .bind(addr)?
+        .run()
+        .await
+}
+
+#[actix_web::test]
+async fn test_job_to_sap_flow_against_stub() {
+    use crate::backend::models::{
+        EndInputRollPayload, InputRoll, InputRollCreatePayload, Job, JobCreatePayload, OutputRoll,
+        OutputRollCreatePayload,
+    };
+    use crate::sap::{HttpSapGateway, SapGateway, sync_material_codes, sync_process_orders};
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    let server = spawn_stub_server().await.unwrap();
+    let sap = HttpSapGateway::with_base_url(&server.url);
+
+    let db = tempfile::NamedTempFile::new().unwrap();
+    let pool = r2d2::Pool::new(SqliteConnectionManager::file(db.path())).unwrap();
+    let mut conn = pool.get().unwrap();
+    crate::manager::migrations::migrate_up(&mut conn, None).unwrap();
+
+    sync_process_orders(&pool, &sap).await.unwrap();
+    sync_material_codes(&pool, &sap).await.unwrap();
+    let orders: i32 = conn
+        .query_row("SELECT COUNT(*) FROM process_order", [], |r| r.get(0))
+        .unwrap();
+    assert_eq!(orders, 2);
+    let description: String = conn
+        .query_row(
+            "SELECT desc FROM materials_value_description WHERE value = '30000950'",
+            [],
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert!(!description.is_empty());
+    let batches = sap.batch_availability("30000950", "DW01").await.unwrap();
+    assert_eq!(
+        batches,
+        vec![("J23-612".to_string(), "9613.700KG".to_string())]
+    );
+
+    conn.execute_batch(
+        "INSERT INTO roles (id, name) VALUES (1, 'operator');
+         INSERT INTO users (id, full_name, staffid, role_id) VALUES (1, 'Test Operator', 'T001', 1);
+         INSERT INTO sections (id, name) VALUES (1, 'Printing');
+         INSERT INTO machines (id, name, label, section_id) VALUES (1, 'Printer 1', 'P1', 1);
+         INSERT INTO shifts (id, name) VALUES (1, 'Day');",
+    )
+    .unwrap();
+
+    let job = Job::create(
+        &mut conn,
+        &JobCreatePayload {
+            machine_id: 1,
+            shift_id: Some(1),
+            production_order: "220012061".to_string(),
+            input_roll: InputRollCreatePayload {
+                job_id: 0,
+                batch: "J23-612".to_string(),
+                material_document: None,
+                material_number: "30000950".to_string(),
+                start_meter: 12000.0,
+                start_weight: "520KG".to_string(),
+                consumed_weight: None,
+                output_batch: None,
+                remnant_id: None,
+                bom_override: None,
+            },
+        },
+        1,
+    )
+    .unwrap();
+
+    let end = EndInputRollPayload {
+        id: job.id,
+        weight_unit: "KG".to_string(),
+        posting_date: "2026-01-09".to_string(),
+        batch: "J23-612".to_string(),
+        input_roll_id: job.input_roll_id,
+        consumed_weight: "498.5".to_string(),
+        material_number: "30000950".to_string(),
+        production_order: "220012061".to_string(),
+    };
+    let document = InputRoll::end_input_roll(&conn, &sap, &end, Some(1))
+        .await
+        .unwrap();
+    assert_eq!(document.as_deref(), Some("4900000001"));
+    let input_roll = InputRoll::find_by_id(&conn, job.input_roll_id).unwrap();
+    assert_eq!(input_roll.material_document.as_deref(), Some("4900000001"));
+
+    let mut roll = OutputRoll::create(
+        &mut conn,
+        &OutputRollCreatePayload {
+            final_meter: 11800.0,
+            batch: "J23-612".to_string(),
+            flag_reason: None,
+            core_weight: Some(2.5),
+            shift_id: Some(1),
+            job_id: job.id,
+            machine_id: 1,
+            input_roll_id: job.input_roll_id,
+            flag_count: 0,
+        },
+        1,
+    )
+    .unwrap();
+    let weigh = serde_json::from_value(json!({ "id": roll.id, "final_weight": 495.0 })).unwrap();
+    roll.update(&conn, &sap, &weigh, Some(1)).await.unwrap();
+    assert_eq!(roll.final_weight, 492.5);
+
+    // Repeating the operator actions must not post anything twice
+    InputRoll::end_input_roll(&conn, &sap, &end, Some(1))
+        .await
+        .unwrap();
+    roll.update(&conn, &sap, &weigh, Some(1)).await.unwrap();
+
+    let issues = server.state.material_documents.lock().unwrap().clone();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0]["MaterialDocumentHeaderText"], "GI-IR1");
+    let rolls = server.state.rolls.lock().unwrap().clone();
+    assert_eq!(rolls.len(), 1);
+    assert_eq!(rolls[0]["Batch"], roll.output_batch);
+
+    server.stop().await;
+}
@@ -2,6 +2,8 @@
 let downtimes = [];
 let downtimeReasons = [];
 let users = [];
+let machines = [];
+let sections = [];
 let currentPage = 1;
 let itemsPerPage = 10;
 let totalCount = 0;
@@ -22,17 +24,24 @@ async function initializePage() {
 
 async function loadFilterOptions() {
 	try {
-		const [reasonsResponse, usersResponse] = await Promise.all([
+		const [reasonsResponse, usersResponse, machinesResponse, sectionsResponse] = await Promise.all([
 			fetch("/api/lookups/downtime-reasons").then(handleApiResponse),
 			fetch("/api/users").then(handleApiResponse),
+			fetch("/api/machines").then(handleApiResponse),
+			fetch("/api/sections").then(handleApiResponse),
 		]);
 
 		downtimeReasons = reasonsResponse;
 		users = usersResponse;
+		machines = machinesResponse;
+		sections = sectionsResponse;
 
 		populateSelect("filter-reason", downtimeReasons, "name", "All Reasons");
 		populateSelect("filter-user", users, "full_name", "All Users");
 		populateSelect("downtime-reason", downtimeReasons, "name", "Select Reason");
+		populateSelect("filter-machine", machines, "name", "All Machines");
+		populateSelect("filter-section", sections, "name", "All Sections");
+		populateSelect("machine", machines, "name", "Select Machine");
 	} catch (error) {
 		showNotification(error.message, "error");
 	}
@@ -56,7 +65,7 @@ async function loadDowntimes() {
 		updatePerPageOptions(result.total_count);
 	} catch (error) {
 		document.getElementById("downtime-table-body").innerHTML =
-			'<tr><td colspan="8" class="text-center text-red-500 py-4">Failed to load downtime records</td></tr>';
+			'<tr><td colspan="9" class="text-center text-red-500 py-4">Failed to load downtime records</td></tr>';
 		showNotification(error.message, "error");
 	} finally {
 		showLoading(false, "downtime-table");
@@ -77,7 +86,7 @@ function updateDowntimeStats(downtimes) {
 function renderDowntimes(downtimesToRender) {
 	const tbody = document.getElementById("downtime-table-body");
 	if (!downtimesToRender || downtimesToRender.length === 0) {
-		tbody.innerHTML = '<tr><td colspan="8" class="text-center text-gray-500 py-4">No downtime records found</td></tr>';
+		tbody.innerHTML = '<tr><td colspan="9" class="text-center text-gray-500 py-4">No downtime records found</td></tr>';
 		return;
 	}
 
@@ -93,14 +102,26 @@ function renderDowntimes(downtimesToRender) {
 
 		row.innerHTML = `
 			<td class="py-3 px-4">${escapeHtml(shift?.name || "Unknown")}</td>
+			<td class="py-3 px-4">${escapeHtml(downtime.machine_name || "-")}</td>
 			<td class="py-3 px-4">${formatDateTime(downtime.start_time)}</td>
-			<td class="py-3 px-4">${formatDateTime(downtime.end_time)}</td>
-            <td class="py-3 px-4 text-center">${formatDowntime(downtime.duration_minutes || 0)}</td>
+			<td class="py-3 px-4">${
+				downtime.end_time
+					? formatDateTime(downtime.end_time)
+					: '<span class="inline-flex items-center px-2 py-1 rounded-full text-xs font-medium bg-red-100 text-red-800">Still down</span>'
+			}</td>
+            <td class="py-3 px-4 text-center">${downtime.end_time ? formatDowntime(downtime.duration_minutes || 0) : "-"}</td>
 			<td class="py-3 px-4">${escapeHtml(reason?.name || "Unknown")}</td>
 			<td class="py-3 px-4">${escapeHtml(createdBy?.full_name || "System")}</td>
 			<td class="py-3 px-4">${formatDate(downtime.created_at)}</td>
 			<td class="py-3 px-4">
 				<div class="flex gap-2">
+					${
+						downtime.end_time
+							? ""
+							: `<button class="text-green-600 hover:text-green-800 close-btn" data-id="${downtime.id}" title="Close downtime">
+						<i class="fas fa-stop-circle"></i>
+					</button>`
+					}
 					<button class="text-blue-600 hover:text-blue-800 edit-btn" data-id="${downtime.id}">
 						<i class="fas fa-edit"></i>
 					</button>
@@ -122,6 +143,11 @@ function renderDowntimes(downtimesToRender) {
 			deleteDowntime(btn.dataset.id);
 		});
 	});
+	document.querySelectorAll(".close-btn").forEach((btn) => {
+		btn.addEventListener("click", function () {
+			closeDowntime(btn.dataset.id);
+		});
+	});
 }
 
 function renderPagination() {
@@ -179,12 +205,18 @@ async function applyFilters() {
 		const startDate = document.getElementById("filter-start-date").value;
 		const endDate = document.getElementById("filter-end-date").value;
 		const shiftFilter = document.getElementById("filter-shift").value;
+		const machineFilter = document.getElementById("filter-machine").value;
+		const sectionFilter = document.getElementById("filter-section").value;
+		const openFilter = document.getElementById("filter-open").value;
 		const reasonFilter = document.getElementById("filter-reason").value;
 		const userFilter = document.getElementById("filter-user").value;
 
 		if (startDate) params.append("start_date", startDate);
 		if (endDate) params.append("end_date", endDate);
 		if (shiftFilter) params.append("shift_id", shiftFilter);
+		if (machineFilter) params.append("machine_id", machineFilter);
+		if (sectionFilter) params.append("section_id", sectionFilter);
+		if (openFilter) params.append("open", openFilter);
 		if (reasonFilter) params.append("downtime_reason_id", reasonFilter);
 		if (userFilter) params.append("created_by", userFilter);
 		params.append("page", currentPage);
@@ -211,6 +243,9 @@ function clearFilters() {
 	document.getElementById("filter-start-date").value = "";
 	document.getElementById("filter-end-date").value = "";
 	document.getElementById("filter-shift").value = "";
+	document.getElementById("filter-machine").value = "";
+	document.getElementById("filter-section").value = "";
+	document.getElementById("filter-open").value = "";
 	document.getElementById("filter-reason").value = "";
 	document.getElementById("filter-user").value = "";
 
@@ -234,6 +269,7 @@ function openModal(downtimeId = null) {
 
 			if (hoursDiff > 24) {
 				document.getElementById("shift").disabled = true;
+				document.getElementById("machine").disabled = true;
 				document.getElementById("downtime-reason").disabled = true;
 				document.getElementById("start-time").disabled = true;
 				document.getElementById("end-time").disabled = true;
@@ -254,6 +290,7 @@ function openModal(downtimeId = null) {
 
 function enableDowntimeForm() {
 	document.getElementById("shift").disabled = false;
+	document.getElementById("machine").disabled = false;
 	document.getElementById("downtime-reason").disabled = false;
 	document.getElementById("start-time").disabled = false;
 	document.getElementById("end-time").disabled = false;
@@ -271,10 +308,11 @@ function closeModal() {
 function populateForm(downtime) {
 	document.getElementById("downtime-id").value = downtime.id;
 	document.getElementById("shift").value = downtime.shift_id;
+	document.getElementById("machine").value = downtime.machine_id || "";
 	document.getElementById("downtime-reason").value = downtime.downtime_reason_id;
 	document.getElementById("start-time").value = formatDateTimeLocal(downtime.start_time);
-	document.getElementById("end-time").value = formatDateTimeLocal(downtime.end_time);
-	document.getElementById("duration").value = downtime.duration_minutes || 0;
+	document.getElementById("end-time").value = downtime.end_time ? formatDateTimeLocal(downtime.end_time) : "";
+	document.getElementById("duration").value = downtime.duration_minutes ?? "";
 }
 
 function calculateDuration() {
@@ -300,10 +338,10 @@ function handleFormSubmit(e) {
 	const downtimeId = document.getElementById("downtime-id").value;
 	const formData = {
 		shift_id: parseInt(document.getElementById("shift").value),
+		machine_id: parseInt(document.getElementById("machine").value),
 		downtime_reason_id: parseInt(document.getElementById("downtime-reason").value),
 		start_time: document.getElementById("start-time").value,
-		end_time: document.getElementById("end-time").value,
-		duration_minutes: parseInt(document.getElementById("duration").value),
+		end_time: document.getElementById("end-time").value || null,
 	};
 
 	if (downtimeId) {
@@ -352,6 +390,29 @@ async function updateDowntime(downtimeData) {
 	}
 }
 
+async function closeDowntime(downtimeId) {
+	if (!confirm("Close this downtime now?")) return;
+
+	const closeBtn = document.querySelector(`.close-btn[data-id="${downtimeId}"]`);
+	if (closeBtn) setButtonLoading(closeBtn, true);
+
+	try {
+		const response = await fetch("/api/downtimes/close", {
+			method: "POST",
+			headers: { "Content-Type": "application/json" },
+			body: JSON.stringify({ id: parseInt(downtimeId) }),
+		});
+		await handleApiResponse(response);
+
+		showNotification("Downtime closed", "success");
+		await applyFilters();
+	} catch (error) {
+		showNotification(error.message, "error");
+	} finally {
+		if (closeBtn) setButtonLoading(closeBtn, false);
+	}
+}
+
 function editDowntime(downtimeId) {
 	openModal(downtimeId);
 }
@@ -388,12 +449,18 @@ async function exportToExcel() {
 		const startDate = document.getElementById("filter-start-date").value;
 		const endDate = document.getElementById("filter-end-date").value;
 		const shiftFilter = document.getElementById("filter-shift").value;
+		const machineFilter = document.getElementById("filter-machine").value;
+		const sectionFilter = document.getElementById("filter-section").value;
+		const openFilter = document.getElementById("filter-open").value;
 		const reasonFilter = document.getElementById("filter-reason").value;
 		const userFilter = document.getElementById("filter-user").value;
 
 		if (startDate) params.append("start_date", startDate);
 		if (endDate) params.append("end_date", endDate);
 		if (shiftFilter) params.append("shift_id", shiftFilter);
+		if (machineFilter) params.append("machine_id", machineFilter);
+		if (sectionFilter) params.append("section_id", sectionFilter);
+		if (openFilter) params.append("open", openFilter);
 		if (reasonFilter) params.append("downtime_reason_id", reasonFilter);
 		if (userFilter) params.append("created_by", userFilter);
 
@@ -413,9 +480,10 @@ async function exportToExcel() {
 
 			return {
 				Shift: shift?.name || "Unknown",
+				Machine: downtime.machine_name || "",
 				"Start Time": formatDateTime(downtime.start_time),
-				"End Time": formatDateTime(downtime.end_time),
-				Duration: formatDowntime(downtime.duration_minutes || 0),
+				"End Time": downtime.end_time ? formatDateTime(downtime.end_time) : "Open",
+				Duration: downtime.end_time ? formatDowntime(downtime.duration_minutes || 0) : "",
 				Reason: reason?.name || "Unknown",
 				"Created By": createdBy?.full_name || "System",
 				"Created At": formatDate(downtime.created_at),
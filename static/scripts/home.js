@@ -46,8 +46,10 @@ async function loadReferenceData() {
 
 async function loadDashboardData() {
 	try {
-		updateCurrentShift();
+		await updateCurrentShift();
 		await loadShiftOverview();
+		await loadOee();
+		await loadMassBalance();
 		await loadRecentJobs();
 		await loadRecentRolls();
 		await loadRecentDowntimes();
@@ -58,14 +60,23 @@ async function loadDashboardData() {
 	}
 }
 
-function updateCurrentShift() {
-	const now = new Date();
-	const hours = now.getHours();
+async function updateCurrentShift() {
+	const hours = new Date().getHours();
 	currentShift = hours >= 7 && hours < 19 ? 1 : 2;
+	let shiftName = currentShift === 1 ? "Day Shift" : "Night Shift";
+	try {
+		const slot = await fetch("/api/lookups/shifts/current").then((r) => r.json());
+		if (slot) {
+			currentShift = slot.shift_id;
+			shiftName = slot.holiday ? `${slot.name} (${slot.holiday})` : slot.name;
+		}
+	} catch (error) {
+		console.error("Error loading current shift:", error);
+	}
 
 	const shiftElement = document.getElementById("current-shift");
 	if (shiftElement) {
-		shiftElement.textContent = currentShift === 1 ? "Day Shift" : "Night Shift";
+		shiftElement.textContent = shiftName;
 		shiftElement.className =
 			currentShift === 1
 				? "bg-blue-100 text-blue-800 px-3 py-1 rounded-full text-sm font-medium"
@@ -96,7 +107,7 @@ function updateShiftStats(jobsData, rollsData, downtimesData, scrapsData) {
 		document.getElementById("shift-jobs").textContent = jobsData.total_count || 0;
 		document.getElementById("shift-rolls").textContent = rollsData.total_count || 0;
 
-		const totalDowntime = downtimesData.data ? downtimesData.data.reduce((sum, downtime) => sum + downtime.duration_minutes, 0) : 0;
+		const totalDowntime = downtimesData.data ? downtimesData.data.reduce((sum, downtime) => sum + (downtime.duration_minutes || 0), 0) : 0;
 		document.getElementById("shift-downtime").textContent = `${formatDowntime(totalDowntime)}`;
 
 		const totalScrap = scrapsData.data ? scrapsData.data.reduce((sum, scrap) => sum + scrap.weight_kg, 0) : 0;
@@ -106,6 +117,105 @@ function updateShiftStats(jobsData, rollsData, downtimesData, scrapsData) {
 	}
 }
 
+async function loadOee() {
+	const group = document.getElementById("oee-group")?.value || "machine";
+	const tbody = document.getElementById("oee-rows");
+	if (!tbody) return;
+
+	try {
+		const today = new Date().toISOString().split("T")[0];
+		const report = await fetch(`/api/oee?group_by=${group}&start_date=${today}&end_date=${today}`).then(handleApiResponse);
+		document.getElementById("oee-group-label").textContent = group.charAt(0).toUpperCase() + group.slice(1);
+
+		if (!report.rows || report.rows.length === 0) {
+			tbody.innerHTML = '<tr><td colspan="5" class="text-center text-gray-500 py-4">No jobs today</td></tr>';
+			return;
+		}
+
+		const percent = (value) => (value === null || value === undefined ? "-" : `${(value * 100).toFixed(1)}%`);
+		const rowHtml = (label, row, extra = "") => `
+			<tr class="border-b ${extra}">
+				<td class="py-2 px-3">${escapeHtml(label || "-")}</td>
+				<td class="py-2 px-3 text-center">${percent(row.availability)}</td>
+				<td class="py-2 px-3 text-center">${percent(row.performance)}</td>
+				<td class="py-2 px-3 text-center">${percent(row.quality)}</td>
+				<td class="py-2 px-3 text-center font-semibold">${percent(row.oee)}</td>
+			</tr>`;
+		tbody.innerHTML =
+			report.rows.map((row) => rowHtml(row[group], row)).join("") + rowHtml("All", report.total, "bg-gray-50 font-medium");
+	} catch (error) {
+		console.error("OEE loading failed:", error);
+		tbody.innerHTML = '<tr><td colspan="5" class="text-center text-red-500 py-4">Failed to load OEE</td></tr>';
+	}
+}
+
+async function loadMassBalance() {
+	const tbody = document.getElementById("mass-balance-rows");
+	if (!tbody) return;
+	const days = parseInt(document.getElementById("mass-balance-days").value) || 0;
+	const flagged = document.getElementById("mass-balance-flagged").checked;
+
+	try {
+		const end = new Date();
+		const start = new Date(end.getTime() - days * 86400000);
+		const day = (d) => d.toISOString().split("T")[0];
+		const params = new URLSearchParams({ start_date: day(start), end_date: day(end), flagged });
+		const report = await fetch(`/api/reports/mass-balance?${params}`).then(handleApiResponse);
+		document.getElementById("mass-balance-tolerance").textContent = `(tolerance ${report.tolerance_pct}%, ${report.flagged} flagged)`;
+
+		if (!report.jobs.length) {
+			tbody.innerHTML = '<tr><td colspan="7" class="text-center text-gray-500 py-4">No jobs in this period</td></tr>';
+			return;
+		}
+
+		const kg = (value) => `${value.toFixed(1)} kg`;
+		const rowHtml = (label, machine, row, attrs = "", extra = "") => `
+			<tr class="border-b ${extra}" ${attrs}>
+				<td class="py-2 px-3">${label}</td>
+				<td class="py-2 px-3">${escapeHtml(machine || "-")}</td>
+				<td class="py-2 px-3 text-right">${kg(row.input_kg)}</td>
+				<td class="py-2 px-3 text-right">${kg(row.output_kg)}</td>
+				<td class="py-2 px-3 text-right">${kg(row.scrap_kg)}</td>
+				<td class="py-2 px-3 text-right ${row.out_of_tolerance ? "text-red-600 font-semibold" : ""}">${kg(row.unaccounted_kg)}${row.unaccounted_pct === null ? "" : ` (${row.unaccounted_pct}%)`}</td>
+				<td class="py-2 px-3 text-center">${row.yield_pct === null ? "-" : `${row.yield_pct}%`}</td>
+			</tr>`;
+		tbody.innerHTML =
+			report.jobs
+				.map((job) => {
+					const label = `#${job.job_id} / ${escapeHtml(job.production_order || "-")}${job.complete ? "" : ' <span class="text-xs text-gray-400">(open)</span>'}`;
+					return rowHtml(label, job.machine, job, `data-job-id="${job.job_id}" style="cursor: pointer"`, job.out_of_tolerance ? "bg-red-50" : "");
+				})
+				.join("") + rowHtml("All", "", report.total, "", "bg-gray-50 font-medium");
+		tbody.querySelectorAll("tr[data-job-id]").forEach((row) => row.addEventListener("click", () => toggleMassBalanceRolls(row)));
+	} catch (error) {
+		console.error("Mass balance loading failed:", error);
+		tbody.innerHTML = '<tr><td colspan="7" class="text-center text-red-500 py-4">Failed to load mass balance</td></tr>';
+	}
+}
+
+async function toggleMassBalanceRolls(row) {
+	const next = row.nextElementSibling;
+	if (next && next.classList.contains("mass-balance-rolls")) {
+		next.remove();
+		return;
+	}
+	try {
+		const detail = await fetch(`/api/reports/mass-balance/job?id=${row.dataset.jobId}`).then(handleApiResponse);
+		const line = (label, weight, note = "") => `<div class="flex justify-between gap-4"><span>${escapeHtml(label || "-")} ${note}</span><span>${weight === null ? "-" : `${weight} kg`}</span></div>`;
+		const column = (title, lines) => `<div><h4 class="font-medium text-gray-700 mb-1">${title}</h4>${lines.join("") || '<span class="text-gray-400">None</span>'}</div>`;
+		const drill = document.createElement("tr");
+		drill.className = "mass-balance-rolls bg-gray-50 border-b";
+		drill.innerHTML = `<td colspan="7" class="py-3 px-3"><div class="grid grid-cols-1 md:grid-cols-3 gap-4 text-xs">
+			${column("Input rolls", detail.inputs.map((r) => line(r.batch, r.consumed_weight, r.consumed_weight === null ? '<span class="text-orange-500">not weighed</span>' : "")))}
+			${column("Output rolls", detail.outputs.map((r) => line(r.output_batch, r.final_weight, r.counted ? "" : '<span class="text-red-500">scrapped</span>')))}
+			${column("Scrap", detail.scraps.map((s) => line(s.scrap_type, s.weight_kg, s.notes ? `<span class="text-gray-400">${escapeHtml(s.notes)}</span>` : "")))}
+		</div></td>`;
+		row.after(drill);
+	} catch (error) {
+		showNotification(error.message, "error");
+	}
+}
+
 async function loadRecentJobs() {
 	try {
 		const response = await fetch(`/api/jobs/filter-with-input-rolls?per_page=5&page=1`);
@@ -297,9 +407,11 @@ function renderRecentDowntimes(downtimes) {
                             <i class="fas fa-clock text-orange-600 text-sm"></i>
                         </div>
                         <div class="min-w-0 flex-1">
-                            <h4 class="text-sm font-semibold text-gray-900">${formatDowntime(downtime.duration_minutes)}</h4>
+                            <h4 class="text-sm font-semibold text-gray-900">${
+								downtime.end_time ? formatDowntime(downtime.duration_minutes) : "Still down"
+							}${downtime.machine_name ? ` · ${escapeHtml(downtime.machine_name)}` : ""}</h4>
                             <p class="text-xs text-gray-600 mt-1">
-                                ${formatTime(downtime.start_time)} - ${formatTime(downtime.end_time)}
+                                ${formatTime(downtime.start_time)} - ${downtime.end_time ? formatTime(downtime.end_time) : "now"}
                             </p>
                         </div>
                     </div>
@@ -480,6 +592,9 @@ function setupEventListeners() {
 	document.getElementById("close-roll-modal").addEventListener("click", closeRollDetailsModal);
 	document.getElementById("close-roll-details").addEventListener("click", closeRollDetailsModal);
 	document.getElementById("search-roll-btn").addEventListener("click", searchRoll);
+	document.getElementById("oee-group").addEventListener("change", loadOee);
+	document.getElementById("mass-balance-days").addEventListener("change", loadMassBalance);
+	document.getElementById("mass-balance-flagged").addEventListener("change", loadMassBalance);
 
 	document.getElementById("roll-lookup-input").addEventListener("keypress", function (e) {
 		if (e.key === "Enter") {
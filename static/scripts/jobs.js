@@ -72,7 +72,7 @@ function updateJobStats(jobs) {
 function renderJobs(jobsToRender) {
 	const tbody = document.getElementById("jobs-table-body");
 	if (!jobsToRender || jobsToRender.length === 0) {
-		tbody.innerHTML = '<tr><td colspan="6" class="text-center text-gray-500 py-4">No job records found</td></tr>';
+		tbody.innerHTML = '<tr><td colspan="7" class="text-center text-gray-500 py-4">No job records found</td></tr>';
 		return;
 	}
 
@@ -92,12 +92,48 @@ function renderJobs(jobsToRender) {
 			<td class="py-3 px-4">${formatDateTime(job.last_updated)}</td>
 			<td class="py-3 px-4">${escapeHtml(createdBy?.full_name || "System")}</td>
 			<td class="py-3 px-4">${formatWeight(job.total_consumed_weight || 0)}</td>
+			<td class="py-3 px-4">${jobStatusHtml(job)}</td>
 		`;
+		row.querySelector(".close-job-btn")?.addEventListener("click", () => closeJob(job));
 
 		tbody.appendChild(row);
 	});
 }
 
+function jobStatusHtml(job) {
+	if (job.end_datetime) return `<span class="text-gray-600" title="${escapeHtml(formatDateTime(job.end_datetime))}">Closed</span>`;
+	const badge = job.stale ? '<span class="text-orange-600 font-medium" title="Open longer than expected">Stale</span>' : '<span class="text-green-600">Active</span>';
+	return `${badge} <button class="close-job-btn text-blue-600 hover:text-blue-800 text-sm ml-2"><i class="fas fa-flag-checkered"></i> Close</button>`;
+}
+
+async function closeJob(job) {
+	if (!confirm(`Close job ${job.production_order}?`)) return;
+	try {
+		const response = await fetch("/api/jobs/close", {
+			method: "POST",
+			headers: { "Content-Type": "application/json" },
+			body: JSON.stringify({ id: job.id }),
+		});
+		if (response.status === 409) {
+			const blocked = await response.json();
+			alert(`${blocked.message}:\n\n- ${blocked.missing.join("\n- ")}`);
+			return;
+		}
+		const summary = await handleApiResponse(response);
+		alert(
+			`Job ${summary.production_order} closed\n\n` +
+				`Input: ${summary.input_rolls} rolls, ${formatWeight(summary.consumed_kg)}\n` +
+				`Output: ${summary.output_rolls} rolls, ${summary.output_meters.toLocaleString()} m, ${formatWeight(summary.output_kg)}\n` +
+				`Flagged rolls: ${summary.flagged_rolls} (${summary.flags} flags)\n` +
+				`Scrap: ${formatWeight(summary.scrap_kg)}\n` +
+				`Downtime: ${summary.downtimes} stops, ${Math.round(summary.downtime_minutes)} min`
+		);
+		await applyFilters();
+	} catch (error) {
+		showNotification(error.message, "error");
+	}
+}
+
 function renderPagination() {
 	const totalPages = Math.ceil(totalCount / itemsPerPage);
 	const paginationContainer = document.getElementById("pagination");
@@ -154,11 +190,13 @@ async function applyFilters() {
 		const shiftFilter = document.getElementById("filter-shift").value;
 		const machineFilter = document.getElementById("filter-machine").value;
 		const userFilter = document.getElementById("filter-user").value;
+		const statusFilter = document.getElementById("filter-status").value;
 
 		if (productionOrder) params.append("production_order", productionOrder);
 		if (shiftFilter) params.append("shift_id", shiftFilter);
 		if (machineFilter) params.append("machine_id", machineFilter);
 		if (userFilter) params.append("created_by", userFilter);
+		if (statusFilter) params.append("status", statusFilter);
 		params.append("page", currentPage);
 		params.append("per_page", itemsPerPage);
 
@@ -185,6 +223,7 @@ function clearFilters() {
 	document.getElementById("filter-shift").value = "";
 	document.getElementById("filter-machine").value = "";
 	document.getElementById("filter-user").value = "";
+	document.getElementById("filter-status").value = "";
 
 	currentPage = 1;
 	applyFilters();
@@ -200,11 +239,13 @@ async function exportToExcel() {
 		const shiftFilter = document.getElementById("filter-shift").value;
 		const machineFilter = document.getElementById("filter-machine").value;
 		const userFilter = document.getElementById("filter-user").value;
+		const statusFilter = document.getElementById("filter-status").value;
 
 		if (productionOrder) params.append("production_order", productionOrder);
 		if (shiftFilter) params.append("shift_id", shiftFilter);
 		if (machineFilter) params.append("machine_id", machineFilter);
 		if (userFilter) params.append("created_by", userFilter);
+		if (statusFilter) params.append("status", statusFilter);
 		const response = await fetch(`/api/jobs/filter?${params}`);
 		const result = await handleApiResponse(response);
 		const filteredJobs = result.data;
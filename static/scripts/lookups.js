@@ -145,6 +145,11 @@ function renderLookups(lookupsToRender) {
 							? `<span class="ml-2 text-xs text-gray-500">(${getSectionName(lookup.section_id)})</span>`
 							: ""
 					}
+                    ${
+						lookup.lookup_type === "shifts" && lookup.start_time
+							? `<span class="ml-2 text-xs text-gray-500">(${escapeHtml(lookup.start_time)} - ${escapeHtml(lookup.end_time)})</span>`
+							: ""
+					}
                 </div>
             </td>
             <td class="py-3 px-4">
@@ -241,7 +246,9 @@ function toggleColorPicker() {
 	const colorSuggestions = document.getElementById("color-suggestions");
 	const sectionPicker = document.getElementById("section-picker");
 	const sectionSelect = document.getElementById("section-id");
+	const shiftTimes = document.getElementById("shift-times");
 
+	shiftTimes.style.display = type === "shifts" ? "block" : "none";
 	if (type === "colours") {
 		colorPicker.style.display = "block";
 		colorSuggestions.style.display = "block";
@@ -314,6 +321,12 @@ function populateForm(lookup) {
 		document.getElementById("section-id").value = lookup.section_id;
 	}
 
+	if (lookup.lookup_type === "shifts") {
+		document.getElementById("shift-start").value = lookup.start_time || "";
+		document.getElementById("shift-end").value = lookup.end_time || "";
+		document.getElementById("shift-sequence").value = lookup.sequence || "";
+	}
+
 	toggleColorPicker();
 }
 
@@ -358,6 +371,16 @@ function handleFormSubmit(e) {
 		}
 	}
 
+	// Shifts without times are picked by hand instead of from the calendar
+	if (finalType === "shifts") {
+		formData.start_time = document.getElementById("shift-start").value;
+		formData.end_time = document.getElementById("shift-end").value;
+		const sequence = document.getElementById("shift-sequence").value;
+		if (sequence) {
+			formData.sequence = parseInt(sequence);
+		}
+	}
+
 	const endpoint = lookupId ? "update" : "create";
 	const apiType = lookupType || selectedType;
 
@@ -276,6 +276,7 @@ function populateMachineForm(machine) {
 	document.getElementById("machine-name").value = machine.name;
 	document.getElementById("machine-label").value = machine.label;
 	document.getElementById("machine-section").value = machine.section_id;
+	document.getElementById("machine-rated-speed").value = machine.rated_speed ?? "";
 }
 
 function handleMachineFormSubmit(e) {
@@ -290,6 +291,8 @@ function handleMachineFormSubmit(e) {
 		label: document.getElementById("machine-label").value,
 		section_id: parseInt(document.getElementById("machine-section").value),
 	};
+	const ratedSpeed = parseFloat(document.getElementById("machine-rated-speed").value);
+	if (Number.isFinite(ratedSpeed)) formData.rated_speed = ratedSpeed;
 
 	if (machineId) {
 		formData.id = parseInt(machineId);
@@ -396,6 +399,7 @@ async function exportToExcel() {
 				Label: machine.label,
 				Section: machine.section_name,
 				"Total Jobs": machine.job_count,
+				"Rated Speed (m/min)": machine.rated_speed ?? "",
 			};
 		});
 
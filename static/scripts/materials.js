@@ -11,6 +11,7 @@ document.addEventListener("DOMContentLoaded", function () {
 async function initializePage() {
 	await loadMaterials();
 	setupEventListeners();
+	await loadAlternatives();
 }
 
 function showLoading(show) {
@@ -468,7 +469,96 @@ async function exportToExcel() {
 	}
 }
 
+async function loadAlternatives() {
+	const tbody = document.getElementById("alternatives-table-body");
+	try {
+		const response = await fetch("/api/bom-alternatives/filter");
+		const alternatives = await handleApiResponse(response);
+
+		if (alternatives.length === 0) {
+			tbody.innerHTML = '<tr><td colspan="6" class="text-center text-gray-500 py-4">No alternatives approved</td></tr>';
+			return;
+		}
+
+		tbody.innerHTML = alternatives
+			.map(
+				(alternative) => `
+			<tr class="hover:bg-gray-50">
+				<td class="py-3 px-4 font-medium">${escapeHtml(alternative.material_number)}</td>
+				<td class="py-3 px-4">${escapeHtml(alternative.alternative_number)}</td>
+				<td class="py-3 px-4">${escapeHtml(alternative.process_order || "All orders")}</td>
+				<td class="py-3 px-4">${escapeHtml(alternative.note || "-")}</td>
+				<td class="py-3 px-4">${formatDateTime(alternative.created_at)}</td>
+				<td class="py-3 px-4">
+					<div class="flex gap-2 justify-center">
+						<button class="text-red-600 hover:text-red-800 delete-alternative-btn" data-id="${alternative.id}" title="Delete">
+							<i class="fas fa-trash"></i>
+						</button>
+					</div>
+				</td>
+			</tr>`,
+			)
+			.join("");
+
+		document.querySelectorAll(".delete-alternative-btn").forEach((btn) => {
+			btn.addEventListener("click", () => deleteAlternative(btn));
+		});
+	} catch (error) {
+		tbody.innerHTML = '<tr><td colspan="6" class="text-center text-red-500 py-4">Failed to load alternatives</td></tr>';
+		console.error("Failed to load alternatives:", error);
+	}
+}
+
+async function handleAlternativeFormSubmit(e) {
+	e.preventDefault();
+	const submitBtn = document.getElementById("add-alternative-btn");
+	setButtonLoading(submitBtn, true);
+
+	try {
+		const response = await fetch("/api/bom-alternatives/create", {
+			method: "POST",
+			headers: { "Content-Type": "application/json" },
+			body: JSON.stringify({
+				material_number: document.getElementById("alternative-material").value.trim(),
+				alternative_number: document.getElementById("alternative-number").value.trim(),
+				process_order: document.getElementById("alternative-order").value.trim() || null,
+				note: document.getElementById("alternative-note").value.trim() || null,
+			}),
+		});
+		await handleApiResponse(response);
+
+		showNotification("Alternative approved", "success");
+		e.target.reset();
+		await loadAlternatives();
+	} catch (error) {
+		showNotification(error.message, "error");
+	} finally {
+		setButtonLoading(submitBtn, false);
+	}
+}
+
+async function deleteAlternative(button) {
+	if (!confirm("Remove this approved alternative?")) return;
+
+	setButtonLoading(button, true);
+	try {
+		const response = await fetch("/api/bom-alternatives/delete", {
+			method: "DELETE",
+			headers: { "Content-Type": "application/json" },
+			body: JSON.stringify({ id: parseInt(button.dataset.id) }),
+		});
+		await handleApiResponse(response);
+
+		showNotification("Alternative removed", "success");
+		await loadAlternatives();
+	} catch (error) {
+		showNotification(error.message, "error");
+		setButtonLoading(button, false);
+	}
+}
+
 function setupEventListeners() {
+	document.getElementById("alternative-form").addEventListener("submit", handleAlternativeFormSubmit);
 	document.getElementById("materials-apply-filter").addEventListener("click", applyFilters);
 	document.getElementById("materials-clear-filter").addEventListener("click", clearFilters);
 	document.getElementById("add-material-btn").addEventListener("click", () => openMaterialModal());
@@ -164,7 +164,9 @@ async function loadMaterialBatches(materialDetail, materialNumber) {
 	startWeight.value = "";
 
 	try {
-		const response = await fetch(`/api/materials-availability?material_number=${encodeURIComponent(materialNumber)}&storage_location=DW01`);
+		const machineSelect = document.getElementById("machine");
+		const sectionId = machineSelect.options[machineSelect.selectedIndex]?.dataset.sectionId || "";
+		const response = await fetch(`/api/materials-availability?material_number=${encodeURIComponent(materialNumber)}&section_id=${sectionId}`);
 		const batches = await handleApiResponse(response);
 
 		batchSelect.innerHTML = '<option value="">Select Batch</option>';
@@ -177,6 +179,36 @@ async function loadMaterialBatches(materialDetail, materialNumber) {
 			batchSelect.appendChild(option);
 		});
 
+		// Output rolls of upstream sections can be loaded as the input of this job
+		const wipResponse = await fetch("/api/output-rolls/available");
+		if (wipResponse.ok) {
+			const wipRolls = await wipResponse.json();
+			wipRolls.forEach((roll) => {
+				const option = document.createElement("option");
+				option.value = roll.output_batch;
+				option.textContent = `${roll.output_batch} (WIP)`;
+				option.dataset.weight = `${roll.final_weight}KG`;
+				option.dataset.meter = roll.final_meter;
+				option.dataset.outputBatch = roll.output_batch;
+				batchSelect.appendChild(option);
+			});
+		}
+
+		// Remnants returned from earlier jobs keep their SAP batch and can be loaded again
+		const remnantResponse = await fetch(`/api/remnants/available?material_number=${encodeURIComponent(materialNumber)}`);
+		if (remnantResponse.ok) {
+			const remnants = await remnantResponse.json();
+			remnants.forEach((remnant) => {
+				const option = document.createElement("option");
+				option.value = remnant.batch;
+				option.textContent = `${remnant.batch} (Remnant ${remnant.weight}${remnant.unit})`;
+				option.dataset.weight = `${remnant.weight}${remnant.unit}`;
+				option.dataset.meter = remnant.meter;
+				option.dataset.remnantId = remnant.id;
+				batchSelect.appendChild(option);
+			});
+		}
+
 		batchSelect.disabled = false;
 
 		if (batches.length > 0) {
@@ -197,6 +229,9 @@ function updateStartWeight(selectedOption) {
 	} else {
 		startWeight.value = "";
 	}
+	if (selectedOption && selectedOption.dataset.meter) {
+		document.getElementById("start-meter").value = selectedOption.dataset.meter;
+	}
 }
 
 async function loadFlagReasons(sectionId = null) {
@@ -446,6 +481,25 @@ function setupEventListeners() {
 
 	const today = new Date().toISOString().split("T")[0];
 	document.getElementById("date-select").value = today;
+	preselectCurrentShift();
+}
+
+// Picks the calendar shift and its production date, so a night shift after midnight stays on the day it started
+async function preselectCurrentShift() {
+	try {
+		const response = await fetch("/api/lookups/shifts/current");
+		if (!response.ok) return;
+		const slot = await response.json();
+		const shiftSelect = document.getElementById("shift-select");
+		if (!slot || shiftSelect.disabled || !shiftSelect.querySelector(`option[value="${slot.shift_id}"]`)) return;
+		shiftSelect.value = slot.shift_id;
+		document.getElementById("date-select").value = slot.production_date;
+		if (slot.holiday) {
+			showNotification(`${slot.name} is a plant holiday (${slot.holiday})`, "warning");
+		}
+	} catch (error) {
+		console.error("Error loading current shift:", error);
+	}
 }
 
 function resetToMachineSelection() {
@@ -493,7 +547,46 @@ function resetToMachineSelection() {
 
 function cancelStartNew() {
 	document.getElementById("start-new-modal").classList.add("hidden");
+	clearEndRollFields();
+}
+
+function clearEndRollFields() {
 	document.getElementById("used-weight").value = "";
+	document.getElementById("remnant-weight").value = "";
+	document.getElementById("remnant-meter").value = "";
+	document.getElementById("remnant-post-return").checked = false;
+}
+
+// Remnant labels come from the same template as roll labels and go to the station's printer
+async function printRemnantLabel(remnant) {
+	const response = await fetch(`/api/remnants/label?id=${remnant.id}&format=pdf&print=true`);
+	if (!response.ok) {
+		showNotification(await response.text(), "error");
+		return;
+	}
+	const labelBlob = await response.blob();
+	const reader = new FileReader();
+	reader.onloadend = async function () {
+		try {
+			const printResponse = await fetch("http://localhost:8080/api/app/print", {
+				method: "POST",
+				headers: { "Content-Type": "application/json" },
+				body: JSON.stringify({ pdf_data: reader.result.split(",")[1] }),
+			});
+			const printResult = await printResponse.json().catch(() => ({}));
+			if (printResult.success === false) {
+				throw new Error(printResult.message);
+			}
+			showNotification(`Remnant label for ${remnant.batch} sent to printer`, "success");
+		} catch (error) {
+			// No hardware agent on this station, hand the PDF to the browser instead
+			const a = document.createElement("a");
+			a.href = URL.createObjectURL(labelBlob);
+			a.download = `remnant-${remnant.batch}.pdf`;
+			a.click();
+		}
+	};
+	reader.readAsDataURL(labelBlob);
 }
 
 async function confirmStartNew() {
@@ -533,6 +626,17 @@ async function confirmStartNew() {
 			return;
 		}
 
+		const remnantWeightInput = document.getElementById("remnant-weight").value;
+		const remnantWeight = remnantWeightInput ? parseFloat(remnantWeightInput) : null;
+		if (remnantWeight !== null && (isNaN(remnantWeight) || remnantWeight <= 0)) {
+			showNotification("Remnant weight must be greater than 0", "error");
+			return;
+		}
+		if (remnantWeight !== null && consumedWeight + remnantWeight > startWeight) {
+			showNotification("Used and remnant weight cannot exceed start weight", "error");
+			return;
+		}
+
 		if (currentJob && currentJob.id) {
 			let materialNumber = currentJob.material_number || currentJob.production_order || "";
 			if (!materialNumber && currentJob.selected_material) {
@@ -557,15 +661,24 @@ async function confirmStartNew() {
 				production_order: currentJob.production_order,
 			};
 
-			const response = await fetch("/api/input-rolls/end", {
+			if (remnantWeight !== null) {
+				endData.remnant_weight = remnantWeight;
+				endData.remnant_meter = parseFloat(document.getElementById("remnant-meter").value) || 0;
+				endData.post_return = document.getElementById("remnant-post-return").checked;
+			}
+
+			const response = await fetch(remnantWeight !== null ? "/api/input-rolls/end-with-remnant" : "/api/input-rolls/end", {
 				method: "POST",
 				headers: { "Content-Type": "application/json" },
 				body: JSON.stringify(endData),
 			});
 
-			await handleApiResponse(response);
+			const result = await handleApiResponse(response);
 
 			showNotification("Current Roll consumed", "success");
+			if (remnantWeight !== null && result?.remnant) {
+				await printRemnantLabel(result.remnant);
+			}
 
 			const activeJobSelect = document.getElementById("active-job");
 			const currentOption = activeJobSelect.querySelector(`option[value="${currentJob.id}"]`);
@@ -581,7 +694,7 @@ async function confirmStartNew() {
 		}
 
 		document.getElementById("start-new-modal").classList.add("hidden");
-		usedWeightInput.value = "";
+		clearEndRollFields();
 
 		document.getElementById("date-select").disabled = false;
 		document.getElementById("process-order").disabled = false;
@@ -810,6 +923,8 @@ async function handleInputSubmit(e) {
 			material_number: document.getElementById("consuming-material").selectedOptions[0].dataset.materialNumber,
 			start_meter: parseFloat(document.getElementById("start-meter").value),
 			start_weight: document.getElementById("start-weight").value,
+			output_batch: selectedBatch.dataset.outputBatch || null,
+			remnant_id: selectedBatch.dataset.remnantId ? Number(selectedBatch.dataset.remnantId) : null,
 		},
 	};
 	if (!formData.production_order) {
@@ -855,7 +970,17 @@ async function handleInputSubmit(e) {
 	}
 
 	try {
-		const newJob = await createJob(formData);
+		let newJob;
+		try {
+			newJob = await createJob(formData);
+		} catch (error) {
+			// Off-BOM material: load it only with a supervisor's sign-off
+			if (!error.message.includes("supervisor override is required")) throw error;
+			const bomOverride = await requestBomOverride(error.message);
+			if (!bomOverride) throw error;
+			formData.input_roll.bom_override = bomOverride;
+			newJob = await createJob(formData);
+		}
 		currentJob = newJob;
 
 		showNotification("Job started successfully!", "success");
@@ -977,6 +1102,35 @@ function addRollToTable(rollData) {
 	tableBody.prepend(row);
 }
 
+function requestBomOverride(message) {
+	const modal = document.getElementById("bom-override-modal");
+	const fields = ["bom-override-staffid", "bom-override-password", "bom-override-reason"].map((id) => document.getElementById(id));
+	fields.forEach((field) => (field.value = ""));
+	document.getElementById("bom-override-message").textContent = message;
+	modal.classList.remove("hidden");
+
+	return new Promise((resolve) => {
+		const confirmBtn = document.getElementById("bom-override-confirm");
+		const cancelBtn = document.getElementById("bom-override-cancel");
+		const close = (result) => {
+			modal.classList.add("hidden");
+			confirmBtn.onclick = null;
+			cancelBtn.onclick = null;
+			resolve(result);
+		};
+		confirmBtn.onclick = () => {
+			const [staffid, password, reason] = fields.map((field) => field.value.trim());
+			const missing = fields.find((field) => !field.value.trim());
+			if (missing) {
+				highlightElement(missing.id);
+				return;
+			}
+			close({ staffid, password, reason });
+		};
+		cancelBtn.onclick = () => close(null);
+	});
+}
+
 async function createJob(jobData) {
 	const response = await fetch("/api/jobs/create", {
 		method: "POST",
@@ -1,6 +1,6 @@
 /** @format */
 
-// --- VECTOR LABEL, RASTER QR PDF EXPORT ---
+// --- HTML PREVIEW; THE PRINTED PDF IS RENDERED BY /api/output-rolls/label ---
 async function printRoll(rollId) {
 	openPrintModal();
 
@@ -116,201 +116,58 @@ async function printRoll(rollId) {
 			}
 		}, 100);
 
-		const { PDFDocument, StandardFonts, rgb } = PDFLib;
-
-		const flagsCount = result.flag_reason
-			? result.flag_reason
-					.split("|")
-					.map((s) => s.trim())
-					.filter(Boolean).length
-			: 0;
-
-		const qr = qrcode(0, "L");
-		qr.addData(JSON.stringify(qrPayload));
-		qr.make();
-		const n = qr.getModuleCount();
-		const scale = 6;
-		const size = n * scale;
-
-		const canvas = document.createElement("canvas");
-		canvas.width = size;
-		canvas.height = size;
-		const ctx = canvas.getContext("2d");
-		ctx.fillStyle = "#fff";
-		ctx.fillRect(0, 0, size, size);
-		ctx.fillStyle = "#000";
-		for (let r = 0; r < n; r++) for (let c = 0; c < n; c++) if (qr.isDark(r, c)) ctx.fillRect(c * scale, r * scale, scale, scale);
-		const qrPng = await fetch(canvas.toDataURL("image/png")).then((r) => r.arrayBuffer());
-
-		const pdf = await PDFDocument.create();
-		const page = pdf.addPage([288, 432]); // 4x6 in @72dpi
-		const font = await pdf.embedFont(StandardFonts.Helvetica);
-		const fontB = await pdf.embedFont(StandardFonts.HelveticaBold);
-
-		// No border, no main padding
-
-		// Centered title and subtitle
-		const centerX = (text, size, fontObj) => (288 - fontObj.widthOfTextAtSize(text, size)) / 2;
-
-		let y = 410;
-		const title = "INSIGNIA PRODUCTION LABEL";
-		page.drawText(title, { x: centerX(title, 14, fontB), y, size: 14, font: fontB });
-		y -= 18;
-		const wrap = (t, max = 38) => {
-			const w = (t || "").replace(/\s+/g, " ").trim();
-			const out = [];
-			let line = "";
-			for (const word of w.split(" ")) {
-				const next = line ? line + " " + word : word;
-				if (next.length > max) {
-					if (line) out.push(line);
-					line = word;
-				} else line = next;
-			}
-			if (line) out.push(line);
-			return out;
-		};
-		// Always show at least one subtitle line
-		const subtitleLines = wrap(result.process_order_description, 38);
-		if (subtitleLines.length === 0) subtitleLines.push("");
-		subtitleLines.slice(0, 2).forEach((ln) => {
-			page.drawText(ln, { x: centerX(ln, 11, fontB), y, size: 11, font: fontB });
-			y -= 13;
-		});
-
-		// Move QR code lower so it does NOT cover title/subtitle
-		const img = await pdf.embedPng(qrPng);
-		// Move QR code up a bit, and push text blocks below QR code further down
-		const qrY = y - 5; // QR code closer to subtitle
-		page.drawImage(img, { x: (288 - 150) / 2, y: qrY - 150, width: 150, height: 150 });
-
-		// Adjust y for key-value text blocks: move slightly up under QR
-		let kvStartY = qrY - 190;
-
-		const leftX = 24,
-			rightX = 288 - 24 - 80; // 24px margin from right, 80px block width
-		// Restore original font sizes for key-value block
-		const kv = (label, value, x, y, align = "left") => {
-			const labelFontSize = 10;
-			const valueFontSize = 13;
-			if (align === "right") {
-				const blockWidth = 80;
-				const margin = 24;
-				const labelWidth = fontB.widthOfTextAtSize(label, labelFontSize);
-				const valueWidth = fontB.widthOfTextAtSize(String(value ?? ""), valueFontSize);
-				const rightEdge = 288 - margin;
-				page.drawText(label, {
-					x: rightEdge - labelWidth,
-					y,
-					size: labelFontSize,
-					font: fontB,
-					color: rgb(0.25, 0.25, 0.25),
-					align: "right",
-				});
-				page.drawText(String(value ?? ""), {
-					x: rightEdge - valueWidth,
-					y: y - 16,
-					size: valueFontSize,
-					font: fontB,
-					align: "right",
-				});
-			} else {
-				page.drawText(label, { x, y, size: labelFontSize, font: fontB, color: rgb(0.25, 0.25, 0.25) });
-				page.drawText(String(value ?? ""), { x, y: y - 16, size: valueFontSize, font: fontB });
+		document.getElementById("close-print-btn").addEventListener("click", closePrintModal);
+		// onclick rather than addEventListener: each print is logged, so reopening the modal must not stack handlers
+		document.getElementById("print-pdf-btn").onclick = async function () {
+			const format = await stationLabelFormat();
+			const response = await fetch(`/api/output-rolls/label?id=${rollId}&format=${format}&print=true`);
+			if (!response.ok) {
+				showNotification(await response.text(), "error");
+				return;
 			}
-		};
-
-		kv("PRODUCTION ORDER", result.production_order, leftX, kvStartY);
-		kv("SECTION", result.section, leftX, kvStartY - 38);
-		kv("MATERIAL NUMBER", result.material_number, rightX, kvStartY, "right");
-		kv("ROLL NO", result.output_batch, rightX, kvStartY - 38, "right");
-
-		kv("FINAL METER", `${formatNumber(result.final_meter)} m`, leftX, kvStartY - 80);
-		kv("FINAL WEIGHT", `${formatNumber(result.final_weight)} kg`, rightX, kvStartY - 80, "right");
-
-		// TIMESTAMP + OPERATOR stays up; REASON sits at footer with full width
-		const timestampY = kvStartY - 120;
-		const valueSize = 12;
-		page.drawText(String(result.created_at ?? ""), { x: leftX, y: timestampY - 14, size: valueSize, font });
-		const operatorName = result.operator_name || "SYSTEM";
-		const operatorValueWidth = font.widthOfTextAtSize(operatorName, valueSize);
-		const rightEdge = 288 - 24;
-		page.drawText(operatorName, { x: rightEdge - operatorValueWidth, y: timestampY - 14, size: valueSize, font });
-
-		const remarkText = result.flag_count > 0 ? `${result.flag_reason || ""}` : "GOOD";
-		const wrapByWidth = (text, maxWidth, size) => {
-			const words = (text || "").replace(/\s+/g, " ").trim().split(" ").filter(Boolean);
-			if (words.length === 0) return [""];
-			const lines = [];
-			let line = "";
-			const fits = (t) => font.widthOfTextAtSize(t, size) <= maxWidth;
-			for (const word of words) {
-				const next = line ? `${line} ${word}` : word;
-				if (fits(next)) {
-					line = next;
-				} else {
-					if (line) lines.push(line);
-					if (fits(word)) {
-						line = word;
-					} else {
-						let chunk = "";
-						for (const ch of word) {
-							const trial = chunk + ch;
-							if (fits(trial)) {
-								chunk = trial;
-							} else {
-								if (chunk) lines.push(chunk);
-								chunk = ch;
-							}
-						}
-						line = chunk;
-					}
-				}
+			const labelBlob = await response.blob();
+			if (format === "pdf") {
+				const a = document.createElement("a");
+				a.href = URL.createObjectURL(labelBlob);
+				a.download = `label-${result.output_batch || rollId}.pdf`;
+				a.click();
 			}
-			if (line) lines.push(line);
-			return lines;
-		};
-
-		const remarkFontSize = 7;
-		const remarkMaxWidth = 288 - leftX * 2;
-		const remarkLines = wrapByWidth(remarkText, remarkMaxWidth, remarkFontSize);
-		const lineHeight = 8;
-		const bottomMargin = 10;
-		const maxLines = Math.max(1, Math.floor((timestampY - 18 - bottomMargin) / lineHeight));
-		const usedLines = Math.min(remarkLines.length, maxLines);
-		const totalHeight = usedLines * lineHeight;
-		const startY = bottomMargin + totalHeight - lineHeight;
-		remarkLines.slice(0, maxLines).forEach((ln, i) => {
-			const x = (288 - font.widthOfTextAtSize(ln, remarkFontSize)) / 2;
-			page.drawText(ln, { x, y: startY - i * lineHeight, size: remarkFontSize, font });
-		});
-
-		const bytes = await pdf.save();
-		const a = document.createElement("a");
-		const pdfBlob = new Blob([bytes], { type: "application/pdf" });
-		a.href = URL.createObjectURL(pdfBlob);
-		a.download = `label-${result.output_batch || rollId}.pdf`;
-		document.getElementById("close-print-btn").addEventListener("click", closePrintModal);
-		document.getElementById("print-pdf-btn").addEventListener("click", async function () {
-			a.click();
+			const reprint = Number(response.headers.get("X-Reprint") || 0);
 			const reader = new FileReader();
 			reader.onloadend = async function () {
 				const base64data = reader.result.split(",")[1];
-				await fetch("http://localhost:8080/api/app/print", {
+				const printResponse = await fetch("http://localhost:8080/api/app/print", {
 					method: "POST",
 					headers: { "Content-Type": "application/json" },
 					body: JSON.stringify({ pdf_data: base64data }),
 				});
-				showNotification("Label sent to printer", "success");
+				const printResult = await printResponse.json().catch(() => ({}));
+				if (printResult.success === false) {
+					showNotification(printResult.message, "error");
+					return;
+				}
+				showNotification(reprint > 0 ? `Label reprint ${reprint} sent to printer` : "Label sent to printer", "success");
 			};
-			reader.readAsDataURL(pdfBlob);
-		});
+			reader.readAsDataURL(labelBlob);
+		};
 	} catch (error) {
 		showNotification(error.message, "error");
 		closePrintModal();
 	}
 }
 
+// Raw (ZPL) printers on the station's hardware agent get ZPL, everything else the PDF
+async function stationLabelFormat() {
+	try {
+		const response = await fetch("http://localhost:8080/api/settings");
+		const { printers } = await response.json();
+		const printer = (printers?.printers || []).find((p) => p.name === printers.default);
+		return printer?.kind === "raw" ? "zpl" : "pdf";
+	} catch {
+		return "pdf";
+	}
+}
+
 let rolls = [];
 let flagReasons = [];
 let users = [];
@@ -352,9 +209,47 @@ async function initializePage() {
 	await loadFlagReasonsForDisplay();
 	await loadRolls();
 	setupEventListeners();
+	connectDeviceEvents();
 	setTimeout(() => toggleDetails(), 100);
 }
 
+// Live scale weight and scans pushed by the station's hardware agent
+function connectDeviceEvents() {
+	let opened = false;
+	const events = new EventSource("http://localhost:8080/api/app/events");
+	events.onopen = () => (opened = true);
+	// No agent on this machine: stop retrying
+	events.onerror = () => {
+		if (!opened) events.close();
+	};
+	events.addEventListener("weight", (e) => {
+		const sample = JSON.parse(e.data);
+		const label = document.getElementById("live-weight");
+		if (!label) return;
+		const state = sample.reading.stable ? "stable" : "moving";
+		label.textContent = `Scale: ${sample.kg.toFixed(3)} kg ${sample.reading.net ? "net" : "gross"} (${state})`;
+		label.classList.remove("hidden");
+		const weightInput = document.getElementById("final-weight");
+		if (sample.confirmed && weightInput && weightInput.disabled) {
+			weightInput.value = Math.round((sample.kg + Number.EPSILON) * 1000) / 1000;
+		}
+	});
+	events.addEventListener("status", (e) => {
+		const status = JSON.parse(e.data);
+		const label = document.getElementById("live-weight");
+		if (status.device !== "scale" || status.connected || !label) return;
+		label.textContent = `Scale: ${status.message}`;
+		label.classList.remove("hidden");
+	});
+	// A scanned batch searches the list unless the roll form is open
+	events.addEventListener("scan", (e) => {
+		const scan = JSON.parse(e.data);
+		if (document.getElementById("roll-modal").style.display === "flex") return;
+		document.getElementById("filter-search").value = scan.data;
+		applyFilters();
+	});
+}
+
 function toggleDetails() {
 	showDetails = !showDetails;
 	const toggle = document.getElementById("details-toggle");
@@ -458,6 +353,13 @@ function renderRolls(rollsToRender) {
 				}">
 					${roll.final_weight === 0 ? "Pending" : roll.flag_count > 0 ? "Flagged" : "Completed"}
 				</span>
+				${
+					roll.qa_status
+						? `<span class="inline-flex items-center px-2 py-1 rounded-full text-xs font-medium bg-purple-100 text-purple-800">${escapeHtml(
+								QA_STATUS_LABELS[roll.qa_status] || roll.qa_status,
+							)}</span>`
+						: ""
+				}
             </td>
             <td class="py-3 px-4 ${showDetails ? "text-center" : ""}">${formatWeight(roll.final_weight || 0)}</td>
             <td class="py-3 px-4 ${showDetails ? "text-center" : ""}">${roll.final_meter || 0} m</td>
@@ -476,6 +378,13 @@ function renderRolls(rollsToRender) {
                             <i class="fas fa-print"></i>
                         </button>`
 					}
+                    ${
+						roll.qa_status === "on_hold" || roll.qa_status === "rework"
+							? `<button class="text-purple-600 hover:text-purple-800 qa-btn" data-id="${roll.id}" title="QA disposition">
+                            <i class="fas fa-clipboard-check"></i>
+                        </button>`
+							: ""
+					}
                     ${
 						isDeletable
 							? `<button class="text-red-600 hover:text-red-800 delete-btn" data-id="${roll.id}">
@@ -508,6 +417,60 @@ function renderRolls(rollsToRender) {
 			printRoll(btn.dataset.id);
 		});
 	});
+
+	document.querySelectorAll(".qa-btn").forEach((btn) => {
+		btn.addEventListener("click", function () {
+			disposeRoll(btn.dataset.id);
+		});
+	});
+}
+
+const QA_STATUS_LABELS = {
+	on_hold: "On Hold",
+	released: "Released",
+	rework: "Rework",
+	downgraded: "Downgraded",
+	scrapped: "Scrapped",
+};
+
+async function disposeRoll(rollId) {
+	const roll = rolls.find((r) => r.id === parseInt(rollId));
+	const action = (prompt(`Disposition for ${roll.output_batch} (release, rework, downgrade, scrap):`) || "")
+		.trim()
+		.toLowerCase();
+	if (!action) return;
+	if (!["release", "rework", "downgrade", "scrap"].includes(action)) {
+		showNotification("Unknown disposition", "warning");
+		return;
+	}
+	const notes = (prompt("QA notes:") || "").trim();
+	if (!notes) {
+		showNotification("Notes are required", "warning");
+		return;
+	}
+
+	const payload = { output_roll_id: roll.id, action, notes };
+	try {
+		if (action === "scrap") {
+			const types = await handleApiResponse(await fetch("/api/lookups/scrap-types"));
+			const list = types.map((t) => `${t.id}: ${t.name}`).join("\n");
+			const scrapTypeId = parseInt(prompt(`Scrap type:\n${list}`));
+			if (!Number.isFinite(scrapTypeId)) return;
+			payload.scrap_type_id = scrapTypeId;
+		}
+
+		const response = await fetch("/api/output-rolls/disposition", {
+			method: "POST",
+			headers: { "Content-Type": "application/json" },
+			body: JSON.stringify(payload),
+		});
+		const disposition = await handleApiResponse(response);
+
+		showNotification(`Roll ${QA_STATUS_LABELS[disposition.to_status].toLowerCase()}`, "success");
+		await applyFilters();
+	} catch (error) {
+		showNotification(error.message, "error");
+	}
 }
 
 function renderPagination() {
@@ -778,6 +741,8 @@ async function exportToExcel() {
 			} else if (statusFilter === "completed") {
 				params.append("final_weight_gt", "0");
 				params.append("number_of_flags", "0");
+			} else {
+				params.append("status", statusFilter);
 			}
 		}
 		if (shiftFilter) params.append("shift_id", shiftFilter);
@@ -0,0 +1,363 @@
+/** @format */
+
+let messages = [];
+let currentPage = 1;
+let itemsPerPage = 10;
+let totalCount = 0;
+
+const statusBadges = {
+	pending: "bg-amber-100 text-amber-800",
+	posting: "bg-blue-100 text-blue-800",
+	posted: "bg-green-100 text-green-800",
+	failed: "bg-red-100 text-red-800",
+	cancelled: "bg-gray-100 text-gray-800",
+};
+
+const kindLabels = {
+	goods_issue: "Goods Issue (261)",
+	goods_return: "Remnant Return",
+	roll_confirmation: "Roll Confirmation",
+	document_cancellation: "Document Cancellation",
+	roll_reversal: "Roll Reversal",
+};
+
+const recordLabels = {
+	input_rolls: "Input roll",
+	output_rolls: "Output roll",
+};
+
+document.addEventListener("DOMContentLoaded", function () {
+	initializePage();
+});
+
+async function initializePage() {
+	setupEventListeners();
+	await applyFilters();
+}
+
+function showLoading(show) {
+	const loadingMessage = document.getElementById("outbox-loading-message");
+	const table = document.getElementById("outbox-table");
+
+	if (show) {
+		loadingMessage.style.display = "block";
+		table.style.display = "none";
+	} else {
+		loadingMessage.style.display = "none";
+		table.style.display = "table";
+	}
+}
+
+async function loadStats() {
+	try {
+		const response = await fetch("/api/sap-outbox/stats");
+		const stats = await handleApiResponse(response);
+		document.getElementById("outbox-pending").textContent = stats.pending + stats.posting;
+		document.getElementById("outbox-posted").textContent = stats.posted;
+		document.getElementById("outbox-failed").textContent = stats.failed;
+		document.getElementById("outbox-cancelled").textContent = stats.cancelled;
+	} catch (error) {
+		console.error("Failed to load outbox stats:", error);
+	}
+}
+
+async function applyFilters() {
+	const applyBtn = document.getElementById("outbox-apply-filter");
+	setButtonLoading(applyBtn, true);
+	showLoading(true);
+
+	try {
+		const params = new URLSearchParams();
+		const status = document.getElementById("filter-outbox-status").value;
+		const kind = document.getElementById("filter-outbox-kind").value;
+		const key = document.getElementById("filter-outbox-key").value;
+		const startDate = document.getElementById("filter-outbox-start").value;
+
+		if (status) params.append("status", status);
+		if (kind) params.append("kind", kind);
+		if (key) params.append("idempotency_key", key);
+		if (startDate) params.append("start_date", startDate);
+
+		params.append("page", currentPage);
+		params.append("per_page", itemsPerPage);
+
+		const response = await fetch(`/api/sap-outbox/filter?${params}`);
+		const result = await handleApiResponse(response);
+
+		messages = result.data;
+		totalCount = result.total_count;
+		renderMessages();
+		renderPagination();
+		updatePerPageOptions(totalCount);
+		await loadStats();
+		await loadReversals();
+	} catch (error) {
+		document.getElementById("outbox-table-body").innerHTML =
+			'<tr><td colspan="8" class="text-center text-red-500 py-4">Failed to load messages</td></tr>';
+		showNotification(error.message, "error");
+	} finally {
+		setButtonLoading(applyBtn, false);
+		showLoading(false);
+	}
+}
+
+function clearFilters() {
+	document.getElementById("filter-outbox-status").value = "";
+	document.getElementById("filter-outbox-kind").value = "";
+	document.getElementById("filter-outbox-key").value = "";
+	document.getElementById("filter-outbox-start").value = "";
+
+	currentPage = 1;
+	applyFilters();
+}
+
+function renderMessages() {
+	const tbody = document.getElementById("outbox-table-body");
+
+	if (messages.length === 0) {
+		tbody.innerHTML = '<tr><td colspan="8" class="text-center text-gray-500 py-4">No messages found</td></tr>';
+		return;
+	}
+
+	tbody.innerHTML = "";
+
+	messages.forEach((message) => {
+		const row = document.createElement("tr");
+		row.className = "hover:bg-gray-50";
+
+		const canRetry = ["failed", "cancelled", "pending"].includes(message.status);
+		const canCancel = ["failed", "pending"].includes(message.status);
+
+		row.innerHTML = `
+			<td class="py-3 px-4">${formatDateTime(message.created_at)}</td>
+			<td class="py-3 px-4">${escapeHtml(kindLabels[message.kind] || message.kind)}</td>
+			<td class="py-3 px-4 font-medium">${escapeHtml(message.idempotency_key)}</td>
+			<td class="py-3 px-4">
+				<span class="inline-block text-xs px-2 py-1 rounded-full ${statusBadges[message.status] || ""}">${escapeHtml(message.status)}</span>
+			</td>
+			<td class="py-3 px-4 text-center">${message.attempts}</td>
+			<td class="py-3 px-4">${escapeHtml(message.document || "-")}</td>
+			<td class="py-3 px-4 text-red-600" title="${escapeHtml(message.last_error || "")}">${escapeHtml(truncateText(message.last_error || "-", 60))}</td>
+			<td class="py-3 px-4">
+				<div class="flex gap-2 justify-center">
+					${
+						canRetry
+							? `<button class="text-blue-600 hover:text-blue-800 retry-btn" data-id="${message.id}" title="Retry now">
+						<i class="fas fa-redo"></i>
+					</button>`
+							: ""
+					}
+					${
+						canCancel
+							? `<button class="text-red-600 hover:text-red-800 cancel-btn" data-id="${message.id}" title="Cancel">
+						<i class="fas fa-ban"></i>
+					</button>`
+							: ""
+					}
+				</div>
+			</td>
+		`;
+
+		tbody.appendChild(row);
+	});
+
+	document.querySelectorAll(".retry-btn").forEach((btn) => {
+		btn.addEventListener("click", () => retryMessage(btn));
+	});
+
+	document.querySelectorAll(".cancel-btn").forEach((btn) => {
+		btn.addEventListener("click", () => cancelMessage(btn));
+	});
+}
+
+function renderPagination() {
+	const totalPages = Math.ceil(totalCount / itemsPerPage);
+	const paginationContainer = document.getElementById("outbox-pagination");
+
+	if (totalPages <= 1) {
+		paginationContainer.innerHTML = "";
+		return;
+	}
+
+	const startItem = (currentPage - 1) * itemsPerPage + 1;
+	const endItem = Math.min(currentPage * itemsPerPage, totalCount);
+
+	paginationContainer.innerHTML = `
+		<div class="flex items-center gap-4">
+			<div class="text-sm text-gray-600">
+				Showing ${startItem} to ${endItem} of ${totalCount} entries
+			</div>
+			<div class="flex gap-1">
+				<button class="pagination-btn" ${currentPage === 1 ? "disabled" : ""} id="prev-page">
+					<i class="fas fa-chevron-left"></i>
+				</button>
+				<button class="pagination-btn" ${currentPage === totalPages ? "disabled" : ""} id="next-page">
+					<i class="fas fa-chevron-right"></i>
+				</button>
+			</div>
+		</div>
+	`;
+
+	document.getElementById("prev-page")?.addEventListener("click", () => {
+		if (currentPage > 1) {
+			currentPage--;
+			applyFilters();
+		}
+	});
+
+	document.getElementById("next-page")?.addEventListener("click", () => {
+		if (currentPage < totalPages) {
+			currentPage++;
+			applyFilters();
+		}
+	});
+}
+
+async function retryMessage(button) {
+	const message = messages.find((m) => String(m.id) === button.dataset.id);
+	if (message?.status === "failed" && message.last_error?.startsWith("Interrupted")) {
+		if (!confirm("This posting was interrupted. Confirm it is not already in SAP before retrying.")) return;
+	}
+
+	setButtonLoading(button, true);
+	try {
+		const response = await fetch("/api/sap-outbox/retry", {
+			method: "PUT",
+			headers: { "Content-Type": "application/json" },
+			body: JSON.stringify({ id: parseInt(button.dataset.id) }),
+		});
+		const result = await handleApiResponse(response);
+
+		if (result.status === "posted") {
+			showNotification("Posted to SAP successfully!", "success");
+		} else {
+			showNotification(`Still not posted: ${result.last_error || result.status}`, "error");
+		}
+		await applyFilters();
+	} catch (error) {
+		showNotification(error.message, "error");
+		setButtonLoading(button, false);
+	}
+}
+
+async function cancelMessage(button) {
+	if (!confirm("Cancel this SAP posting? It will not be retried.")) return;
+
+	try {
+		const response = await fetch("/api/sap-outbox/cancel", {
+			method: "PUT",
+			headers: { "Content-Type": "application/json" },
+			body: JSON.stringify({ id: parseInt(button.dataset.id) }),
+		});
+		await handleApiResponse(response);
+
+		showNotification("Posting cancelled", "success");
+		await applyFilters();
+	} catch (error) {
+		showNotification(error.message, "error");
+	}
+}
+
+async function loadReversals() {
+	const tbody = document.getElementById("reversal-table-body");
+	try {
+		const params = new URLSearchParams();
+		if (document.getElementById("filter-reversal-awaiting").checked) params.append("awaiting_repost", "true");
+
+		const response = await fetch(`/api/sap-outbox/reversals?${params}`);
+		const reversals = await handleApiResponse(response);
+
+		if (reversals.length === 0) {
+			tbody.innerHTML = '<tr><td colspan="7" class="text-center text-gray-500 py-4">No reversals found</td></tr>';
+			return;
+		}
+
+		tbody.innerHTML = "";
+		reversals.forEach((reversal) => {
+			const row = document.createElement("tr");
+			row.className = "hover:bg-gray-50";
+
+			const status = reversal.status || "missing";
+			const canRepost = !reversal.repost_key && status === "posted";
+			const repost = reversal.repost_key
+				? `${escapeHtml(reversal.repost_key)}
+					<span class="inline-block text-xs px-2 py-1 rounded-full ${statusBadges[reversal.repost_status] || ""}">${escapeHtml(reversal.repost_status || "-")}</span>
+					${reversal.repost_document ? `<div class="text-xs text-gray-500">${escapeHtml(reversal.repost_document)}</div>` : ""}`
+				: "-";
+
+			row.innerHTML = `
+				<td class="py-3 px-4">${formatDateTime(reversal.created_at)}</td>
+				<td class="py-3 px-4">${escapeHtml(recordLabels[reversal.ref_model] || reversal.ref_model)} #${reversal.ref_id}</td>
+				<td class="py-3 px-4">
+					<div class="font-medium">${escapeHtml(reversal.original_key)}</div>
+					<div class="text-xs text-gray-500">${escapeHtml(reversal.original_document || "-")}</div>
+				</td>
+				<td class="py-3 px-4">
+					<span class="inline-block text-xs px-2 py-1 rounded-full ${statusBadges[status] || ""}">${escapeHtml(status)}</span>
+					<div class="text-xs text-gray-500">${escapeHtml(reversal.reversal_document || "-")}</div>
+				</td>
+				<td class="py-3 px-4">${escapeHtml(reversal.reason || "-")}</td>
+				<td class="py-3 px-4">${repost}</td>
+				<td class="py-3 px-4">
+					<div class="flex gap-2 justify-center">
+						${
+							canRepost
+								? `<button class="text-blue-600 hover:text-blue-800 repost-btn" data-id="${reversal.id}" title="Re-post corrected value">
+							<i class="fas fa-share"></i>
+						</button>`
+								: ""
+						}
+					</div>
+				</td>
+			`;
+			tbody.appendChild(row);
+		});
+
+		document.querySelectorAll(".repost-btn").forEach((btn) => {
+			btn.addEventListener("click", () => repostReversal(btn));
+		});
+	} catch (error) {
+		tbody.innerHTML = '<tr><td colspan="7" class="text-center text-red-500 py-4">Failed to load reversals</td></tr>';
+		console.error("Failed to load reversals:", error);
+	}
+}
+
+async function repostReversal(button) {
+	if (!confirm("Post the corrected value to SAP? Check the record is right before re-posting.")) return;
+
+	setButtonLoading(button, true);
+	try {
+		const response = await fetch("/api/sap-outbox/reversals/repost", {
+			method: "PUT",
+			headers: { "Content-Type": "application/json" },
+			body: JSON.stringify({ id: parseInt(button.dataset.id) }),
+		});
+		const result = await handleApiResponse(response);
+
+		if (result.status === "posted") {
+			showNotification("Corrected value posted to SAP", "success");
+		} else {
+			showNotification(`Re-post queued: ${result.last_error || result.status}`, "error");
+		}
+		await applyFilters();
+	} catch (error) {
+		showNotification(error.message, "error");
+		setButtonLoading(button, false);
+	}
+}
+
+function setupEventListeners() {
+	document.getElementById("filter-reversal-awaiting").addEventListener("change", loadReversals);
+	document.getElementById("outbox-apply-filter").addEventListener("click", () => {
+		currentPage = 1;
+		applyFilters();
+	});
+	document.getElementById("outbox-clear-filter").addEventListener("click", clearFilters);
+	document.getElementById("outbox-refresh-btn").addEventListener("click", applyFilters);
+
+	document.getElementById("per-page").addEventListener("change", function () {
+		itemsPerPage = parseInt(this.value);
+		currentPage = 1;
+		applyFilters();
+	});
+}
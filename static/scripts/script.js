@@ -19,6 +19,7 @@ const pageModels = {
 	"/lookups": ["downtime_reasons", "flag_reasons"],
 	"/users": ["users"],
 	"/roles": ["roles", "permissions"],
+	"/sap-outbox": ["sap_outbox"],
 	"/logout": [],
 };
 const hasPageAccess = (p) => p.can_create && p.can_read && p.can_delete;
@@ -107,6 +108,7 @@ function showNavBasedOnRole() {
 				{ name: "Manage Lookups", href: "/lookups", icon: "fa-list" },
 				{ name: "Users Management", href: "/users", icon: "fa-users" },
 				{ name: "Roles & Permissions", href: "/roles", icon: "fa-user-tag" },
+				{ name: "SAP Outbox", href: "/sap-outbox", icon: "fa-paper-plane" },
 				{ name: "Logout", href: "/logout", icon: "fa-sign-out-alt", class: "text-red-600 hover:bg-red-50" },
 			],
 		},
@@ -297,6 +297,7 @@ function closeOrderTypesModal() {
 function populateSectionForm(section) {
 	document.getElementById("section-id").value = section.id;
 	document.getElementById("section-name").value = section.name;
+	document.getElementById("section-batch-pattern").value = section.batch_pattern || "";
 }
 
 function handleSectionFormSubmit(e) {
@@ -308,6 +309,7 @@ function handleSectionFormSubmit(e) {
 	const sectionId = document.getElementById("section-id").value;
 	const formData = {
 		name: document.getElementById("section-name").value,
+		batch_pattern: document.getElementById("section-batch-pattern").value,
 	};
 
 	if (sectionId) {
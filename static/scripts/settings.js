@@ -3,6 +3,7 @@
 document.addEventListener("DOMContentLoaded", function () {
 	loadSettings();
 	loadLastUpdate();
+	loadVersion();
 	setupEventListeners();
 });
 
@@ -33,20 +34,40 @@ async function loadLastUpdate() {
 	}
 }
 
+// The agent downloads releases from this server and checks them against the signed manifest
+const releaseQuery = `base_url=${encodeURIComponent(window.location.origin)}`;
+
+async function loadVersion() {
+	const label = document.getElementById("app-version");
+	try {
+		const response = await fetch(`http://localhost:8080/api/app/version?${releaseQuery}`);
+		const version = await response.json();
+		label.textContent = version.update_available ? `Version: ${version.installed} (${version.available} available)` : `Version: ${version.installed}`;
+		label.title = version.success ? "" : version.message;
+		document.getElementById("update-app").disabled = !version.update_available;
+		const rollback = document.getElementById("rollback-app");
+		rollback.style.display = version.previous ? "" : "none";
+		rollback.title = version.previous ? `Back to ${version.previous}` : "";
+	} catch (error) {
+		label.textContent = "Version: Unknown";
+	}
+}
+
 async function updateApp() {
 	const btn = document.getElementById("update-app");
 	setButtonLoading(btn, true);
 
 	try {
-		const response = await fetch("http://localhost:8080/api/app/update");
+		const response = await fetch(`http://localhost:8080/api/app/update?${releaseQuery}`);
 		const result = await response.text();
 
 		if (response.ok) {
 			await new Promise((r) => setTimeout(r, 10000));
 			loadLastUpdate();
+			loadVersion();
 			showNotification("Application updated successfully", "success");
 		} else {
-			showNotification("something went wrong, Try again later!", "error");
+			showNotification(result, "error");
 		}
 	} catch (error) {
 		showNotification(error.message, "error");
@@ -55,19 +76,115 @@ async function updateApp() {
 	}
 }
 
+async function rollbackApp() {
+	if (!confirm("Go back to the previous version of the hardware app?")) return;
+	const btn = document.getElementById("rollback-app");
+	setButtonLoading(btn, true);
+
+	try {
+		const response = await fetch("http://localhost:8080/api/app/rollback", { method: "POST" });
+		const result = await response.json();
+		if (!result.success) throw new Error(result.message);
+		await new Promise((r) => setTimeout(r, 10000));
+		loadVersion();
+		showNotification(result.message, "success");
+	} catch (error) {
+		showNotification(error.message, "error");
+	} finally {
+		setButtonLoading(btn, false);
+	}
+}
+
 function populateForm(config) {
+	document.getElementById("scanner-kind").value = config.scanner.kind || "serial";
+	document.getElementById("scale-kind").value = config.scale.kind || "serial";
+	document.getElementById("scale-simulation").value = config.scale.simulation || "";
 	document.getElementById("scanner-port").value = config.scanner.port_name || "";
 	document.getElementById("scanner-baud").value = config.scanner.baud_rate || 9600;
 	document.getElementById("scale-port").value = config.scale.port_name || "";
 	document.getElementById("scale-baud").value = config.scale.baud_rate || 9600;
+	document.getElementById("scale-protocol").value = config.scale.protocol || "text";
+	document.getElementById("scale-stable-readings").value = config.scale.stable_readings || 1;
+	renderPrinters(config.printers || { default: "", printers: [] });
+}
+
+function renderPrinters(settings) {
+	const list = document.getElementById("printer-list");
+	list.innerHTML = "";
+	settings.printers.forEach((printer) => addPrinterRow(printer, printer.name === settings.default));
+	if (!settings.printers.length) addPrinterRow({ name: "default", kind: "raw", target: "", options: "" }, true);
+}
+
+function addPrinterRow(printer = { name: "", kind: "raw", target: "", options: "" }, isDefault = false) {
+	const list = document.getElementById("printer-list");
+	const row = document.createElement("div");
+	row.className = "printer-row grid grid-cols-1 md:grid-cols-12 gap-2 items-end";
+	row.innerHTML = `
+		<div class="form-group md:col-span-2">
+			<label class="form-label">Name</label>
+			<input type="text" class="form-input printer-name" />
+		</div>
+		<div class="form-group md:col-span-2">
+			<label class="form-label">Type</label>
+			<select class="form-input printer-kind">
+				<option value="raw">Raw (ZPL/EPL)</option>
+				<option value="cups">CUPS</option>
+				<option value="sumatra">SumatraPDF</option>
+				<option value="simulated">Simulated (data/prints)</option>
+			</select>
+		</div>
+		<div class="form-group md:col-span-3">
+			<label class="form-label">Target</label>
+			<input type="text" class="form-input printer-target" placeholder="host:9100 / queue / printer name" />
+		</div>
+		<div class="form-group md:col-span-2">
+			<label class="form-label">Options</label>
+			<input type="text" class="form-input printer-options" />
+		</div>
+		<label class="md:col-span-1 flex items-center gap-1 text-sm text-gray-700">
+			<input type="radio" name="printer-default" class="printer-default" /> Default
+		</label>
+		<div class="md:col-span-2 flex gap-1">
+			<button type="button" class="btn btn-secondary test-printer-row" title="Test"><i class="fas fa-plug"></i></button>
+			<button type="button" class="btn btn-secondary remove-printer-row" title="Remove"><i class="fas fa-trash"></i></button>
+		</div>`;
+	row.querySelector(".printer-name").value = printer.name || "";
+	row.querySelector(".printer-kind").value = printer.kind || "raw";
+	row.querySelector(".printer-target").value = printer.target || "";
+	row.querySelector(".printer-options").value = printer.options || "";
+	row.querySelector(".printer-default").checked = isDefault;
+	row.querySelector(".remove-printer-row").addEventListener("click", () => row.remove());
+	row.querySelector(".test-printer-row").addEventListener("click", function () {
+		testPrinterConnection.call(this, row.querySelector(".printer-name").value);
+	});
+	list.appendChild(row);
+}
+
+function collectPrinters() {
+	const rows = [...document.querySelectorAll("#printer-list .printer-row")];
+	const printers = rows.map((row) => ({
+		name: row.querySelector(".printer-name").value.trim(),
+		kind: row.querySelector(".printer-kind").value,
+		target: row.querySelector(".printer-target").value.trim(),
+		options: row.querySelector(".printer-options").value.trim(),
+	}));
+	const defaultRow = rows.find((row) => row.querySelector(".printer-default").checked);
+	return {
+		default: defaultRow ? defaultRow.querySelector(".printer-name").value.trim() : printers[0]?.name || "",
+		printers,
+	};
 }
 
 function setupEventListeners() {
 	document.getElementById("update-app").addEventListener("click", updateApp);
+	document.getElementById("rollback-app").addEventListener("click", rollbackApp);
 	document.getElementById("settings-form").addEventListener("submit", handleSaveSettings);
 	document.getElementById("test-scanner").addEventListener("click", testScannerConnection);
 	document.getElementById("test-scale").addEventListener("click", testScaleConnection);
-	document.getElementById("test-printer").addEventListener("click", testPrinterConnection);
+	document.getElementById("test-printer").addEventListener("click", function () {
+		testPrinterConnection.call(this, "");
+	});
+	document.getElementById("add-printer").addEventListener("click", () => addPrinterRow());
 }
 
 async function handleSaveSettings(e) {
@@ -77,13 +194,19 @@ async function handleSaveSettings(e) {
 
 	const config = {
 		scanner: {
+			kind: document.getElementById("scanner-kind").value,
 			port_name: document.getElementById("scanner-port").value,
 			baud_rate: parseInt(document.getElementById("scanner-baud").value),
 		},
 		scale: {
+			kind: document.getElementById("scale-kind").value,
+			simulation: document.getElementById("scale-simulation").value,
 			port_name: document.getElementById("scale-port").value,
 			baud_rate: parseInt(document.getElementById("scale-baud").value),
+			protocol: document.getElementById("scale-protocol").value,
+			stable_readings: parseInt(document.getElementById("scale-stable-readings").value) || 1,
 		},
+		printers: collectPrinters(),
 	};
 
 	try {
@@ -95,13 +218,7 @@ async function handleSaveSettings(e) {
 			body: JSON.stringify(config),
 		});
 
-		let errorText;
-		try {
-			errorText = await response.json();
-			errorText = errorText.message;
-		} catch {
-			errorText = await response.text();
-		}
+		if (!response.ok) throw new Error(await response.text());
 
 		showNotification("Settings saved successfully!", "success");
 		checkConfigStatus(config);
@@ -142,12 +259,13 @@ async function testScaleConnection() {
 	}
 }
 
-async function testPrinterConnection() {
-	const btn = document.getElementById("test-printer");
+// Checks the saved profile without printing a page
+async function testPrinterConnection(name) {
+	const btn = this;
 	setButtonLoading(btn, true);
 
 	try {
-		const response = await fetch("http://localhost:8080/api/settings/test/printer");
+		const response = await fetch(`http://localhost:8080/api/settings/test/printer?name=${encodeURIComponent(name)}`);
 		const result = await response.json();
 		showNotification(result.message, result.success ? "success" : "error");
 	} catch (error) {